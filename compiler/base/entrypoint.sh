@@ -4,8 +4,80 @@ set -eu
 
 timeout=${PLAYGROUND_TIMEOUT:-10}
 
+# When the code can't be mounted, it's copied in beside the playground
+# instead
+if [[ -n "${PLAYGROUND_SOURCE:-}" && -f /playground-input.rs ]]; then
+    cp /playground-input.rs "${PLAYGROUND_SOURCE}"
+fi
+if [[ -n "${PLAYGROUND_SOURCE:-}" && -f /playground-hidden-tests.rs ]]; then
+    cp /playground-hidden-tests.rs "$(dirname "${PLAYGROUND_SOURCE}")/hidden_tests.rs"
+fi
+
+# With a read-only root, the crate is built from a writable copy of
+# it instead. Its target directory starts from a copy of the
+# dependencies that the image prebuilt, so they aren't built again
+if [[ -n "${PLAYGROUND_WORK_DIR:-}" ]]; then
+    mkdir -p "${PLAYGROUND_WORK_DIR}"
+    cp /playground/Cargo.toml /playground/Cargo.lock "${PLAYGROUND_WORK_DIR}/"
+    ln -s /playground/src "${PLAYGROUND_WORK_DIR}/src"
+    cp -a /playground/target "${PLAYGROUND_WORK_DIR}/target"
+    cd "${PLAYGROUND_WORK_DIR}"
+fi
+
 modify-cargo-toml
 
 # Don't use `exec` here. The shell is what prints out the useful
 # "Killed" message
-timeout --signal=KILL ${timeout} "$@"
+status=0
+if [[ -n "${PLAYGROUND_OUTPUT_RATE:-}" ]]; then
+    # Programs that write faster than this are stopped, rather than
+    # having all of it kept
+    marker=/dev/null
+    if [[ -d /playground-result ]]; then
+        marker=/playground-result/output-rate-limited
+    fi
+    limit=(limit-output "${PLAYGROUND_OUTPUT_RATE}" "${marker}")
+
+    timeout --signal=KILL ${timeout} "$@" < "${PLAYGROUND_STDIN:-/dev/null}" \
+            2> >("${limit[@]}" >&2) | "${limit[@]}"
+    status=${PIPESTATUS[0]}
+    # For the rest of stderr
+    wait
+else
+    timeout --signal=KILL ${timeout} "$@" < "${PLAYGROUND_STDIN:-/dev/null}" || status=$?
+fi
+
+# Whether anything was killed for using too much memory, which the
+# shell reports as "Killed" just like a timeout. The unified hierarchy
+# of cgroup v2 counts the kills in memory.events, where v1 has them in
+# the memory controller's memory.oom_control
+if [[ -d /playground-result ]]; then
+    for events in /sys/fs/cgroup/memory.events /sys/fs/cgroup/memory/memory.oom_control; do
+        if [[ -r "${events}" ]]; then
+            cp "${events}" /playground-result/memory-events 2>/dev/null || true
+            break
+        fi
+    done
+fi
+
+# How busy it kept the CPU, followed by how long it ran for, so that
+# busy programs that write nothing can be told apart
+if [[ -n "${PLAYGROUND_CPU_USAGE:-}" && -d /playground-result ]]; then
+    { times; echo "${SECONDS}"; } > "${PLAYGROUND_CPU_USAGE}" 2>/dev/null || true
+fi
+
+# So that the program can be run again without building it
+if [[ -n "${PLAYGROUND_KEEP_BINARY:-}" && -d /playground-result ]]; then
+    cp "${PLAYGROUND_KEEP_BINARY}" /playground-result/playground 2>/dev/null || true
+fi
+
+if [[ -n "${PLAYGROUND_SCCACHE_STATS:-}" ]]; then
+    sccache --show-stats --stats-format=json > "${PLAYGROUND_SCCACHE_STATS}" 2>/dev/null || true
+fi
+
+# So that changes to the code, such as formatting, are copied back out
+if [[ -n "${PLAYGROUND_SOURCE:-}" && -d /playground-result ]]; then
+    cp "${PLAYGROUND_SOURCE}" /playground-result/source.rs 2>/dev/null || true
+fi
+
+exit ${status}
@@ -0,0 +1,69 @@
+//! Copies its input to its output for as long as it comes no faster
+//! than the given number of bytes a second, after a first burst of
+//! that many. Once it comes faster, it says so in the output, creates
+//! the marker file, and exits, so that whatever was writing the input
+//! can't any more.
+//!
+//! Usage: `limit-output <bytes per second> [marker file]`
+
+use std::{
+    env, fs,
+    io::{self, ErrorKind, Read, Write},
+    process,
+    time::Instant,
+};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let rate: u64 = match args.next().and_then(|rate| rate.parse().ok()) {
+        Some(rate) => rate,
+        None => {
+            eprintln!("Usage: limit-output <bytes per second> [marker file]");
+            process::exit(2);
+        }
+    };
+    let marker = args.next();
+
+    let start = Instant::now();
+    let mut written = 0;
+    let mut buffer = [0; 8192];
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+
+    loop {
+        let n = match input.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(n) => n as u64,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        };
+
+        let elapsed = start.elapsed().as_secs_f64();
+        let allowed = rate.saturating_add((rate as f64 * elapsed) as u64);
+        let flooding = written + n > allowed;
+        let n = n.min(allowed - written.min(allowed));
+
+        let copied = output
+            .write_all(&buffer[..n as usize])
+            .and_then(|_| output.flush());
+        if copied.is_err() {
+            return;
+        }
+        written += n;
+
+        if flooding {
+            let _ = writeln!(
+                output,
+                "\n[The output was stopped, as it was written faster than {} bytes a second]",
+                rate
+            );
+            if let Some(marker) = &marker {
+                let _ = fs::write(marker, "");
+            }
+            return;
+        }
+    }
+}
@@ -36,6 +36,10 @@ fn main() {
         cargo_toml = set_release_lto(cargo_toml, lto == "true");
     }
 
+    if let Ok(rustflags) = env::var("PLAYGROUND_RUSTFLAGS") {
+        cargo_toml = set_package_rustflags(cargo_toml, &rustflags);
+    }
+
     let output = toml::to_string(&cargo_toml).expect("Cannot convert back to TOML");
 
     fs::write(&output_filename, output)
@@ -165,3 +169,64 @@ fn set_release_lto(cargo_toml: Value, lto: bool) -> Value {
         cargo_toml
     })
 }
+
+// Unlike `RUSTFLAGS`, these only apply to the playground crate, so
+// the dependencies that were already built are used as they are
+fn set_package_rustflags(cargo_toml: Value, rustflags: &str) -> Value {
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct CargoToml {
+        #[serde(default)]
+        cargo_features: Vec<String>,
+        package: Package,
+        #[serde(default)]
+        profile: BTreeMap<String, Profile>,
+        #[serde(flatten)]
+        other: Other,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Package {
+        name: String,
+        #[serde(flatten)]
+        other: Other,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Profile {
+        #[serde(default)]
+        package: BTreeMap<String, PackageProfile>,
+        #[serde(flatten)]
+        other: Other,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct PackageProfile {
+        #[serde(default)]
+        rustflags: Vec<String>,
+        #[serde(flatten)]
+        other: Other,
+    }
+
+    modify(cargo_toml, |mut cargo_toml: CargoToml| {
+        ensure_string_in_vec(&mut cargo_toml.cargo_features, "profile-rustflags");
+
+        // The other profiles inherit from these
+        for profile in ["dev", "release"] {
+            let package = cargo_toml
+                .profile
+                .entry(profile.into())
+                .or_default()
+                .package
+                .entry(cargo_toml.package.name.clone())
+                .or_default();
+            for flag in rustflags.split_whitespace() {
+                ensure_string_in_vec(&mut package.rustflags, flag);
+            }
+        }
+        cargo_toml
+    })
+}
@@ -20,8 +20,21 @@ function install_wasm_gc() {
     cargo install wasm-gc
 }
 
+function install_cargo_expand() {
+    cargo install --locked cargo-expand
+}
+
+function install_cargo_careful() {
+    rustup component add rust-src
+    cargo install cargo-careful
+    # Builds the checked standard library now instead of on each run
+    cargo careful setup
+}
+
 if [[ $1 == "nightly" ]]; then
     (install_wasm_target)
     (install_wasm2wat)
     (install_wasm_gc)
+    (install_cargo_expand)
+    (install_cargo_careful)
 fi
@@ -0,0 +1,168 @@
+//! Evaluates Rust for a REPL session of the playground.
+//!
+//! Each line of the standard input is a JSON request and is answered
+//! by one line of JSON on the standard output. The state of the
+//! session, such as its variables, is kept between requests.
+
+use evcxr::CommandContext;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, Write},
+    mem,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+// The standard error of the evaluation may arrive a moment after the
+// evaluation itself finished
+const STDERR_GRACE: Duration = Duration::from_millis(50);
+
+type Captured = Arc<Mutex<String>>;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Request {
+    Evaluate { code: String },
+    Variables,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Response {
+    Evaluated {
+        success: bool,
+        /// The value of the last expression, when there was one.
+        value: Option<String>,
+        stdout: String,
+        stderr: String,
+    },
+    Variables {
+        variables: Vec<Variable>,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Variable {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+fn main() {
+    // Evaluations run in a copy of this process, which doesn't return
+    // from here
+    evcxr::runtime_hook();
+
+    let (mut context, outputs) = match CommandContext::new() {
+        Ok(context) => context,
+        Err(e) => {
+            respond(&Response::Failed {
+                message: e.to_string(),
+            });
+            std::process::exit(1);
+        }
+    };
+
+    // Evaluation waits for its standard output to be read, so it's
+    // read while the evaluation runs
+    let stdout = capture(outputs.stdout);
+    let stderr = capture(outputs.stderr);
+
+    // There's no network to download crates with
+    if let Err(e) = context.execute(":offline 1") {
+        respond(&Response::Failed {
+            message: e.to_string(),
+        });
+        std::process::exit(1);
+    }
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Evaluate { code }) => evaluate(&mut context, &stdout, &stderr, &code),
+            Ok(Request::Variables) => Response::Variables {
+                variables: context
+                    .variables_and_types()
+                    .map(|(name, ty)| Variable {
+                        name: name.into(),
+                        ty: ty.into(),
+                    })
+                    .collect(),
+            },
+            Err(e) => Response::Failed {
+                message: e.to_string(),
+            },
+        };
+        respond(&response);
+    }
+}
+
+fn evaluate(
+    context: &mut CommandContext,
+    stdout: &Captured,
+    stderr: &Captured,
+    code: &str,
+) -> Response {
+    let result = context.execute(code);
+    thread::sleep(STDERR_GRACE);
+
+    let stdout = take(stdout);
+    let mut stderr = take(stderr);
+
+    match result {
+        Ok(outputs) => Response::Evaluated {
+            success: true,
+            value: outputs.get("text/plain").map(Into::into),
+            stdout,
+            stderr,
+        },
+        Err(e) => {
+            stderr.push_str(&e.to_string());
+            Response::Evaluated {
+                success: false,
+                value: None,
+                stdout,
+                stderr,
+            }
+        }
+    }
+}
+
+fn capture(lines: impl IntoIterator<Item = String> + Send + 'static) -> Captured {
+    let captured = Captured::default();
+
+    let output = captured.clone();
+    thread::spawn(move || {
+        for line in lines {
+            let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+            output.push_str(&line);
+            output.push('\n');
+        }
+    });
+
+    captured
+}
+
+fn take(captured: &Captured) -> String {
+    mem::take(&mut *captured.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn respond(response: &Response) {
+    let mut stdout = io::stdout().lock();
+    // The session is over once the server stops listening
+    let written = serde_json::to_writer(&mut stdout, response)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| stdout.flush());
+    if written.is_err() {
+        std::process::exit(0);
+    }
+}
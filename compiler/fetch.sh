@@ -4,7 +4,7 @@ set -euv -o pipefail
 
 repository=shepmaster
 
-for image in rust-stable rust-beta rust-nightly rustfmt clippy miri; do
+for image in rust-stable rust-beta rust-nightly rustfmt clippy miri kani evcxr rust-analyzer; do
     docker pull "${repository}/${image}"
     # The backend expects images without a repository prefix
     docker tag "${repository}/${image}" "${image}"
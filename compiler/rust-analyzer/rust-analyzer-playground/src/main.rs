@@ -0,0 +1,503 @@
+//! Asks rust-analyzer about one place in the code of the playground.
+//!
+//! Takes what to ask for, which is `completions`, `hover`,
+//! `definition`, `references` or `inlay-hints`, and the file, then the
+//! line and the character of the cursor as the Language Server
+//! Protocol counts them, from zero and in UTF-16 code units. Inlay
+//! hints are for the whole file and need no cursor. Writes one line of
+//! JSON with the answer to the standard output.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{self, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+const ROOT_URI: &str = "file:///playground";
+
+const USAGE: &str = "Usage: rust-analyzer-playground <operation> <file> [<line> <character>]";
+
+// Enough to show most definitions whole without showing all of a
+// long one
+const MAX_EXCERPT_LINES: usize = 40;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Response {
+    Completions(Vec<Completion>),
+    /// `None` when there is nothing at the cursor to tell about.
+    Hover(Option<Hover>),
+    Locations(Vec<Location>),
+    InlayHints(Vec<InlayHint>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Completion {
+    label: String,
+    kind: Option<&'static str>,
+    detail: Option<String>,
+    /// What goes in place of the word at the cursor.
+    insert_text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Hover {
+    /// As Markdown.
+    contents: String,
+    /// Of the symbol that is being told about.
+    range: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct Location {
+    /// Such as `library/core/src/option.rs` for the standard library.
+    /// `None` for the file that was asked about.
+    file: Option<String>,
+    /// Of the name, when there is one.
+    range: Value,
+    /// The lines of what is there, for any other file.
+    excerpt: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InlayHint {
+    /// Where the hint goes.
+    position: Value,
+    label: String,
+    kind: &'static str,
+    /// Whether a space is wanted before or after the hint.
+    padding_left: bool,
+    padding_right: bool,
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (operation, file) = match (args.next(), args.next()) {
+        (Some(operation), Some(file)) => (operation, file),
+        _ => fail(USAGE),
+    };
+    let cursor = match (args.next(), args.next()) {
+        (Some(line), Some(character)) => match (line.parse::<u32>(), character.parse::<u32>()) {
+            (Ok(line), Ok(character)) => Some(json!({ "line": line, "character": character })),
+            _ => fail("The line and character must be numbers"),
+        },
+        _ => None,
+    };
+    let at = || match &cursor {
+        Some(position) => json!({ "position": position }),
+        None => fail(USAGE),
+    };
+
+    let text = match fs::read_to_string(&file) {
+        Ok(text) => text,
+        Err(e) => fail(&format!("Unable to read {}: {}", file, e)),
+    };
+
+    let (method, params) = match &*operation {
+        "completions" => ("textDocument/completion", at()),
+        "hover" => ("textDocument/hover", at()),
+        "definition" => ("textDocument/definition", at()),
+        "references" => {
+            let mut params = at();
+            params["context"] = json!({ "includeDeclaration": true });
+            ("textDocument/references", params)
+        }
+        "inlay-hints" => (
+            "textDocument/inlayHint",
+            json!({ "range": { "start": { "line": 0, "character": 0 }, "end": end(&text) } }),
+        ),
+        _ => fail(&format!("Unable to ask for {}", operation)),
+    };
+
+    let result = match ask(&file, &text, method, params) {
+        Ok(result) => result,
+        Err(e) => fail(&e),
+    };
+    let response = match &*operation {
+        "completions" => Response::Completions(completions(result)),
+        "hover" => Response::Hover(hover(result)),
+        "inlay-hints" => Response::InlayHints(inlay_hints(result)),
+        _ => Response::Locations(locations(&file, result)),
+    };
+
+    let stdout = io::stdout().lock();
+    serde_json::to_writer(stdout, &response).expect("Unable to write the response");
+    println!();
+}
+
+fn ask(file: &str, text: &str, method: &str, mut params: Value) -> Result<Value, String> {
+    let uri = format!("{}/{}", ROOT_URI, file);
+
+    let mut child = Command::new("rust-analyzer")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Unable to start rust-analyzer: {}", e))?;
+    let mut server = Server {
+        stdin: child.stdin.take().expect("The input is piped"),
+        messages: read_messages(child.stdout.take().expect("The output is piped")),
+        next_id: 0,
+    };
+
+    server.request(
+        "initialize",
+        json!({
+            "processId": null,
+            "rootUri": ROOT_URI,
+            "capabilities": {
+                "textDocument": {
+                    "completion": { "completionItem": { "snippetSupport": false } },
+                    "hover": { "contentFormat": ["markdown"] },
+                    // Links also give the whole of what is defined
+                    "definition": { "linkSupport": true },
+                },
+                // Says when the workspace has been loaded
+                "experimental": { "serverStatusNotification": true },
+            },
+            // Nothing is ever saved, and only one place is asked about
+            "initializationOptions": {
+                "checkOnSave": false,
+                "cachePriming": { "enable": false },
+            },
+        }),
+    )?;
+    server.notify("initialized", json!({}))?;
+    server.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": uri, "languageId": "rust", "version": 1, "text": text },
+        }),
+    )?;
+
+    // Anything asked before then wouldn't know about the crates
+    server.wait_until_quiescent()?;
+
+    params["textDocument"] = json!({ "uri": uri });
+    let result = server.request(method, params)?;
+
+    // The container goes away along with the server
+    let _ = child.kill();
+
+    Ok(result)
+}
+
+struct Server {
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    next_id: u64,
+}
+
+impl Server {
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+
+        loop {
+            let message = self.receive()?;
+            if message.get("method").is_some() {
+                self.answer(&message)?;
+                continue;
+            }
+            if message["id"] != id {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(format!(
+                    "rust-analyzer could not {}: {}",
+                    method, error["message"]
+                ));
+            }
+            return Ok(message["result"].clone());
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> Result<(), String> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn wait_until_quiescent(&mut self) -> Result<(), String> {
+        loop {
+            let message = self.receive()?;
+            if message["method"] == "experimental/serverStatus"
+                && message["params"]["quiescent"] == true
+            {
+                return Ok(());
+            }
+            self.answer(&message)?;
+        }
+    }
+
+    // The server asks things of its own, such as to show progress,
+    // which are all fine to agree to
+    fn answer(&mut self, message: &Value) -> Result<(), String> {
+        match message.get("id") {
+            Some(id) if message.get("method").is_some() => {
+                self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": null }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+            .and_then(|()| self.stdin.flush())
+            .map_err(|e| format!("Unable to talk to rust-analyzer: {}", e))
+    }
+
+    fn receive(&self) -> Result<Value, String> {
+        self.messages
+            .recv()
+            .map_err(|_| "rust-analyzer stopped".to_string())
+    }
+}
+
+// Messages are read as they arrive so that the server never waits on
+// its output being read
+fn read_messages(output: impl Read + Send + 'static) -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut output = BufReader::new(output);
+        while let Some(message) = read_message(&mut output) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+fn read_message(output: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if output.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let mut body = vec![0; length?];
+    output.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+// The result is either a list of items or the items themselves
+fn completions(result: Value) -> Vec<Completion> {
+    let items = match result {
+        Value::Array(items) => items,
+        Value::Object(mut list) => match list.remove("items") {
+            Some(Value::Array(items)) => items,
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+
+    let mut items: Vec<_> = items
+        .into_iter()
+        .filter_map(|item| {
+            let label = item["label"].as_str()?.to_owned();
+            let sort_text = item["sortText"].as_str().unwrap_or(&label).to_owned();
+            let insert_text = item["textEdit"]["newText"]
+                .as_str()
+                .or_else(|| item["insertText"].as_str())
+                .unwrap_or(&label)
+                .to_owned();
+
+            let completion = Completion {
+                kind: item["kind"].as_u64().and_then(kind_name),
+                detail: item["detail"].as_str().map(Into::into),
+                insert_text,
+                label,
+            };
+            Some((sort_text, completion))
+        })
+        .collect();
+
+    // In the order that rust-analyzer thinks is the most useful
+    items.sort_by(|(a, _), (b, _)| a.cmp(b));
+    items
+        .into_iter()
+        .map(|(_, completion)| completion)
+        .collect()
+}
+
+// The contents are Markdown, a string or a list of strings, depending
+// on how old the protocol is that they come from
+fn hover(mut result: Value) -> Option<Hover> {
+    let text = |value: &Value| match value {
+        Value::String(text) => Some(text.clone()),
+        _ => value["value"].as_str().map(Into::into),
+    };
+
+    let contents = match result.get("contents")? {
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        contents => text(contents)?,
+    };
+    let range = result.get_mut("range").map(Value::take);
+
+    Some(Hover { contents, range })
+}
+
+// The result is one location, a list of them, or a list of links that
+// also give the whole of what is defined there
+fn locations(file: &str, result: Value) -> Vec<Location> {
+    let own_uri = format!("{}/{}", ROOT_URI, file);
+    let locations = match result {
+        Value::Array(locations) => locations,
+        Value::Null => vec![],
+        location => vec![location],
+    };
+
+    locations
+        .into_iter()
+        .filter_map(|location| {
+            let uri = location["uri"]
+                .as_str()
+                .or_else(|| location["targetUri"].as_str())?;
+            let range = match location.get("targetSelectionRange") {
+                Some(range) => range.clone(),
+                None => location["range"].clone(),
+            };
+            let whole = location.get("targetRange").unwrap_or(&range);
+
+            if uri == own_uri {
+                return Some(Location {
+                    file: None,
+                    range,
+                    excerpt: None,
+                });
+            }
+
+            let path = uri.strip_prefix("file://")?;
+            Some(Location {
+                file: Some(shorten(path).into()),
+                excerpt: excerpt(path, whole),
+                range,
+            })
+        })
+        .collect()
+}
+
+// The sources of the standard library and of the crates are deep
+// inside the toolchain and the registry
+fn shorten(path: &str) -> &str {
+    if let Some((_, rest)) = path.split_once("/lib/rustlib/src/rust/") {
+        return rest;
+    }
+    if let Some((_, rest)) = path.split_once("/registry/src/") {
+        // After the directory of the registry itself
+        if let Some((_, rest)) = rest.split_once('/') {
+            return rest;
+        }
+    }
+    path
+}
+
+fn excerpt(path: &str, range: &Value) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    let start = usize::try_from(range["start"]["line"].as_u64()?).ok()?;
+    let end = usize::try_from(range["end"]["line"].as_u64()?).ok()?;
+    let count = (end.saturating_sub(start) + 1).min(MAX_EXCERPT_LINES);
+
+    let lines: Vec<_> = text.lines().skip(start).take(count).collect();
+    Some(lines.join("\n"))
+}
+
+// Only the hints that tell about types and the names of parameters
+// are kept, not those at the end of blocks and the like
+fn inlay_hints(result: Value) -> Vec<InlayHint> {
+    let hints = match result {
+        Value::Array(hints) => hints,
+        _ => vec![],
+    };
+
+    hints
+        .into_iter()
+        .filter_map(|mut hint| {
+            let kind = match hint["kind"].as_u64()? {
+                1 => "type",
+                2 => "parameter",
+                _ => return None,
+            };
+            // Either the text or the parts of it
+            let label = match &hint["label"] {
+                Value::String(label) => label.clone(),
+                Value::Array(parts) => parts.iter().filter_map(|p| p["value"].as_str()).collect(),
+                _ => return None,
+            };
+
+            Some(InlayHint {
+                position: hint["position"].take(),
+                label,
+                kind,
+                padding_left: hint["paddingLeft"].as_bool().unwrap_or(false),
+                padding_right: hint["paddingRight"].as_bool().unwrap_or(false),
+            })
+        })
+        .collect()
+}
+
+// Where the text ends, as the protocol counts it
+fn end(text: &str) -> Value {
+    let line = text.matches('\n').count();
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    json!({ "line": line, "character": last.encode_utf16().count() })
+}
+
+fn kind_name(kind: u64) -> Option<&'static str> {
+    const NAMES: [&str; 25] = [
+        "text",
+        "method",
+        "function",
+        "constructor",
+        "field",
+        "variable",
+        "class",
+        "interface",
+        "module",
+        "property",
+        "unit",
+        "value",
+        "enum",
+        "keyword",
+        "snippet",
+        "color",
+        "file",
+        "reference",
+        "folder",
+        "enum-member",
+        "constant",
+        "struct",
+        "event",
+        "operator",
+        "type-parameter",
+    ];
+
+    let index = usize::try_from(kind).ok()?.checked_sub(1)?;
+    NAMES.get(index).copied()
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -0,0 +1,47 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc::compile();
+}
+
+#[cfg(feature = "grpc")]
+mod grpc {
+    /// Converted to and from the JSON API types, so the fields need
+    /// the same names and may be missing in the same ways.
+    const JSON_MESSAGES: &[&str] = &[
+        "CompileRequest",
+        "CompileResponse",
+        "ExecuteRequest",
+        "ExecuteResponse",
+        "TestResult",
+        "OutputRecord",
+        "SccacheStats",
+        "Suggestion",
+        "FormatRequest",
+        "LineRange",
+        "FormatResponse",
+        "ClippyRequest",
+        "ClippyResponse",
+    ];
+
+    pub(super) fn compile() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("No protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+
+        let mut builder = tonic_build::configure().build_client(false);
+        for message in JSON_MESSAGES {
+            builder = builder.type_attribute(
+                format!("playground.{message}"),
+                "#[derive(serde::Serialize, serde::Deserialize)] \
+                 #[serde(default, rename_all = \"camelCase\")]",
+            );
+        }
+
+        builder
+            .field_attribute(
+                "playground.ClippyRequest.crate_type",
+                "#[serde(skip_serializing_if = \"Option::is_none\")]",
+            )
+            .compile(&["proto/playground.proto"], &["proto"])
+            .expect("Unable to compile the gRPC API");
+    }
+}
@@ -0,0 +1,189 @@
+//! Tells when an execution looks like it was using the playground as
+//! free compute, such as to mine cryptocurrency: it kept the CPU busy
+//! until it was stopped, without writing anything.
+//!
+//! How busy it was comes from what the entrypoint writes with the
+//! `times` builtin once the program has stopped, followed by how many
+//! seconds it ran for, like
+//!
+//! ```text
+//! 0m0.004s 0m0.001s
+//! 0m9.812s 0m0.107s
+//! 10
+//! ```
+
+use std::time::Duration;
+
+// How much of the time it ran for that the CPU must have been busy
+const BUSY_FRACTION: f64 = 0.9;
+
+// What cargo says when the CPU time limit stopped the program
+const CPU_LIMIT_EXCEEDED: [&str; 2] = ["SIGXCPU", "CPU time limit exceeded"];
+
+// `entrypoint.sh: line 18:    7 Killed    timeout --signal=KILL ...`
+const TIMED_OUT: [&str; 2] = ["Killed", "timeout"];
+
+// Cargo says this just before running the program
+const CARGO_RUNNING: &str = "Running `";
+
+// Cargo says this once the program has stopped unsuccessfully
+const CARGO_FAILED: &str = "process didn't exit successfully";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct CpuUsage {
+    /// Of every process that was run, on any core.
+    pub cpu: Duration,
+    pub wall: Duration,
+}
+
+pub(crate) fn parse_usage(times: &str) -> Option<CpuUsage> {
+    let mut lines = times.lines().skip(1);
+
+    // The first line is of the shell itself, the second of whatever
+    // it ran
+    let mut children = lines.next()?.split_whitespace();
+    let user = parse_time(children.next()?)?;
+    let system = parse_time(children.next()?)?;
+    let wall = lines.next()?.trim().parse().ok()?;
+
+    Some(CpuUsage {
+        cpu: user + system,
+        wall: Duration::from_secs(wall),
+    })
+}
+
+// Such as `1m9.812s`
+fn parse_time(time: &str) -> Option<Duration> {
+    let (minutes, seconds) = time.strip_suffix('s')?.split_once('m')?;
+    let minutes: u64 = minutes.parse().ok()?;
+    let seconds: f64 = seconds.parse().ok()?;
+    Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+/// Whether the program was stopped for running too long, by either
+/// the CPU time limit or the timeout.
+pub(crate) fn ran_too_long(stderr: &str) -> bool {
+    out_of_cpu(stderr) || timed_out(stderr)
+}
+
+fn out_of_cpu(stderr: &str) -> bool {
+    CPU_LIMIT_EXCEEDED.iter().any(|m| stderr.contains(m))
+}
+
+fn timed_out(stderr: &str) -> bool {
+    stderr
+        .lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .is_some_and(|l| TIMED_OUT.iter().all(|m| l.contains(m)))
+}
+
+/// Whether the program was stopped for running too long, after
+/// keeping the CPU busy without writing anything.
+pub(crate) fn suspected(stdout: &str, stderr: &str, usage: Option<CpuUsage>) -> bool {
+    if !ran_too_long(stderr) {
+        return false;
+    }
+
+    // Kept binaries are run without cargo, so it's all the program's
+    let program_stderr = match stderr.rfind(CARGO_RUNNING) {
+        Some(at) => stderr[at..].split_once('\n').map_or("", |(_, rest)| rest),
+        None => stderr,
+    };
+    let wrote_something = !stdout.trim().is_empty()
+        || program_stderr.lines().any(|l| {
+            let l = l.trim();
+            !l.is_empty() && !l.contains(CARGO_FAILED) && !TIMED_OUT.iter().all(|m| l.contains(m))
+        });
+    if wrote_something {
+        return false;
+    }
+
+    out_of_cpu(stderr)
+        || usage.is_some_and(|u| u.cpu.as_secs_f64() >= BUSY_FRACTION * u.wall.as_secs_f64())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RUNNING: &str = concat!(
+        "   Compiling playground v0.0.1 (/playground)\n",
+        "    Finished dev [unoptimized + debuginfo] target(s) in 0.52s\n",
+        "     Running `target/debug/playground`\n",
+    );
+    const KILLED: &str = "/playground/tools/entrypoint.sh: line 18:     7 Killed                  timeout --signal=KILL ${timeout} \"$@\"\n";
+
+    fn busy() -> Option<CpuUsage> {
+        Some(CpuUsage {
+            cpu: Duration::from_millis(9900),
+            wall: Duration::from_secs(10),
+        })
+    }
+
+    #[test]
+    fn usage_is_parsed() {
+        let times = "0m0.004s 0m0.001s\n1m9.812s 0m0.107s\n80\n";
+
+        assert_eq!(
+            parse_usage(times),
+            Some(CpuUsage {
+                cpu: Duration::from_millis(69_919),
+                wall: Duration::from_secs(80),
+            })
+        );
+        assert_eq!(parse_usage("0m0.004s 0m0.001s\n"), None);
+    }
+
+    #[test]
+    fn busy_silent_programs_are_suspected() {
+        let stderr = format!("{}{}", RUNNING, KILLED);
+
+        assert!(suspected("", &stderr, busy()));
+    }
+
+    #[test]
+    fn programs_out_of_cpu_time_are_suspected() {
+        let stderr = format!(
+            "{}error: process didn't exit successfully: `target/debug/playground` (signal: 24, SIGXCPU: CPU time limit exceeded)\n",
+            RUNNING
+        );
+
+        assert!(suspected("", &stderr, None));
+    }
+
+    #[test]
+    fn programs_that_write_are_not_suspected() {
+        let stderr = format!("{}{}", RUNNING, KILLED);
+        assert!(!suspected("tick\n", &stderr, busy()));
+
+        let stderr = format!("{}progress: 10%\n{}", RUNNING, KILLED);
+        assert!(!suspected("", &stderr, busy()));
+    }
+
+    #[test]
+    fn idle_programs_are_not_suspected() {
+        let stderr = format!("{}{}", RUNNING, KILLED);
+        let idle = Some(CpuUsage {
+            cpu: Duration::from_millis(600),
+            wall: Duration::from_secs(10),
+        });
+
+        assert!(!suspected("", &stderr, idle));
+        assert!(!suspected("", &stderr, None));
+    }
+
+    #[test]
+    fn programs_that_finish_are_not_suspected() {
+        assert!(!suspected("", RUNNING, busy()));
+    }
+
+    #[test]
+    fn programs_that_wrote_before_running_too_long_still_ran_too_long() {
+        let stderr = format!("{}progress: 10%\n{}", RUNNING, KILLED);
+        assert!(ran_too_long(&stderr));
+
+        let stderr = format!("{}thread 'main' panicked at 'timeout'\n", RUNNING);
+        assert!(!ran_too_long(&stderr));
+    }
+}
@@ -0,0 +1,391 @@
+//! Talks to `rust-analyzer-playground`, which is given the cursor the
+//! way the Language Server Protocol counts it and answers with a line
+//! of JSON.
+
+use serde_derive::Deserialize;
+
+use crate::{CodeRange, Completion, Hover, InlayHint, InlayHintKind, Location};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum DriverResponse {
+    Completions(Vec<Completion>),
+    Hover(Option<DriverHover>),
+    Locations(Vec<DriverLocation>),
+    InlayHints(Vec<DriverInlayHint>),
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DriverHover {
+    contents: String,
+    range: Option<Range>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DriverLocation {
+    file: Option<String>,
+    range: Range,
+    excerpt: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DriverInlayHint {
+    position: Position,
+    label: String,
+    kind: InlayHintKind,
+    padding_left: bool,
+    padding_right: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+#[derive(Debug, Deserialize)]
+struct Position {
+    line: usize,
+    character: usize,
+}
+
+/// The line and the character of the cursor that is `offset` bytes
+/// into `code`, both counted from zero. Characters are counted in
+/// UTF-16 code units, as the protocol does.
+///
+/// Carriage returns before a newline aren't counted, as they are
+/// removed before the code is written out. `None` when the offset is
+/// past the end or in the middle of a character.
+pub(crate) fn position(code: &str, offset: usize) -> Option<(usize, usize)> {
+    let before = code.get(..offset)?;
+    let line = before.matches('\n').count();
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    let mut text = &before[start..];
+    if code[offset..].starts_with('\n') {
+        text = text.strip_suffix('\r').unwrap_or(text);
+    }
+
+    Some((line, text.encode_utf16().count()))
+}
+
+/// Where `line` and `character`, as [`position`] counts them, are in
+/// bytes from the start of `code`. `None` when that's past the end of
+/// the line or of the code.
+pub(crate) fn offset(code: &str, line: usize, character: usize) -> Option<usize> {
+    let mut start = 0;
+    for _ in 0..line {
+        start += code[start..].find('\n')? + 1;
+    }
+
+    let mut units = 0;
+    for (i, c) in code[start..].char_indices() {
+        if units == character {
+            return Some(start + i);
+        }
+        let line_ended = c == '\n' || code[start + i..].starts_with("\r\n");
+        if line_ended || units > character {
+            return None;
+        }
+        units += c.len_utf16();
+    }
+
+    (units == character).then_some(code.len())
+}
+
+/// rust-analyzer puts the type or declaration first and then the
+/// documentation, with a rule between them.
+pub(crate) fn hover(code: &str, hover: DriverHover) -> Hover {
+    let (signature, documentation) = match hover.contents.split_once("\n---\n") {
+        Some((signature, documentation)) => (signature, Some(documentation.trim())),
+        None => (&*hover.contents, None),
+    };
+
+    let range = hover.range.and_then(|range| {
+        Some(CodeRange {
+            start: offset(code, range.start.line, range.start.character)?,
+            end: offset(code, range.end.line, range.end.character)?,
+        })
+    });
+
+    Hover {
+        signature: signature.trim().into(),
+        documentation: documentation.filter(|d| !d.is_empty()).map(Into::into),
+        range,
+    }
+}
+
+/// Locations in the code itself get a range that matches the code as
+/// it was given. Those in other files are left as they are.
+pub(crate) fn location(code: &str, location: DriverLocation) -> Location {
+    let DriverLocation {
+        file,
+        range,
+        excerpt,
+    } = location;
+
+    let code_range = || {
+        Some(CodeRange {
+            start: offset(code, range.start.line, range.start.character)?,
+            end: offset(code, range.end.line, range.end.character)?,
+        })
+    };
+
+    Location {
+        range: file.is_none().then(code_range).flatten(),
+        line: range.start.line + 1,
+        file,
+        excerpt,
+    }
+}
+
+/// `None` when the hint isn't anywhere in the code as it was given.
+pub(crate) fn inlay_hint(code: &str, hint: DriverInlayHint) -> Option<InlayHint> {
+    let DriverInlayHint {
+        position,
+        label,
+        kind,
+        padding_left,
+        padding_right,
+    } = hint;
+
+    Some(InlayHint {
+        offset: offset(code, position.line, position.character)?,
+        label,
+        kind,
+        padding_left,
+        padding_right,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursors_are_found() {
+        let code = "fn main() {\n    let v = vec![1];\n    v.\n}\n";
+        let offset = code.find("v.").unwrap() + 2;
+
+        assert_eq!(position(code, 0), Some((0, 0)));
+        assert_eq!(position(code, offset), Some((2, 6)));
+        assert_eq!(position(code, code.len()), Some((4, 0)));
+    }
+
+    #[test]
+    fn characters_are_counted_in_utf16() {
+        let code = "let s = \"ü𝄞\"; s.";
+        assert_eq!(position(code, code.len()), Some((0, 17)));
+    }
+
+    #[test]
+    fn carriage_returns_before_newlines_are_skipped() {
+        let code = "let a = 1;\r\nlet b = 2;\r\n";
+        assert_eq!(position(code, 21), Some((1, 9)));
+        assert_eq!(position(code, 22), Some((1, 10)));
+        assert_eq!(position(code, 23), Some((1, 10)));
+        assert_eq!(position(code, code.len()), Some((2, 0)));
+    }
+
+    #[test]
+    fn cursors_outside_the_code_are_rejected() {
+        assert_eq!(position("abc", 4), None);
+        assert_eq!(position("ü", 1), None);
+    }
+
+    #[test]
+    fn positions_are_turned_back_into_offsets() {
+        let code = "let a = 1;\r\nlet s = \"ü𝄞\"; s.\n";
+
+        for at in [0, 5, 10, 12, 20, 21, 23, 27, code.len()] {
+            let (line, character) = position(code, at).unwrap();
+            assert_eq!(offset(code, line, character), Some(at), "{}", at);
+        }
+        assert_eq!(offset(code, 0, 11), None);
+        assert_eq!(offset(code, 3, 0), None);
+    }
+
+    #[test]
+    fn hovers_are_split_into_signature_and_documentation() {
+        let code = "fn main() {\n    let numbers = vec![1];\n}\n";
+        let hover = DriverHover {
+            contents: "```rust\nalloc::vec\n```\n\n```rust\npub struct Vec<T>\n```\n\n---\n\nA contiguous growable array type.\n".into(),
+            range: Some(Range {
+                start: Position {
+                    line: 1,
+                    character: 18,
+                },
+                end: Position {
+                    line: 1,
+                    character: 21,
+                },
+            }),
+        };
+
+        assert_eq!(
+            super::hover(code, hover),
+            Hover {
+                signature: "```rust\nalloc::vec\n```\n\n```rust\npub struct Vec<T>\n```".into(),
+                documentation: Some("A contiguous growable array type.".into()),
+                range: Some(CodeRange { start: 30, end: 33 }),
+            }
+        );
+    }
+
+    #[test]
+    fn hovers_may_have_no_documentation() {
+        let hover = DriverHover {
+            contents: "```rust\nlet numbers: Vec<i32>\n```".into(),
+            range: None,
+        };
+
+        let hover = super::hover("", hover);
+        assert_eq!(hover.signature, "```rust\nlet numbers: Vec<i32>\n```");
+        assert_eq!(hover.documentation, None);
+    }
+
+    #[test]
+    fn locations_in_the_code_get_a_range() {
+        let code = "fn main() {\n    let a = 1;\n}\n";
+        let location = DriverLocation {
+            file: None,
+            range: Range {
+                start: Position {
+                    line: 1,
+                    character: 8,
+                },
+                end: Position {
+                    line: 1,
+                    character: 9,
+                },
+            },
+            excerpt: None,
+        };
+
+        assert_eq!(
+            super::location(code, location),
+            Location {
+                file: None,
+                line: 2,
+                range: Some(CodeRange { start: 20, end: 21 }),
+                excerpt: None,
+            }
+        );
+    }
+
+    #[test]
+    fn locations_elsewhere_are_left_alone() {
+        let location = DriverLocation {
+            file: Some("library/core/src/option.rs".into()),
+            range: Range {
+                start: Position {
+                    line: 571,
+                    character: 9,
+                },
+                end: Position {
+                    line: 571,
+                    character: 15,
+                },
+            },
+            excerpt: Some("pub enum Option<T> {".into()),
+        };
+
+        let location = super::location("", location);
+        assert_eq!(location.file.as_deref(), Some("library/core/src/option.rs"));
+        assert_eq!(location.line, 572);
+        assert_eq!(location.range, None);
+    }
+
+    #[test]
+    fn inlay_hints_are_placed_in_the_code() {
+        let code = "fn main() {\r\n    let é = 1;\r\n}\r\n";
+        let hint = DriverInlayHint {
+            position: Position {
+                line: 1,
+                character: 9,
+            },
+            label: ": i32".into(),
+            kind: InlayHintKind::Type,
+            padding_left: false,
+            padding_right: false,
+        };
+
+        let hint = super::inlay_hint(code, hint).unwrap();
+        assert_eq!(hint.offset, code.find(" =").unwrap());
+        assert_eq!(hint.label, ": i32");
+
+        let hint = DriverInlayHint {
+            position: Position {
+                line: 5,
+                character: 0,
+            },
+            label: "x:".into(),
+            kind: InlayHintKind::Parameter,
+            padding_left: false,
+            padding_right: true,
+        };
+        assert_eq!(super::inlay_hint(code, hint), None);
+    }
+
+    // Has to match what `compiler/rust-analyzer/rust-analyzer-playground` writes
+    #[test]
+    fn responses_are_read_as_the_driver_writes_them() {
+        let response = r#"{"completions":[{"label":"push","kind":"method","detail":"fn(&mut self, T)","insertText":"push(…)"},{"label":"len","kind":null,"detail":null,"insertText":"len"}]}"#;
+        let completions = match serde_json::from_str(response).unwrap() {
+            DriverResponse::Completions(completions) => completions,
+            other => panic!("Expected completions, not {:?}", other),
+        };
+
+        assert_eq!(
+            completions,
+            [
+                Completion {
+                    label: "push".into(),
+                    kind: Some("method".into()),
+                    detail: Some("fn(&mut self, T)".into()),
+                    insert_text: "push(…)".into(),
+                },
+                Completion {
+                    label: "len".into(),
+                    kind: None,
+                    detail: None,
+                    insert_text: "len".into(),
+                },
+            ]
+        );
+
+        let response = r#"{"hover":{"contents":"```rust\nfn main()\n```","range":{"start":{"line":0,"character":3},"end":{"line":0,"character":7}}}}"#;
+        match serde_json::from_str(response).unwrap() {
+            DriverResponse::Hover(Some(hover)) => {
+                assert_eq!(hover.contents, "```rust\nfn main()\n```");
+                assert!(hover.range.is_some());
+            }
+            other => panic!("Expected a hover, not {:?}", other),
+        }
+
+        let response = r#"{"locations":[{"file":"library/alloc/src/vec/mod.rs","range":{"start":{"line":396,"character":11},"end":{"line":396,"character":14}},"excerpt":"pub struct Vec<T> {"}]}"#;
+        match serde_json::from_str(response).unwrap() {
+            DriverResponse::Locations(locations) => {
+                assert_eq!(locations.len(), 1);
+                assert_eq!(locations[0].excerpt.as_deref(), Some("pub struct Vec<T> {"));
+            }
+            other => panic!("Expected locations, not {:?}", other),
+        }
+
+        let response = r#"{"inlay-hints":[{"position":{"character":17,"line":0},"label":": Vec<i32>","kind":"type","paddingLeft":false,"paddingRight":false}]}"#;
+        match serde_json::from_str(response).unwrap() {
+            DriverResponse::InlayHints(hints) => {
+                assert_eq!(hints.len(), 1);
+                assert_eq!(hints[0].kind, InlayHintKind::Type);
+            }
+            other => panic!("Expected inlay hints, not {:?}", other),
+        }
+
+        let response = r#"{"hover":null}"#;
+        assert!(matches!(
+            serde_json::from_str(response).unwrap(),
+            DriverResponse::Hover(None)
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use std::{fmt::Write, iter::Peekable, str::Chars};
+
+const ESCAPE: char = '\u{1b}';
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// The parts of Select Graphic Rendition that the compiler uses.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    underline: bool,
+    /// One of the 16 standard colors; the brighter ones are `8..16`.
+    foreground: Option<u8>,
+}
+
+impl Style {
+    fn apply(&mut self, parameters: &str) {
+        let mut codes = parameters.split(';').map(|c| c.parse::<u8>().unwrap_or(0));
+
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                4 => self.underline = true,
+                24 => self.underline = false,
+                30..=37 => self.foreground = Some(code - 30),
+                90..=97 => self.foreground = Some(code - 90 + 8),
+                39 => self.foreground = None,
+                38 => self.foreground = extended_color(&mut codes),
+                48 => {
+                    extended_color(&mut codes);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn classes(&self) -> String {
+        let mut classes = vec![];
+
+        if self.bold {
+            classes.push("ansi-bold".to_string());
+        }
+        if self.underline {
+            classes.push("ansi-underline".to_string());
+        }
+        if let Some(color) = self.foreground {
+            let name = COLOR_NAMES[usize::from(color % 8)];
+            if color < 8 {
+                classes.push(format!("ansi-{}", name));
+            } else {
+                classes.push(format!("ansi-bright-{}", name));
+            }
+        }
+
+        classes.join(" ")
+    }
+}
+
+// Only the 16 standard colors of the 256 color palette are kept, and
+// true colors are dropped
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<u8> {
+    match codes.next()? {
+        5 => codes.next().filter(|&color| color < 16),
+        2 => {
+            codes.take(3).for_each(drop);
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Converts terminal colors to `<span>` elements with `ansi-*`
+/// classes, escaping everything else. Other escape sequences are
+/// removed.
+pub(crate) fn to_html(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    // Styles are only written out once there is text to style, so
+    // that runs of escape sequences don't leave empty elements
+    let mut style = Style::default();
+    let mut written = Style::default();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ESCAPE {
+            if let Some((parameters, 'm')) = control_sequence(&mut chars) {
+                style.apply(&parameters);
+            }
+            continue;
+        }
+
+        if style != written {
+            if written != Style::default() {
+                output.push_str("</span>");
+            }
+            if style != Style::default() {
+                // Writing to a `String` cannot fail
+                let _ = write!(output, r#"<span class="{}">"#, style.classes());
+            }
+            written = style;
+        }
+
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+
+    if written != Style::default() {
+        output.push_str("</span>");
+    }
+
+    output
+}
+
+/// The text without any escape sequences.
+pub(crate) fn strip(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ESCAPE {
+            control_sequence(&mut chars);
+            continue;
+        }
+        output.push(c);
+    }
+
+    output
+}
+
+// Consumes the rest of `ESC [ <parameters> <command>`
+fn control_sequence(chars: &mut Peekable<Chars<'_>>) -> Option<(String, char)> {
+    chars.next_if_eq(&'[')?;
+
+    let mut parameters = String::new();
+    for c in chars {
+        match c {
+            '\u{40}'..='\u{7e}' => return Some((parameters, c)),
+            c => parameters.push(c),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_escaped() {
+        assert_eq!(
+            to_html("a < b && \"c\""),
+            "a &lt; b &amp;&amp; &quot;c&quot;"
+        );
+    }
+
+    #[test]
+    fn compiler_colors_become_classes() {
+        let error =
+            "\u{1b}[0m\u{1b}[1m\u{1b}[38;5;9merror\u{1b}[0m\u{1b}[0m\u{1b}[1m: oops\u{1b}[0m";
+
+        assert_eq!(
+            to_html(error),
+            concat!(
+                r#"<span class="ansi-bold ansi-bright-red">error</span>"#,
+                r#"<span class="ansi-bold">: oops</span>"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn unterminated_styles_are_closed() {
+        assert_eq!(
+            to_html("\u{1b}[32mok"),
+            r#"<span class="ansi-green">ok</span>"#
+        );
+    }
+
+    #[test]
+    fn other_escape_sequences_are_removed() {
+        assert_eq!(to_html("a\u{1b}[Kb\u{1b}c\u{1b}[38;2;1;2;3md"), "abcd");
+    }
+
+    #[test]
+    fn stripping_leaves_the_text_as_is() {
+        assert_eq!(strip("\u{1b}[1m\u{1b}[31ma < b\u{1b}[0m"), "a < b");
+    }
+}
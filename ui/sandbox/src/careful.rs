@@ -0,0 +1,92 @@
+//! Picks out the panics that only happen under `cargo careful`, whose
+//! standard library checks what it is given, like
+//!
+//! ```text
+//! thread 'main' panicked at library/core/src/panicking.rs:220:5:
+//! unsafe precondition(s) violated: slice::get_unchecked requires that the index is within the slice
+//! ```
+//!
+//! Ordinary panics are reported where the code called into the
+//! standard library, not from within it.
+
+const PANICKED_AT: &str = "' panicked at ";
+
+const PRECONDITION_VIOLATED: &str = "unsafe precondition(s) violated";
+
+// Where the standard library's own code is, whether it was built by
+// the Rust project or from the sources on the toolchain
+const STANDARD_LIBRARY: [&str; 3] = ["library/core/", "library/alloc/", "library/std/"];
+
+/// The messages of the checks that failed.
+pub(crate) fn failed_checks(stderr: &str) -> Vec<String> {
+    let mut lines = stderr.lines();
+    let mut checks = vec![];
+
+    while let Some(line) = lines.next() {
+        let panic = match line.split_once(PANICKED_AT) {
+            Some((thread, panic)) if thread.starts_with("thread '") => panic,
+            _ => continue,
+        };
+
+        let (message, location) = match panic.strip_suffix(':') {
+            // The message is on the lines after
+            Some(location) => match lines.next() {
+                Some(message) => (message, location),
+                None => continue,
+            },
+            // As older compilers put it, `'<message>', <location>`
+            None => match panic.rsplit_once("', ") {
+                Some((message, location)) => (message.trim_start_matches('\''), location),
+                None => continue,
+            },
+        };
+
+        let in_standard_library = STANDARD_LIBRARY.iter().any(|l| location.contains(l));
+        if in_standard_library || message.starts_with(PRECONDITION_VIOLATED) {
+            checks.push(message.to_owned());
+        }
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preconditions_are_checks() {
+        let stderr = concat!(
+            "thread 'main' panicked at library/core/src/panicking.rs:220:5:\n",
+            "unsafe precondition(s) violated: slice::get_unchecked requires that the index is within the slice\n",
+            "note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n",
+            "thread caused non-unwinding panic. aborting.\n",
+        );
+
+        assert_eq!(
+            failed_checks(stderr),
+            ["unsafe precondition(s) violated: slice::get_unchecked requires that the index is within the slice"]
+        );
+    }
+
+    #[test]
+    fn assertions_in_the_standard_library_are_checks() {
+        let stderr = "thread 'main' panicked at 'assertion failed: self.is_char_boundary(n)', /playground/.rustup/toolchains/nightly-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/alloc/src/string.rs:1234:9\n";
+
+        assert_eq!(
+            failed_checks(stderr),
+            ["assertion failed: self.is_char_boundary(n)"]
+        );
+    }
+
+    #[test]
+    fn other_panics_are_not_checks() {
+        let stderr = concat!(
+            "thread 'main' panicked at src/main.rs:3:5:\n",
+            "index out of bounds: the len is 3 but the index is 7\n",
+            "thread 'main' panicked at 'oh no', src/main.rs:4:5\n",
+        );
+
+        assert!(failed_checks(stderr).is_empty());
+    }
+}
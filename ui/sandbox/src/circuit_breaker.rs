@@ -0,0 +1,167 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Stops sending requests to Docker after it has failed repeatedly,
+/// instead of having every request discover that for itself.
+///
+/// Once the cooldown has passed, a single request is let through to
+/// see if Docker has recovered.
+pub struct CircuitBreaker {
+    name: &'static str,
+    threshold: usize,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Closed { consecutive_failures: usize },
+    Open { until: Instant },
+    Probing { since: Instant },
+}
+
+impl CircuitBreaker {
+    /// A `threshold` of zero never trips the breaker.
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            name: "Docker",
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// What is failing, according to the logs.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Decides if a request may use Docker, returning how long to wait
+    /// before trying again if not.
+    pub fn allow(&self) -> Result<(), Duration> {
+        self.allow_at(Instant::now())
+    }
+
+    pub fn record_success(&self) {
+        *self.lock() = State::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now())
+    }
+
+    fn allow_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.lock();
+
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(until - now),
+            // The probe may have been cancelled without reporting back
+            State::Probing { since } if now < since + self.cooldown => {
+                Err(since + self.cooldown - now)
+            }
+            State::Open { .. } | State::Probing { .. } => {
+                *state = State::Probing { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut state = self.lock();
+
+        let consecutive_failures = match *state {
+            State::Closed {
+                consecutive_failures,
+            } => consecutive_failures + 1,
+            State::Open { .. } | State::Probing { .. } => self.threshold,
+        };
+
+        *state = if self.threshold != 0 && consecutive_failures >= self.threshold {
+            tracing::warn!(
+                consecutive_failures,
+                cooldown_secs = self.cooldown.as_secs(),
+                "{} is failing; rejecting requests until it recovers",
+                self.name,
+            );
+            State::Open {
+                until: now + self.cooldown,
+            }
+        } else {
+            State::Closed {
+                consecutive_failures,
+            }
+        };
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn trips_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, COOLDOWN);
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.allow_at(now), Ok(()));
+
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.allow_at(now), Err(COOLDOWN));
+    }
+
+    #[test]
+    fn successes_reset_the_count() {
+        let breaker = CircuitBreaker::new(2, COOLDOWN);
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        breaker.record_success();
+        breaker.record_failure_at(now);
+
+        assert_eq!(breaker.allow_at(now), Ok(()));
+    }
+
+    #[test]
+    fn one_probe_is_allowed_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let now = Instant::now();
+        breaker.record_failure_at(now);
+
+        let later = now + COOLDOWN;
+        assert_eq!(breaker.allow_at(later), Ok(()));
+        assert_eq!(breaker.allow_at(later), Err(COOLDOWN));
+
+        breaker.record_success();
+        assert_eq!(breaker.allow_at(later), Ok(()));
+    }
+
+    #[test]
+    fn failed_probes_trip_the_breaker_again() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        let now = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure_at(now);
+        }
+
+        let later = now + COOLDOWN;
+        assert_eq!(breaker.allow_at(later), Ok(()));
+        breaker.record_failure_at(later);
+
+        assert_eq!(breaker.allow_at(later), Err(COOLDOWN));
+    }
+}
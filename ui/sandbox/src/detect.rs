@@ -0,0 +1,48 @@
+//! Guesses at what kind of code was given, using the same patterns
+//! as the frontend.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub(crate) fn has_main_function(code: &str) -> bool {
+    lazy_static! {
+        static ref MAIN_FUNCTION: Regex =
+            Regex::new(r"(?m)^\s*(pub\s+)?\s*(const\s+)?\s*(async\s+)?\s*fn\s+main\s*\(\s*\)")
+                .unwrap();
+    }
+
+    MAIN_FUNCTION.is_match(code)
+}
+
+pub(crate) fn has_tests(code: &str) -> bool {
+    lazy_static! {
+        static ref TEST_ATTRIBUTE: Regex =
+            Regex::new(r#"(?m)^\s*#\s*\[\s*test\s*([^"]*)]"#).unwrap();
+    }
+
+    TEST_ATTRIBUTE.is_match(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn main_functions_are_found() {
+        assert!(has_main_function("fn main() {}"));
+        assert!(has_main_function("struct A;\n\npub fn main ( ) {\n}"));
+        assert!(has_main_function("  async fn main() {}"));
+        assert!(!has_main_function("fn not_main() {}"));
+        assert!(!has_main_function("// fn main() {}"));
+    }
+
+    #[test]
+    fn tests_are_found() {
+        assert!(has_tests("#[test]\nfn it_works() {}"));
+        assert!(has_tests(
+            "mod tests {\n    # [ test ]\n    fn it_works() {}\n}"
+        ));
+        assert!(!has_tests("#[cfg(test)]\nmod tests {}"));
+        assert!(!has_tests("fn main() {}"));
+    }
+}
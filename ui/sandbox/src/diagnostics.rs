@@ -0,0 +1,145 @@
+//! Counts the diagnostics that the compiler reported from how they
+//! are shown on stderr, like
+//!
+//! ```text
+//! warning: unused variable: `x`
+//! error[E0425]: cannot find value `y` in this scope
+//! ```
+//!
+//! or, in the short format, after where each one is, like
+//!
+//! ```text
+//! src/main.rs:3:13: error[E0425]: cannot find value `y` in this scope
+//! ```
+
+use crate::{ansi, DiagnosticCounts};
+
+// What the compiler and cargo say about the diagnostics as a whole,
+// such as "warning: `playground` (bin "playground") generated 1
+// warning", which aren't diagnostics of their own
+const SUMMARIES: [&str; 4] = [
+    "aborting due to ",
+    "could not compile ",
+    "build failed",
+    "`playground` ",
+];
+
+// Cargo says this before running what it built, whose output is the
+// program's own
+const CARGO_RUNNING: &str = "Running ";
+
+pub(crate) fn count(stderr: &str) -> DiagnosticCounts {
+    let stderr = ansi::strip(stderr);
+    let mut counts = DiagnosticCounts::default();
+
+    for line in stderr.lines() {
+        if line.trim_start().starts_with(CARGO_RUNNING) {
+            break;
+        }
+
+        let (level, message) = match line.split_once(": ") {
+            Some((location, rest)) if is_location(location) => match rest.split_once(": ") {
+                Some(parts) => parts,
+                None => continue,
+            },
+            Some(parts) => parts,
+            None => continue,
+        };
+        let is_summary =
+            SUMMARIES.iter().any(|s| message.starts_with(s)) || message.ends_with(" emitted");
+        if is_summary {
+            continue;
+        }
+
+        // With a code, as in `error[E0425]`
+        let level = level.split('[').next().unwrap_or(level);
+        match level {
+            "warning" => counts.warnings += 1,
+            "error" => counts.errors += 1,
+            _ => {}
+        }
+    }
+
+    counts
+}
+
+// Such as `src/main.rs:3:13`
+fn is_location(s: &str) -> bool {
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let mut parts = s.rsplitn(3, ':');
+
+    parts.next().is_some_and(is_number)
+        && parts.next().is_some_and(is_number)
+        && parts.next().is_some()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn counts(warnings: usize, errors: usize) -> DiagnosticCounts {
+        DiagnosticCounts { warnings, errors }
+    }
+
+    #[test]
+    fn warnings_and_errors_are_counted() {
+        let stderr = concat!(
+            "   Compiling playground v0.0.1 (/playground)\n",
+            "warning: unused variable: `x`\n",
+            " --> src/main.rs:2:9\n",
+            "  |\n",
+            "2 |     let x = 1;\n",
+            "  |         ^ help: if this is intentional, prefix it with an underscore: `_x`\n",
+            "\n",
+            "error[E0425]: cannot find value `y` in this scope\n",
+            "error[E0308]: mismatched types\n",
+            "warning: `playground` (bin \"playground\") generated 1 warning\n",
+            "error: could not compile `playground` due to 2 previous errors; 1 warning emitted\n",
+        );
+
+        assert_eq!(count(stderr), counts(1, 2));
+    }
+
+    #[test]
+    fn short_diagnostics_are_counted() {
+        let stderr = concat!(
+            "   Compiling playground v0.0.1 (/playground)\n",
+            "src/main.rs:2:9: warning: unused variable: `x`\n",
+            "src/main.rs:3:13: error[E0425]: cannot find value `y` in this scope\n",
+            "warning: `playground` (bin \"playground\") generated 1 warning\n",
+            "error: could not compile `playground` due to previous error; 1 warning emitted\n",
+        );
+
+        assert_eq!(count(stderr), counts(1, 1));
+    }
+
+    #[test]
+    fn summaries_are_not_counted() {
+        let stderr = concat!(
+            "error: aborting due to previous error\n",
+            "warning: 2 warnings emitted\n",
+            "warning: build failed, waiting for other jobs to finish...\n",
+        );
+
+        assert_eq!(count(stderr), counts(0, 0));
+    }
+
+    #[test]
+    fn the_programs_output_is_not_counted() {
+        let stderr = concat!(
+            "warning: unused variable: `x`\n",
+            "    Finished dev [unoptimized + debuginfo] target(s) in 0.52s\n",
+            "     Running `target/debug/playground`\n",
+            "error: the program is printing this\n",
+        );
+
+        assert_eq!(count(stderr), counts(1, 0));
+    }
+
+    #[test]
+    fn colors_are_ignored() {
+        let stderr = "\u{1b}[0m\u{1b}[1m\u{1b}[33mwarning\u{1b}[0m\u{1b}[0m\u{1b}[1m: unused variable: `x`\u{1b}[0m\n";
+
+        assert_eq!(count(stderr), counts(1, 0));
+    }
+}
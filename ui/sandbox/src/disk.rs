@@ -0,0 +1,42 @@
+//! Tells when something failed because it ran out of disk, either
+//! the limit of the container or a full disk on the host. Most tools
+//! print the message of the error, `ENOSPC` or `EDQUOT`, but some
+//! print only its name.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub fn is_full(stderr: &str) -> bool {
+    lazy_static! {
+        static ref DISK_FULL_REGEX: Regex =
+            Regex::new(r"No space left on device|Disk quota exceeded|\bENOSPC\b|\bEDQUOT\b")
+                .unwrap();
+    }
+
+    DISK_FULL_REGEX.is_match(stderr)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn running_out_of_space_is_full() {
+        assert!(is_full(
+            "error: failed to write `/playground/target/debug/deps/playground`: \
+             No space left on device (os error 28)"
+        ));
+        assert!(is_full(
+            "thread 'main' panicked: Disk quota exceeded (os error 122)"
+        ));
+        assert!(is_full("write /tmp/out: ENOSPC"));
+    }
+
+    #[test]
+    fn other_failures_are_not() {
+        assert!(!is_full(
+            "error[E0425]: cannot find value `space` in this scope"
+        ));
+        assert!(!is_full("thread 'main' panicked at 'ENOSPCX'"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Tests that are kept apart from the code that they test, such as
+//! an instructor's tests of a student's code.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{TestOutcome, TestResult};
+
+/// The module that the tests are compiled as, from a file of their
+/// own next to the code.
+pub(crate) const MODULE: &str = "hidden_tests";
+pub(crate) const FILE_NAME: &str = "hidden_tests.rs";
+
+/// Declares the module of tests before anything else in `code`, so
+/// that nothing in it can be put on the declaration, such as a
+/// `#[cfg]` that would leave the tests out. Only the crate attributes
+/// and comments that must come first are kept in front of it.
+///
+/// Everything stays on the line it was on, so that the line numbers
+/// of errors still match the code that was given.
+pub(crate) fn declare(code: &str) -> String {
+    let declaration = format!("#[cfg(test)] mod {}; ", MODULE);
+
+    let mut declared = String::with_capacity(code.len() + declaration.len());
+    let mut lines = code.split_inclusive('\n');
+    let mut is_declared = false;
+    for line in lines.by_ref() {
+        if !comes_first(line) {
+            declared.push_str(&declaration);
+            is_declared = true;
+        }
+        declared.push_str(line);
+        if is_declared {
+            break;
+        }
+    }
+    if !is_declared {
+        // Nothing but crate attributes and comments
+        if !declared.is_empty() && !declared.ends_with('\n') {
+            declared.push('\n');
+        }
+        declared.push_str(&declaration);
+    }
+    declared.extend(lines);
+    declared
+}
+
+// Line comments end with the line, but a block comment could hide
+// the declaration and an attribute could follow `]`
+fn comes_first(line: &str) -> bool {
+    let line = line.trim();
+    let crate_attribute = line.starts_with("#![")
+        && line.ends_with(']')
+        && line.rfind('#') == Some(0)
+        && !line.contains("/*");
+    line.is_empty() || line.starts_with("//") || crate_attribute
+}
+
+/// Whether every test in `tests` ran and passed. A test that is
+/// missing from the `results` counts as having failed, as it may have
+/// been left out by the code that it tests.
+pub(crate) fn all_passed(tests: &str, results: &[TestResult]) -> bool {
+    let prefix = format!("{}::", MODULE);
+    let hidden: Vec<_> = results
+        .iter()
+        .filter(|r| r.name.starts_with(&prefix))
+        .collect();
+
+    let expected = names(tests);
+    let all_ran = expected.iter().all(|name| {
+        hidden
+            .iter()
+            .any(|r| r.name.rsplit("::").next() == Some(name))
+    });
+
+    !hidden.is_empty()
+        && hidden.len() >= expected.len()
+        && all_ran
+        && hidden.iter().all(|r| r.outcome == TestOutcome::Passed)
+}
+
+fn names(tests: &str) -> Vec<&str> {
+    lazy_static! {
+        static ref TEST_FUNCTION: Regex = Regex::new(
+            r"#\s*\[\s*test\s*\](?:\s*#\s*\[[^\]]*\])*\s*(?:pub\s+)?(?:async\s+)?fn\s+(\w+)"
+        )
+        .unwrap();
+    }
+
+    TEST_FUNCTION
+        .captures_iter(tests)
+        .filter_map(|c| c.get(1))
+        .map(|m| m.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result(name: &str, outcome: TestOutcome) -> TestResult {
+        TestResult {
+            name: name.into(),
+            outcome,
+            duration_ms: None,
+            stdout: None,
+        }
+    }
+
+    #[test]
+    fn the_module_is_declared_before_the_code() {
+        assert_eq!(
+            declare("fn add() {}\n#[cfg(any())]"),
+            "#[cfg(test)] mod hidden_tests; fn add() {}\n#[cfg(any())]",
+        );
+    }
+
+    #[test]
+    fn crate_attributes_and_comments_stay_in_front() {
+        let code = "//! Adds\n#![allow(unused)]\n\n// Two\nfn add() {}\n";
+        assert_eq!(
+            declare(code),
+            "//! Adds\n#![allow(unused)]\n\n// Two\n#[cfg(test)] mod hidden_tests; fn add() {}\n",
+        );
+    }
+
+    #[test]
+    fn attributes_after_a_crate_attribute_are_not_in_front() {
+        let code = "#![allow(unused)] #[cfg(any())]\nfn add() {}";
+        assert!(declare(code).starts_with("#[cfg(test)] mod hidden_tests; #![allow"));
+
+        let code = "#![allow(unused)] /*\n*/ fn add() {}";
+        assert!(declare(code).starts_with("#[cfg(test)] mod hidden_tests; #![allow"));
+    }
+
+    #[test]
+    fn code_with_only_crate_attributes_is_followed_by_the_module() {
+        assert_eq!(
+            declare("#![allow(unused)]"),
+            "#![allow(unused)]\n#[cfg(test)] mod hidden_tests; ",
+        );
+        assert_eq!(declare(""), "#[cfg(test)] mod hidden_tests; ");
+    }
+
+    const TESTS: &str = r#"
+        use super::*;
+
+        #[test]
+        fn it_adds() {}
+
+        #[test]
+        #[should_panic]
+        fn it_overflows() {}
+    "#;
+
+    #[test]
+    fn every_test_must_pass() {
+        let results = [
+            result("hidden_tests::it_adds", TestOutcome::Passed),
+            result("hidden_tests::it_overflows", TestOutcome::Passed),
+            result("tests::mine", TestOutcome::Failed),
+        ];
+        assert!(all_passed(TESTS, &results));
+
+        let results = [
+            result("hidden_tests::it_adds", TestOutcome::Passed),
+            result("hidden_tests::it_overflows", TestOutcome::Ignored),
+        ];
+        assert!(!all_passed(TESTS, &results));
+    }
+
+    #[test]
+    fn tests_that_did_not_run_have_failed() {
+        assert!(!all_passed(TESTS, &[]));
+
+        let results = [result("hidden_tests::it_adds", TestOutcome::Passed)];
+        assert!(!all_passed(TESTS, &results));
+
+        // Tests of the same name, from a module the code made itself
+        let results = [
+            result("tests::it_adds", TestOutcome::Passed),
+            result("tests::it_overflows", TestOutcome::Passed),
+        ];
+        assert!(!all_passed(TESTS, &results));
+    }
+}
@@ -0,0 +1,59 @@
+//! Reads the compiler's errors for something the user could do about
+//! them other than changing the code.
+
+use crate::{Channel, Hint};
+
+// "`#![feature]` may not be used on the stable release channel"
+const FEATURE_ATTRIBUTE_NOT_ALLOWED: &str = "[E0554]";
+// "use of unstable library feature", "... is experimental" and the
+// like
+const UNSTABLE_FEATURE: &str = "[E0658]";
+
+/// Only failed builds should be asked about.
+pub(crate) fn for_failure(channel: Channel, stderr: &str) -> Option<Hint> {
+    let nightly_only = [FEATURE_ATTRIBUTE_NOT_ALLOWED, UNSTABLE_FEATURE]
+        .iter()
+        .any(|code| stderr.contains(code));
+
+    // Nightly says which feature to enable instead
+    match channel {
+        Channel::Stable { .. } | Channel::Beta if nightly_only => Some(Hint::TryNightly),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FEATURE_ATTRIBUTE: &str = r#"
+error[E0554]: `#![feature]` may not be used on the stable release channel
+ --> src/main.rs:1:1
+"#;
+
+    const UNSTABLE_LIBRARY_FEATURE: &str = r#"
+error[E0658]: use of unstable library feature `test`
+ --> src/main.rs:3:5
+"#;
+
+    #[test]
+    fn nightly_features_on_other_channels_suggest_nightly() {
+        let stable = Channel::Stable { version: None };
+
+        assert_eq!(
+            for_failure(stable, FEATURE_ATTRIBUTE),
+            Some(Hint::TryNightly),
+        );
+        assert_eq!(
+            for_failure(Channel::Beta, UNSTABLE_LIBRARY_FEATURE),
+            Some(Hint::TryNightly),
+        );
+        assert_eq!(for_failure(stable, "error[E0308]: mismatched types"), None);
+    }
+
+    #[test]
+    fn nightly_is_not_suggested_on_nightly() {
+        let nightly = Channel::Nightly { date: None };
+        assert_eq!(for_failure(nightly, UNSTABLE_LIBRARY_FEATURE), None);
+    }
+}
@@ -0,0 +1,244 @@
+//! Requests and responses wrapped up so that they can be handed to
+//! another process, such as a worker taking jobs from a queue, and
+//! run there.
+
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::{
+    BisectRequest, BisectResponse, ClippyRequest, ClippyResponse, CodeRequest, CompileRequest,
+    CompileResponse, CompletionsRequest, CompletionsResponse, EditionMigrationRequest,
+    EditionMigrationResponse, Error, ExecuteRequest, ExecuteResponse, FormatRequest,
+    FormatResponse, HoverRequest, HoverResponse, InlayHintsRequest, InlayHintsResponse,
+    KaniRequest, KaniResponse, MacroExpansionRequest, MacroExpansionResponse, MiriRequest,
+    MiriResponse, NavigationRequest, NavigationResponse, OptimizationRemarksRequest,
+    OptimizationRemarksResponse, ReduceRequest, ReduceResponse, Result, Sandbox, TimePassesRequest,
+    TimePassesResponse,
+};
+
+/// A request for any of the operations that a [`Sandbox`] can run.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobRequest {
+    Compile(CompileRequest),
+    Execute(ExecuteRequest),
+    Format(FormatRequest),
+    EditionMigration(EditionMigrationRequest),
+    Clippy(ClippyRequest),
+    Miri(MiriRequest),
+    Kani(KaniRequest),
+    MacroExpansion(MacroExpansionRequest),
+    TimePasses(TimePassesRequest),
+    OptimizationRemarks(OptimizationRemarksRequest),
+    Completions(CompletionsRequest),
+    Hover(HoverRequest),
+    Navigation(NavigationRequest),
+    InlayHints(InlayHintsRequest),
+    Bisect(BisectRequest),
+    Reduce(ReduceRequest),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobResponse {
+    Compile(CompileResponse),
+    Execute(ExecuteResponse),
+    Format(FormatResponse),
+    EditionMigration(EditionMigrationResponse),
+    Clippy(ClippyResponse),
+    Miri(MiriResponse),
+    Kani(KaniResponse),
+    MacroExpansion(MacroExpansionResponse),
+    TimePasses(TimePassesResponse),
+    OptimizationRemarks(OptimizationRemarksResponse),
+    Completions(CompletionsResponse),
+    Hover(HoverResponse),
+    Navigation(NavigationResponse),
+    InlayHints(InlayHintsResponse),
+    Bisect(BisectResponse),
+    Reduce(ReduceResponse),
+}
+
+/// What running a job came to. Errors only keep their message, as
+/// that is all that is shown of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+// There's only ever one of these for each job, so boxing the response
+// wouldn't save anything
+#[allow(clippy::large_enum_variant)]
+pub enum JobOutcome {
+    Finished(JobResponse),
+    Failed { message: String },
+}
+
+impl JobRequest {
+    pub async fn run(&self, sandbox: Sandbox) -> Result<JobResponse> {
+        Ok(match self {
+            JobRequest::Compile(req) => JobResponse::Compile(sandbox.compile(req).await?),
+            JobRequest::Execute(req) => JobResponse::Execute(sandbox.execute(req).await?),
+            JobRequest::Format(req) => JobResponse::Format(sandbox.format(req).await?),
+            JobRequest::EditionMigration(req) => {
+                JobResponse::EditionMigration(sandbox.migrate_edition(req).await?)
+            }
+            JobRequest::Clippy(req) => JobResponse::Clippy(sandbox.clippy(req).await?),
+            JobRequest::Miri(req) => JobResponse::Miri(sandbox.miri(req).await?),
+            JobRequest::Kani(req) => JobResponse::Kani(sandbox.kani(req).await?),
+            JobRequest::MacroExpansion(req) => {
+                JobResponse::MacroExpansion(sandbox.macro_expansion(req).await?)
+            }
+            JobRequest::TimePasses(req) => JobResponse::TimePasses(sandbox.time_passes(req).await?),
+            JobRequest::OptimizationRemarks(req) => {
+                JobResponse::OptimizationRemarks(sandbox.optimization_remarks(req).await?)
+            }
+            JobRequest::Completions(req) => {
+                JobResponse::Completions(sandbox.completions(req).await?)
+            }
+            JobRequest::Hover(req) => JobResponse::Hover(sandbox.hover(req).await?),
+            JobRequest::Navigation(req) => JobResponse::Navigation(sandbox.navigate(req).await?),
+            JobRequest::InlayHints(req) => JobResponse::InlayHints(sandbox.inlay_hints(req).await?),
+            JobRequest::Bisect(req) => JobResponse::Bisect(sandbox.bisect(req).await?),
+            JobRequest::Reduce(req) => JobResponse::Reduce(sandbox.reduce(req).await?),
+        })
+    }
+}
+
+impl CodeRequest for JobRequest {
+    fn code(&self) -> &str {
+        match self {
+            JobRequest::Compile(req) => req.code(),
+            JobRequest::Execute(req) => req.code(),
+            JobRequest::Format(req) => req.code(),
+            JobRequest::EditionMigration(req) => req.code(),
+            JobRequest::Clippy(req) => req.code(),
+            JobRequest::Miri(req) => req.code(),
+            JobRequest::Kani(req) => req.code(),
+            JobRequest::MacroExpansion(req) => req.code(),
+            JobRequest::TimePasses(req) => req.code(),
+            JobRequest::OptimizationRemarks(req) => req.code(),
+            JobRequest::Completions(req) => req.code(),
+            JobRequest::Hover(req) => req.code(),
+            JobRequest::Navigation(req) => req.code(),
+            JobRequest::InlayHints(req) => req.code(),
+            JobRequest::Bisect(req) => req.code(),
+            JobRequest::Reduce(req) => req.code(),
+        }
+    }
+}
+
+impl JobOutcome {
+    /// The response to the request that the job was made from.
+    pub fn into_response<R>(self) -> Result<R>
+    where
+        R: TryFrom<JobResponse>,
+    {
+        match self {
+            JobOutcome::Finished(response) => {
+                R::try_from(response).map_err(|_| Error::MismatchedJobResponse)
+            }
+            JobOutcome::Failed { message } => Err(Error::WorkerFailed { message }),
+        }
+    }
+}
+
+impl From<Result<JobResponse>> for JobOutcome {
+    fn from(result: Result<JobResponse>) -> Self {
+        match result {
+            Ok(response) => JobOutcome::Finished(response),
+            Err(error) => JobOutcome::Failed {
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+macro_rules! jobs {
+    ($($variant:ident($request:ident, $response:ident),)*) => {
+        $(
+            impl From<$request> for JobRequest {
+                fn from(request: $request) -> Self {
+                    JobRequest::$variant(request)
+                }
+            }
+
+            impl TryFrom<JobResponse> for $response {
+                type Error = JobResponse;
+
+                fn try_from(response: JobResponse) -> Result<Self, Self::Error> {
+                    match response {
+                        JobResponse::$variant(response) => Ok(response),
+                        other => Err(other),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+jobs! {
+    Compile(CompileRequest, CompileResponse),
+    Execute(ExecuteRequest, ExecuteResponse),
+    Format(FormatRequest, FormatResponse),
+    EditionMigration(EditionMigrationRequest, EditionMigrationResponse),
+    Clippy(ClippyRequest, ClippyResponse),
+    Miri(MiriRequest, MiriResponse),
+    Kani(KaniRequest, KaniResponse),
+    MacroExpansion(MacroExpansionRequest, MacroExpansionResponse),
+    TimePasses(TimePassesRequest, TimePassesResponse),
+    OptimizationRemarks(OptimizationRemarksRequest, OptimizationRemarksResponse),
+    Completions(CompletionsRequest, CompletionsResponse),
+    Hover(HoverRequest, HoverResponse),
+    Navigation(NavigationRequest, NavigationResponse),
+    InlayHints(InlayHintsRequest, InlayHintsResponse),
+    Bisect(BisectRequest, BisectResponse),
+    Reduce(ReduceRequest, ReduceResponse),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Edition;
+
+    #[test]
+    fn jobs_survive_serialization() {
+        let request = JobRequest::from(FormatRequest {
+            code: "fn main() {}".into(),
+            edition: Some(Edition::Rust2021),
+            lines: None,
+        });
+
+        let json = serde_json::to_string(&request).unwrap();
+        let parsed: JobRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, request);
+
+        let outcome = JobOutcome::Finished(JobResponse::Format(FormatResponse {
+            success: true,
+            code: "fn main() {}\n".into(),
+            stdout: String::new(),
+            stderr: String::new(),
+        }));
+        let json = serde_json::to_string(&outcome).unwrap();
+        let parsed: JobOutcome = serde_json::from_str(&json).unwrap();
+
+        let response: FormatResponse = parsed.into_response().unwrap();
+        assert_eq!(response.code, "fn main() {}\n");
+    }
+
+    #[test]
+    fn responses_must_match_their_request() {
+        let outcome = JobOutcome::Finished(JobResponse::Format(FormatResponse {
+            success: true,
+            code: String::new(),
+            stdout: String::new(),
+            stderr: String::new(),
+        }));
+
+        let response = outcome.into_response::<CompileResponse>();
+        assert!(matches!(response, Err(Error::MismatchedJobResponse)));
+
+        let outcome = JobOutcome::Failed {
+            message: "Docker is unreachable".into(),
+        };
+        let response = outcome.into_response::<FormatResponse>();
+        assert!(matches!(response, Err(Error::WorkerFailed { .. })));
+    }
+}
@@ -0,0 +1,141 @@
+//! Reads how each proof harness went from what `cargo kani` prints,
+//! like
+//!
+//! ```text
+//! Checking harness check_add...
+//! ...
+//! Failed Checks: attempt to add with overflow
+//!  File: "src/main.rs", line 3, in add
+//!
+//! VERIFICATION:- FAILED
+//! ```
+
+use crate::{KaniCheck, KaniHarness};
+
+const CHECKING_HARNESS: &str = "Checking harness ";
+const FAILED_CHECKS: &str = "Failed Checks: ";
+const VERIFICATION: &str = "VERIFICATION:- ";
+const SUCCESSFUL: &str = "SUCCESSFUL";
+
+pub(crate) fn harnesses(stdout: &str) -> Vec<KaniHarness> {
+    let mut harnesses = vec![];
+    let mut current: Option<KaniHarness> = None;
+    let mut lines = stdout.lines().map(str::trim).peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(name) = line.strip_prefix(CHECKING_HARNESS) {
+            harnesses.extend(current.take());
+            current = Some(KaniHarness {
+                name: name.trim_end_matches("...").into(),
+                verified: false,
+                failed_checks: vec![],
+            });
+        } else if let Some(description) = line.strip_prefix(FAILED_CHECKS) {
+            let line = lines.peek().and_then(|l| check_line(l));
+            if line.is_some() {
+                lines.next();
+            }
+            if let Some(harness) = &mut current {
+                harness.failed_checks.push(KaniCheck {
+                    description: description.into(),
+                    line,
+                });
+            }
+        } else if let Some(status) = line.strip_prefix(VERIFICATION) {
+            if let Some(harness) = &mut current {
+                harness.verified = status == SUCCESSFUL;
+            }
+        }
+    }
+
+    harnesses.extend(current);
+    harnesses
+}
+
+// Such as `File: "src/main.rs", line 3, in add`, when the file is
+// the code itself instead of the standard library
+fn check_line(location: &str) -> Option<usize> {
+    let (file, rest) = location.strip_prefix("File: ")?.split_once(", line ")?;
+    if !file.starts_with("\"src/") {
+        return None;
+    }
+    let line = rest.split(',').next()?;
+    line.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OUTPUT: &str = r#"Kani Rust Verifier 0.37.0 (cargo plugin)
+Checking harness check_abs...
+
+RESULTS:
+Check 1: check_abs.assertion.1
+         - Status: SUCCESS
+         - Description: "assertion failed: x.abs() >= 0"
+
+SUMMARY:
+ ** 0 of 1 failed
+
+VERIFICATION:- SUCCESSFUL
+Verification Time: 0.31s
+
+Checking harness check_add...
+
+SUMMARY:
+ ** 1 of 2 failed
+Failed Checks: attempt to add with overflow
+ File: "src/main.rs", line 3, in add
+
+VERIFICATION:- FAILED
+Verification Time: 0.12s
+
+Summary:
+Verification failed for - check_add
+Complete - 1 successfully verified harnesses, 1 failures, 2 total.
+"#;
+
+    #[test]
+    fn each_harness_has_a_result() {
+        assert_eq!(
+            harnesses(OUTPUT),
+            [
+                KaniHarness {
+                    name: "check_abs".into(),
+                    verified: true,
+                    failed_checks: vec![],
+                },
+                KaniHarness {
+                    name: "check_add".into(),
+                    verified: false,
+                    failed_checks: vec![KaniCheck {
+                        description: "attempt to add with overflow".into(),
+                        line: Some(3),
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unfinished_harnesses_are_not_verified() {
+        let harnesses = harnesses("Checking harness check_slow...\n");
+
+        assert_eq!(harnesses.len(), 1);
+        assert!(!harnesses[0].verified);
+    }
+
+    #[test]
+    fn checks_elsewhere_have_no_line() {
+        let output = concat!(
+            "Checking harness check_unwrap...\n",
+            "Failed Checks: called `Option::unwrap()` on a `None` value\n",
+            " File: \"/github/home/.rustup/toolchains/nightly/lib/rustlib/src/rust/library/core/src/option.rs\", line 935, in std::option::Option::<u8>::unwrap\n",
+        );
+
+        let harnesses = harnesses(output);
+
+        assert_eq!(harnesses[0].failed_checks[0].line, None);
+    }
+}
@@ -0,0 +1,7200 @@
+//! Runs Rust code in Docker containers, isolated from the host and
+//! from each other, for the Rust Playground.
+//!
+//! A [`Sandbox`] is created for each request from a [`Config`], which
+//! names the images to use and the resources each container may use.
+//! Requests, such as [`CompileRequest`], and their responses may be
+//! serialized, so that they can be handed to whatever runs them as a
+//! [`job::JobRequest`].
+//!
+//! When serialized, fields are named in camelCase and variants in
+//! kebab-case, matching the names the playground API uses where it
+//! has them, such as `"llvm-ir"` and `"2021"`. Variants with data
+//! are externally tagged, as in `{"asm": ["att", "demangle",
+//! "filter"]}`.
+
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::{OsStr, OsString},
+    fmt, io,
+    io::ErrorKind,
+    iter,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    str::FromStr,
+    string,
+    sync::Arc,
+    time::Duration,
+};
+use tempfile::TempDir;
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt},
+    process::Command,
+    sync::mpsc,
+    time,
+};
+
+use crate::{
+    circuit_breaker::CircuitBreaker,
+    spill::{OutputSpill, Spilled, SpilledFile},
+};
+
+mod abuse;
+mod analyzer;
+mod ansi;
+mod asm_cleanup;
+mod careful;
+pub mod circuit_breaker;
+mod detect;
+mod diagnostics;
+pub mod disk;
+mod hidden_tests;
+mod hint;
+pub mod job;
+mod kani;
+mod messages;
+mod nextest;
+mod oom;
+mod progress;
+mod records;
+mod reduce;
+mod remarks;
+pub mod repl;
+pub mod spill;
+mod time_passes;
+mod wrap_main;
+
+const DOCKER_PROCESS_TIMEOUT_SOFT: Duration = Duration::from_secs(10);
+
+// How much longer than the container's own timeout to wait for it
+// before giving up on it
+const DOCKER_PROCESS_TIMEOUT_GRACE: Duration = Duration::from_secs(2);
+
+// How long to wait for the last of the live output once a container
+// has stopped
+const LIVE_OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+// Pulling a new toolchain can take a while on a slow connection
+const IMAGE_PULL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+// Has cargo write what it's doing to stdout as it does it, while the
+// diagnostics are still written to stderr as they otherwise would be
+const CARGO_MESSAGE_FORMAT: &str = "--message-format=json-render-diagnostics";
+
+// Runs the tests in place of `cargo test`, writing how each went to
+// stdout. The libtest format is still experimental.
+const NEXTEST_COMMAND: [&str; 5] = [
+    "nextest",
+    "run",
+    "--no-fail-fast",
+    "--message-format",
+    "libtest-json",
+];
+const NEXTEST_EXPERIMENTAL: &str = "NEXTEST_EXPERIMENTAL_LIBTEST_JSON=1";
+// As nextest has a message format of its own
+const NEXTEST_CARGO_MESSAGE_FORMAT: &str = "--cargo-message-format=json-render-diagnostics";
+
+// Runs the program with a faked clock and the tests one at a time, so
+// that each run writes the same. Only what was built is run with the
+// clock, which would confuse cargo and the compiler.
+const DETERMINISTIC_ENV: [&str; 4] = [
+    "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER=/playground/tools/deterministic.sh",
+    "RUST_TEST_THREADS=1",
+    "NEXTEST_TEST_THREADS=1",
+    // Should the tests be shuffled, it's always the same way
+    "RUST_TEST_SHUFFLE_SEED=0",
+];
+
+// Has cargo write the diagnostics to stdout as JSON, which is the only
+// way to see their suggestions. They are shown again from there, as
+// they were rendered.
+fn cargo_diagnostics_format(color: ColorOutput, error_format: ErrorFormat) -> &'static str {
+    use self::ErrorFormat::*;
+
+    match (color, error_format) {
+        (ColorOutput::Plain, Human) => "--message-format=json",
+        (ColorOutput::Plain, Short) => "--message-format=json-diagnostic-short",
+        (ColorOutput::Ansi | ColorOutput::Html, Human) => {
+            "--message-format=json-diagnostic-rendered-ansi"
+        }
+        (ColorOutput::Ansi | ColorOutput::Html, Short) => {
+            "--message-format=json-diagnostic-rendered-ansi,json-diagnostic-short"
+        }
+    }
+}
+
+// How cargo writes what it's doing while executing, when not as it
+// usually does. Only the rendering is shortened when its messages are
+// JSON.
+fn execution_message_format(
+    progress: bool,
+    nextest: bool,
+    error_format: ErrorFormat,
+) -> Option<&'static str> {
+    use self::ErrorFormat::*;
+
+    Some(match (progress, nextest, error_format) {
+        (false, _, Human) => return None,
+        (false, false, Short) => "--message-format=short",
+        (false, true, Short) => "--cargo-message-format=short",
+        (true, false, Human) => CARGO_MESSAGE_FORMAT,
+        (true, true, Human) => NEXTEST_CARGO_MESSAGE_FORMAT,
+        (true, false, Short) => "--message-format=json-render-diagnostics,json-diagnostic-short",
+        (true, true, Short) => {
+            "--cargo-message-format=json-render-diagnostics,json-diagnostic-short"
+        }
+    })
+}
+
+const HELLO_WORLD_CODE: &str = r#"fn main() { println!("Hello, world!"); }"#;
+const HELLO_WORLD_OUTPUT: &str = "Hello, world!";
+
+const SCRATCH_PREFIX: &str = "playground";
+// Sandboxes are done with their scratch directory long before this,
+// even when bisecting
+const SCRATCH_MAX_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+const SCRATCH_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// The only place in a container with a read-only root that may be
+// written to, other than the mounted input and output
+const WRITABLE_DIR: &str = "/tmp";
+// Where the entrypoint copies the crate to in a container with a
+// read-only root, so that cargo can change its manifest and build it
+const READ_ONLY_ROOT_WORK_DIR: &str = "/tmp/playground";
+
+// `docker run` exits with this when Docker itself failed, as opposed
+// to the command inside the container
+const DOCKER_RUN_FAILED: i32 = 125;
+// How Docker reports that it failed because the image isn't there,
+// which only affects the channels that use the image
+const IMAGE_ID_PREFIX: &str = "sha256:";
+const MISSING_IMAGE: [&str; 2] = ["Unable to find image", "No such image"];
+// What Docker Desktop says when the scratch directory is somewhere it
+// doesn't share with its virtual machine
+const MOUNT_DENIED: [&str; 2] = ["Mounts denied", "not shared from"];
+
+const OUTPUT_DIR: &str = "/playground-result";
+// Used instead of mounts when the files are copied
+const COPIED_INPUT_PATH: &str = "/playground-input.rs";
+const COPIED_HIDDEN_TESTS_PATH: &str = "/playground-hidden-tests.rs";
+const COPIED_SOURCE_FILE_NAME: &str = "source.rs";
+// Put beside the output, as that is already shared with the container
+const STDIN_FILE_NAME: &str = "stdin";
+const KEPT_BINARY_FILE_NAME: &str = "playground";
+
+const REQUEST_ID_LABEL: &str = "org.rust-lang.playground.request-id";
+const INSTANCE_LABEL: &str = "org.rust-lang.playground.instance";
+
+lazy_static! {
+    // Distinguishes the containers started by this process from those
+    // started by any other playground on the same host.
+    static ref INSTANCE_ID: String = format!("{:032x}", rand::random::<u128>());
+}
+
+const SCCACHE_CONTAINER_DIR: &str = "/playground-sccache";
+const SCCACHE_STATS_FILE_NAME: &str = "sccache-stats.json";
+// Written by the container entrypoint once executions have finished
+const CPU_USAGE_FILE_NAME: &str = "cpu-usage";
+// Created by the container entrypoint when it stopped the output
+const OUTPUT_RATE_LIMITED_FILE_NAME: &str = "output-rate-limited";
+// Copied from the container's memory cgroup by the entrypoint
+const MEMORY_EVENTS_FILE_NAME: &str = "memory-events";
+
+#[derive(Debug, Deserialize)]
+struct CrateInformationInner {
+    name: String,
+    version: String,
+    id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrateInformation {
+    pub name: String,
+    pub version: String,
+    pub id: String,
+}
+
+impl From<CrateInformationInner> for CrateInformation {
+    fn from(me: CrateInformationInner) -> Self {
+        let CrateInformationInner { name, version, id } = me;
+        Self { name, version, id }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClippyLint {
+    pub name: String,
+    /// Such as `style` or `pedantic`.
+    pub group: Option<String>,
+    /// The level the lint has unless configured otherwise.
+    pub level: String,
+    pub description: String,
+}
+
+/// The versions exactly as the tools report them, so that bug reports
+/// can say precisely which toolchain was used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FullVersion {
+    pub rustc: String,
+    pub cargo: String,
+    /// Of the image that the channel was run from, such as
+    /// `sha256:0123…`.
+    pub image_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Version {
+    pub release: String,
+    pub commit_hash: String,
+    pub commit_date: String,
+}
+
+/// Deployment-specific settings shared by every sandbox.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// A host directory used as a shared sccache cache. When unset,
+    /// sccache is not used.
+    pub sccache_dir: Option<PathBuf>,
+    /// Where each sandbox makes its scratch directory. When unset,
+    /// the system's temporary directory is used.
+    pub work_dir: Option<PathBuf>,
+    /// A seccomp profile on the host that replaces the default one of
+    /// Docker for containers that run user code.
+    pub seccomp_profile: Option<PathBuf>,
+    /// Copies the code into each container and the output back out,
+    /// instead of mounting them, for when Docker cannot see the files
+    /// of this host, such as when it is remote. This cannot be used
+    /// with `read_only_root`.
+    pub copy_files: bool,
+    /// Keeps user code from changing the image. Only `/tmp` may be
+    /// written to, and the crate is built from a copy of it there,
+    /// along with the dependencies that the image prebuilt.
+    pub read_only_root: bool,
+    pub images: Images,
+    pub limits: Limits,
+    /// Replaces `limits` for requests that use a specific channel.
+    /// Dated nightlies use the limits of the nightly channel.
+    pub channel_limits: HashMap<Channel, Limits>,
+    /// When unset, regressions cannot be bisected.
+    pub bisect: Option<Bisect>,
+    /// Code larger than this, in bytes, is refused before it is
+    /// written anywhere. When unset, code of any size is accepted.
+    pub max_code_size: Option<usize>,
+    /// Includes the ID of the image that each compilation and
+    /// execution was run from in its response, which takes asking
+    /// Docker when the image is named instead of pinned.
+    pub report_image_ids: bool,
+}
+
+impl Config {
+    /// Where scratch directories are made, which may not exist yet.
+    pub fn work_dir(&self) -> PathBuf {
+        self.work_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    fn limits(&self, channel: Option<Channel>) -> &Limits {
+        channel
+            .and_then(|channel| self.channel_limits.get(&channel.latest()))
+            .unwrap_or(&self.limits)
+    }
+}
+
+/// The Docker images that provide each toolchain and tool.
+#[derive(Debug, Clone)]
+pub struct Images {
+    pub stable: String,
+    /// Older stable releases that are kept around so that they can
+    /// still be asked for, such as to show when something changed.
+    pub stable_releases: BTreeMap<RustVersion, String>,
+    pub beta: String,
+    pub nightly: String,
+    /// Older nightlies that are kept around so that they can still be
+    /// asked for, such as to reproduce a regression.
+    pub dated_nightlies: BTreeMap<NaiveDate, String>,
+    pub rustfmt: String,
+    pub clippy: String,
+    pub miri: String,
+    /// Provides `cargo kani`, for verifying proof harnesses.
+    pub kani: String,
+    /// Provides `evcxr-playground`, for REPL sessions.
+    pub evcxr: String,
+    /// Provides `rust-analyzer-playground`, for completions.
+    pub rust_analyzer: String,
+}
+
+impl Default for Images {
+    fn default() -> Self {
+        Self {
+            stable: Channel::Stable { version: None }.container_name().into(),
+            stable_releases: BTreeMap::new(),
+            beta: Channel::Beta.container_name().into(),
+            nightly: Channel::Nightly { date: None }.container_name().into(),
+            dated_nightlies: BTreeMap::new(),
+            rustfmt: "rustfmt".into(),
+            clippy: "clippy".into(),
+            miri: "miri".into(),
+            kani: "kani".into(),
+            evcxr: "evcxr".into(),
+            rust_analyzer: "rust-analyzer".into(),
+        }
+    }
+}
+
+impl Images {
+    /// Pulls every image from `registry`, except for those that name
+    /// a registry of their own.
+    pub fn in_registry(self, registry: &str) -> Self {
+        let qualify = |image: String| image_in_registry(registry, &image);
+
+        Self {
+            stable: qualify(self.stable),
+            stable_releases: self
+                .stable_releases
+                .into_iter()
+                .map(|(version, image)| (version, qualify(image)))
+                .collect(),
+            beta: qualify(self.beta),
+            nightly: qualify(self.nightly),
+            dated_nightlies: self
+                .dated_nightlies
+                .into_iter()
+                .map(|(date, image)| (date, qualify(image)))
+                .collect(),
+            rustfmt: qualify(self.rustfmt),
+            clippy: qualify(self.clippy),
+            miri: qualify(self.miri),
+            kani: qualify(self.kani),
+            evcxr: qualify(self.evcxr),
+            rust_analyzer: qualify(self.rust_analyzer),
+        }
+    }
+
+    /// Switches the latest release of `channel` to `image`. Stable
+    /// releases and dated nightlies are already pinned, so they are
+    /// left as they are.
+    pub fn set_channel(&mut self, channel: Channel, image: String) {
+        match channel {
+            Channel::Stable { version: None } => self.stable = image,
+            Channel::Beta => self.beta = image,
+            Channel::Nightly { date: None } => self.nightly = image,
+            Channel::Stable { .. } | Channel::Nightly { .. } => {}
+        }
+    }
+
+    // Requests for a release or nightly that isn't kept are turned away
+    // by `ensure_available` before getting here
+    pub fn channel(&self, channel: Channel) -> &str {
+        match channel {
+            Channel::Stable { version: None } => &self.stable,
+            Channel::Stable {
+                version: Some(version),
+            } => self
+                .stable_releases
+                .get(&version)
+                .map_or(&self.stable, String::as_str),
+            Channel::Beta => &self.beta,
+            Channel::Nightly { date: None } => &self.nightly,
+            Channel::Nightly { date: Some(date) } => self
+                .dated_nightlies
+                .get(&date)
+                .map_or(&self.nightly, String::as_str),
+        }
+    }
+
+    fn ensure_available(&self, channel: Channel) -> Result<()> {
+        match channel {
+            Channel::Stable {
+                version: Some(version),
+            } => ensure!(
+                self.stable_releases.contains_key(&version),
+                StableReleaseUnavailableSnafu { version }
+            ),
+            Channel::Nightly { date: Some(date) } => ensure!(
+                self.dated_nightlies.contains_key(&date),
+                NightlyUnavailableSnafu { date }
+            ),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Prefixes `image` with `registry`, such as
+/// `registry.example.com/playground`. Tags and digests, such as
+/// `rust-nightly@sha256:...`, are kept.
+///
+/// Like Docker, the first part of a name is a registry when it has a
+/// `.` or a `:`, or is `localhost`; such names are left as they are.
+pub fn image_in_registry(registry: &str, image: &str) -> String {
+    let has_registry = image
+        .split_once('/')
+        .is_some_and(|(first, _)| first.contains(['.', ':']) || first == "localhost");
+
+    if has_registry {
+        image.into()
+    } else {
+        format!("{}/{}", registry.trim_end_matches('/'), image)
+    }
+}
+
+/// Where bisections are run. Unlike other containers, these need to
+/// reach the network to download each nightly that is tried.
+#[derive(Debug, Clone)]
+pub struct Bisect {
+    /// Provides `cargo bisect-rustc`.
+    pub image: String,
+    /// How long a bisection may run for.
+    pub timeout: Duration,
+    /// How much CPU time each process in the container may use.
+    /// Installing the toolchains takes more than code is usually
+    /// allowed, so this is separate from [`Limits::cpu_time`].
+    pub cpu_time: Duration,
+    /// The Docker network that each toolchain is downloaded over. The
+    /// code is compiled, though never run, in the same container, so
+    /// it should only be able to reach the servers that the toolchains
+    /// come from, such as through a proxy.
+    pub network: String,
+}
+
+/// The resources that each container may use.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// In the format accepted by `docker run --memory`.
+    pub memory: String,
+    /// In the format accepted by `docker run --memory-swap`.
+    pub memory_swap: String,
+    pub pids: Option<u32>,
+    /// How much the container may write, in the format accepted by
+    /// `docker run --storage-opt size`. With a read-only root, this
+    /// limits `/tmp` instead, which must then have room for the copy
+    /// of the prebuilt dependencies.
+    pub disk: Option<String>,
+    /// How long the code in the container may run for.
+    pub timeout: Duration,
+    /// How much CPU time each process in the container may use, which
+    /// stops busy programs even when they run on several cores. When
+    /// unset, only `timeout` applies.
+    pub cpu_time: Option<Duration>,
+    /// How many bytes a second the code may write to each of stdout
+    /// and stderr, after a first burst of that many. Writing faster
+    /// stops the output, which stops programs that keep writing.
+    pub output_rate: Option<u64>,
+    /// How quickly the container may read from a block device, in the
+    /// format accepted by `docker run --device-read-bps`, such as
+    /// `/dev/sda:10mb`. This keeps code that thrashes the disk from
+    /// slowing down the other containers on the host.
+    pub device_read_bps: Option<String>,
+    /// Likewise, in the format accepted by `docker run
+    /// --device-write-bps`.
+    pub device_write_bps: Option<String>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            memory: "512m".into(),
+            memory_swap: "640m".into(),
+            pids: if cfg!(feature = "fork-bomb-prevention") {
+                Some(512)
+            } else {
+                None
+            },
+            disk: None,
+            timeout: DOCKER_PROCESS_TIMEOUT_SOFT,
+            cpu_time: None,
+            output_rate: None,
+            device_read_bps: None,
+            device_write_bps: None,
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to create temporary directory: {}", source))]
+    UnableToCreateTempDir { source: io::Error },
+    #[snafu(display("Unable to remove leftover temporary directories: {}", source))]
+    UnableToSweepScratch { source: io::Error },
+    #[snafu(display("Unable to create output directory: {}", source))]
+    UnableToCreateOutputDir { source: io::Error },
+    #[snafu(display("Unable to set permissions for output directory: {}", source))]
+    UnableToSetOutputPermissions { source: io::Error },
+    #[snafu(display("Unable to create source file: {}", source))]
+    UnableToCreateSourceFile { source: io::Error },
+    #[snafu(display("Unable to set permissions for source file: {}", source))]
+    UnableToSetSourcePermissions { source: io::Error },
+    #[snafu(display("Unable to create the file for standard input: {}", source))]
+    UnableToCreateStdinFile { source: io::Error },
+    #[snafu(display("Unable to create the binary file: {}", source))]
+    UnableToCreateBinaryFile { source: io::Error },
+    #[snafu(display("The code is {} bytes, but may be at most {} bytes", size, max_size))]
+    CodeTooLarge { size: usize, max_size: usize },
+    #[snafu(display("Unable to create sccache directory: {}", source))]
+    UnableToCreateSccacheDir { source: io::Error },
+    #[snafu(display("Unable to set permissions for sccache directory: {}", source))]
+    UnableToSetSccachePermissions { source: io::Error },
+
+    #[snafu(display("Unable to start the compiler: {}", source))]
+    UnableToStartCompiler { source: io::Error },
+    #[snafu(display("Docker was unable to start the compiler: {}", stderr))]
+    DockerFailed { stderr: String },
+    #[snafu(display("Unable to find the compiler ID"))]
+    MissingCompilerId,
+    #[snafu(display("Unable to wait for the compiler: {}", source))]
+    UnableToWaitForCompiler { source: io::Error },
+    #[snafu(display("Unable to get output from the compiler: {}", source))]
+    UnableToGetOutputFromCompiler { source: io::Error },
+    #[snafu(display("Unable to write the output to {}: {}", path.display(), source))]
+    UnableToSpillOutput { path: PathBuf, source: io::Error },
+    #[snafu(display("Unable to kill the compiler: {}", source))]
+    UnableToKillCompiler { source: io::Error },
+    #[snafu(display("Unable to remove the compiler: {}", source))]
+    UnableToRemoveCompiler { source: io::Error },
+    #[snafu(display("Compiler execution took longer than {} ms", timeout.as_millis()))]
+    CompilerExecutionTimedOut {
+        source: tokio::time::error::Elapsed,
+        timeout: Duration,
+    },
+
+    #[snafu(display("Unable to run Docker: {}", source))]
+    UnableToRunDocker { source: io::Error },
+    #[snafu(display("Docker did not respond within {} ms", timeout.as_millis()))]
+    DockerTimedOut {
+        source: tokio::time::error::Elapsed,
+        timeout: Duration,
+    },
+    #[snafu(display("Unable to reach the Docker daemon: {}", stderr))]
+    DockerUnreachable { stderr: String },
+    #[snafu(display("The {} image is not available", image))]
+    ImageMissing { image: String },
+    #[snafu(display("The image for this channel is not available: {}", stderr))]
+    ImageNotFound { stderr: String },
+    #[snafu(display(
+        "Docker is unable to mount the work directory; it must be somewhere that Docker shares from the host: {}",
+        stderr
+    ))]
+    MountDenied { stderr: String },
+    #[snafu(display("The {} image is temporarily unavailable; please try again in {} seconds", image, retry_after.as_secs()))]
+    ChannelUnavailable {
+        image: String,
+        retry_after: Duration,
+    },
+    #[snafu(display("Unable to pull the {} image: {}", image, stderr))]
+    UnableToPullImage { image: String, stderr: String },
+    #[snafu(display("The {} image was unable to run a program: {}", image, stderr))]
+    HelloWorldFailed { image: String, stderr: String },
+    #[snafu(display("Unable to list containers: {}", stderr))]
+    UnableToListContainers { stderr: String },
+    #[snafu(display("Unable to copy files to or from the container: {}", stderr))]
+    UnableToCopyFiles { stderr: String },
+    #[snafu(display("Unable to read the seccomp profile {}: {}", path.display(), source))]
+    UnableToReadSeccompProfile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Unable to read output file: {}", source))]
+    UnableToReadOutput { source: io::Error },
+    #[snafu(display("Unable to read crate information: {}", source))]
+    UnableToParseCrateInformation { source: ::serde_json::Error },
+    #[snafu(display("Output was not valid UTF-8: {}", source))]
+    OutputNotUtf8 { source: string::FromUtf8Error },
+    #[snafu(display("Output was missing"))]
+    OutputMissing,
+    #[snafu(display("Release was missing from the version output"))]
+    VersionReleaseMissing,
+    #[snafu(display("Commit hash was missing from the version output"))]
+    VersionHashMissing,
+    #[snafu(display("Commit date was missing from the version output"))]
+    VersionDateMissing,
+    #[snafu(display("No Clippy lints were found"))]
+    ClippyLintsMissing,
+    #[snafu(display("The nightly from {} is not available", date))]
+    NightlyUnavailable { date: NaiveDate },
+    #[snafu(display("Rust {} is not available", version))]
+    StableReleaseUnavailable { version: RustVersion },
+    #[snafu(display("Bisecting regressions is not available"))]
+    BisectUnavailable,
+    #[snafu(display("There is no edition newer than {} to migrate to", edition.cargo_ident()))]
+    NoNewerEdition { edition: Edition },
+    #[snafu(display("The cursor at byte {} is not within the code", offset))]
+    InvalidCursor { offset: usize },
+    #[snafu(display("The lines {} to {} are not a valid range", start, end))]
+    InvalidLineRange { start: usize, end: usize },
+    #[snafu(display("{:?} is not the path of an item", path))]
+    InvalidItemPath { path: String },
+    #[snafu(display("Unable to understand rust-analyzer: {}", source))]
+    InvalidAnalyzerResponse { source: ::serde_json::Error },
+    #[snafu(display("rust-analyzer responded to a different kind of request"))]
+    MismatchedAnalyzerResponse,
+
+    #[snafu(display("Unable to start the REPL: {}", source))]
+    UnableToStartRepl { source: io::Error },
+    #[snafu(display("Unable to talk to the REPL: {}", source))]
+    UnableToTalkToRepl { source: io::Error },
+    #[snafu(display("The REPL stopped: {}", stderr))]
+    ReplStopped { stderr: String },
+    #[snafu(display("The REPL session has ended"))]
+    ReplEnded,
+    #[snafu(display("Unable to understand the REPL: {}", source))]
+    InvalidReplResponse { source: ::serde_json::Error },
+    #[snafu(display("The REPL responded to a different kind of request"))]
+    MismatchedReplResponse,
+    #[snafu(display("The REPL failed: {}", message))]
+    ReplFailed { message: String },
+    #[snafu(display("Evaluation took longer than {} ms, so the session was ended", timeout.as_millis()))]
+    ReplTimedOut { timeout: Duration },
+
+    #[snafu(display("{}", message))]
+    WorkerFailed { message: String },
+    #[snafu(display("The worker responded to a different kind of job"))]
+    MismatchedJobResponse,
+    #[snafu(display("Unable to hand the job to a worker: {}", message))]
+    #[snafu(visibility(pub))]
+    UnableToReachWorkers { message: String },
+}
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+impl Error {
+    /// Whether the problem lies with Docker rather than the request.
+    pub fn is_docker_failure(&self) -> bool {
+        matches!(
+            self,
+            Error::UnableToStartCompiler { .. }
+                | Error::DockerFailed { .. }
+                | Error::UnableToWaitForCompiler { .. }
+                | Error::UnableToGetOutputFromCompiler { .. }
+                | Error::UnableToKillCompiler { .. }
+                | Error::UnableToRemoveCompiler { .. }
+                | Error::UnableToCopyFiles { .. }
+                | Error::UnableToRunDocker { .. }
+                | Error::DockerTimedOut { .. }
+                | Error::DockerUnreachable { .. }
+                | Error::UnableToStartRepl { .. }
+        )
+    }
+}
+
+/// Keeps track of the image of each channel apart from the others, so
+/// that one that is missing only turns away the requests for its own
+/// channel.
+pub struct ChannelHealth(HashMap<Channel, CircuitBreaker>);
+
+impl ChannelHealth {
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        let channels = [
+            Channel::Stable { version: None },
+            Channel::Beta,
+            Channel::Nightly { date: None },
+        ];
+
+        let breakers = channels
+            .iter()
+            .map(|&channel| {
+                let breaker =
+                    CircuitBreaker::new(threshold, cooldown).with_name(channel.container_name());
+                (channel, breaker)
+            })
+            .collect();
+
+        Self(breakers)
+    }
+
+    // Kept stable releases and dated nightlies share the breaker of
+    // their channel
+    fn breaker(&self, channel: Channel) -> Option<&CircuitBreaker> {
+        self.0.get(&channel.latest())
+    }
+}
+
+fn vec_to_str(v: Vec<u8>) -> Result<String> {
+    String::from_utf8(v).context(OutputNotUtf8Snafu)
+}
+
+// Such as `my_module::MyStruct`, which is all that `cargo expand`
+// should be given
+fn is_item_path(path: &str) -> bool {
+    path.trim_start_matches("::").split("::").all(|segment| {
+        let mut chars = segment.chars();
+        matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+// We must create a world-writable files (rustfmt) and directories
+// (LLVM IR) so that the process inside the Docker container can write
+// into it.
+//
+// This problem does *not* occur when using the indirection of
+// docker-machine.
+#[cfg(unix)]
+async fn set_wide_open_permissions(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, std::fs::Permissions::from_mode(0o777)).await
+}
+
+// Docker Desktop doesn't carry Windows permissions over to the
+// containers, which can write to anything that is shared with them
+#[cfg(windows)]
+async fn set_wide_open_permissions(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
+
+/// How `path` is given to `docker --volume`.
+fn host_path(path: &Path) -> OsString {
+    if cfg!(windows) {
+        windows_host_path(&path.to_string_lossy()).into()
+    } else {
+        path.as_os_str().to_os_string()
+    }
+}
+
+// Canonical paths are verbatim (`\\?\C:\...`), which Docker does not
+// understand, and backslashes are ambiguous once the container's path
+// is appended
+fn windows_host_path(path: &str) -> String {
+    let path = path.strip_prefix(r"\\?\").unwrap_or(path);
+    path.replace('\\', "/")
+}
+
+// The containers are always Linux, so code written on Windows would
+// otherwise keep its carriage returns all the way through formatting
+fn normalize_newlines(code: &str) -> Cow<'_, str> {
+    if code.contains("\r\n") {
+        code.replace("\r\n", "\n").into()
+    } else {
+        code.into()
+    }
+}
+
+macro_rules! docker_command {
+    ($($arg:expr),* $(,)?) => ({
+        let mut cmd = Command::new("docker");
+        $( cmd.arg($arg); )*
+        cmd
+    });
+}
+
+/// How a container is started.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Launch {
+    /// Right away
+    Run,
+    /// Only once `run_command_with_timeout` has copied the files into
+    /// it
+    Create,
+    /// Right away, staying attached to its standard input and output
+    /// until it stops
+    Attach,
+}
+
+fn basic_secure_docker_command(limits: &Limits, launch: Launch) -> Command {
+    secure_docker_command(limits, "none", launch)
+}
+
+fn secure_docker_command(limits: &Limits, network: &str, launch: Launch) -> Command {
+    let mut cmd = match launch {
+        Launch::Run => docker_command!("run", "--detach"),
+        Launch::Create => docker_command!("create"),
+        Launch::Attach => docker_command!("run", "--interactive", "--rm"),
+    };
+
+    cmd.args([
+        "--platform",
+        "linux/amd64",
+        // Reaps orphaned processes and forwards signals
+        "--init",
+        "--cap-drop=ALL",
+        // Needed to allow overwriting the file
+        "--cap-add=DAC_OVERRIDE",
+        "--security-opt=no-new-privileges",
+        "--workdir",
+        "/playground",
+        "--net",
+        network,
+        "--memory",
+        &limits.memory,
+        "--memory-swap",
+        &limits.memory_swap,
+        "--env",
+        &format!("PLAYGROUND_TIMEOUT={}", limits.timeout.as_secs()),
+        "--label",
+        &format!("{}={}", INSTANCE_LABEL, *INSTANCE_ID),
+    ]);
+
+    if let Some(pids) = limits.pids {
+        cmd.arg("--pids-limit").arg(pids.to_string());
+    }
+
+    if let Some(cpu_time) = limits.cpu_time {
+        // Whole seconds, and none would stop every process at once
+        let seconds = cpu_time.as_secs().max(1);
+        cmd.arg("--ulimit").arg(format!("cpu={}", seconds));
+    }
+
+    if let Some(output_rate) = limits.output_rate {
+        cmd.arg("--env")
+            .arg(format!("PLAYGROUND_OUTPUT_RATE={}", output_rate));
+    }
+
+    if let Some(bps) = &limits.device_read_bps {
+        cmd.arg("--device-read-bps").arg(bps);
+    }
+
+    if let Some(bps) = &limits.device_write_bps {
+        cmd.arg("--device-write-bps").arg(bps);
+    }
+
+    cmd.kill_on_drop(true);
+
+    cmd
+}
+
+fn build_execution_command(
+    target: Option<CompileTarget>,
+    channel: Channel,
+    mode: Mode,
+    req: impl CrateTypeRequest,
+    tests: bool,
+) -> Vec<&'static str> {
+    use self::CompileTarget::*;
+    use self::CrateType::*;
+    use self::Mode::*;
+
+    let mut cmd = vec!["cargo"];
+
+    match (target, req.crate_type(), tests) {
+        (Some(Wasm), _, _) => cmd.push("wasm"),
+        (Some(_), _, _) => cmd.push("rustc"),
+        (_, _, true) => cmd.push("test"),
+        (_, Library(_), _) => cmd.push("build"),
+        (_, _, _) => cmd.push("run"),
+    }
+
+    if mode == Release {
+        cmd.push("--release");
+    }
+
+    if let Some(target) = target {
+        cmd.extend(&["--", "-o"]);
+        if target == Hir {
+            // -Zunpretty=hir only emits the HIR, not the binary itself
+            cmd.push("/playground-result/compilation.hir");
+        } else {
+            cmd.push("/playground-result/compilation");
+        }
+
+        cmd.extend(emit_args(target, channel));
+    }
+
+    cmd
+}
+
+fn emit_args(target: CompileTarget, channel: Channel) -> Vec<&'static str> {
+    use self::CompileTarget::*;
+
+    let mut args = vec![];
+
+    match target {
+        Assembly(flavor, _, _) => {
+            use self::AssemblyFlavor::*;
+
+            args.push("--emit=asm");
+
+            // Enable extra assembly comments for nightly builds
+            if let Channel::Nightly { .. } = channel {
+                args.push("-Z");
+                args.push("asm-comments");
+            }
+
+            args.push("-C");
+            match flavor {
+                Att => args.push("llvm-args=-x86-asm-syntax=att"),
+                Intel => args.push("llvm-args=-x86-asm-syntax=intel"),
+            }
+        }
+        LlvmIr => args.push("--emit=llvm-ir"),
+        Mir => args.push("--emit=mir"),
+        Hir => args.push("-Zunpretty=hir"),
+        Wasm => { /* handled by cargo-wasm wrapper */ }
+    }
+
+    args
+}
+
+// Parses the tables printed by `-W help`. Lints look like
+//
+//     clippy::needless-return  warn  using a return statement like ...
+//
+// and groups look like
+//
+//     clippy::style  clippy::needless-return, clippy::...
+fn parse_clippy_lints(help: &str) -> Vec<ClippyLint> {
+    const PREFIX: &str = "clippy::";
+
+    let mut lints = Vec::new();
+    let mut groups = BTreeMap::new();
+
+    for line in help.lines() {
+        let (name, rest) = match line.trim().split_once(char::is_whitespace) {
+            Some(pieces) => pieces,
+            None => continue,
+        };
+        let name = match name.strip_prefix(PREFIX) {
+            Some(name) => name.replace('-', "_"),
+            None => continue,
+        };
+        let rest = rest.trim_start();
+
+        if rest.starts_with(PREFIX) {
+            // Every lint is also in this group
+            if name == "all" {
+                continue;
+            }
+
+            for member in rest.split(',') {
+                if let Some(member) = member.trim().strip_prefix(PREFIX) {
+                    groups.insert(member.replace('-', "_"), name.clone());
+                }
+            }
+        } else if let Some((level, description)) = rest.split_once(char::is_whitespace) {
+            lints.push(ClippyLint {
+                name,
+                group: None,
+                level: level.into(),
+                description: description.trim().into(),
+            });
+        }
+    }
+
+    for lint in &mut lints {
+        lint.group = groups.get(&lint.name).cloned();
+    }
+
+    lints
+}
+
+fn set_execution_environment(
+    cmd: &mut Command,
+    target: Option<CompileTarget>,
+    req: impl CrateTypeRequest
+        + EditionRequest
+        + BacktraceRequest
+        + ColorRequest
+        + MacroDebuggingRequest,
+) {
+    use self::CompileTarget::*;
+
+    if let Some(Wasm) = target {
+        cmd.args(["--env", "PLAYGROUND_NO_DEPENDENCIES=true"]);
+        cmd.args(["--env", "PLAYGROUND_RELEASE_LTO=true"]);
+    }
+
+    cmd.apply_crate_type(&req);
+    cmd.apply_edition(&req);
+    cmd.apply_backtrace(&req);
+    cmd.apply_color(&req);
+    cmd.apply_macro_debugging(&req);
+}
+
+/// Called with each [`Phase`] that a container reaches.
+pub type ReportProgress = Arc<dyn Fn(Phase) + Send + Sync>;
+
+/// How far a container has got with what it was asked to do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    ContainerStarted,
+    /// Cargo is building the code. Only reported for executions.
+    Compiling,
+    /// What was built is being run, if there is anything to run. Only
+    /// reported for executions.
+    Running,
+}
+
+pub struct Sandbox {
+    #[allow(dead_code)]
+    scratch: Scratch,
+    input_file: PathBuf,
+    hidden_tests_file: PathBuf,
+    output_dir: PathBuf,
+    config: Arc<Config>,
+    request_id: Option<String>,
+    channel_health: Option<Arc<ChannelHealth>>,
+    progress: Option<ReportProgress>,
+    live_output: Option<mpsc::Sender<LiveOutput>>,
+    output_spill: Option<OutputSpill>,
+}
+
+/// What a container writes, sent while it runs rather than once it
+/// has finished. The response still has all of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiveOutput {
+    Stdout(String),
+    Stderr(String),
+}
+
+impl Sandbox {
+    pub async fn new() -> Result<Self> {
+        Self::with_config(Default::default()).await
+    }
+
+    pub async fn with_config(config: Arc<Config>) -> Result<Self> {
+        let scratch = scratch_dir(&config).await?;
+        let input_file = scratch.path().join("input.rs");
+        let hidden_tests_file = scratch.path().join(hidden_tests::FILE_NAME);
+        let output_dir = scratch.path().join("output");
+
+        fs::create_dir(&output_dir)
+            .await
+            .context(UnableToCreateOutputDirSnafu)?;
+        set_wide_open_permissions(&output_dir)
+            .await
+            .context(UnableToSetOutputPermissionsSnafu)?;
+
+        if let Some(sccache_dir) = &config.sccache_dir {
+            // The cache is shared between all containers, each of
+            // which needs to be able to write to it.
+            fs::create_dir_all(sccache_dir)
+                .await
+                .context(UnableToCreateSccacheDirSnafu)?;
+            set_wide_open_permissions(sccache_dir)
+                .await
+                .context(UnableToSetSccachePermissionsSnafu)?;
+        }
+
+        Ok(Sandbox {
+            scratch,
+            input_file,
+            hidden_tests_file,
+            output_dir,
+            config,
+            request_id: None,
+            channel_health: None,
+            progress: None,
+            live_output: None,
+            output_spill: None,
+        })
+    }
+
+    /// Labels the containers that are started so they can be traced
+    /// back to the request that caused them.
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn with_channel_health(mut self, channel_health: Arc<ChannelHealth>) -> Self {
+        self.channel_health = Some(channel_health);
+        self
+    }
+
+    /// Tells `report` as each container starts and, for executions,
+    /// as cargo starts building and then running the code.
+    pub fn with_progress(mut self, report: impl Fn(Phase) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(report));
+        self
+    }
+
+    /// Sends what the containers write to `live_output` as they write
+    /// it. A receiver that falls behind only delays what it's sent,
+    /// not the code.
+    pub fn with_live_output(mut self, live_output: mpsc::Sender<LiveOutput>) -> Self {
+        self.live_output = Some(live_output);
+        self
+    }
+
+    /// Writes the outputs of executions that are larger than
+    /// `spill.keep` to files as they are read, so that only the start
+    /// of them is held in memory and part of the response.
+    pub fn with_output_spill(mut self, spill: OutputSpill) -> Self {
+        self.output_spill = Some(spill);
+        self
+    }
+
+    pub async fn compile(&self, req: &CompileRequest) -> Result<CompileResponse> {
+        self.config.images.ensure_available(req.channel)?;
+        self.write_source_code(&req.code).await?;
+
+        let mut success = true;
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let mut suggestions = Vec::new();
+        let mut outputs = HashMap::new();
+
+        for (target, also_emit) in req.builds() {
+            let mut command =
+                self.compile_command(target, &also_emit, req.channel, req.mode, req.tests, req);
+            if req.unoptimized_mir {
+                // The arguments at the end are passed on to the compiler
+                command.arg("-Zmir-opt-level=0");
+            }
+
+            let output = self.run(command, Some(req.channel)).await?;
+
+            success &= output.status.success();
+            let rendered = messages::render(
+                &vec_to_str(output.stdout)?,
+                &vec_to_str(output.stderr)?,
+                req.crate_type.file_name(),
+            );
+            stdout.push_str(&rendered.stdout);
+            stderr.push_str(&rendered.stderr);
+            // Each build reports the same problems again
+            for suggestion in rendered.suggestions {
+                if !suggestions.contains(&suggestion) {
+                    suggestions.push(suggestion);
+                }
+            }
+
+            for target in iter::once(target).chain(also_emit) {
+                let code = self.read_compilation(target, &mut stderr).await?;
+                outputs.insert(target.extension(), code);
+            }
+        }
+
+        let output_of = |target: &CompileTarget| -> String {
+            outputs.get(target.extension()).cloned().unwrap_or_default()
+        };
+        let code = output_of(&req.target);
+        let extra_outputs = req
+            .extra_targets
+            .iter()
+            .map(|target| (*target, output_of(target)))
+            .collect();
+
+        let diagnostics = diagnostics::count(&stderr);
+        let disk_quota_exceeded = !success && disk::is_full(&stderr);
+
+        if req.color == ColorOutput::Html {
+            stdout = crate::ansi::to_html(&stdout);
+            stderr = crate::ansi::to_html(&stderr);
+        }
+
+        let sccache = self.sccache_stats().await?;
+        let hint = if success {
+            None
+        } else {
+            hint::for_failure(req.channel, &stderr)
+        };
+
+        Ok(CompileResponse {
+            success,
+            code,
+            extra_outputs,
+            stdout,
+            stderr,
+            sccache,
+            hint,
+            diagnostics,
+            suggestions,
+            image_id: self.reported_image_id(req.channel).await?,
+            disk_quota_exceeded,
+        })
+    }
+
+    async fn read_compilation(&self, target: CompileTarget, stderr: &mut String) -> Result<String> {
+        // The compiler writes the file to a name like
+        // `compilation-3b75174cac3d47fb.ll`, so we just find the
+        // first with the right extension.
+        async fn path_to_first_file_with_extension(
+            dir: &Path,
+            extension: &OsStr,
+        ) -> Result<Option<PathBuf>> {
+            let mut files = fs::read_dir(dir).await.context(UnableToReadOutputSnafu)?;
+
+            while let Some(entry) = files.next_entry().await.transpose() {
+                if let Ok(entry) = entry {
+                    let path = entry.path();
+                    if path.extension() == Some(extension) {
+                        return Ok(Some(path));
+                    }
+                }
+            }
+
+            Ok(None)
+        }
+
+        let file = path_to_first_file_with_extension(&self.output_dir, target.extension()).await?;
+
+        let mut code = match file {
+            Some(file) => read(&file).await?.unwrap_or_default(),
+            None => {
+                // If we didn't find the file, it's *most* likely that
+                // the user's code was invalid. Tack on our own error
+                // to the compiler's error instead of failing the
+                // request.
+                use std::fmt::Write;
+                write!(stderr, "\nUnable to locate file for {} output", target)
+                    .expect("Unable to write to a string");
+                String::new()
+            }
+        };
+
+        if let CompileTarget::Assembly(_, demangle, process) = target {
+            if demangle == DemangleAssembly::Demangle {
+                code = crate::asm_cleanup::demangle_asm(&code);
+            }
+
+            if process == ProcessAssembly::Filter {
+                code = crate::asm_cleanup::filter_asm(&code);
+            }
+        } else if CompileTarget::Hir == target {
+            // TODO: Run rustfmt on the generated HIR.
+        }
+
+        Ok(code)
+    }
+
+    pub async fn execute(&self, req: &ExecuteRequest) -> Result<ExecuteResponse> {
+        self.config.images.ensure_available(req.channel)?;
+        self.write_source_code(&req.source()).await?;
+        if let Some(tests) = &req.hidden_tests {
+            self.write_hidden_tests(tests).await?;
+        }
+        self.write_stdin(&req.stdin).await?;
+        let command = self.execute_command(req);
+
+        let extras = Extras {
+            follow_cargo: true,
+            records: req.output_records,
+            spill: true,
+        };
+        let (mut output, mut captured) = self.run_with(command, Some(req.channel), extras).await?;
+        if self.progress.is_some() {
+            output.stdout = progress::strip_messages(&output.stdout);
+            captured.records = captured.records.map(progress::strip_message_records);
+            self.filter_spilled(
+                &mut output.stdout,
+                &mut captured.stdout,
+                progress::keeps_line(),
+            )
+            .await?;
+        }
+        let mut test_results = None;
+        if req.uses_nextest() {
+            let (rest, mut results) = nextest::results(&output.stdout);
+            output.stdout = rest;
+            captured.records = captured.records.map(nextest::strip_event_records);
+            if captured.stdout.is_some() {
+                // The events that were kept in memory are in the file too
+                results.clear();
+                let keeps_line = nextest::keeps_line(&mut results);
+                self.filter_spilled(&mut output.stdout, &mut captured.stdout, keeps_line)
+                    .await?;
+            }
+            test_results = Some(results);
+        }
+        let diagnostics = diagnostics::count(&String::from_utf8_lossy(&output.stderr));
+        let careful_checks = if req.careful {
+            careful::failed_checks(&String::from_utf8_lossy(&output.stderr))
+        } else {
+            vec![]
+        };
+
+        let mut response = self.execute_response(output, captured, req.color).await?;
+        response.diagnostics = diagnostics;
+        response.test_results = test_results;
+        response.careful_checks = careful_checks;
+        if let Some(tests) = &req.hidden_tests {
+            let results = response.test_results.as_deref().unwrap_or_default();
+            response.success &= hidden_tests::all_passed(tests, results);
+        }
+        response.image_id = self.reported_image_id(req.channel).await?;
+        if !response.success {
+            response.hint = hint::for_failure(req.channel, &response.stderr);
+        }
+        if req.keeps_binary() {
+            response.binary = self.read_kept_binary().await?;
+        }
+        Ok(response)
+    }
+
+    /// Runs a binary that an earlier execution kept, with different
+    /// arguments or input but without building it again.
+    pub async fn rerun(&self, req: &RerunRequest, binary: &[u8]) -> Result<ExecuteResponse> {
+        self.config.images.ensure_available(req.channel)?;
+        // There's nothing to build, but the container expects a crate
+        self.write_source_code("").await?;
+        self.write_stdin(&req.stdin).await?;
+        self.write_binary(binary).await?;
+        let command = self.rerun_command(req);
+
+        let extras = Extras {
+            spill: true,
+            ..Extras::default()
+        };
+        let (output, captured) = self.run_with(command, Some(req.channel), extras).await?;
+
+        let mut response = self.execute_response(output, captured, req.color).await?;
+        response.image_id = self.reported_image_id(req.channel).await?;
+        Ok(response)
+    }
+
+    async fn execute_response(
+        &self,
+        output: std::process::Output,
+        captured: Captured,
+        color: ColorOutput,
+    ) -> Result<ExecuteResponse> {
+        let Captured {
+            mut records,
+            stdout: spilled_stdout,
+            stderr: spilled_stderr,
+        } = captured;
+        let mut stdout = vec_to_str(output.stdout)?;
+        let mut stderr = vec_to_str(output.stderr)?;
+
+        let out_of_memory = self.out_of_memory().await?;
+        if out_of_memory {
+            tracing::debug!("An execution ran out of memory");
+        }
+
+        // Which also stops it with the "Killed" of a timeout
+        let abuse_suspected = !output.status.success()
+            && !out_of_memory
+            && abuse::suspected(&stdout, &stderr, self.cpu_usage().await?);
+        if abuse_suspected {
+            tracing::warn!("Stopped a busy execution that wrote nothing");
+        }
+
+        let output_rate_limited = self.output_rate_limited().await;
+        if output_rate_limited {
+            tracing::warn!("Stopped an execution that wrote too quickly");
+        }
+
+        let disk_quota_exceeded = !output.status.success() && disk::is_full(&stderr);
+        if disk_quota_exceeded {
+            tracing::debug!("An execution ran out of disk");
+        }
+
+        if color == ColorOutput::Html {
+            stdout = crate::ansi::to_html(&stdout);
+            stderr = crate::ansi::to_html(&stderr);
+            for record in records.iter_mut().flatten() {
+                record.text = crate::ansi::to_html(&record.text);
+            }
+        }
+
+        Ok(ExecuteResponse {
+            success: output.status.success(),
+            stdout,
+            stderr,
+            sccache: self.sccache_stats().await?,
+            hint: None,
+            binary: None,
+            records,
+            diagnostics: DiagnosticCounts::default(),
+            test_results: None,
+            careful_checks: vec![],
+            image_id: None,
+            abuse_suspected,
+            output_rate_limited,
+            out_of_memory,
+            disk_quota_exceeded,
+            spilled: Spilled {
+                stdout: spilled_stdout.map(Arc::new),
+                stderr: spilled_stderr.map(Arc::new),
+            },
+        })
+    }
+
+    /// Only the lines of a spilled output that `keep_line` is true
+    /// for, with `output` becoming the start of them. Outputs that
+    /// weren't spilled have already been filtered in memory.
+    async fn filter_spilled(
+        &self,
+        output: &mut Vec<u8>,
+        spilled: &mut Option<SpilledFile>,
+        keep_line: impl FnMut(&[u8]) -> bool,
+    ) -> Result<()> {
+        if let (Some(file), Some(spill)) = (spilled.take(), &self.output_spill) {
+            let (kept, file) = spill::filter(file, spill, keep_line).await?;
+            *output = kept;
+            *spilled = file;
+        }
+        Ok(())
+    }
+
+    pub async fn format(&self, req: &FormatRequest) -> Result<FormatResponse> {
+        if let Some(LineRange { start, end }) = req.lines {
+            ensure!(
+                start >= 1 && start <= end,
+                InvalidLineRangeSnafu { start, end }
+            );
+        }
+
+        self.write_source_code(&req.code).await?;
+        let command = self.format_command(req);
+
+        let output = self.run(command, None).await?;
+
+        Ok(FormatResponse {
+            success: output.status.success(),
+            code: read(self.input_file.as_ref())
+                .await?
+                .context(OutputMissingSnafu)?,
+            stdout: vec_to_str(output.stdout)?,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
+    /// Changes the code with `cargo fix --edition` so that it keeps
+    /// working in the edition after the one it was written for.
+    pub async fn migrate_edition(
+        &self,
+        req: &EditionMigrationRequest,
+    ) -> Result<EditionMigrationResponse> {
+        let edition = req.edition.next().context(NoNewerEditionSnafu {
+            edition: req.edition,
+        })?;
+
+        self.write_source_code(&req.code).await?;
+        let command = self.edition_migration_command(req);
+
+        let channel = Channel::Stable { version: None };
+        let output = self.run(command, Some(channel)).await?;
+
+        Ok(EditionMigrationResponse {
+            success: output.status.success(),
+            code: read(self.input_file.as_ref())
+                .await?
+                .context(OutputMissingSnafu)?,
+            edition,
+            stdout: vec_to_str(output.stdout)?,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
+    pub async fn clippy(&self, req: &ClippyRequest) -> Result<ClippyResponse> {
+        self.write_source_code(&req.code).await?;
+        let command = self.clippy_command(req);
+
+        let output = self.run(command, None).await?;
+        let rendered = messages::render(
+            &vec_to_str(output.stdout)?,
+            &vec_to_str(output.stderr)?,
+            req.crate_type.file_name(),
+        );
+
+        Ok(ClippyResponse {
+            success: output.status.success(),
+            stdout: rendered.stdout,
+            diagnostics: diagnostics::count(&rendered.stderr),
+            stderr: rendered.stderr,
+            suggestions: rendered.suggestions,
+        })
+    }
+
+    pub async fn miri(&self, req: &MiriRequest) -> Result<MiriResponse> {
+        self.write_source_code(&req.code).await?;
+        let command = self.miri_command(req);
+
+        let output = self.run(command, None).await?;
+
+        Ok(MiriResponse {
+            success: output.status.success(),
+            stdout: vec_to_str(output.stdout)?,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
+    /// Verifies each `#[kani::proof]` harness in the code with Kani,
+    /// which uses a toolchain of its own.
+    pub async fn kani(&self, req: &KaniRequest) -> Result<KaniResponse> {
+        self.write_source_code(&req.code).await?;
+        let command = self.kani_command(req);
+
+        let output = self.run(command, None).await?;
+        let stdout = vec_to_str(output.stdout)?;
+
+        Ok(KaniResponse {
+            success: output.status.success(),
+            harnesses: kani::harnesses(&stdout),
+            stdout,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
+    pub async fn macro_expansion(
+        &self,
+        req: &MacroExpansionRequest,
+    ) -> Result<MacroExpansionResponse> {
+        if let Some(path) = &req.expand_item {
+            ensure!(is_item_path(path), InvalidItemPathSnafu { path });
+        }
+
+        self.write_source_code(&req.code).await?;
+        let command = self.macro_expansion_command(req);
+
+        let output = self
+            .run(command, Some(Channel::Nightly { date: None }))
+            .await?;
+
+        Ok(MacroExpansionResponse {
+            success: output.status.success(),
+            stdout: vec_to_str(output.stdout)?,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
+    /// Builds the code on nightly with `-Ztime-passes`, to see where
+    /// the compiler spends its time.
+    pub async fn time_passes(&self, req: &TimePassesRequest) -> Result<TimePassesResponse> {
+        self.write_source_code(&req.code).await?;
+        let command = self.time_passes_command(req);
+
+        let output = self
+            .run(command, Some(Channel::Nightly { date: None }))
+            .await?;
+
+        let (passes, stderr) = time_passes::split(&vec_to_str(output.stderr)?);
+
+        Ok(TimePassesResponse {
+            success: output.status.success(),
+            passes,
+            stdout: vec_to_str(output.stdout)?,
+            stderr,
+        })
+    }
+
+    /// Builds the code with `-Cremark=all`, to see what LLVM did and
+    /// didn't manage to optimize.
+    pub async fn optimization_remarks(
+        &self,
+        req: &OptimizationRemarksRequest,
+    ) -> Result<OptimizationRemarksResponse> {
+        self.write_source_code(&req.code).await?;
+        let command = self.optimization_remarks_command(req);
+
+        let output = self.run(command, Some(req.channel)).await?;
+
+        let (remarks, stderr) = remarks::split(&vec_to_str(output.stderr)?);
+
+        Ok(OptimizationRemarksResponse {
+            success: output.status.success(),
+            remarks,
+            stdout: vec_to_str(output.stdout)?,
+            stderr,
+        })
+    }
+
+    /// Asks rust-analyzer what could be written at the cursor.
+    pub async fn completions(&self, req: &CompletionsRequest) -> Result<CompletionsResponse> {
+        let (response, stderr) = self
+            .ask_rust_analyzer("completions", req, &req.code, Some(req.offset))
+            .await?;
+
+        let completions = match response {
+            Some(analyzer::DriverResponse::Completions(completions)) => completions,
+            Some(_) => return MismatchedAnalyzerResponseSnafu.fail(),
+            None => vec![],
+        };
+
+        Ok(CompletionsResponse {
+            success: stderr.is_none(),
+            completions,
+            stderr: stderr.unwrap_or_default(),
+        })
+    }
+
+    /// Asks rust-analyzer about the symbol at the cursor, such as its
+    /// type and documentation.
+    pub async fn hover(&self, req: &HoverRequest) -> Result<HoverResponse> {
+        let (response, stderr) = self
+            .ask_rust_analyzer("hover", req, &req.code, Some(req.offset))
+            .await?;
+
+        let hover = match response {
+            Some(analyzer::DriverResponse::Hover(hover)) => {
+                hover.map(|hover| analyzer::hover(&req.code, hover))
+            }
+            Some(_) => return MismatchedAnalyzerResponseSnafu.fail(),
+            None => None,
+        };
+
+        Ok(HoverResponse {
+            success: stderr.is_none(),
+            hover,
+            stderr: stderr.unwrap_or_default(),
+        })
+    }
+
+    /// Asks rust-analyzer where the symbol at the cursor is defined
+    /// or used, which may be in the sources of the standard library.
+    pub async fn navigate(&self, req: &NavigationRequest) -> Result<NavigationResponse> {
+        let operation = match req.target {
+            NavigationTarget::Definition => "definition",
+            NavigationTarget::References => "references",
+        };
+        let (response, stderr) = self
+            .ask_rust_analyzer(operation, req, &req.code, Some(req.offset))
+            .await?;
+
+        let locations = match response {
+            Some(analyzer::DriverResponse::Locations(locations)) => locations
+                .into_iter()
+                .map(|location| analyzer::location(&req.code, location))
+                .collect(),
+            Some(_) => return MismatchedAnalyzerResponseSnafu.fail(),
+            None => vec![],
+        };
+
+        Ok(NavigationResponse {
+            success: stderr.is_none(),
+            locations,
+            stderr: stderr.unwrap_or_default(),
+        })
+    }
+
+    /// The types that rust-analyzer infers and the names of the
+    /// parameters that arguments are passed to, to be shown in the
+    /// code where they apply.
+    pub async fn inlay_hints(&self, req: &InlayHintsRequest) -> Result<InlayHintsResponse> {
+        let (response, stderr) = self
+            .ask_rust_analyzer("inlay-hints", req, &req.code, None)
+            .await?;
+
+        let hints = match response {
+            Some(analyzer::DriverResponse::InlayHints(hints)) => hints
+                .into_iter()
+                .filter_map(|hint| analyzer::inlay_hint(&req.code, hint))
+                .collect(),
+            Some(_) => return MismatchedAnalyzerResponseSnafu.fail(),
+            None => vec![],
+        };
+
+        Ok(InlayHintsResponse {
+            success: stderr.is_none(),
+            hints,
+            stderr: stderr.unwrap_or_default(),
+        })
+    }
+
+    // Either what rust-analyzer-playground answered or, when it
+    // failed, what it said about why
+    async fn ask_rust_analyzer(
+        &self,
+        operation: &str,
+        req: impl CrateTypeRequest + EditionRequest,
+        code: &str,
+        cursor: Option<usize>,
+    ) -> Result<(Option<analyzer::DriverResponse>, Option<String>)> {
+        let position = cursor
+            .map(|offset| analyzer::position(code, offset).context(InvalidCursorSnafu { offset }))
+            .transpose()?;
+
+        self.write_source_code(code).await?;
+        let command = self.rust_analyzer_command(operation, req, position);
+
+        let output = self.run(command, None).await?;
+
+        if output.status.success() {
+            let response =
+                serde_json::from_slice(&output.stdout).context(InvalidAnalyzerResponseSnafu)?;
+            Ok((Some(response), None))
+        } else {
+            Ok((None, Some(vec_to_str(output.stderr)?)))
+        }
+    }
+
+    pub async fn bisect(&self, req: &BisectRequest) -> Result<BisectResponse> {
+        let bisect = self
+            .config
+            .bisect
+            .as_ref()
+            .context(BisectUnavailableSnafu)?;
+
+        self.write_source_code(&req.code).await?;
+        let command = self.bisect_command(req, bisect);
+
+        let (output, _) = run_command_with_timeout(
+            command,
+            bisect.timeout + DOCKER_PROCESS_TIMEOUT_GRACE,
+            self.files(),
+            None,
+            self.live_output.as_ref(),
+            false,
+            None,
+        )
+        .await?;
+        let stdout = vec_to_str(output.stdout)?;
+        let stderr = vec_to_str(output.stderr)?;
+        let report = BisectReport::parse(&stdout, &stderr);
+
+        Ok(BisectResponse {
+            success: output.status.success(),
+            regressed_nightly: report.regressed_nightly,
+            commit_range: report.commit_range,
+            regressed_commit: report.regressed_commit,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// Removes as much of the code as it can while the compiler still
+    /// shows the problem, compiling it each time.
+    pub async fn reduce(&self, req: &ReduceRequest) -> Result<ReduceResponse> {
+        let compile = |code: String| CompileRequest {
+            // Far enough for problems in code generation to show
+            target: CompileTarget::LlvmIr,
+            extra_targets: vec![],
+            unoptimized_mir: false,
+            channel: req.channel,
+            crate_type: req.crate_type,
+            mode: Mode::Debug,
+            edition: req.edition,
+            tests: false,
+            backtrace: false,
+            color: ColorOutput::Plain,
+            code,
+            trace_macros: false,
+            macro_backtrace: false,
+            error_format: ErrorFormat::default(),
+        };
+
+        let original = self.compile(&compile(req.code.clone())).await?;
+        let mut attempts = 1;
+
+        let preserved = match &req.preserved {
+            Preserved::Error(text) if text.is_empty() => {
+                reduce::first_error(&original.stderr).map(Preserved::Error)
+            }
+            preserved => Some(preserved.clone()),
+        };
+        let preserved = match preserved {
+            Some(preserved) if preserved.is_shown_by(&original.stderr) => preserved,
+            _ => {
+                return Ok(ReduceResponse {
+                    reproduced: false,
+                    code: req.code.clone(),
+                    attempts,
+                    stderr: original.stderr,
+                })
+            }
+        };
+
+        let mut reducer = reduce::Reducer::new(&req.code);
+        let mut stderr = original.stderr;
+        while attempts < reduce::MAX_ATTEMPTS {
+            let candidate = match reducer.next_candidate() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+
+            let response = self.compile(&compile(candidate)).await?;
+            attempts += 1;
+
+            let kept = preserved.is_shown_by(&response.stderr);
+            if kept {
+                stderr = response.stderr;
+            }
+            reducer.record(kept);
+        }
+
+        Ok(ReduceResponse {
+            reproduced: true,
+            code: reducer.code(),
+            attempts,
+            stderr,
+        })
+    }
+
+    pub async fn crates(&self) -> Result<Vec<CrateInformation>> {
+        let mut command = basic_secure_docker_command(
+            self.config.limits(Some(Channel::Stable { version: None })),
+            self.launch(),
+        );
+        command.args([self
+            .config
+            .images
+            .channel(Channel::Stable { version: None })]);
+        command.args(["cat", "crate-information.json"]);
+
+        let output = self
+            .run(command, Some(Channel::Stable { version: None }))
+            .await?;
+
+        let crate_info: Vec<CrateInformationInner> =
+            ::serde_json::from_slice(&output.stdout).context(UnableToParseCrateInformationSnafu)?;
+
+        let crates = crate_info.into_iter().map(Into::into).collect();
+
+        Ok(crates)
+    }
+
+    pub async fn version(&self, channel: Channel) -> Result<Version> {
+        let mut command =
+            basic_secure_docker_command(self.config.limits(Some(channel)), self.launch());
+        command.args([self.config.images.channel(channel)]);
+        command.args(["rustc", "--version", "--verbose"]);
+
+        let output = self.run(command, Some(channel)).await?;
+        let version_output = vec_to_str(output.stdout)?;
+
+        let mut info: BTreeMap<String, String> = version_output
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let mut pieces = line.splitn(2, ':').fuse();
+                match (pieces.next(), pieces.next()) {
+                    (Some(name), Some(value)) => Some((name.trim().into(), value.trim().into())),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let release = info.remove("release").context(VersionReleaseMissingSnafu)?;
+        let commit_hash = info
+            .remove("commit-hash")
+            .context(VersionHashMissingSnafu)?;
+        let commit_date = info
+            .remove("commit-date")
+            .context(VersionDateMissingSnafu)?;
+
+        Ok(Version {
+            release,
+            commit_hash,
+            commit_date,
+        })
+    }
+
+    pub async fn full_version(&self, channel: Channel) -> Result<FullVersion> {
+        let image = self.config.images.channel(channel);
+        let rustc = self.tool_output(image, Some(channel), &["rustc", "--version"]);
+        let cargo = self.tool_output(image, Some(channel), &["cargo", "--version"]);
+        let (rustc, cargo, image_id) = futures::try_join!(rustc, cargo, pinned_image_id(image))?;
+
+        Ok(FullVersion {
+            rustc,
+            cargo,
+            image_id,
+        })
+    }
+
+    /// The ID of the image that `channel` is run from, when image IDs
+    /// are reported.
+    async fn reported_image_id(&self, channel: Channel) -> Result<Option<String>> {
+        if !self.config.report_image_ids {
+            return Ok(None);
+        }
+        pinned_image_id(self.config.images.channel(channel))
+            .await
+            .map(Some)
+    }
+
+    /// The targets that code can be compiled for, such as
+    /// `wasm32-unknown-unknown`.
+    pub async fn targets(&self, channel: Channel) -> Result<Vec<String>> {
+        let image = self.config.images.channel(channel);
+        let known = self.tool_output(image, Some(channel), &["rustc", "--print", "target-list"]);
+        let installed = self.tool_output(
+            image,
+            Some(channel),
+            &["rustup", "target", "list", "--installed"],
+        );
+        let (known, installed) = futures::try_join!(known, installed)?;
+
+        let known: BTreeSet<_> = known.lines().map(str::trim).collect();
+        let targets = installed
+            .lines()
+            .map(str::trim)
+            .filter(|target| known.contains(target))
+            .map(Into::into)
+            .collect();
+
+        Ok(targets)
+    }
+
+    pub async fn full_version_rustfmt(&self) -> Result<String> {
+        self.tool_output(&self.config.images.rustfmt, None, &["rustfmt", "--version"])
+            .await
+    }
+
+    pub async fn full_version_clippy(&self) -> Result<String> {
+        self.tool_output(
+            &self.config.images.clippy,
+            None,
+            &["cargo", "clippy", "--version"],
+        )
+        .await
+    }
+
+    async fn tool_output(
+        &self,
+        image: &str,
+        channel: Option<Channel>,
+        args: &[&str],
+    ) -> Result<String> {
+        let mut command = basic_secure_docker_command(self.config.limits(channel), self.launch());
+        command.arg(image).args(args);
+
+        let output = self.run(command, channel).await?;
+        Ok(vec_to_str(output.stdout)?.trim().into())
+    }
+
+    pub async fn version_rustfmt(&self) -> Result<Version> {
+        let mut command = basic_secure_docker_command(&self.config.limits, self.launch());
+        command.arg(&self.config.images.rustfmt);
+        command.args(["cargo", "fmt", "--version"]);
+        self.cargo_tool_version(command).await
+    }
+
+    pub async fn version_clippy(&self) -> Result<Version> {
+        let mut command = basic_secure_docker_command(&self.config.limits, self.launch());
+        command.arg(&self.config.images.clippy);
+        command.args(["cargo", "clippy", "--version"]);
+        self.cargo_tool_version(command).await
+    }
+
+    pub async fn clippy_lints(&self) -> Result<Vec<ClippyLint>> {
+        let mut command = basic_secure_docker_command(&self.config.limits, self.launch());
+        command.arg(&self.config.images.clippy);
+        // Describes every lint, including Clippy's, without compiling anything
+        command.args(["clippy-driver", "-W", "help"]);
+
+        let output = self.run(command, None).await?;
+        let lints = parse_clippy_lints(&vec_to_str(output.stdout)?);
+        ensure!(!lints.is_empty(), ClippyLintsMissingSnafu);
+
+        Ok(lints)
+    }
+
+    pub async fn version_miri(&self) -> Result<Version> {
+        let mut command = basic_secure_docker_command(&self.config.limits, self.launch());
+        command.arg(&self.config.images.miri);
+        command.args(["cargo", "miri", "--version"]);
+        self.cargo_tool_version(command).await
+    }
+
+    // Parses versions of the shape `toolname 0.0.0 (0000000 0000-00-00)`
+    async fn cargo_tool_version(&self, command: Command) -> Result<Version> {
+        let output = self.run(command, None).await?;
+        let version_output = vec_to_str(output.stdout)?;
+        let mut parts = version_output.split_whitespace().fuse().skip(1);
+
+        let release = parts.next().unwrap_or("").into();
+        let commit_hash = parts.next().unwrap_or("").trim_start_matches('(').into();
+        let commit_date = parts.next().unwrap_or("").trim_end_matches(')').into();
+
+        Ok(Version {
+            release,
+            commit_hash,
+            commit_date,
+        })
+    }
+
+    // The container entrypoint dumps the statistics of its sccache
+    // server into the output directory once the command completes.
+    async fn sccache_stats(&self) -> Result<Option<SccacheStats>> {
+        if self.config.sccache_dir.is_none() {
+            return Ok(None);
+        }
+
+        let stats = read(&self.output_dir.join(SCCACHE_STATS_FILE_NAME)).await?;
+        Ok(stats.as_deref().and_then(SccacheStats::from_json))
+    }
+
+    // Also from the entrypoint, for executions
+    async fn cpu_usage(&self) -> Result<Option<abuse::CpuUsage>> {
+        let usage = read(&self.output_dir.join(CPU_USAGE_FILE_NAME)).await?;
+        Ok(usage.as_deref().and_then(abuse::parse_usage))
+    }
+
+    // Also from the entrypoint, for any command
+    async fn output_rate_limited(&self) -> bool {
+        fs::metadata(self.output_dir.join(OUTPUT_RATE_LIMITED_FILE_NAME))
+            .await
+            .is_ok()
+    }
+
+    // Also from the entrypoint, for any command
+    async fn out_of_memory(&self) -> Result<bool> {
+        let events = read(&self.output_dir.join(MEMORY_EVENTS_FILE_NAME)).await?;
+        Ok(events.as_deref().is_some_and(oom::killed))
+    }
+
+    async fn write_source_code(&self, code: &str) -> Result<()> {
+        // Servers refuse these before they get here
+        if let Some(max_size) = self.config.max_code_size {
+            let size = code.len();
+            ensure!(size <= max_size, CodeTooLargeSnafu { size, max_size });
+        }
+
+        let code = normalize_newlines(code);
+        fs::write(&self.input_file, code.as_bytes())
+            .await
+            .context(UnableToCreateSourceFileSnafu)?;
+        set_wide_open_permissions(&self.input_file)
+            .await
+            .context(UnableToSetSourcePermissionsSnafu)?;
+
+        tracing::debug!(
+            bytes = code.len(),
+            path = %self.input_file.display(),
+            "Wrote source code"
+        );
+        Ok(())
+    }
+
+    async fn write_hidden_tests(&self, tests: &str) -> Result<()> {
+        let tests = normalize_newlines(tests);
+        fs::write(&self.hidden_tests_file, tests.as_bytes())
+            .await
+            .context(UnableToCreateSourceFileSnafu)?;
+        set_wide_open_permissions(&self.hidden_tests_file)
+            .await
+            .context(UnableToSetSourcePermissionsSnafu)
+    }
+
+    async fn write_stdin(&self, stdin: &str) -> Result<()> {
+        if stdin.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.output_dir.join(STDIN_FILE_NAME);
+        fs::write(&path, stdin)
+            .await
+            .context(UnableToCreateStdinFileSnafu)?;
+        set_wide_open_permissions(&path)
+            .await
+            .context(UnableToCreateStdinFileSnafu)
+    }
+
+    async fn write_binary(&self, binary: &[u8]) -> Result<()> {
+        let path = self.output_dir.join(KEPT_BINARY_FILE_NAME);
+        fs::write(&path, binary)
+            .await
+            .context(UnableToCreateBinaryFileSnafu)?;
+        set_wide_open_permissions(&path)
+            .await
+            .context(UnableToCreateBinaryFileSnafu)
+    }
+
+    async fn read_kept_binary(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.output_dir.join(KEPT_BINARY_FILE_NAME)).await {
+            Ok(binary) => Ok(Some(binary)),
+            // Such as when the build failed
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(UnableToReadOutputSnafu),
+        }
+    }
+
+    fn compile_command(
+        &self,
+        target: CompileTarget,
+        also_emit: &[CompileTarget],
+        channel: Channel,
+        mode: Mode,
+        tests: bool,
+        req: impl CrateTypeRequest
+            + EditionRequest
+            + BacktraceRequest
+            + ColorRequest
+            + ErrorFormatRequest
+            + MacroDebuggingRequest,
+    ) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type()), Some(channel));
+        set_execution_environment(&mut cmd, Some(target), &req);
+        self.apply_sccache(&mut cmd);
+
+        let mut execution_cmd = build_execution_command(Some(target), channel, mode, &req, tests);
+        for &target in also_emit {
+            execution_cmd.extend(emit_args(target, channel));
+        }
+        // The wasm wrapper builds with arguments of its own
+        if target != CompileTarget::Wasm {
+            // Before the arguments that are passed on to the compiler
+            let at = execution_cmd
+                .iter()
+                .position(|&arg| arg == "--")
+                .unwrap_or(execution_cmd.len());
+            execution_cmd.insert(
+                at,
+                cargo_diagnostics_format(req.color(), req.error_format()),
+            );
+        }
+
+        cmd.arg(self.config.images.channel(channel))
+            .args(&execution_cmd);
+
+        tracing::debug!(command = ?cmd, "Compilation command");
+
+        cmd
+    }
+
+    fn execute_command(&self, req: &ExecuteRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type), Some(req.channel));
+        set_execution_environment(&mut cmd, None, req);
+        self.apply_sccache(&mut cmd);
+        cmd.apply_stdin(req);
+        cmd.apply_cpu_usage();
+        if req.hidden_tests.is_some() {
+            self.mount_hidden_tests(&mut cmd);
+        }
+        if req.deterministic {
+            for env in DETERMINISTIC_ENV {
+                cmd.args(["--env", env]);
+            }
+        }
+
+        if req.keeps_binary() {
+            let profile = match req.mode {
+                Mode::Debug => "debug",
+                Mode::Release => "release",
+            };
+            // The entrypoint copies it out once the program has run
+            cmd.args([
+                "--env",
+                &format!("PLAYGROUND_KEEP_BINARY=target/{}/playground", profile),
+            ]);
+        }
+
+        let tests = req.runs_tests();
+        let mut execution_cmd = build_execution_command(None, req.channel, req.mode, req, tests);
+        let nextest = req.uses_nextest();
+        if nextest {
+            // In place of `test`
+            execution_cmd.splice(1..2, NEXTEST_COMMAND);
+            cmd.args(["--env", NEXTEST_EXPERIMENTAL]);
+        }
+        if req.careful {
+            // Before whatever it would otherwise run
+            execution_cmd.insert(1, "careful");
+        }
+        let message_format =
+            execution_message_format(self.progress.is_some(), nextest, req.error_format);
+        execution_cmd.extend(message_format);
+
+        cmd.arg(self.config.images.channel(req.channel))
+            .args(&execution_cmd);
+
+        // Libraries are only built, so there's nothing to pass them to
+        if (tests || req.crate_type == CrateType::Binary) && !req.args.is_empty() {
+            cmd.arg("--").args(&req.args);
+        }
+
+        tracing::debug!(command = ?cmd, "Execution command");
+
+        cmd
+    }
+
+    fn rerun_command(&self, req: &RerunRequest) -> Command {
+        let mut cmd = self.docker_command(None, Some(req.channel));
+        cmd.apply_backtrace(req);
+        cmd.apply_stdin(req);
+        cmd.apply_cpu_usage();
+
+        cmd.arg(self.config.images.channel(req.channel))
+            .arg(format!("{}/{}", OUTPUT_DIR, KEPT_BINARY_FILE_NAME))
+            .args(&req.args);
+
+        tracing::debug!(command = ?cmd, "Rerun command");
+
+        cmd
+    }
+
+    fn format_command(&self, req: &FormatRequest) -> Command {
+        let crate_type = CrateType::Binary;
+
+        let mut cmd = self.docker_command(Some(crate_type), None);
+
+        cmd.apply_edition(req);
+
+        cmd.arg(&self.config.images.rustfmt).args(["cargo", "fmt"]);
+
+        // Only available on nightly, which the image is built from
+        if let Some(lines) = req.lines {
+            let file_lines = serde_json::json!([{
+                "file": crate_type.file_name(),
+                "range": [lines.start, lines.end],
+            }]);
+            cmd.args(["--", "--file-lines"]).arg(file_lines.to_string());
+        }
+
+        tracing::debug!(command = ?cmd, "Formatting command");
+
+        cmd
+    }
+
+    fn edition_migration_command(&self, req: &EditionMigrationRequest) -> Command {
+        let channel = Channel::Stable { version: None };
+        let mut cmd = self.docker_command(Some(req.crate_type), Some(channel));
+
+        cmd.apply_crate_type(req);
+        cmd.apply_edition(req);
+
+        // The crate isn't under version control, which `cargo fix`
+        // otherwise insists on
+        cmd.arg(self.config.images.channel(channel)).args([
+            "cargo",
+            "fix",
+            "--edition",
+            "--allow-no-vcs",
+        ]);
+
+        tracing::debug!(command = ?cmd, "Edition migration command");
+
+        cmd
+    }
+
+    fn clippy_command(&self, req: impl CrateTypeRequest + EditionRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type()), None);
+
+        cmd.apply_crate_type(&req);
+        cmd.apply_edition(&req);
+
+        cmd.arg(&self.config.images.clippy)
+            .args(["cargo", "clippy"])
+            .arg(cargo_diagnostics_format(
+                ColorOutput::Plain,
+                ErrorFormat::Human,
+            ));
+
+        tracing::debug!(command = ?cmd, "Clippy command");
+
+        cmd
+    }
+
+    fn miri_command(&self, req: impl EditionRequest) -> Command {
+        let mut cmd = self.docker_command(None, None);
+        cmd.apply_edition(req);
+
+        cmd.arg(&self.config.images.miri)
+            .args(["cargo", "miri-playground"]);
+
+        tracing::debug!(command = ?cmd, "Miri command");
+
+        cmd
+    }
+
+    fn kani_command(&self, req: impl EditionRequest) -> Command {
+        let mut cmd = self.docker_command(None, None);
+        cmd.apply_edition(req);
+
+        cmd.arg(&self.config.images.kani).args(["cargo", "kani"]);
+
+        tracing::debug!(command = ?cmd, "Kani command");
+
+        cmd
+    }
+
+    fn macro_expansion_command(&self, req: &MacroExpansionRequest) -> Command {
+        let mut cmd = self.docker_command(None, Some(Channel::Nightly { date: None }));
+        cmd.apply_edition(req);
+
+        cmd.arg(self.config.images.channel(Channel::Nightly { date: None }));
+        match &req.expand_item {
+            // Only `cargo expand` can pick a single item out
+            Some(path) => cmd.args(["cargo", "expand", path]),
+            None => cmd.args(["cargo", "rustc", "--", "-Zunpretty=expanded"]),
+        };
+
+        tracing::debug!(command = ?cmd, "Macro expansion command");
+
+        cmd
+    }
+
+    fn time_passes_command(&self, req: &TimePassesRequest) -> Command {
+        let channel = Channel::Nightly { date: None };
+        let mut cmd = self.docker_command(Some(req.crate_type), Some(channel));
+        cmd.apply_crate_type(req);
+        cmd.apply_edition(req);
+
+        cmd.arg(self.config.images.channel(channel))
+            .args(["cargo", "rustc"]);
+        if req.mode == Mode::Release {
+            cmd.arg("--release");
+        }
+        // Only the playground crate is reported on, as the
+        // dependencies are already built
+        cmd.args(["--", "-Ztime-passes"]);
+
+        tracing::debug!(command = ?cmd, "Time passes command");
+
+        cmd
+    }
+
+    fn optimization_remarks_command(&self, req: &OptimizationRemarksRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type), Some(req.channel));
+        cmd.apply_crate_type(req);
+        cmd.apply_edition(req);
+
+        cmd.arg(self.config.images.channel(req.channel))
+            .args(["cargo", "rustc"]);
+        if req.mode == Mode::Release {
+            cmd.arg("--release");
+        }
+        // The remarks only point at the code when there is debug
+        // information to find it with
+        cmd.args(["--", "-Cremark=all", "-Cdebuginfo=1"]);
+
+        tracing::debug!(command = ?cmd, "Optimization remarks command");
+
+        cmd
+    }
+
+    fn rust_analyzer_command(
+        &self,
+        operation: &str,
+        req: impl CrateTypeRequest + EditionRequest,
+        position: Option<(usize, usize)>,
+    ) -> Command {
+        let crate_type = req.crate_type();
+        let mut cmd = self.docker_command(Some(crate_type), None);
+        cmd.apply_crate_type(&req);
+        cmd.apply_edition(&req);
+
+        cmd.arg(&self.config.images.rust_analyzer).args([
+            "rust-analyzer-playground",
+            operation,
+            crate_type.file_name(),
+        ]);
+        if let Some((line, character)) = position {
+            cmd.arg(line.to_string()).arg(character.to_string());
+        }
+
+        tracing::debug!(command = ?cmd, "rust-analyzer command");
+
+        cmd
+    }
+
+    fn bisect_command(&self, req: &BisectRequest, bisect: &Bisect) -> Command {
+        let limits = Limits {
+            timeout: bisect.timeout,
+            cpu_time: Some(bisect.cpu_time),
+            ..self.config.limits.clone()
+        };
+
+        let mut cmd = secure_docker_command(&limits, &bisect.network, self.launch());
+        self.apply_seccomp(&mut cmd);
+        self.mount_playground(&mut cmd, req.crate_type);
+        cmd.apply_crate_type(req);
+        cmd.apply_edition(req);
+
+        cmd.arg(&bisect.image).args([
+            "cargo",
+            "bisect-rustc",
+            "--start",
+            &req.start.to_string(),
+            "--regress",
+            req.regression.cargo_bisect_ident(),
+        ]);
+        if let Some(end) = req.end {
+            cmd.args(["--end", &end.to_string()]);
+        }
+
+        tracing::debug!(command = ?cmd, "Bisection command");
+
+        cmd
+    }
+
+    fn docker_command(&self, crate_type: Option<CrateType>, channel: Option<Channel>) -> Command {
+        let limits = self.config.limits(channel);
+        let mut cmd = basic_secure_docker_command(limits, self.launch());
+        self.apply_seccomp(&mut cmd);
+        self.apply_filesystem_limits(&mut cmd, limits);
+        self.mount_playground(&mut cmd, crate_type.unwrap_or(CrateType::Binary));
+        cmd
+    }
+
+    fn apply_seccomp(&self, cmd: &mut Command) {
+        if let Some(profile) = &self.config.seccomp_profile {
+            let mut security_opt = OsString::from("seccomp=");
+            security_opt.push(profile);
+
+            cmd.arg("--security-opt").arg(security_opt);
+        }
+    }
+
+    // Bisections install each toolchain they try, so a read-only root
+    // isn't used for them
+    fn apply_filesystem_limits(&self, cmd: &mut Command, limits: &Limits) {
+        if self.config.read_only_root {
+            cmd.arg("--read-only");
+            // Programs are built and run from here
+            let mut options = format!("{}:exec,mode=1777", WRITABLE_DIR);
+            if let Some(disk) = &limits.disk {
+                options.push_str(",size=");
+                options.push_str(disk);
+            }
+            cmd.arg("--tmpfs").arg(options);
+            cmd.arg("--env")
+                .arg(format!("PLAYGROUND_WORK_DIR={}", READ_ONLY_ROOT_WORK_DIR));
+        } else if let Some(disk) = &limits.disk {
+            cmd.arg("--storage-opt").arg(format!("size={}", disk));
+        }
+    }
+
+    fn apply_request_id(&self, cmd: &mut Command) {
+        if let Some(request_id) = &self.request_id {
+            cmd.arg("--label")
+                .arg(format!("{}={}", REQUEST_ID_LABEL, request_id));
+        }
+    }
+
+    fn mount_playground(&self, cmd: &mut Command, crate_type: CrateType) {
+        self.apply_request_id(cmd);
+
+        if self.config.copy_files {
+            // The entrypoint puts the copied input here
+            cmd.args([
+                "--env",
+                &format!("PLAYGROUND_SOURCE=/playground/{}", crate_type.file_name()),
+            ]);
+            return;
+        }
+
+        let mut mount_input_file = host_path(&self.input_file);
+        mount_input_file.push(":");
+        mount_input_file.push("/playground/");
+        mount_input_file.push(crate_type.file_name());
+
+        let mut mount_output_dir = host_path(&self.output_dir);
+        mount_output_dir.push(":");
+        mount_output_dir.push(OUTPUT_DIR);
+
+        cmd.arg("--volume")
+            .arg(&mount_input_file)
+            .arg("--volume")
+            .arg(&mount_output_dir);
+    }
+
+    // Copied files are put beside the code by the entrypoint instead
+    fn mount_hidden_tests(&self, cmd: &mut Command) {
+        if self.config.copy_files {
+            return;
+        }
+
+        let mut mount = host_path(&self.hidden_tests_file);
+        mount.push(":/playground/src/");
+        mount.push(hidden_tests::FILE_NAME);
+        cmd.arg("--volume").arg(&mount);
+    }
+
+    fn launch(&self) -> Launch {
+        if self.config.copy_files {
+            Launch::Create
+        } else {
+            Launch::Run
+        }
+    }
+
+    fn files(&self) -> Option<Files<'_>> {
+        self.config.copy_files.then(|| Files {
+            input: &self.input_file,
+            hidden_tests: &self.hidden_tests_file,
+            output: &self.output_dir,
+        })
+    }
+
+    async fn run(
+        &self,
+        command: Command,
+        channel: Option<Channel>,
+    ) -> Result<std::process::Output> {
+        let (output, _) = self.run_with(command, channel, Extras::default()).await?;
+        Ok(output)
+    }
+
+    async fn run_with(
+        &self,
+        command: Command,
+        channel: Option<Channel>,
+        extras: Extras,
+    ) -> Result<(std::process::Output, Captured)> {
+        let timeout = self.config.limits(channel).timeout + DOCKER_PROCESS_TIMEOUT_GRACE;
+
+        let breaker = channel.and_then(|channel| {
+            let breaker = self.channel_health.as_ref()?.breaker(channel)?;
+            Some((channel, breaker))
+        });
+        if let Some((channel, breaker)) = breaker {
+            if let Err(retry_after) = breaker.allow() {
+                let image = self.config.images.channel(channel);
+                return ChannelUnavailableSnafu { image, retry_after }.fail();
+            }
+        }
+
+        let progress = self.progress.as_ref().map(|report| Progress {
+            report,
+            follow_cargo: extras.follow_cargo,
+        });
+        let output = run_command_with_timeout(
+            command,
+            timeout,
+            self.files(),
+            progress,
+            self.live_output.as_ref(),
+            extras.records,
+            self.output_spill.as_ref().filter(|_| extras.spill),
+        )
+        .await;
+
+        if let Some((_, breaker)) = breaker {
+            match &output {
+                Err(Error::ImageNotFound { .. }) => breaker.record_failure(),
+                _ => breaker.record_success(),
+            }
+        }
+
+        output
+    }
+
+    fn apply_sccache(&self, cmd: &mut Command) {
+        if let Some(sccache_dir) = &self.config.sccache_dir {
+            let mut mount_sccache_dir = host_path(sccache_dir);
+            mount_sccache_dir.push(":");
+            mount_sccache_dir.push(SCCACHE_CONTAINER_DIR);
+
+            cmd.arg("--volume").arg(&mount_sccache_dir);
+            cmd.args(["--env", "RUSTC_WRAPPER=sccache"]);
+            cmd.args(["--env", &format!("SCCACHE_DIR={}", SCCACHE_CONTAINER_DIR)]);
+            cmd.args([
+                "--env",
+                &format!(
+                    "PLAYGROUND_SCCACHE_STATS=/playground-result/{}",
+                    SCCACHE_STATS_FILE_NAME
+                ),
+            ]);
+        }
+    }
+}
+
+/// Checks that sandboxes could be run, without running one. Returns
+/// every problem that was found.
+pub async fn self_check(config: &Config) -> Vec<Error> {
+    let mut problems = Vec::new();
+
+    match check_docker().await {
+        Ok(()) => {
+            let images = &config.images;
+            let channels = vec![&images.stable, &images.beta, &images.nightly];
+
+            let kept = images
+                .stable_releases
+                .values()
+                .chain(images.dated_nightlies.values());
+
+            let bisect = config.bisect.iter().map(|bisect| &bisect.image);
+
+            for image in channels.into_iter().chain(kept).chain(bisect) {
+                if let Err(e) = check_image(image).await {
+                    problems.push(e);
+                }
+            }
+        }
+        // There's no point checking for images
+        Err(e) => problems.push(e),
+    }
+
+    if let Some(path) = &config.seccomp_profile {
+        if let Err(source) = fs::metadata(path).await {
+            problems.push(Error::UnableToReadSeccompProfile {
+                path: path.clone(),
+                source,
+            });
+        }
+    }
+
+    if let Err(e) = check_temp_dir(config).await {
+        problems.push(e);
+    }
+
+    problems
+}
+
+async fn check_docker() -> Result<()> {
+    let command = docker_command!("version", "--format", "{{.Server.Version}}");
+    let output = run_docker_check(command).await?;
+
+    ensure!(
+        output.status.success(),
+        DockerUnreachableSnafu {
+            stderr: String::from_utf8_lossy(&output.stderr).trim(),
+        }
+    );
+
+    Ok(())
+}
+
+async fn check_image(image: &str) -> Result<()> {
+    image_id(image).await.map(drop)
+}
+
+/// The ID of the image that `image` currently names, which keeps
+/// naming the same image when a newer one is pulled.
+pub async fn image_id(image: &str) -> Result<String> {
+    let command = docker_command!("image", "inspect", "--format", "{{.Id}}", image);
+    let output = run_docker_check(command).await?;
+
+    ensure!(output.status.success(), ImageMissingSnafu { image });
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// Images that updates have pinned are already named by their ID
+async fn pinned_image_id(image: &str) -> Result<String> {
+    if image.starts_with(IMAGE_ID_PREFIX) {
+        return Ok(image.into());
+    }
+    image_id(image).await
+}
+
+/// Fetches the newest image that `image` names from its registry.
+pub async fn pull_image(image: &str) -> Result<()> {
+    let mut command = docker_command!("pull", "--quiet", image);
+    command.kill_on_drop(true);
+
+    let output = time::timeout(IMAGE_PULL_TIMEOUT, command.output())
+        .await
+        .context(DockerTimedOutSnafu {
+            timeout: IMAGE_PULL_TIMEOUT,
+        })?
+        .context(UnableToRunDockerSnafu)?;
+
+    ensure!(
+        output.status.success(),
+        UnableToPullImageSnafu {
+            image,
+            stderr: String::from_utf8_lossy(&output.stderr).trim(),
+        }
+    );
+
+    Ok(())
+}
+
+/// Checks that `image` is able to stand in for the image of
+/// `channel`, by asking for its version and by building and running
+/// a program with it.
+pub async fn smoke_test(config: &Config, channel: Channel, image: &str) -> Result<()> {
+    let mut config = config.clone();
+    config.images.set_channel(channel, image.into());
+    let config = Arc::new(config);
+
+    Sandbox::with_config(config.clone())
+        .await?
+        .version(channel)
+        .await?;
+    warm_up(config, channel).await
+}
+
+/// Builds and runs a program for `channel`, so that the image and the
+/// caches are ready for the first request that needs them.
+pub async fn warm_up(config: Arc<Config>, channel: Channel) -> Result<()> {
+    let image = config.images.channel(channel).to_owned();
+    let sandbox = Sandbox::with_config(config).await?;
+
+    let response = sandbox
+        .execute(&ExecuteRequest {
+            channel,
+            mode: Mode::Debug,
+            edition: None,
+            crate_type: CrateType::Binary,
+            tests: false,
+            backtrace: false,
+            color: ColorOutput::Plain,
+            code: HELLO_WORLD_CODE.into(),
+            args: vec![],
+            stdin: String::new(),
+            keep_binary: false,
+            wrap_main: false,
+            strategy: Strategy::Exact,
+            trace_macros: false,
+            macro_backtrace: false,
+            output_records: false,
+            test_runner: TestRunner::Libtest,
+            careful: false,
+            deterministic: false,
+            error_format: ErrorFormat::Human,
+            hidden_tests: None,
+        })
+        .await?;
+
+    ensure!(
+        response.success && response.stdout.contains(HELLO_WORLD_OUTPUT),
+        HelloWorldFailedSnafu {
+            image,
+            stderr: response.stderr,
+        }
+    );
+
+    Ok(())
+}
+
+async fn check_temp_dir(config: &Config) -> Result<()> {
+    let scratch = scratch_dir(config).await?;
+
+    fs::write(scratch.path().join("input.rs"), "")
+        .await
+        .context(UnableToCreateSourceFileSnafu)
+}
+
+async fn scratch_dir(config: &Config) -> Result<Scratch> {
+    let work_dir = config.work_dir();
+    fs::create_dir_all(&work_dir)
+        .await
+        .context(UnableToCreateTempDirSnafu)?;
+
+    // `TempDir` only has synchronous filesystem operations
+    let dir = tokio::task::spawn_blocking(move || {
+        tempfile::Builder::new()
+            .prefix(SCRATCH_PREFIX)
+            .tempdir_in(work_dir)
+    })
+    .await
+    .map_err(io::Error::from)
+    .and_then(|dir| dir)
+    .context(UnableToCreateTempDirSnafu)?;
+
+    Ok(Scratch(Some(dir)))
+}
+
+/// A scratch directory that is removed without blocking the async
+/// threads, as removing the output of a large build can take a
+/// while. Any that are never removed, such as when the runtime shuts
+/// down first, are swept up later.
+struct Scratch(Option<TempDir>);
+
+impl Scratch {
+    fn path(&self) -> &Path {
+        self.0
+            .as_ref()
+            .expect("The scratch directory is only taken when dropped")
+            .path()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if let Some(dir) = self.0.take() {
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => {
+                    runtime.spawn_blocking(move || drop(dir));
+                }
+                Err(_) => drop(dir),
+            }
+        }
+    }
+}
+
+/// Removes the scratch directories that were left behind, such as by
+/// a process that was killed, every so often, forever. The first
+/// sweep happens right away.
+pub async fn sweep_scratch_periodically(config: Arc<Config>) {
+    let mut interval = time::interval(SCRATCH_SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match remove_stale_scratch(&config.work_dir(), SCRATCH_MAX_AGE).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "Removed leftover scratch directories"),
+            Err(error) => tracing::error!(%error, "Unable to remove leftover scratch directories"),
+        }
+    }
+}
+
+async fn remove_stale_scratch(work_dir: &Path, max_age: Duration) -> Result<usize> {
+    let mut entries = match fs::read_dir(work_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).context(UnableToSweepScratchSnafu),
+    };
+
+    let mut count = 0;
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .context(UnableToSweepScratchSnafu)?
+    {
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SCRATCH_PREFIX)
+        {
+            continue;
+        }
+
+        // Another sandbox may have just finished with it
+        let age = match entry.metadata().await.and_then(|m| m.modified()) {
+            Ok(modified) => modified.elapsed().unwrap_or_default(),
+            Err(_) => continue,
+        };
+
+        if age >= max_age && fs::remove_dir_all(entry.path()).await.is_ok() {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Forcibly removes every container started by this process that is
+/// still around, returning how many there were.
+pub async fn remove_containers() -> Result<usize> {
+    let filter = format!("label={}={}", INSTANCE_LABEL, *INSTANCE_ID);
+    let command = docker_command!("ps", "--all", "--quiet", "--filter", filter);
+    let output = run_docker_check(command).await?;
+
+    ensure!(
+        output.status.success(),
+        UnableToListContainersSnafu {
+            stderr: String::from_utf8_lossy(&output.stderr).trim(),
+        }
+    );
+
+    let ids = String::from_utf8_lossy(&output.stdout);
+    let ids: Vec<_> = ids.split_whitespace().collect();
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut command = docker_command!("rm", "--force");
+    command.args(&ids).stdout(std::process::Stdio::null());
+    command
+        .status()
+        .await
+        .context(UnableToRemoveCompilerSnafu)?;
+
+    Ok(ids.len())
+}
+
+async fn run_docker_check(mut command: Command) -> Result<std::process::Output> {
+    let timeout = DOCKER_PROCESS_TIMEOUT_SOFT;
+
+    command.kill_on_drop(true);
+    time::timeout(timeout, command.output())
+        .await
+        .context(DockerTimedOutSnafu { timeout })?
+        .context(UnableToRunDockerSnafu)
+}
+
+/// The files of a sandbox that are copied into and out of a container
+/// that was created by a `Launch::Create` command.
+struct Files<'a> {
+    input: &'a Path,
+    hidden_tests: &'a Path,
+    output: &'a Path,
+}
+
+impl Files<'_> {
+    async fn copy_in(&self, id: &str) -> Result<()> {
+        // Commands that don't need the code, such as getting the
+        // version, run before any has been written
+        if fs::metadata(self.input).await.is_ok() {
+            docker_cp(
+                self.input.as_os_str(),
+                format!("{}:{}", id, COPIED_INPUT_PATH),
+            )
+            .await?;
+        }
+        if fs::metadata(self.hidden_tests).await.is_ok() {
+            docker_cp(
+                self.hidden_tests.as_os_str(),
+                format!("{}:{}", id, COPIED_HIDDEN_TESTS_PATH),
+            )
+            .await?;
+        }
+        docker_cp(self.output.as_os_str(), format!("{}:{}", id, OUTPUT_DIR)).await
+    }
+
+    async fn copy_out(&self, id: &str) -> Result<()> {
+        docker_cp(format!("{}:{}/.", id, OUTPUT_DIR), self.output.as_os_str()).await?;
+
+        // Such as when the code was formatted
+        let source = self.output.join(COPIED_SOURCE_FILE_NAME);
+        if fs::metadata(&source).await.is_ok() {
+            fs::rename(&source, self.input)
+                .await
+                .context(UnableToReadOutputSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+async fn docker_cp(from: impl AsRef<OsStr>, to: impl AsRef<OsStr>) -> Result<()> {
+    let mut command = docker_command!("cp", from, to);
+    let output = command.output().await.context(UnableToRunDockerSnafu)?;
+
+    ensure!(
+        output.status.success(),
+        UnableToCopyFilesSnafu {
+            stderr: String::from_utf8_lossy(&output.stderr).trim(),
+        }
+    );
+
+    Ok(())
+}
+
+async fn start_container(id: &str, files: &Files<'_>) -> Result<()> {
+    files.copy_in(id).await?;
+
+    let mut command = docker_command!("start", id);
+    let output = command.output().await.context(UnableToStartCompilerSnafu)?;
+
+    ensure!(
+        output.status.success(),
+        DockerFailedSnafu {
+            stderr: String::from_utf8_lossy(&output.stderr).trim(),
+        }
+    );
+
+    Ok(())
+}
+
+async fn remove_container(id: &str) -> Result<()> {
+    let mut command = docker_command!(
+        "rm", // Kills container if still running
+        "--force", id
+    );
+    command.stdout(std::process::Stdio::null());
+    command
+        .status()
+        .await
+        .context(UnableToRemoveCompilerSnafu)?;
+
+    Ok(())
+}
+
+/// What an operation wants from its container, beyond its output.
+#[derive(Debug, Default, Copy, Clone)]
+struct Extras {
+    /// Cargo was asked to write its messages, which are only followed
+    /// when they are, as following them takes another Docker process.
+    follow_cargo: bool,
+    /// The output is wanted as [`OutputRecord`]s as well.
+    records: bool,
+    /// Outputs too large to keep in memory are written to files, when
+    /// the sandbox was given somewhere to write them.
+    spill: bool,
+}
+
+/// What was read from a container, besides the output that's kept in
+/// memory.
+#[derive(Debug, Default)]
+struct Captured {
+    /// Not read when either output was spilled, as they would hold all
+    /// of it in memory after all.
+    records: Option<Vec<OutputRecord>>,
+    stdout: Option<SpilledFile>,
+    stderr: Option<SpilledFile>,
+}
+
+/// Who to tell how far a container has got.
+#[derive(Clone, Copy)]
+struct Progress<'a> {
+    report: &'a ReportProgress,
+    /// Whether cargo was asked to write its messages.
+    follow_cargo: bool,
+}
+
+// Read as cargo writes each message, rather than once the container
+// has stopped
+async fn follow_cargo(id: String, report: ReportProgress) {
+    let mut command = docker_command!("logs", "--follow", &id);
+    command.stdout(std::process::Stdio::piped());
+    command.stderr(std::process::Stdio::null());
+    command.kill_on_drop(true);
+
+    let stdout = match command.spawn() {
+        Ok(mut child) => child.stdout.take(),
+        Err(error) => {
+            tracing::warn!(container = %id, %error, "Unable to follow the container");
+            return;
+        }
+    };
+    let mut lines = match stdout {
+        Some(stdout) => tokio::io::BufReader::new(stdout).lines(),
+        None => return,
+    };
+
+    let mut reported = None;
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(phase) = progress::phase(&line) {
+            if reported != Some(phase) {
+                reported = Some(phase);
+                report(phase);
+            }
+        }
+        if reported == Some(Phase::Running) {
+            break;
+        }
+    }
+}
+
+fn docker_run_error(stderr: String) -> Error {
+    if MISSING_IMAGE.iter().any(|m| stderr.contains(m)) {
+        return Error::ImageNotFound { stderr };
+    }
+    if MOUNT_DENIED.iter().any(|m| stderr.contains(m)) {
+        return Error::MountDenied { stderr };
+    }
+    Error::DockerFailed { stderr }
+}
+
+async fn run_command_with_timeout(
+    mut command: Command,
+    timeout: Duration,
+    files: Option<Files<'_>>,
+    progress: Option<Progress<'_>>,
+    live_output: Option<&mpsc::Sender<LiveOutput>>,
+    record_output: bool,
+    spill: Option<&OutputSpill>,
+) -> Result<(std::process::Output, Captured)> {
+    let output = command.output().await.context(UnableToStartCompilerSnafu)?;
+
+    // Exit early, in case we don't have the container
+    if !output.status.success() {
+        if output.status.code() == Some(DOCKER_RUN_FAILED) {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            return Err(docker_run_error(stderr));
+        }
+        return Ok((output, Captured::default()));
+    }
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    let id = output
+        .lines()
+        .next()
+        .context(MissingCompilerIdSnafu)?
+        .trim();
+    if let Some(files) = &files {
+        if let Err(e) = start_container(id, files).await {
+            remove_container(id).await?;
+            return Err(e);
+        }
+    }
+    tracing::debug!(container = id, "Started container");
+
+    if let Some(progress) = progress {
+        (progress.report)(Phase::ContainerStarted);
+    }
+    let follower = progress
+        .filter(|progress| progress.follow_cargo)
+        .map(|progress| tokio::spawn(follow_cargo(id.to_owned(), progress.report.clone())));
+    let live_follower = live_output
+        .map(|live_output| tokio::spawn(follow_output(id.to_owned(), live_output.clone())));
+
+    // ----------
+
+    let mut command = docker_command!("wait", id);
+
+    let timed_out = match time::timeout(timeout, command.output()).await {
+        Ok(Ok(o)) => {
+            // Didn't time out, didn't fail to run
+            let o = String::from_utf8_lossy(&o.stdout);
+            let code = o
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim()
+                .parse()
+                .unwrap_or(i32::MAX);
+            Ok(exit_status(code))
+        }
+        Ok(Err(e)) => return Err(e).context(UnableToWaitForCompilerSnafu), // Failed to run
+        Err(e) => Err(e),                                                  // Timed out
+    };
+
+    if let Some(follower) = follower {
+        follower.abort();
+    }
+
+    // The container is trusted to stop itself once `PLAYGROUND_TIMEOUT`
+    // is up, but it may not have, so it's stopped before anything else
+    // is done with it
+    if timed_out.is_err() {
+        tracing::warn!(container = id, ?timeout, "Container ran past its timeout");
+
+        let mut command = docker_command!("kill", id);
+        command.stdout(std::process::Stdio::null());
+        command.stderr(std::process::Stdio::null());
+        // It may have stopped in the meantime, which is fine
+        command.status().await.context(UnableToKillCompilerSnafu)?;
+    }
+
+    // Following the logs stops along with the container
+    if let Some(mut follower) = live_follower {
+        if time::timeout(LIVE_OUTPUT_GRACE, &mut follower)
+            .await
+            .is_err()
+        {
+            follower.abort();
+        }
+    }
+
+    // ----------
+
+    // Failures are only returned once the container is removed
+    let logs = async {
+        let command = docker_command!("logs", id);
+        let (output, stdout, stderr) = match spill {
+            Some(spill) => spill::output(command, spill).await?,
+            None => {
+                let mut command = command;
+                let output = command
+                    .output()
+                    .await
+                    .context(UnableToGetOutputFromCompilerSnafu)?;
+                (output, None, None)
+            }
+        };
+        let spilled = stdout.is_some() || stderr.is_some();
+
+        // Only asked for when wanted, as the timestamps are on every line
+        let records = if record_output && !spilled {
+            let mut command = docker_command!("logs", "--timestamps", id);
+            let logs = command
+                .output()
+                .await
+                .context(UnableToGetOutputFromCompilerSnafu)?;
+            let stdout = String::from_utf8_lossy(&logs.stdout);
+            let stderr = String::from_utf8_lossy(&logs.stderr);
+            Some(records::merge(&stdout, &stderr))
+        } else {
+            None
+        };
+
+        let captured = Captured {
+            records,
+            stdout,
+            stderr,
+        };
+        Ok::<_, Error>((output, captured))
+    };
+    let logs = logs.await;
+
+    // ----------
+
+    let copied = match &files {
+        Some(files) => files.copy_out(id).await,
+        None => Ok(()),
+    };
+
+    // ----------
+
+    remove_container(id).await?;
+    let (mut output, captured) = logs?;
+    copied?;
+
+    let code = timed_out.context(CompilerExecutionTimedOutSnafu { timeout })?;
+
+    output.status = code;
+
+    Ok((output, captured))
+}
+
+async fn follow_output(id: String, live_output: mpsc::Sender<LiveOutput>) {
+    let mut command = docker_command!("logs", "--follow", &id);
+    command.stdout(std::process::Stdio::piped());
+    command.stderr(std::process::Stdio::piped());
+    command.kill_on_drop(true);
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(error) => {
+            tracing::warn!(container = %id, %error, "Unable to follow the container");
+            return;
+        }
+    };
+
+    futures::join!(
+        forward_output(child.stdout.take(), &live_output, LiveOutput::Stdout),
+        forward_output(child.stderr.take(), &live_output, LiveOutput::Stderr),
+    );
+}
+
+async fn forward_output(
+    reader: Option<impl AsyncRead + Unpin>,
+    live_output: &mpsc::Sender<LiveOutput>,
+    wrap: fn(String) -> LiveOutput,
+) {
+    let mut reader = match reader {
+        Some(reader) => reader,
+        None => return,
+    };
+    let mut buffer = [0; 4096];
+    let mut pending = Vec::new();
+
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => pending.extend_from_slice(&buffer[..n]),
+        }
+
+        let text = take_whole_characters(&mut pending);
+        // Nothing is listening anymore
+        if !text.is_empty() && live_output.send(wrap(text)).await.is_err() {
+            break;
+        }
+    }
+}
+
+// Reads may end partway through a character, which is left for the
+// next read to finish
+fn take_whole_characters(pending: &mut Vec<u8>) -> String {
+    let rest = pending.split_off(whole_characters_len(pending));
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
+}
+
+/// How much of `bytes` is left once a character that's cut short at
+/// the end is removed.
+fn whole_characters_len(bytes: &[u8]) -> usize {
+    let len = bytes.len();
+    let last_start = (len.saturating_sub(3)..len)
+        .rev()
+        .find(|&i| bytes[i] & 0b1100_0000 != 0b1000_0000);
+    match last_start {
+        Some(i) if utf8_width(bytes[i]) > len - i => i,
+        _ => len,
+    }
+}
+
+fn utf8_width(first: u8) -> usize {
+    match first {
+        0b1111_0000..=0xFF => 4,
+        0b1110_0000..=0b1110_1111 => 3,
+        0b1100_0000..=0b1101_1111 => 2,
+        _ => 1,
+    }
+}
+
+async fn read(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path).await {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context(UnableToReadOutputSnafu),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssemblyFlavor {
+    Att,
+    Intel,
+}
+
+/// How the terminal colors of the compiler output are returned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorOutput {
+    Plain,
+    /// As ANSI escape sequences.
+    Ansi,
+    /// As HTML, with the colors as `<span>` elements.
+    Html,
+}
+
+/// How the compiler shows its diagnostics.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorFormat {
+    /// With the code that they are about, and what to do.
+    #[default]
+    Human,
+    /// One line each, with where it is, as `--error-format=short`.
+    Short,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DemangleAssembly {
+    Demangle,
+    Mangle,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProcessAssembly {
+    Filter,
+    Raw,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, strum::IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompileTarget {
+    #[serde(rename = "asm")]
+    Assembly(AssemblyFlavor, DemangleAssembly, ProcessAssembly),
+    LlvmIr,
+    Mir,
+    Hir,
+    Wasm,
+}
+
+impl CompileTarget {
+    fn extension(&self) -> &'static OsStr {
+        let ext = match *self {
+            CompileTarget::Assembly(_, _, _) => "s",
+            CompileTarget::LlvmIr => "ll",
+            CompileTarget::Mir => "mir",
+            CompileTarget::Hir => "hir",
+            CompileTarget::Wasm => "wat",
+        };
+        OsStr::new(ext)
+    }
+
+    /// The compiler can emit these at the same time as each other.
+    fn can_share_build(&self) -> bool {
+        matches!(
+            self,
+            CompileTarget::Assembly(_, _, _) | CompileTarget::LlvmIr | CompileTarget::Mir
+        )
+    }
+}
+
+impl fmt::Display for CompileTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::CompileTarget::*;
+
+        match *self {
+            Assembly(_, _, _) => "assembly".fmt(f),
+            LlvmIr => "LLVM IR".fmt(f),
+            Mir => "Rust MIR".fmt(f),
+            Hir => "Rust HIR".fmt(f),
+            Wasm => "WebAssembly".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, strum::IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Channel {
+    /// The latest stable release, unless a version is given.
+    Stable {
+        version: Option<RustVersion>,
+    },
+    Beta,
+    /// The latest nightly, unless a date is given.
+    Nightly {
+        date: Option<NaiveDate>,
+    },
+}
+
+impl Channel {
+    fn container_name(&self) -> &'static str {
+        use self::Channel::*;
+
+        match *self {
+            Stable { .. } => "rust-stable",
+            Beta => "rust-beta",
+            Nightly { .. } => "rust-nightly",
+        }
+    }
+
+    /// The release channel, whichever release or nightly is used.
+    fn latest(self) -> Self {
+        match self {
+            Channel::Stable { .. } => Channel::Stable { version: None },
+            Channel::Beta => Channel::Beta,
+            Channel::Nightly { .. } => Channel::Nightly { date: None },
+        }
+    }
+}
+
+/// A stable release, such as `1.30.0`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RustVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl FromStr for RustVersion {
+    type Err = ParseRustVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('.').map(|part| part.parse().ok());
+
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => Ok(Self {
+                major,
+                minor,
+                patch,
+            }),
+            _ => Err(ParseRustVersionError),
+        }
+    }
+}
+
+impl fmt::Display for RustVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// Configuration files name the releases as strings
+impl serde::Serialize for RustVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for RustVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let version = String::deserialize(deserializer)?;
+        version.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("Rust versions look like `1.30.0`"))]
+pub struct ParseRustVersionError;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, strum::IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    Debug,
+    Release,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, strum::IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Edition {
+    #[serde(rename = "2015")]
+    Rust2015,
+    #[serde(rename = "2018")]
+    Rust2018,
+    #[serde(rename = "2021")]
+    Rust2021, // TODO - add parallel tests for 2021
+}
+
+impl Edition {
+    fn cargo_ident(&self) -> &'static str {
+        use self::Edition::*;
+
+        match *self {
+            Rust2015 => "2015",
+            Rust2018 => "2018",
+            Rust2021 => "2021",
+        }
+    }
+
+    /// The edition that `cargo fix --edition` migrates to.
+    fn next(&self) -> Option<Edition> {
+        use self::Edition::*;
+
+        match *self {
+            Rust2015 => Some(Rust2018),
+            Rust2018 => Some(Rust2021),
+            Rust2021 => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, strum::IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrateType {
+    Binary,
+    Library(LibraryType),
+}
+
+impl CrateType {
+    fn file_name(&self) -> &'static str {
+        use self::CrateType::*;
+
+        match *self {
+            Binary => "src/main.rs",
+            Library(_) => "src/lib.rs",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, strum::IntoStaticStr, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LibraryType {
+    Lib,
+    Dylib,
+    Rlib,
+    Staticlib,
+    Cdylib,
+    ProcMacro,
+}
+
+impl LibraryType {
+    fn cargo_ident(&self) -> &'static str {
+        use self::LibraryType::*;
+
+        match *self {
+            Lib => "lib",
+            Dylib => "dylib",
+            Rlib => "rlib",
+            Staticlib => "staticlib",
+            Cdylib => "cdylib",
+            ProcMacro => "proc-macro",
+        }
+    }
+}
+
+trait DockerCommandExt {
+    fn apply_crate_type(&mut self, req: impl CrateTypeRequest);
+    fn apply_edition(&mut self, req: impl EditionRequest);
+    fn apply_backtrace(&mut self, req: impl BacktraceRequest);
+    fn apply_color(&mut self, req: impl ColorRequest);
+    fn apply_macro_debugging(&mut self, req: impl MacroDebuggingRequest);
+    fn apply_stdin(&mut self, req: impl RunRequest);
+    fn apply_cpu_usage(&mut self);
+}
+
+impl DockerCommandExt for Command {
+    fn apply_crate_type(&mut self, req: impl CrateTypeRequest) {
+        if let CrateType::Library(lib) = req.crate_type() {
+            self.args([
+                "--env",
+                &format!("PLAYGROUND_CRATE_TYPE={}", lib.cargo_ident()),
+            ]);
+        }
+    }
+
+    fn apply_edition(&mut self, req: impl EditionRequest) {
+        if let Some(edition) = req.edition() {
+            self.args([
+                "--env",
+                &format!("PLAYGROUND_EDITION={}", edition.cargo_ident()),
+            ]);
+        }
+    }
+
+    fn apply_backtrace(&mut self, req: impl BacktraceRequest) {
+        if req.backtrace() {
+            self.args(["--env", "RUST_BACKTRACE=1"]);
+        }
+    }
+
+    fn apply_color(&mut self, req: impl ColorRequest) {
+        if req.color() != ColorOutput::Plain {
+            // Cargo passes this on to the compiler as `--color=always`
+            self.args(["--env", "CARGO_TERM_COLOR=always"]);
+        }
+    }
+
+    fn apply_macro_debugging(&mut self, req: impl MacroDebuggingRequest) {
+        let mut flags = vec![];
+        if req.trace_macros() {
+            flags.push("-Ztrace-macros");
+        }
+        if req.macro_backtrace() {
+            flags.push("-Zmacro-backtrace");
+        }
+
+        // Only the playground crate is built with these, so the
+        // dependencies don't have to be built again
+        if !flags.is_empty() {
+            self.args([
+                "--env",
+                &format!("PLAYGROUND_RUSTFLAGS={}", flags.join(" ")),
+            ]);
+        }
+    }
+
+    fn apply_stdin(&mut self, req: impl RunRequest) {
+        if !req.stdin().is_empty() {
+            self.args([
+                "--env",
+                &format!("PLAYGROUND_STDIN={}/{}", OUTPUT_DIR, STDIN_FILE_NAME),
+            ]);
+        }
+    }
+
+    fn apply_cpu_usage(&mut self) {
+        self.args([
+            "--env",
+            &format!(
+                "PLAYGROUND_CPU_USAGE={}/{}",
+                OUTPUT_DIR, CPU_USAGE_FILE_NAME
+            ),
+        ]);
+    }
+}
+
+/// The requests that come with code to work on.
+pub trait CodeRequest {
+    fn code(&self) -> &str;
+}
+
+impl<R: CodeRequest> CodeRequest for &'_ R {
+    fn code(&self) -> &str {
+        (*self).code()
+    }
+}
+
+macro_rules! code_requests {
+    ($($request:ident,)*) => {
+        $(
+            impl CodeRequest for $request {
+                fn code(&self) -> &str {
+                    &self.code
+                }
+            }
+        )*
+    };
+}
+
+code_requests! {
+    CompileRequest,
+    ExecuteRequest,
+    FormatRequest,
+    EditionMigrationRequest,
+    ClippyRequest,
+    MiriRequest,
+    KaniRequest,
+    MacroExpansionRequest,
+    TimePassesRequest,
+    OptimizationRemarksRequest,
+    CompletionsRequest,
+    HoverRequest,
+    NavigationRequest,
+    InlayHintsRequest,
+    BisectRequest,
+    ReduceRequest,
+}
+
+trait CrateTypeRequest {
+    fn crate_type(&self) -> CrateType;
+}
+
+impl<R: CrateTypeRequest> CrateTypeRequest for &'_ R {
+    fn crate_type(&self) -> CrateType {
+        (*self).crate_type()
+    }
+}
+
+trait EditionRequest {
+    fn edition(&self) -> Option<Edition>;
+}
+
+impl<R: EditionRequest> EditionRequest for &'_ R {
+    fn edition(&self) -> Option<Edition> {
+        (*self).edition()
+    }
+}
+
+trait BacktraceRequest {
+    fn backtrace(&self) -> bool;
+}
+
+impl<R: BacktraceRequest> BacktraceRequest for &'_ R {
+    fn backtrace(&self) -> bool {
+        (*self).backtrace()
+    }
+}
+
+trait ColorRequest {
+    fn color(&self) -> ColorOutput;
+}
+
+impl<R: ColorRequest> ColorRequest for &'_ R {
+    fn color(&self) -> ColorOutput {
+        (*self).color()
+    }
+}
+
+trait ErrorFormatRequest {
+    fn error_format(&self) -> ErrorFormat;
+}
+
+impl<R: ErrorFormatRequest> ErrorFormatRequest for &'_ R {
+    fn error_format(&self) -> ErrorFormat {
+        (*self).error_format()
+    }
+}
+
+trait MacroDebuggingRequest {
+    fn trace_macros(&self) -> bool;
+    fn macro_backtrace(&self) -> bool;
+}
+
+impl<R: MacroDebuggingRequest> MacroDebuggingRequest for &'_ R {
+    fn trace_macros(&self) -> bool {
+        (*self).trace_macros()
+    }
+
+    fn macro_backtrace(&self) -> bool {
+        (*self).macro_backtrace()
+    }
+}
+
+trait RunRequest {
+    fn stdin(&self) -> &str;
+}
+
+impl<R: RunRequest> RunRequest for &'_ R {
+    fn stdin(&self) -> &str {
+        (*self).stdin()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileRequest {
+    pub target: CompileTarget,
+    /// Also compiled to, in the same build when possible.
+    pub extra_targets: Vec<CompileTarget>,
+    /// Turns off the MIR optimizations, which only nightly allows.
+    pub unoptimized_mir: bool,
+    pub channel: Channel,
+    pub crate_type: CrateType,
+    pub mode: Mode,
+    pub edition: Option<Edition>,
+    pub tests: bool,
+    pub backtrace: bool,
+    pub color: ColorOutput,
+    pub code: String,
+    /// Prints each macro expansion, which only nightly allows.
+    #[serde(default)]
+    pub trace_macros: bool,
+    /// Shows every macro that an error came out of, which only
+    /// nightly allows.
+    #[serde(default)]
+    pub macro_backtrace: bool,
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+}
+
+impl CompileRequest {
+    // Each build is a target along with those it can share the build
+    // with. Targets with the same output are only built once.
+    fn builds(&self) -> Vec<(CompileTarget, Vec<CompileTarget>)> {
+        let mut targets: Vec<CompileTarget> = vec![];
+        for &target in iter::once(&self.target).chain(&self.extra_targets) {
+            if !targets.iter().any(|t| t.extension() == target.extension()) {
+                targets.push(target);
+            }
+        }
+
+        let (shared, separate): (Vec<_>, Vec<_>) = targets
+            .into_iter()
+            .partition(CompileTarget::can_share_build);
+
+        let mut builds = vec![];
+        if let Some((&first, rest)) = shared.split_first() {
+            builds.push((first, rest.to_vec()));
+        }
+        builds.extend(separate.into_iter().map(|target| (target, vec![])));
+        builds
+    }
+}
+
+impl CrateTypeRequest for CompileRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for CompileRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+impl BacktraceRequest for CompileRequest {
+    fn backtrace(&self) -> bool {
+        self.backtrace
+    }
+}
+
+impl ColorRequest for CompileRequest {
+    fn color(&self) -> ColorOutput {
+        self.color
+    }
+}
+
+impl ErrorFormatRequest for CompileRequest {
+    fn error_format(&self) -> ErrorFormat {
+        self.error_format
+    }
+}
+
+impl MacroDebuggingRequest for CompileRequest {
+    fn trace_macros(&self) -> bool {
+        self.trace_macros
+    }
+
+    fn macro_backtrace(&self) -> bool {
+        self.macro_backtrace
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileResponse {
+    pub success: bool,
+    pub code: String,
+    /// In the same order as the extra targets of the request.
+    pub extra_outputs: Vec<(CompileTarget, String)>,
+    pub stdout: String,
+    pub stderr: String,
+    pub sccache: Option<SccacheStats>,
+    /// When the build failed in a way that something other than the
+    /// code could fix.
+    #[serde(default)]
+    pub hint: Option<Hint>,
+    #[serde(default)]
+    pub diagnostics: DiagnosticCounts,
+    /// The fixes that the compiler is sure of, once each.
+    #[serde(default)]
+    pub suggestions: Vec<Suggestion>,
+    /// When image IDs are reported.
+    #[serde(default)]
+    pub image_id: Option<String>,
+    /// It failed for writing more than the disk limit allows, or
+    /// because the host's disk is full.
+    #[serde(default)]
+    pub disk_quota_exceeded: bool,
+}
+
+/// Something to try when the code doesn't build.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hint {
+    /// It uses features that only nightly has.
+    TryNightly,
+}
+
+/// A change to the code that the compiler is sure fixes what it
+/// reported, such as prefixing an unused variable with an underscore.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Suggestion {
+    /// What the change does, as the compiler puts it.
+    pub message: String,
+    pub range: CodeRange,
+    /// What goes in place of the range.
+    pub replacement: String,
+}
+
+/// How many warnings and errors the compiler reported, not counting
+/// the summaries of them at the end.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticCounts {
+    pub warnings: usize,
+    pub errors: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteRequest {
+    pub channel: Channel,
+    pub mode: Mode,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    pub tests: bool,
+    pub backtrace: bool,
+    pub color: ColorOutput,
+    pub code: String,
+    /// Passed to the program, or to the test harness.
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub stdin: String,
+    /// Returns the binary that was built, so that it can be given to
+    /// [`Sandbox::rerun`]. Tests and libraries don't have one.
+    #[serde(default)]
+    pub keep_binary: bool,
+    /// Puts code that has no `main` function inside one, which prints
+    /// the value of the last expression. Tests and libraries don't
+    /// need one.
+    #[serde(default)]
+    pub wrap_main: bool,
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Prints each macro expansion, which only nightly allows.
+    #[serde(default)]
+    pub trace_macros: bool,
+    /// Shows every macro that an error came out of, which only
+    /// nightly allows.
+    #[serde(default)]
+    pub macro_backtrace: bool,
+    /// Also returns stdout and stderr as [`OutputRecord`]s, in the
+    /// order that they were written.
+    #[serde(default)]
+    pub output_records: bool,
+    /// What runs the tests, when they are run.
+    #[serde(default)]
+    pub test_runner: TestRunner,
+    /// Builds and runs it with `cargo careful`, whose standard
+    /// library checks more of what it is given, which only nightly
+    /// has. Nothing is kept to run again.
+    #[serde(default)]
+    pub careful: bool,
+    /// Runs the program with a clock that starts at the same moment
+    /// every time, and the tests one at a time, so that shared
+    /// examples write the same each time they are run. Randomness,
+    /// such as what `HashMap`s are seeded with, is not fixed.
+    #[serde(default)]
+    pub deterministic: bool,
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+    /// Tests that are compiled as a module of their own, `hidden_tests`,
+    /// which the code can't change. They must all run and pass for it
+    /// to succeed, which only the nextest runner can tell.
+    #[serde(default)]
+    pub hidden_tests: Option<String>,
+}
+
+/// Whether an execution may do something other than what it was
+/// asked to, when what it was asked to do can only fail.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Runs the tests of a program that has tests but no `main`
+    /// function, which would otherwise fail to link.
+    #[default]
+    AutoDetect,
+    /// Does just what was asked.
+    Exact,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestRunner {
+    /// The harness that the tests are built with.
+    #[default]
+    Libtest,
+    /// `cargo nextest`, which runs each test in a process of its own
+    /// and reports how each went as a [`TestResult`].
+    Nextest,
+}
+
+impl ExecuteRequest {
+    /// Whether the tests are run instead of the program.
+    pub fn runs_tests(&self) -> bool {
+        self.tests
+            || (self.strategy == Strategy::AutoDetect
+                && self.crate_type == CrateType::Binary
+                && detect::has_tests(&self.code)
+                && !detect::has_main_function(&self.code))
+    }
+
+    fn is_program(&self) -> bool {
+        self.crate_type == CrateType::Binary && !self.runs_tests()
+    }
+
+    fn uses_nextest(&self) -> bool {
+        self.test_runner == TestRunner::Nextest && self.runs_tests()
+    }
+
+    fn keeps_binary(&self) -> bool {
+        // Careful builds are for a target of their own, somewhere else
+        self.keep_binary && self.is_program() && !self.careful
+    }
+
+    fn source(&self) -> Cow<'_, str> {
+        if self.hidden_tests.is_some() {
+            return hidden_tests::declare(&self.code).into();
+        }
+        if self.wrap_main && self.is_program() {
+            if let Some(wrapped) = wrap_main::wrap_main(&self.code) {
+                return wrapped.into();
+            }
+        }
+        self.code.as_str().into()
+    }
+}
+
+impl CrateTypeRequest for ExecuteRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for ExecuteRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+impl BacktraceRequest for ExecuteRequest {
+    fn backtrace(&self) -> bool {
+        self.backtrace
+    }
+}
+
+impl ColorRequest for ExecuteRequest {
+    fn color(&self) -> ColorOutput {
+        self.color
+    }
+}
+
+impl MacroDebuggingRequest for ExecuteRequest {
+    fn trace_macros(&self) -> bool {
+        self.trace_macros
+    }
+
+    fn macro_backtrace(&self) -> bool {
+        self.macro_backtrace
+    }
+}
+
+impl RunRequest for ExecuteRequest {
+    fn stdin(&self) -> &str {
+        &self.stdin
+    }
+}
+
+impl ExecuteResponse {
+    /// Whether it was stopped before it could finish, by the timeout
+    /// or one of the limits. Running the same code again might not be
+    /// stopped in the same place, or at all.
+    pub fn was_stopped(&self) -> bool {
+        self.out_of_memory
+            || self.output_rate_limited
+            || self.abuse_suspected
+            || self.disk_quota_exceeded
+            || (!self.success && abuse::ran_too_long(&self.stderr))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteResponse {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub sccache: Option<SccacheStats>,
+    /// When the build failed in a way that something other than the
+    /// code could fix.
+    #[serde(default)]
+    pub hint: Option<Hint>,
+    /// When it was asked to be kept. It stays with whoever ran the
+    /// sandbox, as it is too large to send anywhere else.
+    #[serde(skip)]
+    pub binary: Option<Vec<u8>>,
+    /// When they were asked for.
+    #[serde(default)]
+    pub records: Option<Vec<OutputRecord>>,
+    /// Zero when a kept binary was run again, as nothing was built.
+    #[serde(default)]
+    pub diagnostics: DiagnosticCounts,
+    /// When the tests were run by nextest.
+    #[serde(default)]
+    pub test_results: Option<Vec<TestResult>>,
+    /// The messages of the checks that only careful executions make,
+    /// which failed.
+    #[serde(default)]
+    pub careful_checks: Vec<String>,
+    /// When image IDs are reported.
+    #[serde(default)]
+    pub image_id: Option<String>,
+    /// It was stopped after keeping the CPU busy without writing
+    /// anything, as programs that mine cryptocurrency do.
+    #[serde(default)]
+    pub abuse_suspected: bool,
+    /// It was stopped for writing faster than the limits allow, so the
+    /// output is cut short.
+    #[serde(default)]
+    pub output_rate_limited: bool,
+    /// A process was killed for using more memory than the limits
+    /// allow.
+    #[serde(default)]
+    pub out_of_memory: bool,
+    /// It failed for writing more than the disk limit allows, or
+    /// because the host's disk is full.
+    #[serde(default)]
+    pub disk_quota_exceeded: bool,
+    /// When outputs were spilled, `stdout` and `stderr` are only the
+    /// start of them. Like the binary, the files stay with whoever ran
+    /// the sandbox.
+    #[serde(skip)]
+    pub spilled: Spilled,
+}
+
+/// A line that was written to stdout or stderr.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputRecord {
+    pub stream: OutputStream,
+    /// Since the first line that was written to either stream.
+    pub timestamp_ms: u64,
+    /// With the newline, when the line was finished.
+    pub text: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// How one of the tests went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestResult {
+    /// Its path within the crate, such as `tests::it_works`.
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub duration_ms: Option<u64>,
+    /// What it wrote, when it failed.
+    pub stdout: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// Runs the binary kept by an [`ExecuteRequest`] again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RerunRequest {
+    /// The channel that the binary was built with.
+    pub channel: Channel,
+    pub backtrace: bool,
+    pub color: ColorOutput,
+    pub args: Vec<String>,
+    pub stdin: String,
+}
+
+impl BacktraceRequest for RerunRequest {
+    fn backtrace(&self) -> bool {
+        self.backtrace
+    }
+}
+
+impl RunRequest for RerunRequest {
+    fn stdin(&self) -> &str {
+        &self.stdin
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SccacheStats {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+impl SccacheStats {
+    // Parses the output of `sccache --show-stats --stats-format=json`
+    fn from_json(stats: &str) -> Option<Self> {
+        let stats: serde_json::Value = serde_json::from_str(stats).ok()?;
+        let stats = stats.get("stats")?;
+
+        let total = |name: &str| -> u64 {
+            stats
+                .get(name)
+                .and_then(|v| v.get("counts"))
+                .and_then(|v| v.as_object())
+                .map_or(0, |counts| counts.values().filter_map(|v| v.as_u64()).sum())
+        };
+
+        Some(Self {
+            cache_hits: total("cache_hits"),
+            cache_misses: total("cache_misses"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    /// Only these lines are formatted, leaving the rest of the code as
+    /// it was written.
+    pub lines: Option<LineRange>,
+}
+
+/// Counted from one, including both the first and the last line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl EditionRequest for FormatRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatResponse {
+    pub success: bool,
+    pub code: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditionMigrationRequest {
+    pub code: String,
+    /// The edition that the code was written for.
+    pub edition: Edition,
+    pub crate_type: CrateType,
+}
+
+impl CrateTypeRequest for EditionMigrationRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for EditionMigrationRequest {
+    fn edition(&self) -> Option<Edition> {
+        Some(self.edition)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditionMigrationResponse {
+    pub success: bool,
+    /// As it was left, even when not all of it could be migrated.
+    pub code: String,
+    /// The edition that the code was migrated to.
+    pub edition: Edition,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClippyRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+}
+
+impl CrateTypeRequest for ClippyRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for ClippyRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClippyResponse {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(default)]
+    pub diagnostics: DiagnosticCounts,
+    #[serde(default)]
+    pub suggestions: Vec<Suggestion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MiriRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+}
+
+impl EditionRequest for MiriRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MiriResponse {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KaniRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+}
+
+impl EditionRequest for KaniRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KaniResponse {
+    /// Whether every harness was verified.
+    pub success: bool,
+    pub harnesses: Vec<KaniHarness>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// How verifying one of the proof harnesses went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KaniHarness {
+    pub name: String,
+    pub verified: bool,
+    pub failed_checks: Vec<KaniCheck>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KaniCheck {
+    /// Such as `attempt to add with overflow`.
+    pub description: String,
+    /// Counted from one, when the check is in the code itself.
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroExpansionRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    /// Only expands the item at this path, such as
+    /// `my_module::MyStruct`, instead of the whole crate.
+    #[serde(default)]
+    pub expand_item: Option<String>,
+}
+
+impl EditionRequest for MacroExpansionRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroExpansionResponse {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimePassesRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    pub mode: Mode,
+}
+
+impl CrateTypeRequest for TimePassesRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for TimePassesRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimePassesResponse {
+    pub success: bool,
+    /// In the order the compiler reported them. Some passes are made
+    /// up of others, so their times overlap.
+    pub passes: Vec<CompilerPass>,
+    pub stdout: String,
+    /// Without the report of the passes.
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilerPass {
+    pub name: String,
+    pub milliseconds: u64,
+    /// Not every platform reports the memory that was used.
+    pub rss_before_mb: Option<u64>,
+    pub rss_after_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizationRemarksRequest {
+    pub code: String,
+    pub channel: Channel,
+    pub mode: Mode,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+}
+
+impl CrateTypeRequest for OptimizationRemarksRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for OptimizationRemarksRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizationRemarksResponse {
+    pub success: bool,
+    /// Only those about the code itself, in the order the compiler
+    /// reported them.
+    pub remarks: Vec<OptimizationRemark>,
+    pub stdout: String,
+    /// Without any of the remarks.
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizationRemark {
+    /// The LLVM pass that made the remark, such as `inline` or
+    /// `loop-vectorize`.
+    pub pass: String,
+    pub kind: RemarkKind,
+    /// With the symbols demangled.
+    pub message: String,
+    pub span: RemarkSpan,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemarkKind {
+    /// The optimization was made.
+    Passed,
+    /// The optimization was considered but not made.
+    Missed,
+    /// What a pass found out about the code.
+    Analysis,
+    /// The optimization was asked for but could not be made.
+    Failure,
+}
+
+/// Where in the code a remark is about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemarkSpan {
+    pub line: u32,
+    /// Not always known, such as for remarks about a whole function.
+    pub column: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionsRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    /// Where the cursor is, in bytes from the start of the code.
+    pub offset: usize,
+}
+
+impl CrateTypeRequest for CompletionsRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for CompletionsRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionsResponse {
+    pub success: bool,
+    /// The most likely first, as rust-analyzer ranks them.
+    pub completions: Vec<Completion>,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub label: String,
+    /// Such as `function`, `struct` or `keyword`.
+    pub kind: Option<String>,
+    /// Usually the type or the signature.
+    pub detail: Option<String>,
+    /// What goes in place of the word at the cursor.
+    pub insert_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoverRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    /// Where the cursor is, in bytes from the start of the code.
+    pub offset: usize,
+}
+
+impl CrateTypeRequest for HoverRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for HoverRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoverResponse {
+    pub success: bool,
+    /// `None` when there is nothing at the cursor to tell about.
+    pub hover: Option<Hover>,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hover {
+    /// The type of the symbol, or how it is declared, as Markdown.
+    pub signature: String,
+    /// As Markdown.
+    pub documentation: Option<String>,
+    /// Of the symbol that is being told about.
+    pub range: Option<CodeRange>,
+}
+
+/// In bytes from the start of the code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    /// Where the cursor is, in bytes from the start of the code.
+    pub offset: usize,
+    pub target: NavigationTarget,
+}
+
+impl CrateTypeRequest for NavigationRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for NavigationRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+/// What to find for the symbol at the cursor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NavigationTarget {
+    Definition,
+    /// Every use, along with the definition.
+    References,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationResponse {
+    pub success: bool,
+    pub locations: Vec<Location>,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    /// Such as `library/core/src/option.rs` for the standard library.
+    /// `None` for the code itself.
+    pub file: Option<String>,
+    /// Counted from one.
+    pub line: usize,
+    /// Only known for the code itself.
+    pub range: Option<CodeRange>,
+    /// What is there, for any other file.
+    pub excerpt: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintsRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+}
+
+impl CrateTypeRequest for InlayHintsRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for InlayHintsRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintsResponse {
+    pub success: bool,
+    pub hints: Vec<InlayHint>,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHint {
+    /// Where the hint goes, in bytes from the start of the code.
+    pub offset: usize,
+    /// Such as `: Vec<i32>` or `capacity:`.
+    pub label: String,
+    pub kind: InlayHintKind,
+    /// Whether a space is wanted before the hint.
+    pub padding_left: bool,
+    /// Whether a space is wanted after the hint.
+    pub padding_right: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InlayHintKind {
+    /// The inferred type of a binding or an expression.
+    Type,
+    /// The name of the parameter that an argument is passed to.
+    Parameter,
+}
+
+/// What makes a nightly count as having the regression.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Regression {
+    /// The code stopped compiling
+    Error,
+    /// The code started compiling
+    Success,
+    /// The compiler started panicking
+    Ice,
+    /// The compiler stopped panicking
+    NonIce,
+    /// The code started compiling, or the compiler started panicking
+    NonError,
+}
+
+impl Regression {
+    fn cargo_bisect_ident(&self) -> &'static str {
+        use self::Regression::*;
+
+        match *self {
+            Error => "error",
+            Success => "success",
+            Ice => "ice",
+            NonIce => "non-ice",
+            NonError => "non-error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BisectRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    /// A nightly without the regression
+    pub start: NaiveDate,
+    /// A nightly with the regression. When unset, the latest nightly
+    /// is used.
+    pub end: Option<NaiveDate>,
+    pub regression: Regression,
+}
+
+impl CrateTypeRequest for BisectRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for BisectRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BisectResponse {
+    pub success: bool,
+    pub regressed_nightly: Option<NaiveDate>,
+    /// A link to the commits that went into the regressed nightly
+    pub commit_range: Option<String>,
+    /// A link to the commit with the regression, when it could be
+    /// narrowed down that far
+    pub regressed_commit: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// What the compiler must keep doing for less of the code to do.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preserved {
+    /// Panicking
+    Ice,
+    /// Reporting an error with this text, such as its code, `E0308`.
+    /// When empty, the first error that the code has.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReduceRequest {
+    pub channel: Channel,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    pub code: String,
+    pub preserved: Preserved,
+}
+
+impl CrateTypeRequest for ReduceRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for ReduceRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReduceResponse {
+    /// Whether the code had the problem to begin with
+    pub reproduced: bool,
+    /// As little of the code as still has the problem
+    pub code: String,
+    /// How many times it was compiled
+    pub attempts: usize,
+    /// From compiling the reduced code
+    pub stderr: String,
+}
+
+/// The summary that `cargo bisect-rustc` prints once it is done.
+#[derive(Debug, Default, PartialEq)]
+struct BisectReport {
+    regressed_nightly: Option<NaiveDate>,
+    commit_range: Option<String>,
+    regressed_commit: Option<String>,
+}
+
+impl BisectReport {
+    fn parse(stdout: &str, stderr: &str) -> Self {
+        let mut report = Self::default();
+
+        for line in stdout.lines().chain(stderr.lines()) {
+            let line = line.trim();
+
+            if let Some(date) = line.strip_prefix("regressed nightly: nightly-") {
+                report.regressed_nightly = date.parse().ok();
+            } else if let Some(url) = line.strip_prefix("searched commit range: ") {
+                report.commit_range = Some(url.into());
+            } else if let Some(url) = line.strip_prefix("regressed commit: ") {
+                report.regressed_commit = Some(url.into());
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Running the tests completely in parallel causes spurious
+    // failures due to my resource-limited Docker
+    // environment. Additionally, we have some tests that *require*
+    // that no other Docker processes are running.
+    fn one_test_at_a_time() -> impl Drop {
+        use lazy_static::lazy_static;
+        use std::sync::Mutex;
+
+        lazy_static! {
+            static ref DOCKER_SINGLETON: Mutex<()> = Default::default();
+        }
+
+        // We can't poison the empty tuple
+        DOCKER_SINGLETON.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    const HELLO_WORLD_CODE: &str = r#"
+    fn main() {
+        println!("Hello, world!");
+    }
+    "#;
+
+    impl Default for ExecuteRequest {
+        fn default() -> Self {
+            ExecuteRequest {
+                channel: Channel::Stable { version: None },
+                crate_type: CrateType::Binary,
+                mode: Mode::Debug,
+                tests: false,
+                code: HELLO_WORLD_CODE.to_string(),
+                edition: None,
+                backtrace: false,
+                color: ColorOutput::Plain,
+                args: vec![],
+                stdin: String::new(),
+                keep_binary: false,
+                wrap_main: false,
+                strategy: Strategy::default(),
+                trace_macros: false,
+                macro_backtrace: false,
+                output_records: false,
+                test_runner: TestRunner::default(),
+                careful: false,
+                deterministic: false,
+                error_format: ErrorFormat::default(),
+                hidden_tests: None,
+            }
+        }
+    }
+
+    impl Default for CompileRequest {
+        fn default() -> Self {
+            CompileRequest {
+                target: CompileTarget::LlvmIr,
+                extra_targets: vec![],
+                unoptimized_mir: false,
+                channel: Channel::Stable { version: None },
+                crate_type: CrateType::Binary,
+                mode: Mode::Debug,
+                tests: false,
+                code: HELLO_WORLD_CODE.to_string(),
+                edition: None,
+                backtrace: false,
+                color: ColorOutput::Plain,
+                trace_macros: false,
+                macro_backtrace: false,
+                error_format: ErrorFormat::default(),
+            }
+        }
+    }
+
+    impl Default for ClippyRequest {
+        fn default() -> Self {
+            ClippyRequest {
+                code: HELLO_WORLD_CODE.to_string(),
+                crate_type: CrateType::Binary,
+                edition: None,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn code_over_the_limit_is_never_written() {
+        let config = Config {
+            max_code_size: Some(16),
+            ..Default::default()
+        };
+        let req = ClippyRequest::default();
+
+        let sb = Sandbox::with_config(Arc::new(config))
+            .await
+            .expect("Unable to create sandbox");
+        let resp = sb.clippy(&req).await;
+
+        assert!(matches!(
+            resp,
+            Err(Error::CodeTooLarge { max_size: 16, .. })
+        ));
+        assert!(!sb.input_file.exists());
+    }
+
+    #[tokio::test]
+    async fn pinned_images_are_their_own_id() {
+        let mut config = Config {
+            report_image_ids: true,
+            ..Default::default()
+        };
+        config
+            .images
+            .set_channel(Channel::Beta, "sha256:0123".into());
+
+        let sb = Sandbox::with_config(Arc::new(config))
+            .await
+            .expect("Unable to create sandbox");
+        let id = sb.reported_image_id(Channel::Beta).await;
+
+        assert_eq!(
+            id.expect("Unable to find the image"),
+            Some("sha256:0123".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn executions_report_their_image_id() {
+        let _singleton = one_test_at_a_time();
+        let config = Config {
+            report_image_ids: true,
+            ..Default::default()
+        };
+        let req = ExecuteRequest::default();
+
+        let sb = Sandbox::with_config(Arc::new(config))
+            .await
+            .expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        let id = resp.image_id.expect("The image ID was not reported");
+        assert!(id.starts_with("sha256:"), "was: {}", id);
+    }
+
+    #[tokio::test]
+    async fn basic_functionality() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest::default();
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("Hello, world!"));
+    }
+
+    #[tokio::test]
+    async fn basic_functionality_with_a_read_only_root() {
+        let _singleton = one_test_at_a_time();
+        let config = Config {
+            read_only_root: true,
+            ..Default::default()
+        };
+        let req = ExecuteRequest {
+            code: r#"fn main() { println!("{}", rand::random::<u8>() <= u8::MAX); }"#.into(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::with_config(Arc::new(config))
+            .await
+            .expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.success, "stderr: {}", resp.stderr);
+        assert_eq!(resp.stdout, "true\n");
+        // The prebuilt dependencies were used rather than built again
+        assert!(
+            !resp.stderr.contains("Compiling rand"),
+            "stderr: {}",
+            resp.stderr
+        );
+    }
+
+    #[tokio::test]
+    async fn executions_report_their_progress() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest::default();
+        let phases = Arc::new(std::sync::Mutex::new(vec![]));
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let sb = sb.with_progress({
+            let phases = phases.clone();
+            move |phase| phases.lock().unwrap().push(phase)
+        });
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert_eq!(resp.stdout, "Hello, world!\n");
+        assert_eq!(
+            *phases.lock().unwrap(),
+            [Phase::ContainerStarted, Phase::Compiling, Phase::Running]
+        );
+    }
+
+    #[tokio::test]
+    async fn nextest_reports_each_test() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        #[test]
+        fn it_works() {}
+
+        #[test]
+        fn it_fails() {
+            panic!("oh no");
+        }
+        "#;
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            tests: true,
+            test_runner: TestRunner::Nextest,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(!resp.success);
+        let mut results: Vec<_> = resp
+            .test_results
+            .expect("Tests were not reported")
+            .into_iter()
+            .map(|r| (r.name, r.outcome))
+            .collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            results,
+            [
+                ("it_fails".to_string(), TestOutcome::Failed),
+                ("it_works".to_string(), TestOutcome::Passed),
+            ]
+        );
+    }
+
+    const HIDDEN_TESTS: &str = r#"
+    use super::*;
+
+    #[test]
+    fn it_adds() {
+        assert_eq!(add(1, 2), 3);
+    }
+    "#;
+
+    #[tokio::test]
+    async fn hidden_tests_are_run_against_the_code() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: "pub fn add(a: i32, b: i32) -> i32 { a + b }".to_string(),
+            tests: true,
+            test_runner: TestRunner::Nextest,
+            hidden_tests: Some(HIDDEN_TESTS.to_string()),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.success, "stderr: {}", resp.stderr);
+        let names: Vec<_> = resp
+            .test_results
+            .expect("Tests were not reported")
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        assert_eq!(names, ["hidden_tests::it_adds"]);
+    }
+
+    #[tokio::test]
+    async fn code_cannot_disable_hidden_tests() {
+        let _singleton = one_test_at_a_time();
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+
+        for code in [
+            // Would apply to whatever came after it
+            "pub fn add(a: i32, b: i32) -> i32 { a - b }\n#[cfg(any())]",
+            // Tests of its own, in place of the hidden ones
+            "pub fn add(a: i32, b: i32) -> i32 { a - b }\n#[test] fn it_adds() {}",
+        ] {
+            let req = ExecuteRequest {
+                code: code.to_string(),
+                tests: true,
+                test_runner: TestRunner::Nextest,
+                hidden_tests: Some(HIDDEN_TESTS.to_string()),
+                ..ExecuteRequest::default()
+            };
+            let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+            assert!(!resp.success, "{} succeeded", code);
+        }
+    }
+
+    #[tokio::test]
+    async fn careful_executions_report_failed_checks() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn main() {
+            let v = [1, 2, 3];
+            println!("{}", unsafe { v.get_unchecked(7) });
+        }
+        "#;
+        let req = ExecuteRequest {
+            channel: Channel::Nightly { date: None },
+            code: code.to_string(),
+            careful: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(!resp.success);
+        assert!(
+            resp.careful_checks[0].starts_with("unsafe precondition(s) violated"),
+            "Was: {:?}",
+            resp.careful_checks
+        );
+    }
+
+    #[tokio::test]
+    async fn deterministic_executions_have_a_fixed_clock() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        fn main() {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            println!("{}", now.as_secs() / (24 * 60 * 60));
+        }
+        "#;
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            deterministic: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        // 2000-01-01
+        assert_eq!(resp.stdout, "10957\n");
+    }
+
+    #[tokio::test]
+    async fn busy_silent_executions_are_suspected_of_abuse() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn main() {
+            let mut n = 0u64;
+            loop {
+                n = std::hint::black_box(n.wrapping_add(1));
+            }
+        }
+        "#;
+        let config = Config {
+            limits: Limits {
+                cpu_time: Some(Duration::from_secs(2)),
+                ..Limits::default()
+            },
+            ..Config::default()
+        };
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::with_config(Arc::new(config))
+            .await
+            .expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(!resp.success);
+        assert!(resp.abuse_suspected, "Was: {}", resp.stderr);
+    }
+
+    #[tokio::test]
+    async fn executions_that_write_too_quickly_are_stopped() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn main() {
+            loop {
+                println!("Hello, world!");
+            }
+        }
+        "#;
+        let config = Config {
+            limits: Limits {
+                output_rate: Some(64 * 1024),
+                ..Limits::default()
+            },
+            ..Config::default()
+        };
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::with_config(Arc::new(config))
+            .await
+            .expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.output_rate_limited);
+        assert!(
+            resp.stdout.len() < 2 * 64 * 1024,
+            "Was: {}",
+            resp.stdout.len()
+        );
+        assert!(resp.stdout.contains("The output was stopped"));
+    }
+
+    #[tokio::test]
+    async fn output_records_keep_the_order_it_was_written_in() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: r#"fn main() { println!("before"); panic!("oh no"); }"#.to_string(),
+            output_records: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        let records = resp.records.expect("The records were not returned");
+        let before = records
+            .iter()
+            .position(|r| r.stream == OutputStream::Stdout && r.text == "before\n");
+        let panic = records
+            .iter()
+            .position(|r| r.stream == OutputStream::Stderr && r.text.contains("oh no"));
+        assert!(before < panic && before.is_some(), "{:?}", records);
+    }
+
+    const COMPILATION_MODE_CODE: &str = r#"
+    #[cfg(debug_assertions)]
+    fn main() {
+        println!("Compiling in debug mode");
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn main() {
+        println!("Compiling in release mode");
+    }
+    "#;
+
+    #[tokio::test]
+    async fn debug_mode() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: COMPILATION_MODE_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("debug mode"));
+    }
+
+    #[tokio::test]
+    async fn release_mode() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            mode: Mode::Release,
+            code: COMPILATION_MODE_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("release mode"));
+    }
+
+    const ECHO_CODE: &str = r#"
+    use std::io::Read;
+
+    fn main() {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input).unwrap();
+        let args: Vec<_> = std::env::args().skip(1).collect();
+        println!("{} {}", args.join(","), input);
+    }
+    "#;
+
+    #[tokio::test]
+    async fn programs_are_given_arguments_and_input() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: ECHO_CODE.to_string(),
+            args: vec!["a".into(), "b c".into()],
+            stdin: "input".into(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("a,b c input"), "{}", resp.stdout);
+        assert_eq!(resp.binary, None);
+    }
+
+    #[tokio::test]
+    async fn kept_binaries_run_again() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: ECHO_CODE.to_string(),
+            keep_binary: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+        let binary = resp.binary.expect("The binary was not kept");
+
+        let req = RerunRequest {
+            channel: req.channel,
+            backtrace: false,
+            color: ColorOutput::Plain,
+            args: vec!["again".into()],
+            stdin: "more input".into(),
+        };
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.rerun(&req, &binary).await.expect("Unable to rerun");
+
+        assert!(resp.stdout.contains("again more input"), "{}", resp.stdout);
+        assert!(!resp.stderr.contains("Compiling"), "{}", resp.stderr);
+    }
+
+    const TEST_ONLY_CODE: &str = r#"
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+    "#;
+
+    #[tokio::test]
+    async fn programs_with_only_tests_run_them() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: TEST_ONLY_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.success, "{}", resp.stderr);
+        assert!(
+            resp.stdout.contains("test it_works ... ok"),
+            "{}",
+            resp.stdout
+        );
+    }
+
+    #[tokio::test]
+    async fn macros_can_be_traced() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly { date: None },
+            code: r#"macro_rules! one { () => { 1 } } fn main() { one!(); }"#.to_string(),
+            trace_macros: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(
+            resp.stderr.contains("expanding `one! {  }`"),
+            "{}",
+            resp.stderr
+        );
+    }
+
+    #[tokio::test]
+    async fn snippets_without_main_are_wrapped() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: "let a = 2;\na * 21".to_string(),
+            wrap_main: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("42"), "{}", resp.stdout);
+    }
+
+    static VERSION_CODE: &str = r#"
+    use std::process::Command;
+
+    fn main() {
+        let output = Command::new("rustc").arg("--version").output().unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        println!("{}", output);
+    }
+    "#;
+
+    #[tokio::test]
+    async fn stable_channel() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Stable { version: None },
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(!resp.stdout.contains("beta"));
+        assert!(!resp.stdout.contains("nightly"));
+    }
+
+    #[tokio::test]
+    async fn beta_channel() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Beta,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(resp.stdout.contains("beta"));
+        assert!(!resp.stdout.contains("nightly"));
+    }
+
+    #[tokio::test]
+    async fn nightly_channel() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly { date: None },
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(!resp.stdout.contains("beta"));
+        assert!(resp.stdout.contains("nightly"));
+    }
+
+    // Code that will only work in Rust 2015
+    const EDITION_CODE: &str = r#"
+    fn main() {
+        let async = true;
+    }
+    "#;
+
+    const EDITION_ERROR: &str = "found keyword `async`";
+
+    #[tokio::test]
+    async fn rust_edition_default() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly { date: None },
+            code: EDITION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new().await?.execute(&req).await?;
+
+        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rust_edition_2015() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly { date: None },
+            code: EDITION_CODE.to_string(),
+            edition: Some(Edition::Rust2015),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new().await?.execute(&req).await?;
+
+        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rust_edition_2018() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly { date: None },
+            code: EDITION_CODE.to_string(),
+            edition: Some(Edition::Rust2018),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new().await?.execute(&req).await?;
+
+        assert!(resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    const BACKTRACE_CODE: &str = r#"
+    fn trigger_the_problem() {
+        None::<u8>.unwrap();
+    }
+
+    fn main() {
+        trigger_the_problem()
+    }
+    "#;
+
+    const BACKTRACE_NOTE: &str =
+        "run with `RUST_BACKTRACE=1` environment variable to display a backtrace";
+
+    #[tokio::test]
+    async fn backtrace_disabled() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: BACKTRACE_CODE.to_string(),
+            backtrace: false,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.execute(&req).await?;
+
+        assert!(resp.stderr.contains(BACKTRACE_NOTE), "Was: {}", resp.stderr);
+        assert!(
+            !resp.stderr.contains("stack backtrace:"),
+            "Was: {}",
+            resp.stderr
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backtrace_enabled() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: BACKTRACE_CODE.to_string(),
+            backtrace: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.execute(&req).await?;
+
+        assert!(
+            !resp.stderr.contains(BACKTRACE_NOTE),
+            "Was: {}",
+            resp.stderr
+        );
+        assert!(
+            resp.stderr.contains("stack backtrace:"),
+            "Was: {}",
+            resp.stderr
+        );
+
+        Ok(())
+    }
+
+    const COLOR_CODE: &str = r#"
+    fn main() {
+        let unused = 1;
+    }
+    "#;
+
+    #[tokio::test]
+    async fn colors_are_kept() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: COLOR_CODE.to_string(),
+            color: ColorOutput::Ansi,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.execute(&req).await?;
+
+        assert!(resp.stderr.contains("\u{1b}["), "Was: {}", resp.stderr);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn colors_can_be_converted_to_html() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: COLOR_CODE.to_string(),
+            color: ColorOutput::Html,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.execute(&req).await?;
+
+        assert!(!resp.stderr.contains("\u{1b}["), "Was: {}", resp.stderr);
+        assert!(
+            resp.stderr.contains(r#"<span class="ansi-bold"#),
+            "Was: {}",
+            resp.stderr
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn short_errors_are_one_line_each() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: COLOR_CODE.to_string(),
+            error_format: ErrorFormat::Short,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.execute(&req).await?;
+
+        assert!(
+            resp.stderr
+                .contains("src/main.rs:3:13: warning: unused variable: `unused`"),
+            "Was: {}",
+            resp.stderr
+        );
+        assert!(!resp.stderr.contains(" --> "), "Was: {}", resp.stderr);
+        assert_eq!(resp.diagnostics.warnings, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn output_llvm_ir() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::LlvmIr,
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.compile(&req).await.expect("Unable to compile code");
+
+        assert!(resp.code.contains("ModuleID"));
+        assert!(resp.code.contains("target datalayout"));
+        assert!(resp.code.contains("target triple"));
+    }
+
+    #[tokio::test]
+    async fn unused_variables_have_a_suggestion() {
+        let _singleton = one_test_at_a_time();
+        let code = "fn main() { let x = 1; }";
+        let req = CompileRequest {
+            code: code.to_string(),
+            target: CompileTarget::LlvmIr,
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.compile(&req).await.expect("Unable to compile code");
+
+        assert!(resp.stderr.contains("unused variable: `x`"));
+        assert_eq!(
+            resp.suggestions,
+            [Suggestion {
+                message: "if this is intentional, prefix it with an underscore".into(),
+                range: CodeRange { start: 16, end: 17 },
+                replacement: "_x".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn output_llvm_ir_and_mir() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::LlvmIr,
+            extra_targets: vec![CompileTarget::Mir],
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.compile(&req).await.expect("Unable to compile code");
+
+        assert!(resp.code.contains("ModuleID"));
+        let (target, mir) = &resp.extra_outputs[0];
+        assert_eq!(*target, CompileTarget::Mir);
+        assert!(mir.contains("fn main() -> ()"));
+    }
+
+    #[tokio::test]
+    async fn output_assembly() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(
+                AssemblyFlavor::Att,
+                DemangleAssembly::Mangle,
+                ProcessAssembly::Raw,
+            ),
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.compile(&req).await.expect("Unable to compile code");
+
+        assert!(resp.code.contains(".text"));
+        assert!(resp.code.contains(".file"));
+        assert!(resp.code.contains(".section"));
+        assert!(resp.code.contains(".p2align"));
+    }
+
+    #[tokio::test]
+    async fn output_demangled_assembly() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(
+                AssemblyFlavor::Att,
+                DemangleAssembly::Demangle,
+                ProcessAssembly::Raw,
+            ),
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.compile(&req).await.expect("Unable to compile code");
+
+        assert!(resp.code.contains("core::fmt::Arguments::new_v1"));
+        assert!(resp.code.contains("std::io::stdio::_print@GOTPCREL"));
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn output_filtered_assembly() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(
+                AssemblyFlavor::Att,
+                DemangleAssembly::Mangle,
+                ProcessAssembly::Filter,
+            ),
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.compile(&req).await.expect("Unable to compile code");
+
+        assert!(resp.code.contains(".text"));
+        assert!(resp.code.contains(".file"));
+    }
+
+    #[tokio::test]
+    async fn formatting_code() {
+        let _singleton = one_test_at_a_time();
+        let req = FormatRequest {
+            code: "fn foo () { method_call(); }".to_string(),
+            edition: None,
+            lines: None,
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.format(&req).await.expect("Unable to format code");
+
+        let lines: Vec<_> = resp.code.lines().collect();
+
+        assert_eq!(lines[0], "fn foo() {");
+        assert_eq!(lines[1], "    method_call();");
+        assert_eq!(lines[2], "}");
+    }
+
+    #[tokio::test]
+    async fn formatting_only_some_lines() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = FormatRequest {
+            code: "fn a () {}\nfn b () {}\nfn c () {}\n".to_string(),
+            edition: None,
+            lines: Some(LineRange { start: 2, end: 2 }),
+        };
+
+        let resp = Sandbox::new().await?.format(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        assert_eq!(resp.code, "fn a () {}\nfn b() {}\nfn c () {}\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn formatting_backwards_lines_is_rejected() {
+        let req = FormatRequest {
+            code: String::new(),
+            edition: None,
+            lines: Some(LineRange { start: 3, end: 2 }),
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.format(&req).await;
+
+        assert!(matches!(resp, Err(Error::InvalidLineRange { .. })));
+    }
+
+    // Code that is only syntactically valid in Rust 2018
+    const FORMAT_IN_EDITION_2018: &str = r#"fn main() { use std::num::ParseIntError; let result: Result<i32, ParseIntError> = try { "1".parse::<i32>()? + "2".parse::<i32>()? + "3".parse::<i32>()? }; assert_eq!(result, Ok(6)); }"#;
+
+    const FORMAT_ERROR: &str = r#"error: expected identifier, found `"1"`"#;
+
+    #[tokio::test]
+    async fn formatting_code_edition_2015() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = FormatRequest {
+            code: FORMAT_IN_EDITION_2018.to_string(),
+            edition: Some(Edition::Rust2015),
+            lines: None,
+        };
+
+        let resp = Sandbox::new().await?.format(&req).await?;
+
+        assert!(resp.stderr.contains(FORMAT_ERROR));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn formatting_code_edition_2018() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = FormatRequest {
+            code: FORMAT_IN_EDITION_2018.to_string(),
+            edition: Some(Edition::Rust2018),
+            lines: None,
+        };
+
+        let resp = Sandbox::new().await?.format(&req).await?;
+        assert!(!resp.stderr.contains(FORMAT_ERROR));
+
+        let lines: Vec<_> = resp.code.lines().collect();
+        assert_eq!(lines[0], r#"fn main() {"#);
+        assert_eq!(lines[1], r#"    use std::num::ParseIntError;"#);
+        assert_eq!(lines[2], r#"    let result: Result<i32, ParseIntError> ="#);
+        assert_eq!(
+            lines[3],
+            r#"        try { "1".parse::<i32>()? + "2".parse::<i32>()? + "3".parse::<i32>()? };"#
+        );
+        assert_eq!(lines[4], r#"    assert_eq!(result, Ok(6));"#);
+        assert_eq!(lines[5], r#"}"#);
+        Ok(())
+    }
+
+    // `async` is only a keyword from Rust 2018 on
+    const MIGRATE_FROM_EDITION_2015: &str =
+        r#"fn main() { let async = 1; println!("{}", async); }"#;
+
+    #[tokio::test]
+    async fn migrating_code_edition_2015() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = EditionMigrationRequest {
+            code: MIGRATE_FROM_EDITION_2015.to_string(),
+            edition: Edition::Rust2015,
+            crate_type: CrateType::Binary,
+        };
+
+        let resp = Sandbox::new().await?.migrate_edition(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        assert_eq!(resp.edition, Edition::Rust2018);
+        assert!(resp.code.contains("let r#async = 1;"), "{}", resp.code);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn the_newest_edition_cannot_be_migrated() -> Result<()> {
+        let req = EditionMigrationRequest {
+            code: HELLO_WORLD_CODE.to_string(),
+            edition: Edition::Rust2021,
+            crate_type: CrateType::Binary,
+        };
+
+        let resp = Sandbox::new().await?.migrate_edition(&req).await;
+
+        assert!(
+            matches!(resp, Err(Error::NoNewerEdition { .. })),
+            "{:?}",
+            resp
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compiler_passes_are_timed() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = TimePassesRequest {
+            code: HELLO_WORLD_CODE.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            mode: Mode::Debug,
+        };
+
+        let resp = Sandbox::new().await?.time_passes(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        assert!(
+            resp.passes.iter().any(|p| p.name == "total"),
+            "{:?}",
+            resp.passes
+        );
+        assert!(!resp.stderr.contains("time:"), "{}", resp.stderr);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missed_inlining_is_remarked_on() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+            #[inline(never)]
+            fn answer() -> u8 { 42 }
+            fn main() { println!("{}", answer()); }
+        "#;
+        let req = OptimizationRemarksRequest {
+            code: code.to_string(),
+            channel: Channel::Stable { version: None },
+            mode: Mode::Release,
+            edition: None,
+            crate_type: CrateType::Binary,
+        };
+
+        let resp = Sandbox::new().await?.optimization_remarks(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        let remark = resp
+            .remarks
+            .iter()
+            .find(|r| r.pass == "inline" && r.kind == RemarkKind::Missed)
+            .unwrap_or_else(|| panic!("{:?}", resp.remarks));
+        assert!(
+            remark.message.contains("playground::answer"),
+            "{}",
+            remark.message
+        );
+        assert_eq!(remark.span.line, 4);
+        assert!(!resp.stderr.contains("note:"), "{}", resp.stderr);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn methods_are_completed() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = "fn main() {\n    let v = vec![1, 2, 3];\n    v.\n}\n";
+        let req = CompletionsRequest {
+            code: code.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            offset: code.find("v.\n").unwrap() + 2,
+        };
+
+        let resp = Sandbox::new().await?.completions(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        assert!(
+            resp.completions.iter().any(|c| c.label.starts_with("push")),
+            "{:?}",
+            resp.completions
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn types_are_shown_on_hover() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = "fn main() {\n    let numbers = vec![1u8, 2, 3];\n}\n";
+        let req = HoverRequest {
+            code: code.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            offset: code.find("numbers").unwrap() + 1,
+        };
+
+        let resp = Sandbox::new().await?.hover(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        let hover = resp.hover.expect("There was nothing to hover over");
+        assert!(hover.signature.contains("Vec<u8"), "{}", hover.signature);
+        let start = code.find("numbers").unwrap();
+        assert_eq!(
+            hover.range,
+            Some(CodeRange {
+                start,
+                end: start + "numbers".len(),
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn definitions_in_std_are_found() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = "fn main() {\n    let v: Vec<u8> = Vec::new();\n}\n";
+        let req = NavigationRequest {
+            code: code.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            offset: code.find("Vec<").unwrap(),
+            target: NavigationTarget::Definition,
+        };
+
+        let resp = Sandbox::new().await?.navigate(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        let location = resp
+            .locations
+            .iter()
+            .find(|l| l.file.as_deref() == Some("library/alloc/src/vec/mod.rs"))
+            .unwrap_or_else(|| panic!("{:?}", resp.locations));
+        let excerpt = location.excerpt.as_deref().unwrap_or_default();
+        assert!(excerpt.contains("struct Vec"), "{}", excerpt);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn references_in_the_code_are_found() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = "fn main() {\n    let a = 1;\n    println!(\"{}\", a + a);\n}\n";
+        let req = NavigationRequest {
+            code: code.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            offset: code.find("a = 1").unwrap(),
+            target: NavigationTarget::References,
+        };
+
+        let resp = Sandbox::new().await?.navigate(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        let lines: Vec<_> = resp
+            .locations
+            .iter()
+            .map(|l| (l.file.clone(), l.line))
+            .collect();
+        assert_eq!(lines, [(None, 2), (None, 3), (None, 3)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inferred_types_are_hinted() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = "fn main() {\n    let v = Vec::<u8>::with_capacity(1);\n}\n";
+        let req = InlayHintsRequest {
+            code: code.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+        };
+
+        let resp = Sandbox::new().await?.inlay_hints(&req).await?;
+
+        assert!(resp.success, "{}", resp.stderr);
+        let hints: Vec<_> = resp
+            .hints
+            .iter()
+            .map(|h| (h.kind, h.offset, &*h.label))
+            .collect();
+        assert!(
+            hints.contains(&(InlayHintKind::Type, code.find(" =").unwrap(), ": Vec<u8>")),
+            "{:?}",
+            hints,
+        );
+        assert!(
+            hints.contains(&(
+                InlayHintKind::Parameter,
+                code.find("1)").unwrap(),
+                "capacity:"
+            )),
+            "{:?}",
+            hints,
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expanding_a_single_item() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        mod shapes {
+            #[derive(Debug)]
+            pub struct Circle;
+        }
+
+        #[derive(Debug)]
+        struct Square;
+
+        fn main() {}
+        "#;
+        let req = MacroExpansionRequest {
+            code: code.to_string(),
+            edition: None,
+            expand_item: Some("shapes::Circle".into()),
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.macro_expansion(&req).await.expect("Unable to expand");
+
+        assert!(resp.stdout.contains("Circle"), "was: {}", resp.stdout);
+        assert!(!resp.stdout.contains("Square"), "was: {}", resp.stdout);
+    }
+
+    #[tokio::test]
+    async fn expanding_something_other_than_an_item_is_rejected() {
+        let req = MacroExpansionRequest {
+            code: String::new(),
+            edition: None,
+            expand_item: Some("a; rm -rf /".into()),
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.macro_expansion(&req).await;
+
+        assert!(matches!(resp, Err(Error::InvalidItemPath { .. })));
+    }
+
+    #[test]
+    fn item_paths() {
+        assert!(is_item_path("MyStruct"));
+        assert!(is_item_path("my_module::MyStruct"));
+        assert!(!is_item_path(""));
+        assert!(!is_item_path("my_module::"));
+        assert!(!is_item_path("--help"));
+    }
+
+    #[test]
+    fn unshared_work_directories_are_reported() {
+        let stderr = concat!(
+            "docker: Error response from daemon: Mounts denied: \n",
+            "The path /var/folders/dx/T/playground.6gEHdGUM6XPU/output is not shared from the host and is not known to Docker.\n",
+            "You can configure shared paths from Docker -> Preferences... -> Resources -> File Sharing.",
+        );
+
+        assert!(matches!(
+            docker_run_error(stderr.into()),
+            Error::MountDenied { .. }
+        ));
+        assert!(matches!(
+            docker_run_error("docker: invalid reference format.".into()),
+            Error::DockerFailed { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn linting_code() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn main() {
+            let a = 0.0 / 0.0;
+            println!("NaN is {}", a);
+        }
+        "#;
+
+        let req = ClippyRequest {
+            code: code.to_string(),
+            ..ClippyRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.clippy(&req).await.expect("Unable to lint code");
+
+        assert!(resp.stderr.contains("deny(clippy::eq_op)"));
+        assert!(resp.stderr.contains("warn(clippy::zero_divided_by_zero)"));
+    }
+
+    #[tokio::test]
+    async fn lints_are_counted() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn main() {
+            let a = 0.0 / 0.0;
+            println!("NaN is {}", a);
+        }
+        "#;
+
+        let req = ClippyRequest {
+            code: code.to_string(),
+            ..ClippyRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.clippy(&req).await.expect("Unable to lint code");
+
+        assert_eq!(
+            resp.diagnostics,
+            DiagnosticCounts {
+                warnings: 1,
+                errors: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn linting_code_options() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        use itertools::Itertools; // Edition 2018 feature
+
+        fn example() {
+            let a = 0.0 / 0.0;
+            println!("NaN is {}", a);
+        }
+        "#;
+
+        let req = ClippyRequest {
+            code: code.to_string(),
+            crate_type: CrateType::Library(LibraryType::Rlib),
+            edition: Some(Edition::Rust2018),
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.clippy(&req).await.expect("Unable to lint code");
+
+        assert!(resp.stderr.contains("deny(clippy::eq_op)"));
+        assert!(resp.stderr.contains("warn(clippy::zero_divided_by_zero)"));
+    }
+
+    #[tokio::test]
+    async fn interpreting_code() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn main() {
+            let mut a: [u8; 0] = [];
+            unsafe { *a.get_unchecked_mut(1) = 1; }
+        }
+        "#;
+
+        let req = MiriRequest {
+            code: code.to_string(),
+            edition: None,
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.miri(&req).await?;
+
+        assert!(
+            resp.stderr.contains("Undefined Behavior"),
+            "was: {}",
+            resp.stderr
+        );
+        assert!(
+            resp.stderr.contains("pointer to 1 byte"),
+            "was: {}",
+            resp.stderr
+        );
+        assert!(
+            resp.stderr.contains("starting at offset 0"),
+            "was: {}",
+            resp.stderr
+        );
+        assert!(
+            resp.stderr.contains("is out-of-bounds"),
+            "was: {}",
+            resp.stderr
+        );
+        assert!(resp.stderr.contains("has size 0"), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verifying_proof_harnesses() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn add(a: u8, b: u8) -> u8 {
+            a + b
+        }
+
+        #[cfg(kani)]
+        #[kani::proof]
+        fn check_add() {
+            add(kani::any(), kani::any());
+        }
+
+        fn main() {}
+        "#;
+
+        let req = KaniRequest {
+            code: code.to_string(),
+            edition: None,
+        };
+
+        let sb = Sandbox::new().await?;
+        let resp = sb.kani(&req).await?;
+
+        assert!(!resp.success);
+        assert_eq!(
+            resp.harnesses,
+            [KaniHarness {
+                name: "check_add".into(),
+                verified: false,
+                failed_checks: vec![KaniCheck {
+                    description: "attempt to add with overflow".into(),
+                    line: Some(3),
+                }],
+            }],
+            "was: {}",
+            resp.stdout
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn network_connections_are_disabled() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+            fn main() {
+                match ::std::net::TcpStream::connect("google.com:80") {
+                    Ok(_) => println!("Able to connect to the outside world"),
+                    Err(e) => println!("Failed to connect {}, {:?}", e, e),
+                }
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("Failed to connect"));
+    }
+
+    #[tokio::test]
+    async fn memory_usage_is_limited() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+            fn main() {
+                let gigabyte = 1024 * 1024 * 1024;
+                let mut big = vec![0u8; 1 * gigabyte];
+                for i in &mut big { *i += 1; }
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stderr.contains("Killed"), "was: {}", resp.stderr);
+        assert!(resp.out_of_memory);
+    }
+
+    #[tokio::test]
+    async fn orphaned_processes_are_reaped() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+            use std::{fs, process::Command, thread, time::Duration};
+
+            fn main() {
+                // The shell exits right away, orphaning the sleep
+                Command::new("sh").args(["-c", "sleep 0.1 &"]).status().unwrap();
+                thread::sleep(Duration::from_millis(500));
+
+                let zombies = fs::read_dir("/proc")
+                    .unwrap()
+                    .filter_map(|e| fs::read_to_string(e.ok()?.path().join("stat")).ok())
+                    .filter(|stat| stat.rsplit(") ").next().map_or(false, |s| s.starts_with('Z')))
+                    .count();
+                println!("zombies: {}", zombies);
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("zombies: 0"), "was: {}", resp.stdout);
+    }
+
+    #[tokio::test]
+    async fn wallclock_time_is_limited() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+            fn main() {
+                let a_long_time = std::time::Duration::from_secs(20);
+                std::thread::sleep(a_long_time);
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stderr.contains("Killed"), "was: {}", resp.stderr);
+    }
+
+    #[tokio::test]
+    async fn wallclock_time_is_limited_from_outside() {
+        let _singleton = one_test_at_a_time();
+        let code = r##"
+            use std::{process::Command, thread, time::Duration};
+
+            fn main() {
+                let output = Command::new("pgrep").args(&["timeout"]).output().unwrap();
+                assert!(output.status.success());
+
+                let out = String::from_utf8(output.stdout).expect("Unable to parse output");
+                let timeout_pid: u32 = out.trim().parse().expect("Unable to find timeout PID");
+
+                let output = Command::new("sh")
+                    .args(&["-c", &format!("kill -s STOP {}", timeout_pid)])
+                    .output()
+                    .unwrap();
+                assert!(output.status.success());
+
+                for _ in 0.. {
+                    thread::sleep(Duration::from_secs(1));
+                }
+            }
+        "##;
+
+        async fn docker_process_count() -> usize {
+            let mut cmd = docker_command!("ps", "-a");
+            let output = cmd.output().await.expect("Unable to get process count");
+            let output = String::from_utf8_lossy(&output.stdout);
+            // Skip one line of header
+            output.lines().skip(1).count()
+        }
+
+        assert_eq!(
+            0,
+            docker_process_count().await,
+            "There must be no running docker processes"
+        );
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        match sb.execute(&req).await {
+            Ok(_) => panic!("Expected an error"),
+            Err(Error::CompilerExecutionTimedOut { .. }) => { /* Ok */ }
+            Err(e) => panic!("Got the wrong error: {}", e),
+        }
+
+        assert_eq!(
+            0,
+            docker_process_count().await,
+            "A docker process continues to run"
+        );
+    }
+
+    #[tokio::test]
+    async fn number_of_pids_is_limited() {
+        let _singleton = one_test_at_a_time();
+        let forkbomb = r##"
+            fn main() {
+                ::std::process::Command::new("sh").arg("-c").arg(r#"
+                    z() {
+                        z&
+                        z
+                    }
+                    z
+                "#).status().unwrap();
+            }
+        "##;
+
+        let req = ExecuteRequest {
+            code: forkbomb.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().await.expect("Unable to create sandbox");
+        let resp = sb.execute(&req).await.expect("Unable to execute code");
+
+        assert!(resp.stderr.contains("Cannot fork"));
+    }
+
+    #[test]
+    fn sccache_stats_are_summed_across_languages() {
+        let stats = r#"{
+            "stats": {
+                "compile_requests": 5,
+                "cache_hits": { "counts": { "Rust": 3, "C/C++": 1 } },
+                "cache_misses": { "counts": { "Rust": 1 } }
+            }
+        }"#;
+
+        assert_eq!(
+            SccacheStats::from_json(stats),
+            Some(SccacheStats {
+                cache_hits: 4,
+                cache_misses: 1,
+            })
+        );
+        assert_eq!(SccacheStats::from_json("not json"), None);
+    }
+
+    #[test]
+    fn only_kept_dated_nightlies_are_available() {
+        let kept = NaiveDate::from_ymd(2022, 6, 1);
+        let mut images = Images::default();
+        images
+            .dated_nightlies
+            .insert(kept, "rust-nightly:2022-06-01".into());
+
+        let channel = Channel::Nightly { date: Some(kept) };
+        assert!(images.ensure_available(channel).is_ok());
+        assert_eq!(images.channel(channel), "rust-nightly:2022-06-01");
+
+        let missing = Channel::Nightly {
+            date: Some(NaiveDate::from_ymd(2022, 5, 1)),
+        };
+        assert!(matches!(
+            images.ensure_available(missing),
+            Err(Error::NightlyUnavailable { .. })
+        ));
+        assert!(images
+            .ensure_available(Channel::Nightly { date: None })
+            .is_ok());
+    }
+
+    #[test]
+    fn images_are_pulled_from_the_registry() {
+        let registry = "registry.example.com/playground/";
+
+        assert_eq!(
+            image_in_registry(registry, "rust-nightly:2023-10-01"),
+            "registry.example.com/playground/rust-nightly:2023-10-01"
+        );
+        assert_eq!(
+            image_in_registry(registry, "rust-stable@sha256:0123abcd"),
+            "registry.example.com/playground/rust-stable@sha256:0123abcd"
+        );
+        assert_eq!(
+            image_in_registry(registry, "integer32/rust-stable"),
+            "registry.example.com/playground/integer32/rust-stable"
+        );
+        assert_eq!(
+            image_in_registry(registry, "localhost:5000/rust-beta"),
+            "localhost:5000/rust-beta"
+        );
+        assert_eq!(
+            image_in_registry(registry, "ghcr.io/example/miri"),
+            "ghcr.io/example/miri"
+        );
+    }
+
+    #[test]
+    fn targets_share_a_build_when_they_can() {
+        let asm = CompileTarget::Assembly(
+            AssemblyFlavor::Att,
+            DemangleAssembly::Demangle,
+            ProcessAssembly::Filter,
+        );
+        let req = CompileRequest {
+            target: CompileTarget::Hir,
+            extra_targets: vec![
+                asm,
+                CompileTarget::Mir,
+                CompileTarget::Hir,
+                CompileTarget::LlvmIr,
+            ],
+            ..CompileRequest::default()
+        };
+
+        assert_eq!(
+            req.builds(),
+            vec![
+                (asm, vec![CompileTarget::Mir, CompileTarget::LlvmIr]),
+                (CompileTarget::Hir, vec![]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_scratch_directories_are_removed() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let leftover = work_dir.path().join("playground.abc123");
+        let unrelated = work_dir.path().join("unrelated");
+        fs::create_dir(&leftover).await.unwrap();
+        fs::write(leftover.join("input.rs"), "").await.unwrap();
+        fs::create_dir(&unrelated).await.unwrap();
+
+        let removed = remove_stale_scratch(work_dir.path(), SCRATCH_MAX_AGE)
+            .await
+            .unwrap();
+        assert_eq!(removed, 0);
+
+        let removed = remove_stale_scratch(work_dir.path(), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert!(!leftover.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[test]
+    fn windows_paths_are_given_to_docker_with_forward_slashes() {
+        assert_eq!(
+            windows_host_path(r"C:\Users\me\AppData\Local\Temp\playground"),
+            "C:/Users/me/AppData/Local/Temp/playground"
+        );
+        assert_eq!(
+            windows_host_path(r"\\?\D:\playground\input.rs"),
+            "D:/playground/input.rs"
+        );
+    }
+
+    #[test]
+    fn carriage_returns_are_removed_from_code() {
+        assert_eq!(
+            normalize_newlines("fn main() {\r\n}\r\n"),
+            "fn main() {\n}\n"
+        );
+        assert!(matches!(
+            normalize_newlines("fn main() {}\n"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn requests_survive_serialization() {
+        let request = ExecuteRequest {
+            channel: Channel::Stable {
+                version: Some("1.30.0".parse().unwrap()),
+            },
+            mode: Mode::Release,
+            edition: Some(Edition::Rust2021),
+            crate_type: CrateType::Library(LibraryType::Rlib),
+            tests: true,
+            backtrace: false,
+            color: ColorOutput::Html,
+            code: HELLO_WORLD_CODE.into(),
+            args: vec!["--nocapture".into()],
+            stdin: "input".into(),
+            keep_binary: false,
+            wrap_main: false,
+            strategy: Strategy::Exact,
+            trace_macros: false,
+            macro_backtrace: false,
+            output_records: false,
+            test_runner: TestRunner::Nextest,
+            careful: true,
+            deterministic: true,
+            error_format: ErrorFormat::Short,
+            hidden_tests: None,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(json.contains(r#""version":"1.30.0""#));
+        assert_eq!(
+            serde_json::from_str::<ExecuteRequest>(&json).unwrap(),
+            request
+        );
+
+        // Such as jobs queued by an older server
+        let mut json = serde_json::to_value(&request).unwrap();
+        for field in ["args", "stdin", "keepBinary", "wrapMain", "strategy"] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        let request = serde_json::from_value::<ExecuteRequest>(json).unwrap();
+        assert!(request.args.is_empty() && request.stdin.is_empty());
+        assert_eq!(request.strategy, Strategy::AutoDetect);
+    }
+
+    #[test]
+    fn programs_with_only_tests_are_detected() {
+        let only_tests = ExecuteRequest {
+            code: TEST_ONLY_CODE.into(),
+            ..ExecuteRequest::default()
+        };
+        assert!(only_tests.runs_tests());
+        assert!(!only_tests.keeps_binary());
+
+        let exact = ExecuteRequest {
+            strategy: Strategy::Exact,
+            ..only_tests.clone()
+        };
+        assert!(!exact.runs_tests());
+
+        let library = ExecuteRequest {
+            crate_type: CrateType::Library(LibraryType::Lib),
+            ..only_tests.clone()
+        };
+        assert!(!library.runs_tests());
+
+        let with_main = ExecuteRequest {
+            code: format!("{}\nfn main() {{}}", TEST_ONLY_CODE),
+            ..only_tests
+        };
+        assert!(!with_main.runs_tests());
+    }
+
+    #[test]
+    fn the_wire_format_uses_the_api_names() {
+        let request = CompileRequest {
+            target: CompileTarget::Assembly(
+                AssemblyFlavor::Intel,
+                DemangleAssembly::Demangle,
+                ProcessAssembly::Filter,
+            ),
+            extra_targets: vec![CompileTarget::LlvmIr],
+            unoptimized_mir: false,
+            channel: Channel::Nightly { date: None },
+            crate_type: CrateType::Library(LibraryType::ProcMacro),
+            mode: Mode::Debug,
+            edition: Some(Edition::Rust2021),
+            tests: false,
+            backtrace: false,
+            color: ColorOutput::Plain,
+            code: String::new(),
+            trace_macros: true,
+            macro_backtrace: false,
+            error_format: ErrorFormat::Short,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "target": { "asm": ["intel", "demangle", "filter"] },
+                "extraTargets": ["llvm-ir"],
+                "unoptimizedMir": false,
+                "channel": { "nightly": { "date": null } },
+                "crateType": { "library": "proc-macro" },
+                "mode": "debug",
+                "edition": "2021",
+                "tests": false,
+                "backtrace": false,
+                "color": "plain",
+                "code": "",
+                "traceMacros": true,
+                "macroBacktrace": false,
+                "errorFormat": "short",
+            })
+        );
+    }
+
+    fn assert_round_trip<T>(value: T)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + fmt::Debug,
+    {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value, "{json}");
+    }
+
+    #[test]
+    fn every_operation_survives_serialization() {
+        let date = NaiveDate::from_ymd(2022, 6, 1);
+        let code = String::from(HELLO_WORLD_CODE);
+        let output = || String::from("output");
+
+        assert_round_trip(FormatRequest {
+            code: code.clone(),
+            edition: None,
+            lines: Some(LineRange { start: 1, end: 3 }),
+        });
+        assert_round_trip(TimePassesRequest {
+            code: code.clone(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            mode: Mode::Release,
+        });
+        assert_round_trip(TimePassesResponse {
+            success: true,
+            passes: vec![CompilerPass {
+                name: "total".into(),
+                milliseconds: 101,
+                rss_before_mb: Some(28),
+                rss_after_mb: None,
+            }],
+            stdout: output(),
+            stderr: output(),
+        });
+        assert_round_trip(OptimizationRemarksRequest {
+            code: code.clone(),
+            channel: Channel::Beta,
+            mode: Mode::Release,
+            edition: None,
+            crate_type: CrateType::Binary,
+        });
+        assert_round_trip(OptimizationRemarksResponse {
+            success: true,
+            remarks: vec![OptimizationRemark {
+                pass: "loop-vectorize".into(),
+                kind: RemarkKind::Passed,
+                message: "vectorized loop".into(),
+                span: RemarkSpan {
+                    line: 2,
+                    column: None,
+                },
+            }],
+            stdout: output(),
+            stderr: output(),
+        });
+        assert_round_trip(CompletionsRequest {
+            code: code.clone(),
+            edition: Some(Edition::Rust2021),
+            crate_type: CrateType::Library(LibraryType::Lib),
+            offset: 11,
+        });
+        assert_round_trip(CompletionsResponse {
+            success: true,
+            completions: vec![Completion {
+                label: "push(…)".into(),
+                kind: Some("method".into()),
+                detail: None,
+                insert_text: "push".into(),
+            }],
+            stderr: output(),
+        });
+        assert_round_trip(HoverRequest {
+            code: code.clone(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            offset: 3,
+        });
+        assert_round_trip(HoverResponse {
+            success: true,
+            hover: Some(Hover {
+                signature: "```rust\nfn main()\n```".into(),
+                documentation: None,
+                range: Some(CodeRange { start: 3, end: 7 }),
+            }),
+            stderr: output(),
+        });
+        assert_round_trip(NavigationRequest {
+            code: code.clone(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            offset: 3,
+            target: NavigationTarget::References,
+        });
+        assert_round_trip(NavigationResponse {
+            success: true,
+            locations: vec![
+                Location {
+                    file: None,
+                    line: 1,
+                    range: Some(CodeRange { start: 3, end: 7 }),
+                    excerpt: None,
+                },
+                Location {
+                    file: Some("library/core/src/option.rs".into()),
+                    line: 572,
+                    range: None,
+                    excerpt: Some("pub enum Option<T> {".into()),
+                },
+            ],
+            stderr: output(),
+        });
+        assert_round_trip(InlayHintsRequest {
+            code: code.clone(),
+            edition: None,
+            crate_type: CrateType::Binary,
+        });
+        assert_round_trip(InlayHintsResponse {
+            success: true,
+            hints: vec![InlayHint {
+                offset: 5,
+                label: ": i32".into(),
+                kind: InlayHintKind::Type,
+                padding_left: false,
+                padding_right: false,
+            }],
+            stderr: output(),
+        });
+        assert_round_trip(EditionMigrationRequest {
+            code: code.clone(),
+            edition: Edition::Rust2015,
+            crate_type: CrateType::Binary,
+        });
+        assert_round_trip(ClippyRequest {
+            code: code.clone(),
+            edition: Some(Edition::Rust2015),
+            crate_type: CrateType::Binary,
+        });
+        assert_round_trip(MiriRequest {
+            code: code.clone(),
+            edition: Some(Edition::Rust2018),
+        });
+        assert_round_trip(KaniRequest {
+            code: code.clone(),
+            edition: Some(Edition::Rust2021),
+        });
+        assert_round_trip(MacroExpansionRequest {
+            code: code.clone(),
+            edition: None,
+            expand_item: Some("a::B".into()),
+        });
+        assert_round_trip(BisectRequest {
+            code: code.clone(),
+            edition: None,
+            crate_type: CrateType::Library(LibraryType::Cdylib),
+            start: date,
+            end: None,
+            regression: Regression::NonIce,
+        });
+        assert_round_trip(ReduceRequest {
+            channel: Channel::Nightly { date: Some(date) },
+            edition: Some(Edition::Rust2021),
+            crate_type: CrateType::Binary,
+            code,
+            preserved: Preserved::Error("E0308".into()),
+        });
+
+        assert_round_trip(CompileResponse {
+            success: true,
+            code: output(),
+            extra_outputs: vec![
+                (CompileTarget::Mir, output()),
+                (CompileTarget::Wasm, output()),
+            ],
+            stdout: output(),
+            stderr: output(),
+            sccache: Some(SccacheStats {
+                cache_hits: 1,
+                cache_misses: 2,
+            }),
+            hint: None,
+            diagnostics: DiagnosticCounts {
+                warnings: 1,
+                errors: 0,
+            },
+            suggestions: vec![Suggestion {
+                message: output(),
+                range: CodeRange { start: 3, end: 4 },
+                replacement: output(),
+            }],
+            image_id: Some("sha256:0123".into()),
+            disk_quota_exceeded: true,
+        });
+        assert_round_trip(ExecuteResponse {
+            success: false,
+            stdout: output(),
+            stderr: output(),
+            sccache: None,
+            hint: Some(Hint::TryNightly),
+            binary: None,
+            records: Some(vec![OutputRecord {
+                stream: OutputStream::Stderr,
+                timestamp_ms: 12,
+                text: "thread 'main' panicked\n".into(),
+            }]),
+            diagnostics: DiagnosticCounts::default(),
+            test_results: Some(vec![TestResult {
+                name: "tests::it_works".into(),
+                outcome: TestOutcome::Failed,
+                duration_ms: Some(3),
+                stdout: Some(output()),
+            }]),
+            careful_checks: vec![output()],
+            image_id: None,
+            abuse_suspected: true,
+            output_rate_limited: true,
+            out_of_memory: true,
+            disk_quota_exceeded: true,
+            spilled: Default::default(),
+        });
+        assert_round_trip(RerunRequest {
+            channel: Channel::Stable { version: None },
+            backtrace: true,
+            color: ColorOutput::Ansi,
+            args: vec![output()],
+            stdin: output(),
+        });
+        assert_round_trip(BisectResponse {
+            success: true,
+            regressed_nightly: Some(date),
+            commit_range: Some(output()),
+            regressed_commit: None,
+            stdout: output(),
+            stderr: output(),
+        });
+        assert_round_trip(ReduceResponse {
+            reproduced: true,
+            code: output(),
+            attempts: 12,
+            stderr: output(),
+        });
+        assert_round_trip(Channel::Beta);
+        assert_round_trip(Channel::Nightly { date: Some(date) });
+    }
+
+    #[test]
+    fn channels_are_tracked_apart() {
+        let health = ChannelHealth::new(1, Duration::from_secs(30));
+        let dated = Channel::Nightly {
+            date: Some(NaiveDate::from_ymd(2022, 6, 1)),
+        };
+
+        health.breaker(dated).unwrap().record_failure();
+
+        let nightly = health.breaker(Channel::Nightly { date: None }).unwrap();
+        assert!(nightly.allow().is_err());
+        let stable = health.breaker(Channel::Stable { version: None }).unwrap();
+        assert!(stable.allow().is_ok());
+    }
+
+    #[test]
+    fn bisect_report_is_parsed() {
+        let stderr = concat!(
+            "searched nightlies: from nightly-2022-01-01 to nightly-2022-02-01\n",
+            "regressed nightly: nightly-2022-01-15\n",
+            "searched commit range: https://github.com/rust-lang/rust/compare/abc...def\n",
+            "regressed commit: https://github.com/rust-lang/rust/commit/cba\n",
+        );
+
+        assert_eq!(
+            BisectReport::parse("", stderr),
+            BisectReport {
+                regressed_nightly: Some(NaiveDate::from_ymd(2022, 1, 15)),
+                commit_range: Some("https://github.com/rust-lang/rust/compare/abc...def".into()),
+                regressed_commit: Some("https://github.com/rust-lang/rust/commit/cba".into()),
+            }
+        );
+        assert_eq!(
+            BisectReport::parse("", "error: no regression found\n"),
+            BisectReport::default()
+        );
+    }
+
+    #[test]
+    fn rust_versions_have_three_parts() {
+        let version: RustVersion = "1.30.0".parse().unwrap();
+        assert_eq!(version.to_string(), "1.30.0");
+        assert!(version < "1.31.0".parse().unwrap());
+
+        assert!("1.30".parse::<RustVersion>().is_err());
+        assert!("1.30.0.1".parse::<RustVersion>().is_err());
+        assert!("1.x.0".parse::<RustVersion>().is_err());
+    }
+
+    #[test]
+    fn clippy_lints_are_parsed_from_the_help() {
+        let help = r#"
+Lint checks provided by rustc:
+
+    name                      default  meaning
+    ----                      -------  -------
+    unused-variables          warn     detect variables which are not used in any way
+
+Lint checks loaded by this crate:
+
+    name                      default  meaning
+    ----                      -------  -------
+    clippy::needless-return   warn     using a return statement like `return expr;`
+    clippy::unwrap-used       allow    using `.unwrap()` on `Result` or `Option`
+
+Lint groups loaded by this crate:
+
+    name             sub-lints
+    ----             ---------
+    clippy::all      clippy::needless-return
+    clippy::style    clippy::needless-return
+"#;
+
+        let lints = parse_clippy_lints(help);
+
+        assert_eq!(lints.len(), 2);
+        assert_eq!(lints[0].name, "needless_return");
+        assert_eq!(lints[0].group.as_deref(), Some("style"));
+        assert_eq!(lints[0].level, "warn");
+        assert_eq!(
+            lints[0].description,
+            "using a return statement like `return expr;`"
+        );
+        assert_eq!(lints[1].name, "unwrap_used");
+        assert_eq!(lints[1].group, None);
+    }
+
+    #[test]
+    fn live_output_keeps_characters_whole() {
+        let mut pending = "a≠".as_bytes().to_vec();
+        let last = pending.pop().unwrap();
+
+        assert_eq!(take_whole_characters(&mut pending), "a");
+        assert_eq!(pending.len(), 2);
+
+        pending.push(last);
+        pending.extend_from_slice(b"b");
+        assert_eq!(take_whole_characters(&mut pending), "≠b");
+        assert!(pending.is_empty());
+
+        let mut invalid = vec![b'c', 0xFF, b'd'];
+        assert_eq!(take_whole_characters(&mut invalid), "c\u{FFFD}d");
+    }
+
+    #[test]
+    fn executions_stopped_by_a_limit_are_reported() {
+        let finished = ExecuteResponse {
+            success: false,
+            stdout: String::new(),
+            stderr: "thread 'main' panicked at 'timeout'\n".into(),
+            sccache: None,
+            hint: None,
+            binary: None,
+            records: None,
+            diagnostics: DiagnosticCounts::default(),
+            test_results: None,
+            careful_checks: vec![],
+            image_id: None,
+            abuse_suspected: false,
+            output_rate_limited: false,
+            out_of_memory: false,
+            disk_quota_exceeded: false,
+            spilled: Default::default(),
+        };
+        assert!(!finished.was_stopped());
+
+        let timed_out = ExecuteResponse {
+            stderr: "entrypoint.sh: line 18:     7 Killed      timeout --signal=KILL 10 \"$@\"\n"
+                .into(),
+            ..finished.clone()
+        };
+        assert!(timed_out.was_stopped());
+
+        let out_of_memory = ExecuteResponse {
+            out_of_memory: true,
+            ..finished.clone()
+        };
+        assert!(out_of_memory.was_stopped());
+
+        let output_rate_limited = ExecuteResponse {
+            output_rate_limited: true,
+            ..finished
+        };
+        assert!(output_rate_limited.was_stopped());
+    }
+}
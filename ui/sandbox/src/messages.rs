@@ -0,0 +1,201 @@
+//! Reads the compiler's diagnostics from the JSON messages that cargo
+//! writes to stdout with `--message-format=json`, like
+//!
+//! ```text
+//! {"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"rendered":"warning: unused variable: `x`\n...",...}}
+//! ```
+//!
+//! The diagnostics are then shown as cargo would have shown them,
+//! along with the suggestions that can be applied as they are.
+
+use serde_derive::Deserialize;
+
+use crate::{ansi, CodeRange, Suggestion};
+
+// The only suggestions that are sure to do what was meant
+const MACHINE_APPLICABLE: &str = "MachineApplicable";
+
+// Cargo says one of these as it starts on each crate, before the
+// compiler reports anything about it
+const CARGO_STARTING: [&str; 2] = ["Compiling ", "Checking "];
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum Message {
+    CompilerMessage {
+        message: Diagnostic,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Diagnostic {
+    message: String,
+    #[serde(default)]
+    spans: Vec<Span>,
+    #[serde(default)]
+    children: Vec<Diagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Span {
+    file_name: String,
+    byte_start: usize,
+    byte_end: usize,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Rendered {
+    /// Without cargo's messages.
+    pub stdout: String,
+    /// With the diagnostics where cargo would have written them.
+    pub stderr: String,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// Turns cargo's output back into what it would have been without
+/// the JSON messages, keeping the suggestions for `file_name`.
+pub(crate) fn render(stdout: &str, stderr: &str, file_name: &str) -> Rendered {
+    let mut rendered = Rendered::default();
+    let mut diagnostics = String::new();
+
+    for line in stdout.split_inclusive('\n') {
+        let message = if line.starts_with('{') {
+            serde_json::from_str(line).ok()
+        } else {
+            None
+        };
+
+        match message {
+            Some(Message::CompilerMessage { message }) => {
+                diagnostics.push_str(message.rendered.as_deref().unwrap_or_default());
+                rendered
+                    .suggestions
+                    .extend(suggestions(&message, file_name));
+            }
+            Some(Message::Other) => {}
+            None => rendered.stdout.push_str(line),
+        }
+    }
+
+    let at = diagnostics_start(stderr);
+    rendered.stderr = format!("{}{}{}", &stderr[..at], diagnostics, &stderr[at..]);
+
+    rendered
+}
+
+fn suggestions<'a>(
+    diagnostic: &'a Diagnostic,
+    file_name: &'a str,
+) -> impl Iterator<Item = Suggestion> + 'a {
+    diagnostic.children.iter().filter_map(move |child| {
+        // Changes in several places are left to `cargo fix`
+        let span = match &*child.spans {
+            [span] => span,
+            _ => return None,
+        };
+        let replacement = span.suggested_replacement.as_ref()?;
+        let applies = span.file_name == file_name
+            && span.suggestion_applicability.as_deref() == Some(MACHINE_APPLICABLE);
+        if !applies {
+            return None;
+        }
+
+        Some(Suggestion {
+            message: child.message.clone(),
+            range: CodeRange {
+                start: span.byte_start,
+                end: span.byte_end,
+            },
+            replacement: replacement.clone(),
+        })
+    })
+}
+
+// Just after cargo said it started on the last crate, or the start
+// when it never did
+fn diagnostics_start(stderr: &str) -> usize {
+    let mut at = 0;
+    let mut offset = 0;
+
+    for line in stderr.split_inclusive('\n') {
+        offset += line.len();
+        let line = ansi::strip(line);
+        let line = line.trim_start();
+        if CARGO_STARTING.iter().any(|s| line.starts_with(s)) {
+            at = offset;
+        }
+    }
+
+    at
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UNUSED_VARIABLE: &str = r#"{"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","byte_start":20,"byte_end":21,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"if this is intentional, prefix it with an underscore","code":null,"level":"help","spans":[{"file_name":"src/main.rs","byte_start":20,"byte_end":21,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":"warning: unused variable: `x`\n"}}"#;
+    const FINISHED: &str = r#"{"reason":"build-finished","success":true}"#;
+
+    #[test]
+    fn diagnostics_go_where_cargo_would_write_them() {
+        let stdout = format!("{}\n{}\n", UNUSED_VARIABLE, FINISHED);
+        let stderr = concat!(
+            "   Compiling playground v0.0.1 (/playground)\n",
+            "    Finished dev [unoptimized + debuginfo] target(s) in 0.52s\n",
+        );
+
+        let rendered = render(&stdout, stderr, "src/main.rs");
+
+        assert_eq!(rendered.stdout, "");
+        assert_eq!(
+            rendered.stderr,
+            concat!(
+                "   Compiling playground v0.0.1 (/playground)\n",
+                "warning: unused variable: `x`\n",
+                "    Finished dev [unoptimized + debuginfo] target(s) in 0.52s\n",
+            )
+        );
+    }
+
+    #[test]
+    fn machine_applicable_suggestions_are_kept() {
+        let rendered = render(UNUSED_VARIABLE, "", "src/main.rs");
+
+        assert_eq!(
+            rendered.suggestions,
+            [Suggestion {
+                message: "if this is intentional, prefix it with an underscore".into(),
+                range: CodeRange { start: 20, end: 21 },
+                replacement: "_x".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn other_suggestions_are_not_kept() {
+        let maybe_incorrect = UNUSED_VARIABLE.replace("MachineApplicable", "MaybeIncorrect");
+        assert!(render(&maybe_incorrect, "", "src/main.rs")
+            .suggestions
+            .is_empty());
+
+        assert!(render(UNUSED_VARIABLE, "", "src/lib.rs")
+            .suggestions
+            .is_empty());
+    }
+
+    #[test]
+    fn other_output_is_kept() {
+        let stdout = format!("{}\n{{\"json\": true}}\nHello\n", FINISHED);
+        let stderr = "\u{1b}[1m\u{1b}[32m   Compiling\u{1b}[0m playground\nerror: oops\n";
+
+        let rendered = render(&stdout, stderr, "src/main.rs");
+
+        assert_eq!(rendered.stdout, "{\"json\": true}\nHello\n");
+        assert_eq!(rendered.stderr, stderr);
+    }
+}
@@ -0,0 +1,176 @@
+//! Reads how each test went from the events that nextest writes to
+//! stdout with `--message-format libtest-json`, like
+//!
+//! ```text
+//! {"type":"test","event":"started","name":"playground::bin/playground$tests::it_works"}
+//! {"type":"test","event":"ok","name":"playground::bin/playground$tests::it_works","exec_time":0.001}
+//! ```
+
+use serde_derive::Deserialize;
+
+use crate::{OutputRecord, OutputStream, TestOutcome, TestResult};
+
+// Every event is a single line starting like this
+const EVENT_PREFIX: &[u8] = b"{\"type\":";
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Event {
+    Test(TestEvent),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum TestEvent {
+    Ok(Finished),
+    Failed(Finished),
+    Ignored(Finished),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Finished {
+    name: String,
+    /// In seconds.
+    exec_time: Option<f64>,
+    stdout: Option<String>,
+}
+
+/// How each test went, and the output without nextest's events.
+pub(crate) fn results(stdout: &[u8]) -> (Vec<u8>, Vec<TestResult>) {
+    let mut rest = Vec::with_capacity(stdout.len());
+    let mut results = vec![];
+
+    let mut keeps_line = keeps_line(&mut results);
+    for line in stdout.split_inclusive(|&b| b == b'\n') {
+        if keeps_line(line) {
+            rest.extend_from_slice(line);
+        }
+    }
+    drop(keeps_line);
+
+    (rest, results)
+}
+
+/// Whether each line of stdout is one that [`results`] leaves, adding
+/// how the test went to `results` when it's an event.
+pub(crate) fn keeps_line(results: &mut Vec<TestResult>) -> impl FnMut(&[u8]) -> bool + '_ {
+    move |line| match event(line) {
+        Some(Event::Test(event)) => {
+            results.extend(result(event));
+            false
+        }
+        Some(Event::Other) => false,
+        None => true,
+    }
+}
+
+/// The records without those of nextest's events, as [`results`]
+/// leaves the output.
+pub(crate) fn strip_event_records(records: Vec<OutputRecord>) -> Vec<OutputRecord> {
+    records
+        .into_iter()
+        .filter(|record| {
+            record.stream != OutputStream::Stdout || event(record.text.as_bytes()).is_none()
+        })
+        .collect()
+}
+
+fn event(line: &[u8]) -> Option<Event> {
+    if !line.starts_with(EVENT_PREFIX) {
+        return None;
+    }
+    serde_json::from_slice(line).ok()
+}
+
+fn result(event: TestEvent) -> Option<TestResult> {
+    let (finished, outcome) = match event {
+        TestEvent::Ok(finished) => (finished, TestOutcome::Passed),
+        TestEvent::Failed(finished) => (finished, TestOutcome::Failed),
+        TestEvent::Ignored(finished) => (finished, TestOutcome::Ignored),
+        TestEvent::Other => return None,
+    };
+
+    // Named after the crate and binary too, as in
+    // `playground::bin/playground$tests::it_works`
+    let name = match finished.name.rsplit_once('$') {
+        Some((_, name)) => name.into(),
+        None => finished.name,
+    };
+
+    Some(TestResult {
+        name,
+        outcome,
+        duration_ms: finished.exec_time.map(|s| (s * 1000.0).round() as u64),
+        stdout: finished.stdout,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STARTED: &str =
+        r#"{"type":"test","event":"started","name":"playground::bin/playground$tests::it_works"}"#;
+    const PASSED: &str = r#"{"type":"test","event":"ok","name":"playground::bin/playground$tests::it_works","exec_time":0.0021}"#;
+    const FAILED: &str = r#"{"type":"test","event":"failed","name":"playground::bin/playground$tests::it_fails","exec_time":0.002,"stdout":"thread 'tests::it_fails' panicked\n"}"#;
+    const IGNORED: &str =
+        r#"{"type":"test","event":"ignored","name":"playground::bin/playground$tests::later"}"#;
+    const SUITE: &str = r#"{"type":"suite","event":"failed","passed":1,"failed":1,"ignored":1}"#;
+
+    #[test]
+    fn each_test_has_a_result() {
+        let stdout = [STARTED, PASSED, FAILED, IGNORED, SUITE].join("\n");
+
+        let (_, results) = results(stdout.as_bytes());
+
+        assert_eq!(
+            results,
+            [
+                TestResult {
+                    name: "tests::it_works".into(),
+                    outcome: TestOutcome::Passed,
+                    duration_ms: Some(2),
+                    stdout: None,
+                },
+                TestResult {
+                    name: "tests::it_fails".into(),
+                    outcome: TestOutcome::Failed,
+                    duration_ms: Some(2),
+                    stdout: Some("thread 'tests::it_fails' panicked\n".into()),
+                },
+                TestResult {
+                    name: "tests::later".into(),
+                    outcome: TestOutcome::Ignored,
+                    duration_ms: None,
+                    stdout: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn events_are_stripped_from_the_output() {
+        let stdout = format!("{}\nnot an event\n{}\n{{\"type\": 1}}\n", STARTED, SUITE);
+
+        let (rest, _) = results(stdout.as_bytes());
+
+        assert_eq!(rest, b"not an event\n{\"type\": 1}\n");
+    }
+
+    #[test]
+    fn events_are_stripped_from_the_records() {
+        let record = |text: &str| OutputRecord {
+            stream: OutputStream::Stdout,
+            timestamp_ms: 0,
+            text: format!("{}\n", text),
+        };
+
+        let records = strip_event_records(vec![record(STARTED), record("hello"), record(PASSED)]);
+
+        assert_eq!(records, [record("hello")]);
+    }
+}
@@ -0,0 +1,48 @@
+//! Tells when the kernel killed a process for using more memory than
+//! the container may. Either way, the shell only says "Killed", just
+//! as when the program ran for too long.
+//!
+//! The entrypoint copies out the events of the container's memory
+//! cgroup. On hosts with the unified hierarchy of cgroup v2 that's
+//! `memory.events`, like
+//!
+//! ```text
+//! low 0
+//! high 0
+//! max 14
+//! oom 1
+//! oom_kill 1
+//! ```
+//!
+//! where cgroup v1 has `memory.oom_control` instead, which only
+//! counts the kills the same way.
+
+const OOM_KILL: &str = "oom_kill";
+
+pub(crate) fn killed(events: &str) -> bool {
+    events.lines().any(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next() == Some(OOM_KILL)
+            && fields
+                .next()
+                .and_then(|count| count.parse::<u64>().ok())
+                .is_some_and(|count| count > 0)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kills_are_found_in_either_hierarchy() {
+        let v2 = "low 0\nhigh 0\nmax 14\noom 1\noom_kill 1\noom_group_kill 0\n";
+        let v1 = "oom_kill_disable 0\nunder_oom 0\noom_kill 2\n";
+        assert!(killed(v2));
+        assert!(killed(v1));
+
+        assert!(!killed("low 0\nhigh 0\nmax 0\noom 0\noom_kill 0\n"));
+        assert!(!killed("oom_kill_disable 1\nunder_oom 0\n"));
+        assert!(!killed(""));
+    }
+}
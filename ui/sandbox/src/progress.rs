@@ -0,0 +1,161 @@
+//! Follows how far an execution has got from the JSON messages that
+//! cargo writes to stdout with `--message-format=json-render-diagnostics`,
+//! like
+//!
+//! ```text
+//! {"reason":"compiler-artifact","package_id":"playground 0.0.1 (path+file:///playground)",...}
+//! {"reason":"build-finished","success":true}
+//! ```
+//!
+//! Everything after the build has finished is what the program wrote.
+
+use serde_derive::Deserialize;
+
+use crate::{OutputRecord, OutputStream, Phase};
+
+// Cargo's messages are always a single line starting like this, as
+// serde_json writes the fields in order
+const MESSAGE_PREFIX: &[u8] = b"{\"reason\":";
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum Message {
+    BuildFinished {
+        success: bool,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// The phase that an execution is in once cargo has written `line`,
+/// if it's one of cargo's messages.
+pub(crate) fn phase(line: &str) -> Option<Phase> {
+    if !line.as_bytes().starts_with(MESSAGE_PREFIX) {
+        return None;
+    }
+
+    match serde_json::from_str(line).ok()? {
+        Message::BuildFinished { success: true } => Some(Phase::Running),
+        Message::BuildFinished { success: false } => None,
+        Message::Other => Some(Phase::Compiling),
+    }
+}
+
+/// The output without cargo's messages, leaving only what the program
+/// wrote.
+pub(crate) fn strip_messages(stdout: &[u8]) -> Vec<u8> {
+    let mut rest = stdout;
+    while rest.starts_with(MESSAGE_PREFIX) {
+        let (line, after) = match rest.iter().position(|&b| b == b'\n') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, &[][..]),
+        };
+        rest = after;
+
+        let finished = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| serde_json::from_str(line).ok());
+        if let Some(Message::BuildFinished { .. }) = finished {
+            break;
+        }
+    }
+    rest.to_vec()
+}
+
+/// The records without those of cargo's messages, as
+/// [`strip_messages`] leaves the output.
+pub(crate) fn strip_message_records(records: Vec<OutputRecord>) -> Vec<OutputRecord> {
+    let mut keeps_line = keeps_line();
+    records
+        .into_iter()
+        .filter(|record| {
+            record.stream != OutputStream::Stdout || keeps_line(record.text.as_bytes())
+        })
+        .collect()
+}
+
+/// Whether each line of stdout, given in order, is one that
+/// [`strip_messages`] leaves.
+pub(crate) fn keeps_line() -> impl FnMut(&[u8]) -> bool {
+    let mut finished = false;
+    move |line| {
+        if finished {
+            return true;
+        }
+        if !line.starts_with(MESSAGE_PREFIX) {
+            finished = true;
+            return true;
+        }
+        let message = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| serde_json::from_str(line.trim_end()).ok());
+        finished = matches!(message, Some(Message::BuildFinished { .. }));
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ARTIFACT: &str = r#"{"reason":"compiler-artifact","package_id":"playground 0.0.1 (path+file:///playground)","fresh":false}"#;
+    const FINISHED: &str = r#"{"reason":"build-finished","success":true}"#;
+
+    #[test]
+    fn phases_follow_the_messages() {
+        assert_eq!(phase(ARTIFACT), Some(Phase::Compiling));
+        assert_eq!(phase(FINISHED), Some(Phase::Running));
+        assert_eq!(
+            phase(r#"{"reason":"build-finished","success":false}"#),
+            None
+        );
+        assert_eq!(phase("Hello, world!"), None);
+    }
+
+    #[test]
+    fn messages_are_stripped_from_the_output() {
+        let stdout = format!("{}\n{}\n{}\nHello, world!\n", ARTIFACT, ARTIFACT, FINISHED);
+        assert_eq!(strip_messages(stdout.as_bytes()), b"Hello, world!\n");
+    }
+
+    #[test]
+    fn output_like_a_message_is_kept() {
+        let stdout = format!("{}\n{}\n", FINISHED, ARTIFACT);
+        assert_eq!(
+            strip_messages(stdout.as_bytes()),
+            format!("{}\n", ARTIFACT).as_bytes()
+        );
+    }
+
+    #[test]
+    fn messages_are_stripped_from_the_records() {
+        let record = |stream, text: &str| OutputRecord {
+            stream,
+            timestamp_ms: 0,
+            text: format!("{}\n", text),
+        };
+        let records = vec![
+            record(OutputStream::Stdout, ARTIFACT),
+            record(OutputStream::Stderr, "   Compiling playground v0.0.1"),
+            record(OutputStream::Stdout, FINISHED),
+            record(OutputStream::Stdout, ARTIFACT),
+        ];
+
+        assert_eq!(
+            strip_message_records(records),
+            [
+                record(OutputStream::Stderr, "   Compiling playground v0.0.1"),
+                record(OutputStream::Stdout, ARTIFACT),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_builds_leave_no_output() {
+        let stdout = format!(
+            "{}\n{}",
+            ARTIFACT, r#"{"reason":"build-finished","success":false}"#
+        );
+        assert_eq!(strip_messages(stdout.as_bytes()), b"");
+    }
+}
@@ -0,0 +1,94 @@
+//! Puts what a container wrote to stdout and stderr back in the order
+//! it was written, from `docker logs --timestamps`, which starts each
+//! line like
+//!
+//! ```text
+//! 2022-06-01T12:34:56.123456789Z thread 'main' panicked at ...
+//! ```
+
+use chrono::{DateTime, FixedOffset};
+use std::convert::TryFrom;
+
+use crate::{OutputRecord, OutputStream};
+
+/// Each line of both streams, timed from the first line of either.
+/// Lines written at the same time keep stdout first.
+pub(crate) fn merge(stdout: &str, stderr: &str) -> Vec<OutputRecord> {
+    let mut lines: Vec<_> = parse(stdout, OutputStream::Stdout)
+        .chain(parse(stderr, OutputStream::Stderr))
+        .collect();
+    // Each stream is already in order, which a stable sort keeps
+    lines.sort_by_key(|&(at, _, _)| at);
+
+    let start = match lines.first() {
+        Some(&(at, _, _)) => at,
+        None => return vec![],
+    };
+
+    lines
+        .into_iter()
+        .map(|(at, stream, text)| OutputRecord {
+            stream,
+            timestamp_ms: u64::try_from((at - start).num_milliseconds()).unwrap_or(0),
+            text: text.into(),
+        })
+        .collect()
+}
+
+fn parse(
+    logs: &str,
+    stream: OutputStream,
+) -> impl Iterator<Item = (DateTime<FixedOffset>, OutputStream, &str)> {
+    logs.split_inclusive('\n').filter_map(move |line| {
+        let (timestamp, text) = line.split_once(' ')?;
+        let at = DateTime::parse_from_rfc3339(timestamp).ok()?;
+        Some((at, stream, text))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn streams_are_interleaved_by_time() {
+        let stdout = "2022-06-01T12:00:00.100000000Z before\n\
+                      2022-06-01T12:00:00.350000000Z after\n";
+        let stderr = "2022-06-01T12:00:00.200000000Z thread 'main' panicked\n";
+
+        let records = merge(stdout, stderr);
+
+        let records: Vec<_> = records
+            .iter()
+            .map(|r| (r.stream, r.timestamp_ms, &*r.text))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                (OutputStream::Stdout, 0, "before\n"),
+                (OutputStream::Stderr, 100, "thread 'main' panicked\n"),
+                (OutputStream::Stdout, 250, "after\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn stdout_comes_first_at_the_same_time() {
+        let stdout = "2022-06-01T12:00:00.1Z out\n";
+        let stderr = "2022-06-01T12:00:00.1Z err\n";
+
+        let records = merge(stdout, stderr);
+
+        assert_eq!(records[0].stream, OutputStream::Stdout);
+        assert_eq!(records[1].stream, OutputStream::Stderr);
+    }
+
+    #[test]
+    fn unfinished_lines_are_kept() {
+        let records = merge("2022-06-01T12:00:00.1Z Enter a number: ", "");
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].text, "Enter a number: ");
+        assert!(merge("", "").is_empty());
+    }
+}
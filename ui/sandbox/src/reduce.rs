@@ -0,0 +1,232 @@
+//! Makes code that shows a compiler problem smaller, by removing
+//! blocks and then lines for as long as the problem is still shown.
+//! What to remove is only chosen by the braces, so it's left to the
+//! compiler to reject what that breaks.
+
+use std::ops::RangeInclusive;
+
+use crate::Preserved;
+
+/// How many times the code may be compiled while reducing it.
+pub(crate) const MAX_ATTEMPTS: usize = 100;
+
+const ICE_MARKERS: [&str; 2] = ["internal compiler error", "thread 'rustc' panicked"];
+
+impl Preserved {
+    pub(crate) fn is_shown_by(&self, stderr: &str) -> bool {
+        match self {
+            Preserved::Ice => ICE_MARKERS.iter().any(|m| stderr.contains(m)),
+            Preserved::Error(text) => error_lines(stderr).any(|line| line.contains(text.as_str())),
+        }
+    }
+}
+
+/// Such as `error[E0308]: mismatched types`.
+pub(crate) fn first_error(stderr: &str) -> Option<String> {
+    error_lines(stderr).next().map(Into::into)
+}
+
+fn error_lines(stderr: &str) -> impl Iterator<Item = &str> {
+    stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("error"))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Level {
+    Blocks,
+    Lines,
+}
+
+/// Suggests smaller versions of the code one at a time, keeping
+/// those that still show the problem.
+#[derive(Debug)]
+pub(crate) struct Reducer {
+    lines: Vec<String>,
+    level: Level,
+    /// The lines from here on have been tried at this level
+    cursor: usize,
+    /// Whether anything was removed since the blocks were last tried
+    progressed: bool,
+    candidate: Option<RangeInclusive<usize>>,
+}
+
+impl Reducer {
+    pub(crate) fn new(code: &str) -> Self {
+        let lines: Vec<String> = code.lines().map(Into::into).collect();
+
+        Self {
+            cursor: lines.len(),
+            lines,
+            level: Level::Blocks,
+            progressed: false,
+            candidate: None,
+        }
+    }
+
+    /// The next smaller version of the code to try, or `None` once
+    /// nothing more can be removed.
+    pub(crate) fn next_candidate(&mut self) -> Option<String> {
+        loop {
+            if let Some(range) = self.removable() {
+                let mut lines = self.lines.clone();
+                lines.drain(range.clone());
+                self.candidate = Some(range);
+                return Some(join(&lines));
+            }
+
+            match self.level {
+                Level::Blocks => self.level = Level::Lines,
+                // Removing a line may have left a block unused
+                Level::Lines if self.progressed => {
+                    self.level = Level::Blocks;
+                    self.progressed = false;
+                }
+                Level::Lines => return None,
+            }
+            self.cursor = self.lines.len();
+        }
+    }
+
+    /// Whether the compiler still showed the problem for the last
+    /// candidate, in which case that's kept.
+    pub(crate) fn record(&mut self, preserved: bool) {
+        if let Some(range) = self.candidate.take() {
+            self.cursor = *range.start();
+            if preserved {
+                self.lines.drain(range);
+                self.progressed = true;
+            }
+        }
+    }
+
+    pub(crate) fn code(&self) -> String {
+        join(&self.lines)
+    }
+
+    // The last block or line before the cursor, along with the blank
+    // lines after it
+    fn removable(&self) -> Option<RangeInclusive<usize>> {
+        let depths = depths(&self.lines);
+        let opens = |i: usize| depths[i + 1] > depths[i];
+
+        let range = match self.level {
+            Level::Blocks => (0..self.cursor).rev().find_map(|start| {
+                if !opens(start) {
+                    return None;
+                }
+                let end = (start..self.lines.len()).find(|&i| depths[i + 1] <= depths[start])?;
+                // Attributes and documentation go along with it
+                let start = (0..start)
+                    .rev()
+                    .take_while(|&i| is_attached(&self.lines[i]))
+                    .last()
+                    .unwrap_or(start);
+                Some(start..=end)
+            }),
+            // Lines that open or close a block would leave the braces
+            // unbalanced
+            Level::Lines => (0..self.cursor)
+                .rev()
+                .find(|&i| !self.lines[i].trim().is_empty() && depths[i + 1] == depths[i])
+                .map(|i| i..=i),
+        }?;
+
+        let blank = self.lines[range.end() + 1..]
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        Some(*range.start()..=range.end() + blank)
+    }
+}
+
+// How deeply nested in braces each line starts, followed by how
+// deeply the last one ends
+fn depths(lines: &[String]) -> Vec<isize> {
+    let mut depth = 0;
+    let mut depths = vec![depth];
+    for line in lines {
+        for c in line.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        depths.push(depth);
+    }
+    depths
+}
+
+fn is_attached(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("#[") || line.starts_with("///")
+}
+
+fn join(lines: &[String]) -> String {
+    lines
+        .iter()
+        .flat_map(|line| [line.as_str(), "\n"])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reduce(code: &str, preserved: impl Fn(&str) -> bool) -> (String, usize) {
+        let mut reducer = Reducer::new(code);
+        let mut attempts = 0;
+        while let Some(candidate) = reducer.next_candidate() {
+            attempts += 1;
+            reducer.record(preserved(&candidate));
+        }
+        (reducer.code(), attempts)
+    }
+
+    #[test]
+    fn everything_that_does_not_matter_is_removed() {
+        let code = r#"use std::fmt;
+
+#[derive(Debug)]
+struct Unused {
+    a: i32,
+}
+
+fn main() {
+    let a = 1;
+    bad();
+}
+"#;
+
+        let (reduced, _) = reduce(code, |c| c.contains("fn main() {") && c.contains("bad();"));
+
+        assert_eq!(reduced, "fn main() {\n    bad();\n}\n");
+    }
+
+    #[test]
+    fn code_that_can_not_be_reduced_is_kept() {
+        let code = "fn main() {\n    bad();\n}\n";
+
+        let (reduced, attempts) = reduce(code, |_| false);
+
+        assert_eq!(reduced, code);
+        // The block, then the line inside it
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn problems_are_found_in_the_output() {
+        let stderr = "   Compiling playground v0.0.1\nerror[E0308]: mismatched types\n";
+
+        assert!(Preserved::Error("E0308".into()).is_shown_by(stderr));
+        assert!(!Preserved::Error("E0599".into()).is_shown_by(stderr));
+        assert!(!Preserved::Ice.is_shown_by(stderr));
+        assert!(Preserved::Ice.is_shown_by("error: internal compiler error: unexpected panic"));
+        assert_eq!(
+            first_error(stderr).as_deref(),
+            Some("error[E0308]: mismatched types")
+        );
+    }
+}
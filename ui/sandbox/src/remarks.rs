@@ -0,0 +1,162 @@
+//! Reads the optimization remarks that `-Cremark=all` has LLVM write
+//! to stderr, like
+//!
+//! ```text
+//! note: src/main.rs:3:67 inline (missed): '_ZN10playground3sum17h820d16e45d1844a9E' not inlined into ...
+//! ```
+//!
+//! Some remarks go on for more lines, which are indented.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{asm_cleanup::demangle_asm, OptimizationRemark, RemarkKind, RemarkSpan};
+
+lazy_static! {
+    static ref REMARK: Regex = Regex::new(
+        r"^note: (?P<file>.+?):(?P<line>\d+):(?P<column>\d+) (?P<pass>\S+) \((?P<kind>[a-z]+)\): ?(?P<message>.*)$"
+    )
+    .unwrap();
+}
+
+// Where the playground crate is, as cargo gives it to the compiler
+const SOURCE_DIRECTORY: &str = "src/";
+
+/// The remarks about the playground's own code, in the order the
+/// compiler reported them, and what was left of the output without
+/// any of the remarks.
+///
+/// Remarks about the standard library and those without a location
+/// are left out, as there is nothing in the code to point them at.
+pub(crate) fn split(stderr: &str) -> (Vec<OptimizationRemark>, String) {
+    let mut remarks = vec![];
+    let mut rest = String::new();
+
+    let mut lines = stderr.lines().peekable();
+    while let Some(line) = lines.next() {
+        let (file, mut remark) = match parse(line) {
+            Some(parsed) => parsed,
+            None => {
+                rest.push_str(line);
+                rest.push('\n');
+                continue;
+            }
+        };
+
+        while let Some(more) = lines.next_if(|l| l.starts_with(char::is_whitespace)) {
+            remark.message.push('\n');
+            remark.message.push_str(more.trim());
+        }
+        // Each remark is followed by a blank line
+        lines.next_if(|l| l.is_empty());
+
+        if file.starts_with(SOURCE_DIRECTORY) {
+            remark.message = demangle_asm(remark.message.trim_end());
+            remarks.push(remark);
+        }
+    }
+
+    (remarks, rest)
+}
+
+fn parse(line: &str) -> Option<(&str, OptimizationRemark)> {
+    let captures = REMARK.captures(line)?;
+    let kind = match &captures["kind"] {
+        "success" => RemarkKind::Passed,
+        "missed" => RemarkKind::Missed,
+        "analysis" => RemarkKind::Analysis,
+        "failure" => RemarkKind::Failure,
+        _ => return None,
+    };
+    let line = captures["line"].parse().ok()?;
+    let column = captures["column"].parse().ok()?;
+
+    let remark = OptimizationRemark {
+        pass: captures["pass"].into(),
+        kind,
+        message: captures["message"].into(),
+        // LLVM doesn't always know the column
+        span: RemarkSpan {
+            line,
+            column: Some(column).filter(|&c| c != 0),
+        },
+    };
+    Some((captures.name("file")?.as_str(), remark))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remarks_are_parsed() {
+        let stderr = concat!(
+            "   Compiling playground v0.0.1 (/playground)\n",
+            "note: src/main.rs:3:67 inline (missed): '_ZN10playground3sum17h820d16e45d1844a9E' not inlined because it should never be inlined\n",
+            "\n",
+            "note: src/main.rs:2:0 loop-vectorize (success): vectorized loop (vectorization width: 4, interleaved count: 2)\n",
+            "\n",
+            "note: src/main.rs:2:0 asm-printer (analysis): instruction counts\n",
+            "      PXORrr: 2\n",
+            "      MOV32ri: 1\n",
+            "\n",
+        );
+
+        let (remarks, rest) = split(stderr);
+
+        assert_eq!(
+            remarks,
+            [
+                OptimizationRemark {
+                    pass: "inline".into(),
+                    kind: RemarkKind::Missed,
+                    message: "'playground::sum' not inlined because it should never be inlined"
+                        .into(),
+                    span: RemarkSpan {
+                        line: 3,
+                        column: Some(67),
+                    },
+                },
+                OptimizationRemark {
+                    pass: "loop-vectorize".into(),
+                    kind: RemarkKind::Passed,
+                    message: "vectorized loop (vectorization width: 4, interleaved count: 2)"
+                        .into(),
+                    span: RemarkSpan {
+                        line: 2,
+                        column: None,
+                    },
+                },
+                OptimizationRemark {
+                    pass: "asm-printer".into(),
+                    kind: RemarkKind::Analysis,
+                    message: "instruction counts\nPXORrr: 2\nMOV32ri: 1".into(),
+                    span: RemarkSpan {
+                        line: 2,
+                        column: None,
+                    },
+                },
+            ]
+        );
+        assert_eq!(rest, "   Compiling playground v0.0.1 (/playground)\n");
+    }
+
+    #[test]
+    fn remarks_outside_the_playground_are_dropped() {
+        let stderr = concat!(
+            "note: /rustc/library/core/src/iter/range.rs:772:12 inline (success): 'lt' inlined into 'next'\n",
+            "\n",
+            "note: <unknown file>:0:0 size-info (analysis): IR instruction count changed\n",
+            "\n",
+            "error[E0425]: cannot find value `note` in this scope\n",
+        );
+
+        let (remarks, rest) = split(stderr);
+
+        assert!(remarks.is_empty(), "{:?}", remarks);
+        assert_eq!(
+            rest,
+            "error[E0425]: cannot find value `note` in this scope\n"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Sessions that evaluate code a piece at a time, so that each piece
+//! can use what the earlier ones defined.
+//!
+//! Each session has a container of its own running `evcxr-playground`,
+//! which answers each line of JSON given to it with another.
+
+use serde_derive::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout},
+    time,
+};
+
+use crate::{
+    basic_secure_docker_command, normalize_newlines, remove_container, Channel, CodeTooLargeSnafu,
+    ImageNotFoundSnafu, InvalidReplResponseSnafu, Launch, MismatchedReplResponseSnafu,
+    ReplEndedSnafu, ReplFailedSnafu, ReplStoppedSnafu, ReplTimedOutSnafu, Result, Sandbox,
+    UnableToStartReplSnafu, UnableToTalkToReplSnafu, DOCKER_PROCESS_TIMEOUT_SOFT, MISSING_IMAGE,
+};
+
+const CONTAINER_PREFIX: &str = "playground-repl";
+
+/// What came of evaluating one piece of code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    pub success: bool,
+    /// The value of the last expression, when the code ended with one.
+    pub value: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A variable defined by an earlier evaluation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Variable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum DriverRequest<'a> {
+    Evaluate { code: &'a str },
+    Variables,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum DriverResponse {
+    Evaluated(Evaluation),
+    Variables { variables: Vec<Variable> },
+    Failed { message: String },
+}
+
+/// A REPL running in its own container.
+///
+/// Once an evaluation fails in a way that leaves the REPL unusable,
+/// such as by running past the timeout, the container is removed and
+/// every later request fails. The container is also removed when the
+/// session is dropped, but [`Session::close`] waits until it is.
+pub struct Session {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    stderr: ChildStderr,
+    timeout: Duration,
+    max_code_size: Option<usize>,
+    ended: bool,
+}
+
+impl Sandbox {
+    /// Starts a REPL session, which has the limits of the nightly
+    /// channel. Each evaluation may take as long as a program of that
+    /// channel may run for.
+    pub async fn start_repl(&self) -> Result<Session> {
+        let limits = self.config.limits(Some(Channel::Nightly { date: None }));
+        let name = format!("{}-{:032x}", CONTAINER_PREFIX, rand::random::<u128>());
+
+        let mut cmd = basic_secure_docker_command(limits, Launch::Attach);
+        self.apply_seccomp(&mut cmd);
+        self.apply_filesystem_limits(&mut cmd, limits);
+        self.apply_request_id(&mut cmd);
+        cmd.arg("--name").arg(&name).arg(&self.config.images.evcxr);
+
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        tracing::debug!(command = ?cmd, "REPL command");
+
+        let mut child = cmd.spawn().context(UnableToStartReplSnafu)?;
+        let (stdin, stdout, stderr) =
+            match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+                (Some(stdin), Some(stdout), Some(stderr)) => (stdin, stdout, stderr),
+                _ => unreachable!("The standard streams of the REPL are piped"),
+            };
+
+        let mut session = Session {
+            name,
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            stderr,
+            timeout: limits.timeout,
+            max_code_size: self.config.max_code_size,
+            ended: false,
+        };
+
+        // Nothing is said until the REPL is asked something, which
+        // also shows whether it started at all
+        let ready = limits.timeout + DOCKER_PROCESS_TIMEOUT_SOFT;
+        session.variables_within(ready).await?;
+        tracing::debug!(container = %session.name, "Started REPL");
+
+        Ok(session)
+    }
+}
+
+impl Session {
+    pub async fn evaluate(&mut self, code: &str) -> Result<Evaluation> {
+        if let Some(max_size) = self.max_code_size {
+            let size = code.len();
+            ensure!(size <= max_size, CodeTooLargeSnafu { size, max_size });
+        }
+
+        let code = normalize_newlines(code);
+        let request = DriverRequest::Evaluate { code: &code };
+        match self.request(&request, self.timeout).await? {
+            DriverResponse::Evaluated(evaluation) => Ok(evaluation),
+            _ => MismatchedReplResponseSnafu.fail(),
+        }
+    }
+
+    pub async fn variables(&mut self) -> Result<Vec<Variable>> {
+        self.variables_within(self.timeout).await
+    }
+
+    /// Whether the REPL can no longer be used.
+    pub fn has_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Removes the container, after which every request fails.
+    pub async fn close(&mut self) -> Result<()> {
+        self.end().await
+    }
+
+    async fn variables_within(&mut self, timeout: Duration) -> Result<Vec<Variable>> {
+        match self.request(&DriverRequest::Variables, timeout).await? {
+            DriverResponse::Variables { variables } => Ok(variables),
+            _ => MismatchedReplResponseSnafu.fail(),
+        }
+    }
+
+    async fn request(
+        &mut self,
+        request: &DriverRequest<'_>,
+        timeout: Duration,
+    ) -> Result<DriverResponse> {
+        ensure!(!self.ended, ReplEndedSnafu);
+
+        let response = match time::timeout(timeout, self.exchange(request)).await {
+            Ok(response) => response,
+            Err(_) => {
+                tracing::warn!(container = %self.name, ?timeout, "REPL ran past its timeout");
+                ReplTimedOutSnafu { timeout }.fail()
+            }
+        };
+
+        match response {
+            // The REPL itself is still fine
+            Ok(DriverResponse::Failed { message }) => ReplFailedSnafu { message }.fail(),
+            Ok(response) => Ok(response),
+            Err(e) => {
+                if let Err(e) = self.end().await {
+                    tracing::warn!(container = %self.name, "Unable to remove the REPL: {}", e);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn exchange(&mut self, request: &DriverRequest<'_>) -> Result<DriverResponse> {
+        let mut line =
+            serde_json::to_string(request).expect("REPL requests are always serializable");
+        line.push('\n');
+
+        let written = async {
+            self.stdin.write_all(line.as_bytes()).await?;
+            self.stdin.flush().await
+        };
+        // The REPL stopping says more than the broken pipe does
+        if written.await.is_err() {
+            return self.stopped().await;
+        }
+
+        match self
+            .stdout
+            .next_line()
+            .await
+            .context(UnableToTalkToReplSnafu)?
+        {
+            Some(line) => serde_json::from_str(&line).context(InvalidReplResponseSnafu),
+            None => self.stopped().await,
+        }
+    }
+
+    async fn stopped<T>(&mut self) -> Result<T> {
+        let mut stderr = String::new();
+        self.stderr
+            .read_to_string(&mut stderr)
+            .await
+            .context(UnableToTalkToReplSnafu)?;
+        let stderr = stderr.trim().to_owned();
+
+        if MISSING_IMAGE.iter().any(|m| stderr.contains(m)) {
+            return ImageNotFoundSnafu { stderr }.fail();
+        }
+        ReplStoppedSnafu { stderr }.fail()
+    }
+
+    async fn end(&mut self) -> Result<()> {
+        if self.ended {
+            return Ok(());
+        }
+        self.ended = true;
+
+        // It may have stopped already, which is fine
+        let _ = self.child.start_kill();
+        remove_container(&self.name).await
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.ended {
+            return;
+        }
+
+        // Stopping the Docker client doesn't stop the container
+        let name = std::mem::take(&mut self.name);
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                if let Err(e) = remove_container(&name).await {
+                    tracing::warn!(container = %name, "Unable to remove the REPL: {}", e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Has to match what `compiler/evcxr/evcxr-playground` expects
+    #[test]
+    fn requests_are_what_the_driver_reads() {
+        let evaluate = DriverRequest::Evaluate { code: "let a = 1;" };
+        assert_eq!(
+            serde_json::to_string(&evaluate).unwrap(),
+            r#"{"evaluate":{"code":"let a = 1;"}}"#,
+        );
+        assert_eq!(
+            serde_json::to_string(&DriverRequest::Variables).unwrap(),
+            r#""variables""#,
+        );
+    }
+
+    #[test]
+    fn responses_are_read_as_the_driver_writes_them() {
+        let evaluated = r#"{"evaluated":{"success":true,"value":"2","stdout":"hi\n","stderr":""}}"#;
+        match serde_json::from_str(evaluated).unwrap() {
+            DriverResponse::Evaluated(evaluation) => assert_eq!(
+                evaluation,
+                Evaluation {
+                    success: true,
+                    value: Some("2".into()),
+                    stdout: "hi\n".into(),
+                    stderr: "".into(),
+                },
+            ),
+            other => panic!("Expected an evaluation, not {:?}", other),
+        }
+
+        let variables = r#"{"variables":{"variables":[{"name":"a","type":"i32"}]}}"#;
+        match serde_json::from_str(variables).unwrap() {
+            DriverResponse::Variables { variables } => assert_eq!(
+                variables,
+                [Variable {
+                    name: "a".into(),
+                    ty: "i32".into(),
+                }],
+            ),
+            other => panic!("Expected variables, not {:?}", other),
+        }
+
+        let failed = r#"{"failed":{"message":"nope"}}"#;
+        match serde_json::from_str(failed).unwrap() {
+            DriverResponse::Failed { message } => assert_eq!(message, "nope"),
+            other => panic!("Expected a failure, not {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! Writes outputs that are too large to keep in memory to disk as
+//! they are read from the container, keeping only the start of them.
+
+use rand::{distributions::Alphanumeric, Rng};
+use snafu::prelude::*;
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+    sync::Arc,
+};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    process::Command,
+};
+
+use crate::{
+    whole_characters_len, Result, UnableToGetOutputFromCompilerSnafu, UnableToSpillOutputSnafu,
+};
+
+const NAME_LENGTH: usize = 24;
+
+const READ_SIZE: usize = 64 * 1024;
+
+// Longer lines can't be any of the messages that are filtered out,
+// so the rest of them isn't held on to
+const MAX_FILTERED_LINE: usize = 64 * 1024;
+
+/// Where executions write outputs larger than `keep` bytes.
+#[derive(Debug, Clone)]
+pub struct OutputSpill {
+    pub dir: PathBuf,
+    /// Bytes of each output kept in memory, and so in the response
+    pub keep: usize,
+}
+
+/// The whole of an output that was too large to keep in memory,
+/// removed once dropped.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SpilledFile {
+    path: PathBuf,
+    /// Bytes
+    size: usize,
+}
+
+/// The whole of each output that was too large for the response,
+/// which then only has the start of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Spilled {
+    pub stdout: Option<Arc<SpilledFile>>,
+    pub stderr: Option<Arc<SpilledFile>>,
+}
+
+impl SpilledFile {
+    /// So that files left behind, such as by a crash, can be found.
+    pub const EXTENSION: &'static str = "spilled";
+
+    /// Removes the file at `path` once dropped.
+    pub fn new(path: PathBuf, size: usize) -> Self {
+        Self { path, size }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    async fn create_in(dir: &Path) -> Result<(Self, BufWriter<fs::File>)> {
+        let name: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(NAME_LENGTH)
+            .map(char::from)
+            .collect();
+        let path = dir.join(name).with_extension(Self::EXTENSION);
+
+        let created = async {
+            fs::create_dir_all(dir).await?;
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+        };
+        let file = created
+            .await
+            .context(UnableToSpillOutputSnafu { path: &path })?;
+
+        Ok((Self::new(path, 0), BufWriter::new(file)))
+    }
+
+    /// Only the lines that `keep` is true for, which are written to
+    /// another file as this one is read. Lines too long to be filtered
+    /// are judged by their start.
+    async fn filter_lines(&self, mut keep: impl FnMut(&[u8]) -> bool) -> Result<Self> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let (mut filtered, mut writer) = SpilledFile::create_in(dir).await?;
+
+        let reader = fs::File::open(&self.path)
+            .await
+            .context(UnableToSpillOutputSnafu { path: &self.path })?;
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        // Once a line is too long to hold on to, whether it's kept
+        let mut long_line_kept = None;
+
+        loop {
+            let buffer = reader
+                .fill_buf()
+                .await
+                .context(UnableToSpillOutputSnafu { path: &self.path })?;
+            if buffer.is_empty() {
+                break;
+            }
+            let (chunk, ends_line) = match buffer.iter().position(|&b| b == b'\n') {
+                Some(i) => (&buffer[..=i], true),
+                None => (buffer, false),
+            };
+            let len = chunk.len();
+
+            match long_line_kept {
+                Some(true) => filtered.write(&mut writer, chunk).await?,
+                Some(false) => {}
+                None if line.len() + len <= MAX_FILTERED_LINE => line.extend_from_slice(chunk),
+                None => {
+                    let kept = keep(&line);
+                    if kept {
+                        filtered.write(&mut writer, &line).await?;
+                        filtered.write(&mut writer, chunk).await?;
+                    }
+                    line.clear();
+                    long_line_kept = Some(kept);
+                }
+            }
+            reader.consume(len);
+
+            if ends_line {
+                if long_line_kept.is_none() && keep(&line) {
+                    filtered.write(&mut writer, &line).await?;
+                }
+                line.clear();
+                long_line_kept = None;
+            }
+        }
+
+        if !line.is_empty() && keep(&line) {
+            filtered.write(&mut writer, &line).await?;
+        }
+        writer.flush().await.context(UnableToSpillOutputSnafu {
+            path: &filtered.path,
+        })?;
+
+        Ok(filtered)
+    }
+
+    /// At most `len` bytes from the start, ending with a whole
+    /// character.
+    async fn start(&self, len: usize) -> Result<Vec<u8>> {
+        let file = fs::File::open(&self.path)
+            .await
+            .context(UnableToSpillOutputSnafu { path: &self.path })?;
+        let mut start = Vec::with_capacity(len.min(self.size));
+        file.take(len as u64)
+            .read_to_end(&mut start)
+            .await
+            .context(UnableToSpillOutputSnafu { path: &self.path })?;
+        if self.size > len {
+            start.truncate(whole_characters_len(&start));
+        }
+        Ok(start)
+    }
+
+    async fn write(&mut self, writer: &mut BufWriter<fs::File>, bytes: &[u8]) -> Result<()> {
+        writer
+            .write_all(bytes)
+            .await
+            .context(UnableToSpillOutputSnafu { path: &self.path })?;
+        self.size += bytes.len();
+        Ok(())
+    }
+}
+
+impl Drop for SpilledFile {
+    fn drop(&mut self) {
+        let path = std::mem::take(&mut self.path);
+        let remove = move || {
+            if let Err(error) = std::fs::remove_file(&path) {
+                tracing::warn!(%error, path = %path.display(), "Unable to remove a spilled output");
+            }
+        };
+
+        // Usually dropped by async code, which shouldn't wait on the
+        // disk
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(remove)),
+            Err(_) => remove(),
+        }
+    }
+}
+
+/// Like [`Command::output`], reading the outputs with [`capture`].
+pub(crate) async fn output(
+    mut command: Command,
+    spill: &OutputSpill,
+) -> Result<(Output, Option<SpilledFile>, Option<SpilledFile>)> {
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+    command.kill_on_drop(true);
+
+    let mut child = command
+        .spawn()
+        .context(UnableToGetOutputFromCompilerSnafu)?;
+    let ((stdout, stdout_file), (stderr, stderr_file)) = futures::try_join!(
+        capture(child.stdout.take(), spill),
+        capture(child.stderr.take(), spill),
+    )?;
+    let status = child
+        .wait()
+        .await
+        .context(UnableToGetOutputFromCompilerSnafu)?;
+
+    let output = Output {
+        status,
+        stdout,
+        stderr,
+    };
+    Ok((output, stdout_file, stderr_file))
+}
+
+/// Only the lines of `file` that `keep_line` is true for, split as
+/// [`capture`] splits an output.
+pub(crate) async fn filter(
+    file: SpilledFile,
+    spill: &OutputSpill,
+    keep_line: impl FnMut(&[u8]) -> bool,
+) -> Result<(Vec<u8>, Option<SpilledFile>)> {
+    let filtered = file.filter_lines(keep_line).await?;
+    drop(file);
+
+    let kept = filtered.start(spill.keep).await?;
+    if filtered.size > spill.keep {
+        Ok((kept, Some(filtered)))
+    } else {
+        Ok((kept, None))
+    }
+}
+
+/// Reads all of `reader`, keeping the start of it in memory and
+/// writing all of it to a file once it's larger than `spill.keep`.
+/// What's kept ends with a whole character.
+pub(crate) async fn capture(
+    reader: Option<impl AsyncRead + Unpin>,
+    spill: &OutputSpill,
+) -> Result<(Vec<u8>, Option<SpilledFile>)> {
+    let mut reader = match reader {
+        Some(reader) => reader,
+        None => return Ok((vec![], None)),
+    };
+    let mut kept = Vec::new();
+    let mut spilled = None;
+    let mut buffer = vec![0; READ_SIZE];
+
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .await
+            .context(UnableToGetOutputFromCompilerSnafu)?;
+        if n == 0 {
+            break;
+        }
+        let chunk = &buffer[..n];
+
+        match &mut spilled {
+            Some((file, writer)) => SpilledFile::write(file, writer, chunk).await?,
+            None if kept.len() + n <= spill.keep => kept.extend_from_slice(chunk),
+            None => {
+                let (mut file, mut writer) = SpilledFile::create_in(&spill.dir).await?;
+                file.write(&mut writer, &kept).await?;
+                file.write(&mut writer, chunk).await?;
+
+                let room = spill.keep - kept.len();
+                kept.extend_from_slice(&chunk[..room]);
+                kept.truncate(whole_characters_len(&kept));
+                spilled = Some((file, writer));
+            }
+        }
+    }
+
+    match spilled {
+        Some((file, mut writer)) => {
+            writer
+                .flush()
+                .await
+                .context(UnableToSpillOutputSnafu { path: &file.path })?;
+            Ok((kept, Some(file)))
+        }
+        None => Ok((kept, None)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spill(name: &str, keep: usize) -> OutputSpill {
+        let dir = std::env::temp_dir().join(format!("spill-{}-{}", name, std::process::id()));
+        OutputSpill { dir, keep }
+    }
+
+    async fn contents(file: &SpilledFile) -> String {
+        fs::read_to_string(file.path()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn small_outputs_are_kept_in_memory() {
+        let spill = spill("small", 16);
+
+        let (kept, file) = capture(Some(&b"hello"[..]), &spill).await.unwrap();
+
+        assert_eq!(kept, b"hello");
+        assert_eq!(file, None);
+    }
+
+    #[tokio::test]
+    async fn large_outputs_keep_their_start_in_memory() {
+        let spill = spill("large", 2);
+
+        let (kept, file) = capture(Some("héllo wörld".as_bytes()), &spill)
+            .await
+            .unwrap();
+        let file = file.unwrap();
+
+        // The `é` would have been cut in half
+        assert_eq!(kept, b"h");
+        assert_eq!(contents(&file).await, "héllo wörld");
+        assert_eq!(file.size(), "héllo wörld".len());
+
+        let path = file.path().to_owned();
+        drop(file);
+        for _ in 0..100 {
+            if !path.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(spill.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lines_are_filtered_as_they_are_read() {
+        let spill = spill("filter", 0);
+        let output = format!(
+            "drop\nkeep\ndrop {}\nkeep {}\nend",
+            "x".repeat(100_000),
+            "y".repeat(100_000),
+        );
+
+        let (_, file) = capture(Some(output.as_bytes()), &spill).await.unwrap();
+        let spill = OutputSpill { keep: 6, ..spill };
+        let (kept, filtered) = filter(file.unwrap(), &spill, |line| {
+            line.starts_with(b"keep") || line == b"end"
+        })
+        .await
+        .unwrap();
+        let filtered = filtered.unwrap();
+
+        let expected = format!("keep\nkeep {}\nend", "y".repeat(100_000));
+        assert_eq!(kept, b"keep\nk");
+        assert_eq!(contents(&filtered).await, expected);
+        assert_eq!(filtered.size(), expected.len());
+
+        let (kept, filtered) = filter(filtered, &spill, |line| line == b"end")
+            .await
+            .unwrap();
+        assert_eq!(kept, b"end");
+        assert_eq!(filtered, None);
+        std::fs::remove_dir_all(spill.dir).ok();
+    }
+}
@@ -0,0 +1,100 @@
+//! Reads the report that `-Ztime-passes` writes to stderr, which is a
+//! line for each pass with a tab before its name, like
+//!
+//! ```text
+//! time:   0.003; rss:   42MB ->   53MB (  +11MB)    expand_crate
+//! ```
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::CompilerPass;
+
+lazy_static! {
+    static ref PASS: Regex = Regex::new(
+        r"^time:\s*(?P<seconds>\d+)\.(?P<millis>\d{3})(?:; rss:\s*(?P<before>\d+)MB\s*->\s*(?P<after>\d+)MB\s*\(\s*[+-]?\d+MB\))?\s+(?P<name>\S.*)$"
+    )
+    .unwrap();
+}
+
+/// The passes, in the order the compiler reported them, and what was
+/// left of the output without them.
+pub(crate) fn split(stderr: &str) -> (Vec<CompilerPass>, String) {
+    let mut passes = vec![];
+    let mut rest = String::new();
+
+    for line in stderr.lines() {
+        match parse(line) {
+            Some(pass) => passes.push(pass),
+            None => {
+                rest.push_str(line);
+                rest.push('\n');
+            }
+        }
+    }
+
+    (passes, rest)
+}
+
+fn parse(line: &str) -> Option<CompilerPass> {
+    let captures = PASS.captures(line)?;
+    let number = |name| captures.name(name)?.as_str().parse::<u64>().ok();
+
+    Some(CompilerPass {
+        name: captures["name"].trim().into(),
+        milliseconds: number("seconds")? * 1000 + number("millis")?,
+        rss_before_mb: number("before"),
+        rss_after_mb: number("after"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passes_are_parsed() {
+        let stderr = concat!(
+            "   Compiling playground v0.0.1 (/playground)\n",
+            "time:   0.003; rss:   42MB ->   53MB (  +11MB)\texpand_crate\n",
+            "time:   1.250; rss:  108MB ->   81MB (  -27MB)\tLLVM_passes\n",
+            "time:   0.000\tlink\n",
+        );
+
+        let (passes, rest) = split(stderr);
+
+        assert_eq!(
+            passes,
+            [
+                CompilerPass {
+                    name: "expand_crate".into(),
+                    milliseconds: 3,
+                    rss_before_mb: Some(42),
+                    rss_after_mb: Some(53),
+                },
+                CompilerPass {
+                    name: "LLVM_passes".into(),
+                    milliseconds: 1250,
+                    rss_before_mb: Some(108),
+                    rss_after_mb: Some(81),
+                },
+                CompilerPass {
+                    name: "link".into(),
+                    milliseconds: 0,
+                    rss_before_mb: None,
+                    rss_after_mb: None,
+                },
+            ]
+        );
+        assert_eq!(rest, "   Compiling playground v0.0.1 (/playground)\n");
+    }
+
+    #[test]
+    fn diagnostics_are_kept() {
+        let stderr = "error[E0425]: cannot find value `time` in this scope\n";
+        let (passes, rest) = split(stderr);
+
+        assert!(passes.is_empty());
+        assert_eq!(rest, stderr);
+    }
+}
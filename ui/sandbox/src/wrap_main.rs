@@ -0,0 +1,85 @@
+//! Turns snippets that have no `main` function, such as the fragments
+//! in the book, into programs that can be run.
+
+use crate::detect::has_main_function;
+
+// Statements and items leave nothing worth showing
+const SHOW_VALUE: &str = r#"
+fn __playground_show<T: std::fmt::Debug>(value: T) {
+    if std::any::type_name::<T>() != "()" {
+        println!("{:?}", value);
+    }
+}
+"#;
+
+/// Puts `code` inside a `main` function that prints the value of its
+/// last expression, when it ends with one. That value must implement
+/// `Debug`. Crate attributes are moved out to the top.
+///
+/// Everything stays on the line it was on, so that the line numbers
+/// of errors still match the code that was given. `None` when there
+/// is already a `main` function.
+pub(crate) fn wrap_main(code: &str) -> Option<String> {
+    if has_main_function(code) {
+        return None;
+    }
+
+    let mut attributes = String::new();
+    let body: Vec<_> = code
+        .lines()
+        .map(|line| {
+            if is_crate_attribute(line) {
+                attributes.push_str(line.trim());
+                attributes.push(' ');
+                ""
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    Some(format!(
+        "{}fn main() {{ __playground_show({{ {}\n}}); }}\n{}",
+        attributes,
+        body.join("\n"),
+        SHOW_VALUE,
+    ))
+}
+
+fn is_crate_attribute(line: &str) -> bool {
+    line.trim_start().starts_with("#![") && line.trim_end().ends_with(']')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn code_with_a_main_function_is_left_alone() {
+        assert_eq!(wrap_main("fn main() {}"), None);
+    }
+
+    #[test]
+    fn the_last_expression_is_shown() {
+        let wrapped = wrap_main("let a = 2;\na * 21").unwrap();
+        assert!(
+            wrapped.starts_with("fn main() { __playground_show({ let a = 2;\na * 21\n}); }\n"),
+            "{}",
+            wrapped,
+        );
+        assert!(wrapped.contains("fn __playground_show"));
+    }
+
+    #[test]
+    fn crate_attributes_are_moved_out_of_main() {
+        let code = "#![allow(unused)]\nlet a = 1;\n  #![feature(never_type)]  \nstruct A;";
+        let wrapped = wrap_main(code).unwrap();
+        let lines: Vec<_> = wrapped.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "#![allow(unused)] #![feature(never_type)] fn main() { __playground_show({ ",
+        );
+        assert_eq!(lines[1..4], ["let a = 1;", "", "struct A;"]);
+    }
+}
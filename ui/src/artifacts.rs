@@ -0,0 +1,505 @@
+use futures::stream::{self, BoxStream, StreamExt};
+use hyper::body::Bytes;
+use playground_sandbox::spill::{OutputSpill, SpilledFile};
+use rand::{distributions::Alphanumeric, Rng};
+use snafu::prelude::*;
+use std::{
+    collections::HashMap,
+    fs, io,
+    io::SeekFrom,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+// Anyone with the ID may download the artifact, so it must not be
+// guessable
+const ID_LENGTH: usize = 24;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// How much of a spilled artifact is read at a time when it's streamed
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Files produced by operations, such as compiler output, kept for a
+/// while so they can be downloaded separately instead of being part
+/// of the response.
+#[derive(Debug)]
+pub(crate) struct Artifacts {
+    config: Config,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    /// Artifacts larger than this many bytes are rejected.
+    pub(crate) max_size: usize,
+    /// When storing another artifact would exceed this many bytes,
+    /// the oldest ones are removed early to make room.
+    pub(crate) max_total_size: usize,
+    pub(crate) time_to_live: Duration,
+    /// Where artifacts larger than `spill_size` are written instead
+    /// of being kept in memory. Outputs that large are then also cut
+    /// short in responses, with the whole of them kept as artifacts,
+    /// and executions write them here as they are read.
+    pub(crate) spill_dir: Option<PathBuf>,
+    /// Bytes
+    pub(crate) spill_size: usize,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_id: HashMap<String, Artifact>,
+    total_size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Artifact {
+    pub(crate) id: String,
+    /// The name to download the artifact as
+    pub(crate) file_name: String,
+    pub(crate) content_type: &'static str,
+    /// Bytes
+    pub(crate) size: usize,
+    content: Content,
+    pub(crate) created_at: SystemTime,
+    pub(crate) expires_at: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Content {
+    Memory(Bytes),
+    /// Removed once the artifact is, and nothing is reading it
+    Spilled(Arc<SpilledFile>),
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "The artifact is {} bytes, but may be at most {} bytes",
+        size,
+        max_size
+    ))]
+    TooLarge { size: usize, max_size: usize },
+    #[snafu(display("Unable to write the artifact to {}: {}", path.display(), source))]
+    UnableToSpill { path: PathBuf, source: io::Error },
+    #[snafu(display("Unable to read the artifact from {}: {}", path.display(), source))]
+    UnableToRead { path: PathBuf, source: io::Error },
+}
+
+pub(crate) type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+impl Artifacts {
+    /// Spilled artifacts left from an earlier run can never be
+    /// downloaded, so they are removed.
+    pub(crate) fn new(config: Config) -> Self {
+        if let Some(dir) = &config.spill_dir {
+            remove_spilled(dir);
+        }
+
+        Self {
+            config,
+            entries: Default::default(),
+        }
+    }
+
+    pub(crate) async fn store(
+        &self,
+        file_name: impl Into<String>,
+        content_type: &'static str,
+        content: impl Into<Bytes>,
+    ) -> Result<Artifact> {
+        self.store_at(
+            file_name.into(),
+            content_type,
+            content.into(),
+            SystemTime::now(),
+        )
+        .await
+    }
+
+    /// Keeps a file that an execution already spilled its output to,
+    /// rather than writing it again.
+    pub(crate) fn adopt(
+        &self,
+        file_name: impl Into<String>,
+        content_type: &'static str,
+        file: Arc<SpilledFile>,
+    ) -> Result<Artifact> {
+        let size = file.size();
+        self.check_size(size)?;
+
+        let now = SystemTime::now();
+        let artifact = self.artifact(
+            file_name.into(),
+            content_type,
+            size,
+            Content::Spilled(file),
+            now,
+        );
+        self.insert(&artifact, now);
+        Ok(artifact)
+    }
+
+    /// Where executions should write outputs that are too large to be
+    /// part of a response, when outputs are being spilled.
+    pub(crate) fn output_spill(&self) -> Option<OutputSpill> {
+        self.config.spill_dir.as_ref().map(|dir| OutputSpill {
+            dir: dir.clone(),
+            keep: self.config.spill_size,
+        })
+    }
+
+    /// Expired artifacts may not have been swept yet, so they are
+    /// never returned.
+    pub(crate) fn load(&self, id: &str) -> Option<Artifact> {
+        self.load_at(id, SystemTime::now())
+    }
+
+    /// Removes expired artifacts every so often, forever.
+    pub(crate) async fn sweep_periodically(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let count = self.sweep_at(SystemTime::now());
+            if count != 0 {
+                tracing::debug!(count, "Removed expired artifacts");
+            }
+        }
+    }
+
+    async fn store_at(
+        &self,
+        file_name: String,
+        content_type: &'static str,
+        content: Bytes,
+        now: SystemTime,
+    ) -> Result<Artifact> {
+        let size = content.len();
+        self.check_size(size)?;
+
+        let content = match &self.config.spill_dir {
+            Some(dir) if size > self.config.spill_size => {
+                let path = dir.join(random_id()).with_extension(SpilledFile::EXTENSION);
+                let written = async {
+                    tokio::fs::create_dir_all(dir).await?;
+                    tokio::fs::write(&path, &content).await
+                };
+                written.await.context(UnableToSpillSnafu { path: &path })?;
+                Content::Spilled(Arc::new(SpilledFile::new(path, size)))
+            }
+            _ => Content::Memory(content),
+        };
+
+        let artifact = self.artifact(file_name, content_type, size, content, now);
+        self.insert(&artifact, now);
+        Ok(artifact)
+    }
+
+    fn check_size(&self, size: usize) -> Result<()> {
+        let max_size = self.config.max_size.min(self.config.max_total_size);
+        ensure!(size <= max_size, TooLargeSnafu { size, max_size });
+        Ok(())
+    }
+
+    fn artifact(
+        &self,
+        file_name: String,
+        content_type: &'static str,
+        size: usize,
+        content: Content,
+        now: SystemTime,
+    ) -> Artifact {
+        Artifact {
+            id: random_id(),
+            file_name,
+            content_type,
+            size,
+            content,
+            created_at: now,
+            expires_at: now + self.config.time_to_live,
+        }
+    }
+
+    fn insert(&self, artifact: &Artifact, now: SystemTime) {
+        let size = artifact.size;
+        let mut entries = self.lock();
+        entries.sweep(now);
+
+        while entries.total_size + size > self.config.max_total_size {
+            let oldest = entries
+                .by_id
+                .values()
+                .min_by_key(|a| a.created_at)
+                .map(|a| a.id.clone());
+            match oldest {
+                Some(id) => entries.remove(&id),
+                None => break,
+            }
+        }
+
+        entries.total_size += size;
+        entries.by_id.insert(artifact.id.clone(), artifact.clone());
+    }
+
+    fn load_at(&self, id: &str, now: SystemTime) -> Option<Artifact> {
+        self.lock()
+            .by_id
+            .get(id)
+            .filter(|a| a.expires_at > now)
+            .cloned()
+    }
+
+    fn sweep_at(&self, now: SystemTime) -> usize {
+        self.lock().sweep(now)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Entries {
+    fn sweep(&mut self, now: SystemTime) -> usize {
+        let expired: Vec<_> = self
+            .by_id
+            .values()
+            .filter(|a| a.expires_at <= now)
+            .map(|a| a.id.clone())
+            .collect();
+
+        for id in &expired {
+            self.remove(id);
+        }
+        expired.len()
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(artifact) = self.by_id.remove(id) {
+            self.total_size -= artifact.size;
+        }
+    }
+}
+
+impl Artifact {
+    pub(crate) async fn read(&self) -> Result<Bytes> {
+        self.read_range(0..self.size).await
+    }
+
+    /// The part of the content in `range`, which is cut short to
+    /// what there is.
+    pub(crate) async fn read_range(&self, range: Range<usize>) -> Result<Bytes> {
+        let Range { start, end } = self.range(range);
+
+        match &self.content {
+            Content::Memory(content) => Ok(content.slice(start..end)),
+            Content::Spilled(file) => {
+                let path = file.path();
+                let read = async {
+                    let mut file = tokio::fs::File::open(path).await?;
+                    file.seek(SeekFrom::Start(start as u64)).await?;
+                    let mut content = vec![0; end - start];
+                    file.read_exact(&mut content).await?;
+                    Ok(content)
+                };
+                read.await
+                    .map(Into::into)
+                    .context(UnableToReadSnafu { path })
+            }
+        }
+    }
+
+    /// `range` cut short to what there is.
+    pub(crate) fn range(&self, range: Range<usize>) -> Range<usize> {
+        let end = range.end.min(self.size);
+        range.start.min(end)..end
+    }
+
+    /// Like [`Artifact::read_range`], but a spilled artifact is read a
+    /// chunk at a time as the stream is polled, so that only a little
+    /// of it is in memory at once.
+    pub(crate) async fn stream_range(
+        &self,
+        range: Range<usize>,
+    ) -> Result<BoxStream<'static, io::Result<Bytes>>> {
+        let Range { start, end } = self.range(range);
+
+        match &self.content {
+            Content::Memory(content) => {
+                let content = content.slice(start..end);
+                Ok(stream::once(async { Ok(content) }).boxed())
+            }
+            Content::Spilled(spilled) => {
+                let path = spilled.path();
+                let open = async {
+                    let mut file = tokio::fs::File::open(path).await?;
+                    file.seek(SeekFrom::Start(start as u64)).await?;
+                    Ok(file)
+                };
+                let file = open.await.context(UnableToReadSnafu { path })?;
+
+                // The file is kept until the stream is finished with it
+                let state = (file, end - start, spilled.clone());
+                let chunks = stream::try_unfold(state, |(mut file, left, spilled)| async move {
+                    if left == 0 {
+                        return Ok(None);
+                    }
+                    let mut chunk = vec![0; left.min(CHUNK_SIZE)];
+                    file.read_exact(&mut chunk).await?;
+                    let left = left - chunk.len();
+                    Ok(Some((Bytes::from(chunk), (file, left, spilled))))
+                });
+                Ok(chunks.boxed())
+            }
+        }
+    }
+}
+
+fn remove_spilled(dir: &std::path::Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(error) => {
+            tracing::warn!(%error, dir = %dir.display(), "Unable to look for spilled artifacts");
+            return;
+        }
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension() == Some(SpilledFile::EXTENSION.as_ref()) {
+            if let Err(error) = fs::remove_file(&path) {
+                tracing::warn!(%error, path = %path.display(), "Unable to remove a spilled artifact");
+            }
+        }
+    }
+}
+
+pub(crate) fn random_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(max_size: usize, max_total_size: usize) -> Config {
+        Config {
+            max_size,
+            max_total_size,
+            time_to_live: Duration::from_secs(60),
+            spill_dir: None,
+            spill_size: 0,
+        }
+    }
+
+    fn artifacts(max_size: usize, max_total_size: usize) -> Artifacts {
+        Artifacts::new(config(max_size, max_total_size))
+    }
+
+    async fn store(
+        artifacts: &Artifacts,
+        content: &'static str,
+        now: SystemTime,
+    ) -> Result<Artifact> {
+        artifacts
+            .store_at("main.s".into(), "text/plain", content.into(), now)
+            .await
+    }
+
+    #[tokio::test]
+    async fn artifacts_expire() {
+        let artifacts = artifacts(10, 100);
+        let start = SystemTime::now();
+
+        let artifact = store(&artifacts, "mov", start).await.unwrap();
+        assert_eq!(artifact.expires_at, start + Duration::from_secs(60));
+        assert_eq!(
+            artifacts.load_at(&artifact.id, start),
+            Some(artifact.clone())
+        );
+
+        let later = artifact.expires_at;
+        assert_eq!(artifacts.load_at(&artifact.id, later), None);
+        assert_eq!(artifacts.sweep_at(later), 1);
+        assert_eq!(artifacts.lock().total_size, 0);
+    }
+
+    #[tokio::test]
+    async fn the_oldest_artifacts_make_room_for_new_ones() {
+        let artifacts = artifacts(4, 10);
+        let start = SystemTime::now();
+
+        let first = store(&artifacts, "1111", start).await.unwrap();
+        let second = store(&artifacts, "2222", start + Duration::from_secs(1))
+            .await
+            .unwrap();
+        let third = store(&artifacts, "3333", start + Duration::from_secs(2))
+            .await
+            .unwrap();
+
+        let now = start + Duration::from_secs(3);
+        assert_eq!(artifacts.load_at(&first.id, now), None);
+        assert!(artifacts.load_at(&second.id, now).is_some());
+        assert!(artifacts.load_at(&third.id, now).is_some());
+        assert_eq!(artifacts.lock().total_size, 8);
+
+        assert!(matches!(
+            store(&artifacts, "55555", now).await,
+            Err(Error::TooLarge {
+                size: 5,
+                max_size: 4
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn large_artifacts_are_spilled_to_disk() {
+        let dir = std::env::temp_dir().join(format!("artifacts-{}", std::process::id()));
+        let artifacts = Artifacts::new(Config {
+            spill_dir: Some(dir.clone()),
+            spill_size: 4,
+            ..config(10, 100)
+        });
+        let start = SystemTime::now();
+
+        let small = store(&artifacts, "mov", start).await.unwrap();
+        let large = store(&artifacts, "movq %rax", start).await.unwrap();
+        assert!(matches!(small.content, Content::Memory(_)));
+        assert_eq!(artifacts.output_spill().map(|spill| spill.keep), Some(4));
+
+        let path = match &large.content {
+            Content::Spilled(file) => file.path().to_owned(),
+            Content::Memory(_) => panic!("It wasn't spilled"),
+        };
+        assert!(path.exists());
+        assert_eq!(large.read().await.unwrap(), "movq %rax");
+        assert_eq!(large.read_range(5..8).await.unwrap(), "%ra");
+        assert_eq!(large.read_range(8..100).await.unwrap(), "x");
+        assert_eq!(large.read_range(20..30).await.unwrap(), "");
+        let streamed: Vec<_> = large.stream_range(2..100).await.unwrap().collect().await;
+        let streamed: Vec<_> = streamed.into_iter().map(Result::unwrap).collect();
+        assert_eq!(streamed.concat(), b"vq %rax");
+
+        assert_eq!(artifacts.sweep_at(large.expires_at), 2);
+        assert!(path.exists(), "It's still being read");
+        drop(large);
+        // It's removed off of the async threads
+        for _ in 0..100 {
+            if !path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,1244 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+use snafu::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    artifacts, coordinator, dispatcher,
+    env::{
+        self, PLAYGROUND_GITHUB_TOKEN, PLAYGROUND_QUEUE_REDIS_URL, PLAYGROUND_SHARE_LINK_KEY,
+        PLAYGROUND_SNIPPET_DATABASE, PLAYGROUND_UI_ROOT,
+    },
+    load_shedding, queue, rate_limit, repl, sandbox, share_link, storage, AdminToken, GhToken,
+    MetricsToken, ShareLinks, SnippetStorage, WorkerToken,
+};
+
+const PLAYGROUND_CONFIG_FILE: &str = "PLAYGROUND_CONFIG_FILE";
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 5000;
+const DEFAULT_RESPONSE_CACHE_SIZE: usize = 1024;
+const DEFAULT_RESPONSE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_MAX_RUNNING_SANDBOXES: usize = 16;
+const DEFAULT_MAX_RUNNING_CHEAP_SANDBOXES: usize = 4;
+const DEFAULT_MAX_WAITING_REQUESTS: usize = 64;
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: usize = 5;
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_SNIPPET_MAX_SIZE: usize = 64 * 1024;
+const DEFAULT_RATE_LIMIT_BISECT: u32 = 1;
+const DEFAULT_BISECT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const DEFAULT_BISECT_CPU_TIME: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_BISECT_NETWORK: &str = "bridge";
+const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_COORDINATOR_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_MAX_CODE_SIZE: usize = 512 * 1024;
+const DEFAULT_MAX_TOTAL_CODE_SIZE: usize = 2 * 1024 * 1024;
+// Longer than requests may wait for a queue or a coordinator
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_BODY_SIZE: usize = 8 * 1024 * 1024;
+const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+// Hyper refuses to read headers into anything smaller
+const MIN_MAX_HEADER_SIZE: usize = 8 * 1024;
+const DEFAULT_ARTIFACT_MAX_SIZE: usize = 8 * 1024 * 1024;
+const DEFAULT_ARTIFACT_MAX_TOTAL_SIZE: usize = 256 * 1024 * 1024;
+const DEFAULT_ARTIFACT_TTL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_ARTIFACT_SPILL_SIZE: usize = 1024 * 1024;
+const DEFAULT_REPL_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+pub(crate) struct Config {
+    address: String,
+    admin_token: Option<String>,
+    api_keys: Vec<rate_limit::ApiKey>,
+    artifacts: artifacts::Config,
+    circuit_breaker_cooldown: Duration,
+    circuit_breaker_threshold: usize,
+    coordinator_timeout: Duration,
+    coordinator_workers: Vec<String>,
+    cors_enabled: bool,
+    gh_token: Option<String>,
+    grpc_port: Option<u16>,
+    http_limits: HttpLimits,
+    image_update_interval: Option<Duration>,
+    load_shedding: load_shedding::Thresholds,
+    max_running_cheap_sandboxes: usize,
+    max_running_sandboxes: usize,
+    max_total_code_size: usize,
+    max_waiting_requests: usize,
+    metrics_token: Option<String>,
+    port: u16,
+    queue_redis_url: Option<String>,
+    queue_timeout: Duration,
+    rate_limit_bisect: u32,
+    rate_limit_cheap: Option<u32>,
+    rate_limit_expensive: Option<u32>,
+    repl: repl::Config,
+    response_cache_size: usize,
+    response_cache_ttl: Duration,
+    root: PathBuf,
+    sandbox: sandbox::Config,
+    share_link_key: Option<String>,
+    shutdown_timeout: Duration,
+    snippet_database: Option<PathBuf>,
+    snippet_max_size: usize,
+    snippet_ttl: Option<Duration>,
+    templates_file: Option<PathBuf>,
+    trust_forwarded_for: bool,
+    worker_token: Option<String>,
+}
+
+/// Limits on each HTTP request, whatever it asks for, so that clients
+/// can't keep connections open by being slow.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct HttpLimits {
+    /// From when the headers were read until the response is ready.
+    pub(crate) request_timeout: Duration,
+    /// For the headers to arrive once the connection is ready for
+    /// them.
+    pub(crate) header_timeout: Duration,
+    pub(crate) max_body_size: usize,
+    /// For the request line and all of the headers together.
+    pub(crate) max_header_size: usize,
+}
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("Unable to read the configuration file {}: {}", path.display(), source))]
+    UnableToReadFile { path: PathBuf, source: io::Error },
+    #[snafu(display("Unable to parse the configuration file {}: {}", path.display(), source))]
+    UnableToParseFile {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[snafu(display("Unable to use the job queue: {}", source))]
+    InvalidQueue { source: queue::Error },
+    #[snafu(display("PLAYGROUND_WORKER_TOKEN must be set to send requests to workers"))]
+    WorkerTokenMissing,
+}
+
+pub(crate) type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+/// The settings that may be given in the configuration file. Every
+/// setting is optional and is overridden by the matching environment
+/// variable, if that is set.
+///
+/// Secrets, such as the GitHub token, may only be set in the
+/// environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct File {
+    address: Option<String>,
+    port: Option<u16>,
+    grpc_port: Option<u16>,
+    root: Option<PathBuf>,
+    cors_enabled: Option<bool>,
+    trust_forwarded_for: Option<bool>,
+    /// Seconds
+    shutdown_timeout: Option<u64>,
+    response_cache: ResponseCacheSection,
+    requests: RequestsSection,
+    concurrency: ConcurrencySection,
+    circuit_breaker: CircuitBreakerSection,
+    load_shedding: LoadSheddingSection,
+    rate_limit: RateLimitSection,
+    /// Keyed by a name for who the key was given to
+    api_keys: BTreeMap<String, ApiKeySection>,
+    sandbox: SandboxSection,
+    snippets: SnippetsSection,
+    templates: TemplatesSection,
+    artifacts: ArtifactsSection,
+    repl: ReplSection,
+    queue: QueueSection,
+    coordinator: CoordinatorSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ResponseCacheSection {
+    size: Option<usize>,
+    /// Seconds
+    ttl: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct RequestsSection {
+    /// Bytes
+    max_code_size: Option<usize>,
+    /// Bytes, for all of the operations of a batch together
+    max_total_code_size: Option<usize>,
+    /// Seconds
+    timeout: Option<u64>,
+    /// Seconds
+    header_timeout: Option<u64>,
+    /// Bytes
+    max_body_size: Option<usize>,
+    /// Bytes
+    max_header_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConcurrencySection {
+    max_running_sandboxes: Option<usize>,
+    max_running_cheap_sandboxes: Option<usize>,
+    max_waiting_requests: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct CircuitBreakerSection {
+    threshold: Option<usize>,
+    /// Seconds
+    cooldown: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct LoadSheddingSection {
+    max_load_average: Option<f64>,
+    /// Bytes
+    min_free_memory: Option<u64>,
+    /// Bytes
+    min_free_disk: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct RateLimitSection {
+    cheap: Option<u32>,
+    expensive: Option<u32>,
+    bisect: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ApiKeySection {
+    sha256: String,
+    cheap: Option<u32>,
+    expensive: Option<u32>,
+    daily_quota: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct SnippetsSection {
+    database: Option<PathBuf>,
+    /// Bytes
+    max_size: Option<usize>,
+    /// Seconds
+    ttl: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct TemplatesSection {
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ArtifactsSection {
+    /// Bytes
+    max_size: Option<usize>,
+    /// Bytes, for all of the artifacts together
+    max_total_size: Option<usize>,
+    /// Seconds
+    ttl: Option<u64>,
+    spill_dir: Option<PathBuf>,
+    /// Bytes
+    spill_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ReplSection {
+    max_sessions: Option<usize>,
+    /// Seconds
+    idle_timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct QueueSection {
+    redis_url: Option<String>,
+    /// Seconds
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct CoordinatorSection {
+    /// The base URLs of the servers that run the requests
+    workers: Vec<String>,
+    /// Seconds
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct SandboxSection {
+    sccache_dir: Option<PathBuf>,
+    work_dir: Option<PathBuf>,
+    seccomp_profile: Option<PathBuf>,
+    read_only_root: Option<bool>,
+    copy_files: Option<bool>,
+    report_image_ids: Option<bool>,
+    memory: Option<String>,
+    memory_swap: Option<String>,
+    pids_limit: Option<u32>,
+    disk: Option<String>,
+    /// Seconds
+    timeout: Option<u64>,
+    /// Seconds
+    cpu_time: Option<u64>,
+    /// Bytes a second
+    output_rate: Option<u64>,
+    device_read_bps: Option<String>,
+    device_write_bps: Option<String>,
+    images: ImagesSection,
+    channel: ChannelSection,
+    bisect: BisectSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ChannelSection {
+    stable: Option<LimitsSection>,
+    beta: Option<LimitsSection>,
+    nightly: Option<LimitsSection>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct LimitsSection {
+    memory: Option<String>,
+    memory_swap: Option<String>,
+    pids_limit: Option<u32>,
+    disk: Option<String>,
+    /// Seconds
+    timeout: Option<u64>,
+    /// Seconds
+    cpu_time: Option<u64>,
+    /// Bytes a second
+    output_rate: Option<u64>,
+    device_read_bps: Option<String>,
+    device_write_bps: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct BisectSection {
+    image: Option<String>,
+    /// Seconds
+    timeout: Option<u64>,
+    /// Seconds, for each process
+    cpu_time: Option<u64>,
+    /// That the toolchains are downloaded over
+    network: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ImagesSection {
+    /// Prefixed to the names of all of the images, including the one
+    /// used for bisecting
+    registry: Option<String>,
+    /// Seconds between checks for newer images of each channel
+    update_interval: Option<u64>,
+    stable: Option<String>,
+    /// Keyed by the version, such as `"1.30.0"`
+    stable_releases: BTreeMap<sandbox::RustVersion, String>,
+    beta: Option<String>,
+    nightly: Option<String>,
+    /// Keyed by the date of the nightly
+    dated_nightlies: BTreeMap<NaiveDate, String>,
+    rustfmt: Option<String>,
+    clippy: Option<String>,
+    miri: Option<String>,
+    kani: Option<String>,
+    evcxr: Option<String>,
+    rust_analyzer: Option<String>,
+}
+
+impl Config {
+    /// Reads the file named by `PLAYGROUND_CONFIG_FILE`, if set, then
+    /// applies any overrides from the environment.
+    pub(crate) fn load() -> Result<Self> {
+        let file = match env::var_os(PLAYGROUND_CONFIG_FILE) {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                let contents = std::fs::read_to_string(&path)
+                    .context(UnableToReadFileSnafu { path: &path })?;
+                let file =
+                    toml::from_str(&contents).context(UnableToParseFileSnafu { path: &path })?;
+                tracing::info!("Loaded configuration from {}", path.display());
+                file
+            }
+            None => File::default(),
+        };
+
+        Ok(Self::new(file, |name| env::var(name).ok()))
+    }
+
+    fn new(file: File, vars: impl Fn(&str) -> Option<String>) -> Self {
+        let vars = Vars(vars);
+
+        let root = if let Some(root) = vars.parsed(PLAYGROUND_UI_ROOT, file.root) {
+            // Ensure it appears as an absolute path in logs to help user orient
+            // themselves about what directory the PLAYGROUND_UI_ROOT
+            // configuration is interpreted relative to.
+            let mut root = root;
+            if !root.is_absolute() {
+                if let Ok(current_dir) = env::current_dir() {
+                    root = current_dir.join(root);
+                }
+            }
+            root
+        } else {
+            // Note this is `env!` (compile time) while the above is
+            // `env::var_os` (run time). We know where the ui is expected to be
+            // relative to the source code that the server was compiled from.
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("frontend")
+                .join("build")
+        };
+
+        let index_html = root.join("index.html");
+        if index_html.exists() {
+            tracing::info!("Serving playground frontend from {}", root.display());
+        } else {
+            tracing::error!(
+                "Playground ui does not exist at {}\n\
+                Playground will not work until `yarn run build` has been run or {PLAYGROUND_UI_ROOT} has been fixed",
+                index_html.display(),
+            );
+        }
+
+        let address = vars
+            .string("PLAYGROUND_UI_ADDRESS", file.address)
+            .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+        let port = vars
+            .parsed("PLAYGROUND_UI_PORT", file.port)
+            .unwrap_or(DEFAULT_PORT);
+        let grpc_port = vars.parsed("PLAYGROUND_GRPC_PORT", file.grpc_port);
+
+        let gh_token = vars.string(PLAYGROUND_GITHUB_TOKEN, None);
+        if gh_token.is_none() {
+            tracing::warn!("Environment variable {} is not set, so reading and writing GitHub gists will not work", PLAYGROUND_GITHUB_TOKEN);
+        }
+
+        let metrics_token = vars.string("PLAYGROUND_METRICS_TOKEN", None);
+        let admin_token = vars.string("PLAYGROUND_ADMIN_TOKEN", None);
+        let share_link_key = vars.string(PLAYGROUND_SHARE_LINK_KEY, None);
+
+        let cors_enabled = vars.flag("PLAYGROUND_CORS_ENABLED", file.cors_enabled);
+
+        let response_cache_size = vars
+            .parsed("PLAYGROUND_RESPONSE_CACHE_SIZE", file.response_cache.size)
+            .unwrap_or(DEFAULT_RESPONSE_CACHE_SIZE);
+        let response_cache_ttl = vars
+            .seconds("PLAYGROUND_RESPONSE_CACHE_TTL", file.response_cache.ttl)
+            .unwrap_or(DEFAULT_RESPONSE_CACHE_TTL);
+
+        let max_code_size = vars
+            .parsed("PLAYGROUND_MAX_CODE_SIZE", file.requests.max_code_size)
+            .unwrap_or(DEFAULT_MAX_CODE_SIZE);
+        let max_total_code_size = vars
+            .parsed(
+                "PLAYGROUND_MAX_TOTAL_CODE_SIZE",
+                file.requests.max_total_code_size,
+            )
+            .unwrap_or(DEFAULT_MAX_TOTAL_CODE_SIZE);
+
+        let concurrency = file.concurrency;
+        let max_running_sandboxes = vars
+            .parsed(
+                "PLAYGROUND_MAX_RUNNING_SANDBOXES",
+                concurrency.max_running_sandboxes,
+            )
+            .unwrap_or(DEFAULT_MAX_RUNNING_SANDBOXES);
+        let max_running_cheap_sandboxes = vars
+            .parsed(
+                "PLAYGROUND_MAX_RUNNING_CHEAP_SANDBOXES",
+                concurrency.max_running_cheap_sandboxes,
+            )
+            .unwrap_or(DEFAULT_MAX_RUNNING_CHEAP_SANDBOXES);
+        let max_waiting_requests = vars
+            .parsed(
+                "PLAYGROUND_MAX_WAITING_REQUESTS",
+                concurrency.max_waiting_requests,
+            )
+            .unwrap_or(DEFAULT_MAX_WAITING_REQUESTS);
+
+        let circuit_breaker_threshold = vars
+            .parsed(
+                "PLAYGROUND_CIRCUIT_BREAKER_THRESHOLD",
+                file.circuit_breaker.threshold,
+            )
+            .unwrap_or(DEFAULT_CIRCUIT_BREAKER_THRESHOLD);
+        let circuit_breaker_cooldown = vars
+            .seconds(
+                "PLAYGROUND_CIRCUIT_BREAKER_COOLDOWN",
+                file.circuit_breaker.cooldown,
+            )
+            .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN);
+
+        let load_shedding = load_shedding::Thresholds {
+            max_load_average: vars.parsed(
+                "PLAYGROUND_MAX_LOAD_AVERAGE",
+                file.load_shedding.max_load_average,
+            ),
+            min_free_memory: vars.parsed(
+                "PLAYGROUND_MIN_FREE_MEMORY",
+                file.load_shedding.min_free_memory,
+            ),
+            min_free_disk: vars
+                .parsed("PLAYGROUND_MIN_FREE_DISK", file.load_shedding.min_free_disk),
+        };
+
+        let rate_limit_cheap = vars.parsed("PLAYGROUND_RATE_LIMIT_CHEAP", file.rate_limit.cheap);
+        let rate_limit_expensive =
+            vars.parsed("PLAYGROUND_RATE_LIMIT_EXPENSIVE", file.rate_limit.expensive);
+        let rate_limit_bisect = vars
+            .parsed("PLAYGROUND_RATE_LIMIT_BISECT", file.rate_limit.bisect)
+            .unwrap_or(DEFAULT_RATE_LIMIT_BISECT);
+        let trust_forwarded_for =
+            vars.flag("PLAYGROUND_TRUST_FORWARDED_FOR", file.trust_forwarded_for);
+        let api_keys = file
+            .api_keys
+            .into_iter()
+            .map(|(name, key)| rate_limit::ApiKey {
+                name: name.into(),
+                sha256: key.sha256,
+                rates: rates(key.cheap, key.expensive),
+                daily_quota: key.daily_quota,
+            })
+            .collect();
+
+        let shutdown_timeout = vars
+            .seconds("PLAYGROUND_SHUTDOWN_TIMEOUT", file.shutdown_timeout)
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+
+        let http_limits = HttpLimits {
+            request_timeout: vars
+                .seconds("PLAYGROUND_REQUEST_TIMEOUT", file.requests.timeout)
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT),
+            header_timeout: vars
+                .seconds("PLAYGROUND_HEADER_TIMEOUT", file.requests.header_timeout)
+                .unwrap_or(DEFAULT_HEADER_TIMEOUT),
+            max_body_size: vars
+                .parsed("PLAYGROUND_MAX_BODY_SIZE", file.requests.max_body_size)
+                .unwrap_or(DEFAULT_MAX_BODY_SIZE),
+            max_header_size: vars
+                .parsed("PLAYGROUND_MAX_HEADER_SIZE", file.requests.max_header_size)
+                .unwrap_or(DEFAULT_MAX_HEADER_SIZE)
+                .max(MIN_MAX_HEADER_SIZE),
+        };
+
+        let snippet_database = vars.parsed(PLAYGROUND_SNIPPET_DATABASE, file.snippets.database);
+        if snippet_database.is_none() {
+            tracing::warn!(
+                "{} is not set, so snippets cannot be stored",
+                PLAYGROUND_SNIPPET_DATABASE
+            );
+        }
+        let snippet_max_size = vars
+            .parsed("PLAYGROUND_SNIPPET_MAX_SIZE", file.snippets.max_size)
+            .unwrap_or(DEFAULT_SNIPPET_MAX_SIZE);
+        let snippet_ttl = vars.seconds("PLAYGROUND_SNIPPET_TTL", file.snippets.ttl);
+
+        let templates_file = vars.parsed("PLAYGROUND_TEMPLATES_FILE", file.templates.file);
+
+        let artifacts = artifacts::Config {
+            max_size: vars
+                .parsed("PLAYGROUND_ARTIFACT_MAX_SIZE", file.artifacts.max_size)
+                .unwrap_or(DEFAULT_ARTIFACT_MAX_SIZE),
+            max_total_size: vars
+                .parsed(
+                    "PLAYGROUND_ARTIFACT_MAX_TOTAL_SIZE",
+                    file.artifacts.max_total_size,
+                )
+                .unwrap_or(DEFAULT_ARTIFACT_MAX_TOTAL_SIZE),
+            time_to_live: vars
+                .seconds("PLAYGROUND_ARTIFACT_TTL", file.artifacts.ttl)
+                .unwrap_or(DEFAULT_ARTIFACT_TTL),
+            spill_dir: vars.parsed("PLAYGROUND_ARTIFACT_SPILL_DIR", file.artifacts.spill_dir),
+            spill_size: vars
+                .parsed("PLAYGROUND_ARTIFACT_SPILL_SIZE", file.artifacts.spill_size)
+                .unwrap_or(DEFAULT_ARTIFACT_SPILL_SIZE),
+        };
+
+        // Each session keeps a container running, so there are none
+        // unless asked for
+        let repl = repl::Config {
+            max_sessions: vars
+                .parsed("PLAYGROUND_REPL_MAX_SESSIONS", file.repl.max_sessions)
+                .unwrap_or(0),
+            idle_timeout: vars
+                .seconds("PLAYGROUND_REPL_IDLE_TIMEOUT", file.repl.idle_timeout)
+                .unwrap_or(DEFAULT_REPL_IDLE_TIMEOUT),
+        };
+
+        let queue_redis_url = vars.string(PLAYGROUND_QUEUE_REDIS_URL, file.queue.redis_url);
+        let queue_timeout = vars
+            .seconds("PLAYGROUND_QUEUE_TIMEOUT", file.queue.timeout)
+            .unwrap_or(DEFAULT_QUEUE_TIMEOUT);
+
+        let coordinator_workers = match vars.string("PLAYGROUND_COORDINATOR_WORKERS", None) {
+            Some(urls) => urls
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(Into::into)
+                .collect(),
+            None => file.coordinator.workers,
+        };
+        let coordinator_timeout = vars
+            .seconds("PLAYGROUND_COORDINATOR_TIMEOUT", file.coordinator.timeout)
+            .unwrap_or(DEFAULT_COORDINATOR_TIMEOUT);
+        if queue_redis_url.is_some() && !coordinator_workers.is_empty() {
+            tracing::warn!("Both a queue and workers are configured; only the queue will be used");
+        }
+        let worker_token = vars.string("PLAYGROUND_WORKER_TOKEN", None);
+
+        let image_update_interval = vars.seconds(
+            "PLAYGROUND_IMAGE_UPDATE_INTERVAL",
+            file.sandbox.images.update_interval,
+        );
+        let sandbox = sandbox_config(file.sandbox, max_code_size, &vars);
+
+        Self {
+            address,
+            admin_token,
+            api_keys,
+            artifacts,
+            circuit_breaker_cooldown,
+            circuit_breaker_threshold,
+            coordinator_timeout,
+            coordinator_workers,
+            cors_enabled,
+            gh_token,
+            grpc_port,
+            http_limits,
+            image_update_interval,
+            load_shedding,
+            max_running_cheap_sandboxes,
+            max_running_sandboxes,
+            max_total_code_size,
+            max_waiting_requests,
+            metrics_token,
+            port,
+            queue_redis_url,
+            queue_timeout,
+            rate_limit_bisect,
+            rate_limit_cheap,
+            rate_limit_expensive,
+            repl,
+            response_cache_size,
+            response_cache_ttl,
+            root,
+            sandbox,
+            share_link_key,
+            shutdown_timeout,
+            snippet_database,
+            snippet_max_size,
+            snippet_ttl,
+            templates_file,
+            trust_forwarded_for,
+            worker_token,
+        }
+    }
+
+    pub(crate) fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    pub(crate) fn asset_path(&self) -> PathBuf {
+        self.root.join("assets")
+    }
+
+    pub(crate) fn use_cors(&self) -> bool {
+        self.cors_enabled
+    }
+
+    pub(crate) fn metrics_token(&self) -> Option<MetricsToken> {
+        self.metrics_token.as_deref().map(MetricsToken::new)
+    }
+
+    pub(crate) fn admin_token(&self) -> Option<AdminToken> {
+        self.admin_token.as_deref().map(AdminToken::new)
+    }
+
+    pub(crate) fn worker_token(&self) -> Option<WorkerToken> {
+        self.worker_token.as_deref().map(WorkerToken::new)
+    }
+
+    pub(crate) fn github_token(&self) -> GhToken {
+        GhToken::new(&self.gh_token)
+    }
+
+    pub(crate) fn snippet_storage(&self) -> storage::Result<SnippetStorage> {
+        let config = storage::Config {
+            max_size: self.snippet_max_size,
+            time_to_live: self.snippet_ttl,
+        };
+        let storage = self
+            .snippet_database
+            .as_deref()
+            .map(|path| storage::Storage::open(path, config))
+            .transpose()?;
+
+        Ok(SnippetStorage(storage))
+    }
+
+    pub(crate) fn share_links(&self) -> ShareLinks {
+        ShareLinks(
+            self.share_link_key
+                .as_deref()
+                .map(share_link::ShareLinks::new),
+        )
+    }
+
+    pub(crate) fn templates_file(&self) -> Option<PathBuf> {
+        self.templates_file.clone()
+    }
+
+    pub(crate) fn artifacts(&self) -> artifacts::Artifacts {
+        artifacts::Artifacts::new(self.artifacts.clone())
+    }
+
+    pub(crate) fn repls(&self) -> repl::Repls {
+        repl::Repls::new(self.repl)
+    }
+
+    pub(crate) fn dispatcher(&self) -> dispatcher::Dispatcher {
+        use crate::{
+            limiter::ConcurrencyLimiter, response_cache::ResponseCache,
+            sandbox::circuit_breaker::CircuitBreaker,
+        };
+
+        let dispatcher = dispatcher::Dispatcher::new(
+            self.sandbox.clone(),
+            ResponseCache::new(self.response_cache_size, self.response_cache_ttl),
+            ConcurrencyLimiter::new(self.max_running_sandboxes, self.max_waiting_requests),
+            ConcurrencyLimiter::new(self.max_running_cheap_sandboxes, self.max_waiting_requests),
+            CircuitBreaker::new(
+                self.circuit_breaker_threshold,
+                self.circuit_breaker_cooldown,
+            ),
+            sandbox::ChannelHealth::new(
+                self.circuit_breaker_threshold,
+                self.circuit_breaker_cooldown,
+            ),
+        );
+
+        if self.load_shedding.is_empty() {
+            dispatcher
+        } else {
+            dispatcher.with_load_shedding(load_shedding::LoadShedding::new(
+                self.load_shedding.clone(),
+                self.sandbox.work_dir(),
+            ))
+        }
+    }
+
+    /// Without a Redis URL, requests are run by the server itself.
+    pub(crate) fn queue(&self) -> queue::Result<Option<queue::Queue>> {
+        self.queue_redis_url
+            .as_deref()
+            .map(|url| queue::Queue::new(url, self.queue_timeout))
+            .transpose()
+    }
+
+    /// What runs the requests instead of this server, if anything.
+    /// The queue is used when workers are also given.
+    pub(crate) fn remote(&self) -> Result<Option<dispatcher::Remote>> {
+        if let Some(queue) = self.queue().context(InvalidQueueSnafu)? {
+            return Ok(Some(dispatcher::Remote::Queue(queue)));
+        }
+
+        if self.coordinator_workers.is_empty() {
+            return Ok(None);
+        }
+        let token = self.worker_token.clone().context(WorkerTokenMissingSnafu)?;
+        let workers = coordinator::WorkerPool::new(
+            self.coordinator_workers.clone(),
+            token,
+            self.coordinator_timeout,
+        );
+
+        Ok(Some(dispatcher::Remote::Workers(Arc::new(workers))))
+    }
+
+    /// Workers run this many jobs at once.
+    pub(crate) fn max_running_sandboxes(&self) -> usize {
+        self.max_running_sandboxes
+    }
+
+    /// The limit for each request is part of the sandbox
+    /// configuration, as sandboxes check it again.
+    pub(crate) fn max_total_code_size(&self) -> usize {
+        self.max_total_code_size
+    }
+
+    pub(crate) fn rate_limiter(&self) -> rate_limit::RateLimiter {
+        let rates = rates(self.rate_limit_cheap, self.rate_limit_expensive);
+
+        rate_limit::RateLimiter::new(rates).with_api_keys(self.api_keys.clone())
+    }
+
+    /// Bisections are kept apart from other operations, as each one
+    /// may occupy a sandbox for a long time. API keys don't raise
+    /// this limit.
+    pub(crate) fn bisect_rate_limiter(&self) -> rate_limit::RateLimiter {
+        let rates = rates(None, Some(self.rate_limit_bisect));
+
+        rate_limit::RateLimiter::new(rates)
+    }
+
+    pub(crate) fn trust_forwarded_for(&self) -> bool {
+        self.trust_forwarded_for
+    }
+
+    /// When unset, the images are only ever those that were there when
+    /// starting.
+    pub(crate) fn image_update_interval(&self) -> Option<Duration> {
+        self.image_update_interval
+    }
+
+    pub(crate) fn shutdown_timeout(&self) -> Duration {
+        self.shutdown_timeout
+    }
+
+    pub(crate) fn http_limits(&self) -> HttpLimits {
+        self.http_limits
+    }
+
+    pub(crate) fn server_socket_addr(&self) -> SocketAddr {
+        let address = self.address.parse().expect("Invalid address");
+        SocketAddr::new(address, self.port)
+    }
+
+    /// When unset, there is no gRPC API.
+    pub(crate) fn grpc_socket_addr(&self) -> Option<SocketAddr> {
+        let address = self.address.parse().expect("Invalid address");
+        self.grpc_port.map(|port| SocketAddr::new(address, port))
+    }
+}
+
+fn rates(
+    cheap: Option<u32>,
+    expensive: Option<u32>,
+) -> HashMap<dispatcher::OperationClass, rate_limit::Rate> {
+    use dispatcher::OperationClass;
+    use rate_limit::Rate;
+
+    vec![
+        (OperationClass::Cheap, cheap),
+        (OperationClass::Expensive, expensive),
+    ]
+    .into_iter()
+    .filter_map(|(class, per_minute)| Some((class, Rate::per_minute(per_minute?))))
+    .collect()
+}
+
+fn sandbox_config<F>(file: SandboxSection, max_code_size: usize, vars: &Vars<F>) -> sandbox::Config
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let defaults = sandbox::Config::default();
+    let images = file.images;
+    let registry = vars.string("PLAYGROUND_SANDBOX_IMAGE_REGISTRY", images.registry);
+
+    let images = sandbox::Images {
+        stable: images.stable.unwrap_or(defaults.images.stable),
+        stable_releases: images.stable_releases,
+        beta: images.beta.unwrap_or(defaults.images.beta),
+        nightly: images.nightly.unwrap_or(defaults.images.nightly),
+        dated_nightlies: images.dated_nightlies,
+        rustfmt: images.rustfmt.unwrap_or(defaults.images.rustfmt),
+        clippy: images.clippy.unwrap_or(defaults.images.clippy),
+        miri: images.miri.unwrap_or(defaults.images.miri),
+        kani: images.kani.unwrap_or(defaults.images.kani),
+        evcxr: images.evcxr.unwrap_or(defaults.images.evcxr),
+        rust_analyzer: images
+            .rust_analyzer
+            .unwrap_or(defaults.images.rust_analyzer),
+    };
+    let images = match &registry {
+        Some(registry) => images.in_registry(registry),
+        None => images,
+    };
+
+    let limits = LimitsSection {
+        memory: vars.string("PLAYGROUND_SANDBOX_MEMORY", file.memory),
+        memory_swap: vars.string("PLAYGROUND_SANDBOX_MEMORY_SWAP", file.memory_swap),
+        pids_limit: vars.parsed("PLAYGROUND_SANDBOX_PIDS_LIMIT", file.pids_limit),
+        disk: vars.string("PLAYGROUND_SANDBOX_DISK", file.disk),
+        timeout: vars.parsed("PLAYGROUND_SANDBOX_TIMEOUT", file.timeout),
+        cpu_time: vars.parsed("PLAYGROUND_SANDBOX_CPU_TIME", file.cpu_time),
+        output_rate: vars.parsed("PLAYGROUND_SANDBOX_OUTPUT_RATE", file.output_rate),
+        device_read_bps: vars.string("PLAYGROUND_SANDBOX_DEVICE_READ_BPS", file.device_read_bps),
+        device_write_bps: vars.string("PLAYGROUND_SANDBOX_DEVICE_WRITE_BPS", file.device_write_bps),
+    }
+    .or(defaults.limits);
+
+    let channel = file.channel;
+    let bisect = file.bisect;
+    let bisect_timeout = bisect
+        .timeout
+        .map_or(DEFAULT_BISECT_TIMEOUT, Duration::from_secs);
+    let bisect_cpu_time = bisect
+        .cpu_time
+        .map_or(DEFAULT_BISECT_CPU_TIME, Duration::from_secs);
+    let bisect_network = bisect
+        .network
+        .unwrap_or_else(|| DEFAULT_BISECT_NETWORK.into());
+    let channel_limits = vec![
+        (sandbox::Channel::Stable { version: None }, channel.stable),
+        (sandbox::Channel::Beta, channel.beta),
+        (sandbox::Channel::Nightly { date: None }, channel.nightly),
+    ]
+    .into_iter()
+    .filter_map(|(channel, section)| Some((channel, section?.or(limits.clone()))))
+    .collect();
+
+    sandbox::Config {
+        sccache_dir: vars.parsed("PLAYGROUND_SCCACHE_DIR", file.sccache_dir),
+        work_dir: vars.parsed("PLAYGROUND_SANDBOX_WORK_DIR", file.work_dir),
+        seccomp_profile: vars.parsed("PLAYGROUND_SANDBOX_SECCOMP_PROFILE", file.seccomp_profile),
+        read_only_root: vars.flag("PLAYGROUND_SANDBOX_READ_ONLY_ROOT", file.read_only_root),
+        copy_files: vars.flag("PLAYGROUND_SANDBOX_COPY_FILES", file.copy_files),
+        images,
+        limits,
+        channel_limits,
+        bisect: bisect.image.map(|image| sandbox::Bisect {
+            image: match &registry {
+                Some(registry) => sandbox::image_in_registry(registry, &image),
+                None => image,
+            },
+            timeout: bisect_timeout,
+            cpu_time: bisect_cpu_time,
+            network: bisect_network,
+        }),
+        max_code_size: Some(max_code_size),
+        report_image_ids: vars.flag("PLAYGROUND_SANDBOX_REPORT_IMAGE_IDS", file.report_image_ids),
+    }
+}
+
+impl LimitsSection {
+    /// Fills in the settings that weren't given from `fallback`.
+    fn or(self, fallback: sandbox::Limits) -> sandbox::Limits {
+        sandbox::Limits {
+            memory: self.memory.unwrap_or(fallback.memory),
+            memory_swap: self.memory_swap.unwrap_or(fallback.memory_swap),
+            pids: self.pids_limit.or(fallback.pids),
+            disk: self.disk.or(fallback.disk),
+            timeout: self
+                .timeout
+                .map(Duration::from_secs)
+                .unwrap_or(fallback.timeout),
+            cpu_time: self.cpu_time.map(Duration::from_secs).or(fallback.cpu_time),
+            output_rate: self.output_rate.or(fallback.output_rate),
+            device_read_bps: self.device_read_bps.or(fallback.device_read_bps),
+            device_write_bps: self.device_write_bps.or(fallback.device_write_bps),
+        }
+    }
+}
+
+/// Looks up environment variables, which take priority over the
+/// configuration file.
+struct Vars<F>(F);
+
+impl<F> Vars<F>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn string(&self, name: &str, file: Option<String>) -> Option<String> {
+        (self.0)(name).or(file)
+    }
+
+    // Values that can't be parsed are ignored, as if they weren't set
+    fn parsed<T: FromStr>(&self, name: &str, file: Option<T>) -> Option<T> {
+        (self.0)(name).and_then(|s| s.parse().ok()).or(file)
+    }
+
+    fn seconds(&self, name: &str, file: Option<u64>) -> Option<Duration> {
+        self.parsed(name, file).map(Duration::from_secs)
+    }
+
+    fn flag(&self, name: &str, file: Option<bool>) -> bool {
+        (self.0)(name).is_some() || file.unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(toml: &str) -> File {
+        toml::from_str(toml).expect("Invalid configuration")
+    }
+
+    #[test]
+    fn settings_are_read_from_the_file() {
+        let file = parse(
+            r#"
+            port = 8080
+            grpc-port = 8081
+            cors-enabled = true
+
+            [concurrency]
+            max-running-sandboxes = 32
+
+            [sandbox]
+            memory = "1g"
+            timeout = 30
+
+            [sandbox.images]
+            nightly = "registry.example.com/rust-nightly"
+            update-interval = 3600
+
+            [sandbox.images.stable-releases]
+            "1.30.0" = "registry.example.com/rust-stable:1.30.0"
+
+            [sandbox.images.dated-nightlies]
+            2022-06-01 = "registry.example.com/rust-nightly:2022-06-01"
+
+            [sandbox.bisect]
+            image = "registry.example.com/cargo-bisect-rustc"
+            network = "toolchains"
+
+            [queue]
+            redis-url = "redis://redis.example.com:6379/0"
+
+            [requests]
+            max-code-size = 100000
+            timeout = 120
+            max-header-size = 1024
+
+            [load-shedding]
+            max-load-average = 24.0
+            min-free-disk = 10737418240
+
+            [artifacts]
+            ttl = 600
+            spill-dir = "/var/lib/playground/artifacts"
+
+            [repl]
+            max-sessions = 4
+
+            [api-keys.ci-bot]
+            sha256 = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+            expensive = 120
+            daily-quota = 5000
+            "#,
+        );
+        let config = Config::new(file, |_| None);
+
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.grpc_port, Some(8081));
+        assert!(config.cors_enabled);
+        assert_eq!(config.max_running_sandboxes, 32);
+        assert_eq!(
+            config.max_running_cheap_sandboxes,
+            DEFAULT_MAX_RUNNING_CHEAP_SANDBOXES
+        );
+        assert_eq!(config.sandbox.max_code_size, Some(100000));
+        assert_eq!(config.max_total_code_size, DEFAULT_MAX_TOTAL_CODE_SIZE);
+        assert_eq!(
+            config.http_limits(),
+            HttpLimits {
+                request_timeout: Duration::from_secs(120),
+                header_timeout: DEFAULT_HEADER_TIMEOUT,
+                max_body_size: DEFAULT_MAX_BODY_SIZE,
+                max_header_size: MIN_MAX_HEADER_SIZE,
+            }
+        );
+        assert_eq!(config.sandbox.limits.memory, "1g");
+        assert_eq!(config.sandbox.limits.timeout, Duration::from_secs(30));
+        assert_eq!(
+            config.sandbox.images.nightly,
+            "registry.example.com/rust-nightly"
+        );
+        assert_eq!(config.sandbox.images.stable, "rust-stable");
+        assert_eq!(
+            config.image_update_interval(),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            config.sandbox.images.stable_releases[&"1.30.0".parse().unwrap()],
+            "registry.example.com/rust-stable:1.30.0"
+        );
+        assert_eq!(
+            config.sandbox.images.dated_nightlies[&NaiveDate::from_ymd(2022, 6, 1)],
+            "registry.example.com/rust-nightly:2022-06-01"
+        );
+        let bisect = config.sandbox.bisect.as_ref().unwrap();
+        assert_eq!(bisect.image, "registry.example.com/cargo-bisect-rustc");
+        assert_eq!(bisect.timeout, DEFAULT_BISECT_TIMEOUT);
+        assert_eq!(bisect.cpu_time, DEFAULT_BISECT_CPU_TIME);
+        assert_eq!(bisect.network, "toolchains");
+        assert_eq!(config.rate_limit_bisect, DEFAULT_RATE_LIMIT_BISECT);
+        assert!(config.queue().unwrap().is_some());
+        assert_eq!(config.queue_timeout, DEFAULT_QUEUE_TIMEOUT);
+        assert_eq!(
+            config.load_shedding,
+            load_shedding::Thresholds {
+                max_load_average: Some(24.0),
+                min_free_memory: None,
+                min_free_disk: Some(10 * 1024 * 1024 * 1024),
+            }
+        );
+        assert_eq!(config.artifacts.time_to_live, Duration::from_secs(600));
+        assert_eq!(config.artifacts.max_size, DEFAULT_ARTIFACT_MAX_SIZE);
+        assert_eq!(
+            config.artifacts.spill_dir.as_deref(),
+            Some(Path::new("/var/lib/playground/artifacts"))
+        );
+        assert_eq!(config.artifacts.spill_size, DEFAULT_ARTIFACT_SPILL_SIZE);
+        assert_eq!(config.repl.max_sessions, 4);
+        assert_eq!(config.repl.idle_timeout, DEFAULT_REPL_IDLE_TIMEOUT);
+        assert_eq!(config.api_keys.len(), 1);
+        assert_eq!(&*config.api_keys[0].name, "ci-bot");
+        assert_eq!(
+            config.api_keys[0].rates[&dispatcher::OperationClass::Expensive],
+            rate_limit::Rate::per_minute(120)
+        );
+        assert_eq!(config.api_keys[0].daily_quota, Some(5000));
+    }
+
+    #[test]
+    fn a_registry_is_used_for_every_image() {
+        let file = parse(
+            r#"
+            [sandbox.images]
+            registry = "registry.example.com/playground"
+            nightly = "rust-nightly@sha256:0123abcd"
+
+            [sandbox.images.dated-nightlies]
+            2022-06-01 = "ghcr.io/example/rust-nightly:2022-06-01"
+
+            [sandbox.bisect]
+            image = "cargo-bisect-rustc"
+            "#,
+        );
+        let config = Config::new(file, |_| None);
+        let sandbox = config.sandbox;
+
+        assert_eq!(
+            sandbox.images.stable,
+            "registry.example.com/playground/rust-stable"
+        );
+        assert_eq!(
+            sandbox.images.nightly,
+            "registry.example.com/playground/rust-nightly@sha256:0123abcd"
+        );
+        assert_eq!(
+            sandbox.images.dated_nightlies[&NaiveDate::from_ymd(2022, 6, 1)],
+            "ghcr.io/example/rust-nightly:2022-06-01"
+        );
+        assert_eq!(
+            sandbox.bisect.unwrap().image,
+            "registry.example.com/playground/cargo-bisect-rustc"
+        );
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file() {
+        let file = parse(
+            r#"
+            port = 8080
+
+            [response-cache]
+            size = 10
+            "#,
+        );
+        let config = Config::new(file, |name| match name {
+            "PLAYGROUND_UI_PORT" => Some("9090".into()),
+            "PLAYGROUND_RESPONSE_CACHE_SIZE" => Some("not a number".into()),
+            _ => None,
+        });
+
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.response_cache_size, 10);
+    }
+
+    #[test]
+    fn channels_may_have_their_own_limits() {
+        let file = parse(
+            r#"
+            [sandbox]
+            memory = "1g"
+            timeout = 30
+            device-write-bps = "/dev/sda:10mb"
+
+            [sandbox.channel.nightly]
+            timeout = 60
+            "#,
+        );
+        let config = Config::new(file, |name| match name {
+            "PLAYGROUND_SANDBOX_MEMORY" => Some("2g".into()),
+            _ => None,
+        });
+        let sandbox = config.sandbox;
+
+        assert_eq!(sandbox.limits.memory, "2g");
+        assert_eq!(sandbox.limits.timeout, Duration::from_secs(30));
+
+        let nightly = &sandbox.channel_limits[&sandbox::Channel::Nightly { date: None }];
+        assert_eq!(nightly.memory, "2g");
+        assert_eq!(nightly.timeout, Duration::from_secs(60));
+        assert_eq!(nightly.device_write_bps.as_deref(), Some("/dev/sda:10mb"));
+        assert_eq!(nightly.device_read_bps, None);
+
+        assert!(!sandbox
+            .channel_limits
+            .contains_key(&sandbox::Channel::Stable { version: None }));
+    }
+
+    #[test]
+    fn workers_need_a_token() {
+        let file = parse(
+            r#"
+            [coordinator]
+            workers = ["http://exec-1.example.com:5000"]
+            "#,
+        );
+        let config = Config::new(file, |name| match name {
+            "PLAYGROUND_COORDINATOR_WORKERS" => Some("http://a:5000, http://b:5000,".into()),
+            _ => None,
+        });
+
+        assert_eq!(
+            config.coordinator_workers,
+            ["http://a:5000", "http://b:5000"]
+        );
+        assert!(matches!(config.remote(), Err(Error::WorkerTokenMissing)));
+
+        let config = Config {
+            worker_token: Some("secret".into()),
+            ..config
+        };
+        assert!(matches!(
+            config.remote(),
+            Ok(Some(dispatcher::Remote::Workers(_)))
+        ));
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let file = toml::from_str::<File>("[sandbox]\nmemroy = \"1g\"\n");
+        assert!(file.is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use hyper::{client::HttpConnector, header, Body, Client, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::sandbox::job::{JobOutcome, JobRequest};
+
+pub(crate) const RUN_PATH: &str = "/worker/run";
+pub(crate) const LOAD_PATH: &str = "/worker/load";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Playground servers that requests are sent to instead of being run
+/// here, each chosen by how busy it is.
+#[derive(Debug)]
+pub(crate) struct WorkerPool {
+    client: Client<HttpConnector>,
+    token: String,
+    timeout: Duration,
+    workers: Vec<Worker>,
+}
+
+#[derive(Debug)]
+struct Worker {
+    url: String,
+    state: Mutex<WorkerState>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct WorkerState {
+    /// Until it has answered a check, a worker is not sent anything.
+    healthy: bool,
+    /// As the worker last reported it, including requests from other
+    /// coordinators.
+    running: usize,
+    capacity: usize,
+    /// Requests from here that haven't been answered yet.
+    dispatched: usize,
+    checked_at: Option<SystemTime>,
+    error: Option<String>,
+}
+
+/// How busy a worker is, as reported at [`LOAD_PATH`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WorkerLoad {
+    pub(crate) running: usize,
+    pub(crate) capacity: usize,
+    /// A draining worker is about to go away.
+    pub(crate) draining: bool,
+}
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("No workers are available"))]
+    NoWorkers,
+    #[snafu(display("Unable to build the request to {}: {}", url, source))]
+    UnableToBuildRequest {
+        url: String,
+        source: hyper::http::Error,
+    },
+    #[snafu(display("Unable to reach the worker at {}: {}", url, source))]
+    UnableToReachWorker { url: String, source: hyper::Error },
+    #[snafu(display("The worker at {} responded with {}", url, status))]
+    UnexpectedStatus { url: String, status: StatusCode },
+    #[snafu(display("Unable to serialize the job: {}", source))]
+    UnableToSerialize { source: serde_json::Error },
+    #[snafu(display("Unable to deserialize the response from {}: {}", url, source))]
+    UnableToDeserialize {
+        url: String,
+        source: serde_json::Error,
+    },
+    #[snafu(display("The worker at {} did not respond within {} seconds", url, timeout.as_secs()))]
+    TimedOut { url: String, timeout: Duration },
+}
+
+pub(crate) type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+impl WorkerPool {
+    /// `urls` are the base URLs of the workers, which must accept
+    /// `token`. Requests that no worker has finished within `timeout`
+    /// are given up on.
+    pub(crate) fn new(urls: Vec<String>, token: String, timeout: Duration) -> Self {
+        let workers = urls
+            .into_iter()
+            .map(|url| Worker {
+                url: url.trim_end_matches('/').into(),
+                state: Default::default(),
+            })
+            .collect();
+
+        Self {
+            client: Client::new(),
+            token,
+            timeout,
+            workers,
+        }
+    }
+
+    /// Sends the job to the least busy worker. Workers that can't be
+    /// reached are skipped until their next check, and the job is
+    /// sent to another one instead.
+    pub(crate) async fn run(&self, request_id: &str, job: JobRequest) -> Result<JobOutcome> {
+        let body = serde_json::to_vec(&job).context(UnableToSerializeSnafu)?;
+
+        loop {
+            let states: Vec<_> = self.workers.iter().map(Worker::state).collect();
+            let worker = least_loaded(&states)
+                .map(|i| &self.workers[i])
+                .context(NoWorkersSnafu)?;
+
+            let outcome = {
+                let _dispatched = Dispatched::new(worker);
+                time::timeout(self.timeout, self.send(worker, request_id, &body)).await
+            };
+
+            match outcome {
+                Ok(Err(Error::UnableToReachWorker { url, source })) if source.is_connect() => {
+                    tracing::warn!(%url, error = %source, "Skipping a worker that can't be reached");
+                    worker.update(|s| {
+                        s.healthy = false;
+                        s.error = Some(source.to_string());
+                    });
+                }
+                Ok(outcome) => return outcome,
+                Err(_) => {
+                    return TimedOutSnafu {
+                        url: &worker.url,
+                        timeout: self.timeout,
+                    }
+                    .fail()
+                }
+            }
+        }
+    }
+
+    async fn send(&self, worker: &Worker, request_id: &str, body: &[u8]) -> Result<JobOutcome> {
+        let request = Request::post(format!("{}{}", worker.url, RUN_PATH))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-request-id", request_id)
+            .body(Body::from(body.to_vec()));
+
+        self.request(worker, request).await
+    }
+
+    /// Asks each worker how busy it is every few seconds, forever.
+    pub(crate) async fn check_periodically(self: Arc<Self>) {
+        let mut interval = time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            futures::future::join_all(self.workers.iter().map(|w| self.check(w))).await;
+        }
+    }
+
+    async fn check(&self, worker: &Worker) {
+        let request = Request::get(format!("{}{}", worker.url, LOAD_PATH)).body(Body::empty());
+        let load = time::timeout(CHECK_TIMEOUT, self.request::<WorkerLoad>(worker, request))
+            .await
+            .unwrap_or_else(|_| {
+                TimedOutSnafu {
+                    url: &worker.url,
+                    timeout: CHECK_TIMEOUT,
+                }
+                .fail()
+            });
+
+        let was_healthy = worker.state().healthy;
+        worker.update(|s| {
+            s.checked_at = Some(SystemTime::now());
+            match load {
+                Ok(load) => {
+                    s.healthy = !load.draining;
+                    s.running = load.running;
+                    s.capacity = load.capacity;
+                    s.error = None;
+                }
+                Err(error) => {
+                    s.healthy = false;
+                    s.error = Some(error.to_string());
+                }
+            }
+        });
+
+        let state = worker.state();
+        match (was_healthy, state.healthy) {
+            (false, true) => tracing::info!(url = %worker.url, "Worker is available"),
+            (true, false) => {
+                tracing::warn!(url = %worker.url, error = ?state.error, "Worker is unavailable")
+            }
+            _ => {}
+        }
+    }
+
+    async fn request<T>(
+        &self,
+        worker: &Worker,
+        request: hyper::http::Result<Request<Body>>,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let url = &worker.url;
+
+        let mut request = request.context(UnableToBuildRequestSnafu { url })?;
+        let authorization = format!("Bearer {}", self.token);
+        let authorization = authorization
+            .parse()
+            .map_err(hyper::http::Error::from)
+            .context(UnableToBuildRequestSnafu { url })?;
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, authorization);
+
+        let response = self
+            .client
+            .request(request)
+            .await
+            .context(UnableToReachWorkerSnafu { url })?;
+        let status = response.status();
+        ensure!(status.is_success(), UnexpectedStatusSnafu { url, status });
+
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .context(UnableToReachWorkerSnafu { url })?;
+        serde_json::from_slice(&body).context(UnableToDeserializeSnafu { url })
+    }
+}
+
+impl Worker {
+    fn state(&self) -> WorkerState {
+        self.lock().clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut WorkerState)) {
+        f(&mut self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WorkerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Counts a request as dispatched to a worker until it is dropped,
+/// including when the request is given up on before it is answered.
+struct Dispatched<'a>(&'a Worker);
+
+impl<'a> Dispatched<'a> {
+    fn new(worker: &'a Worker) -> Self {
+        worker.update(|s| s.dispatched += 1);
+        Self(worker)
+    }
+}
+
+impl Drop for Dispatched<'_> {
+    fn drop(&mut self) {
+        self.0.update(|s| s.dispatched -= 1);
+    }
+}
+
+/// The worker's own count is more complete but out of date, while
+/// ours is current but only knows about our own requests, so the
+/// larger of the two is used.
+fn least_loaded(states: &[WorkerState]) -> Option<usize> {
+    let load = |s: &WorkerState| s.running.max(s.dispatched) as f64 / s.capacity.max(1) as f64;
+
+    states
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.healthy)
+        .min_by(|(_, a), (_, b)| load(a).total_cmp(&load(b)))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn worker(healthy: bool, running: usize, capacity: usize, dispatched: usize) -> WorkerState {
+        WorkerState {
+            healthy,
+            running,
+            capacity,
+            dispatched,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_least_loaded_healthy_worker_is_chosen() {
+        let states = [
+            worker(true, 8, 16, 0),
+            worker(false, 0, 16, 0),
+            worker(true, 2, 4, 0),
+            worker(true, 4, 16, 0),
+        ];
+        assert_eq!(least_loaded(&states), Some(3));
+    }
+
+    #[test]
+    fn requests_that_have_not_been_reported_yet_count() {
+        let states = [worker(true, 1, 16, 12), worker(true, 6, 16, 0)];
+        assert_eq!(least_loaded(&states), Some(1));
+
+        let states = [worker(false, 0, 16, 0)];
+        assert_eq!(least_loaded(&states), None);
+    }
+
+    #[tokio::test]
+    async fn requests_that_are_given_up_on_stop_counting() {
+        // Accepts requests but never answers them
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let pool = WorkerPool::new(vec![url], "token".into(), Duration::from_secs(60));
+        pool.workers[0].update(|s| *s = worker(true, 0, 1, 0));
+
+        let job = JobRequest::Format(crate::sandbox::FormatRequest {
+            code: "fn main() {}".into(),
+            edition: None,
+            lines: None,
+        });
+        let mut run = Box::pin(pool.run("request", job));
+        let sent = time::timeout(Duration::from_millis(200), &mut run).await;
+        assert!(sent.is_err(), "The worker answered");
+        assert_eq!(pool.workers[0].state().dispatched, 1);
+
+        drop(run);
+        assert_eq!(pool.workers[0].state().dispatched, 0);
+    }
+}
@@ -0,0 +1,65 @@
+use similar::TextDiff;
+
+// Cargo reports its progress on stderr along with the diagnostics,
+// including how long the build took
+const CARGO_PROGRESS: [&str; 3] = ["Compiling ", "Finished ", "Running "];
+
+/// A unified diff of the lines of the two texts, which is empty when
+/// they are the same.
+pub(crate) fn unified(old_name: &str, old: &str, new_name: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(old_name, new_name)
+        .to_string()
+}
+
+/// Only the compiler's diagnostics, so that output from different
+/// builds can be compared.
+pub(crate) fn diagnostics(stderr: &str) -> String {
+    stderr
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !CARGO_PROGRESS.iter().any(|p| line.starts_with(p))
+        })
+        .flat_map(|line| [line, "\n"])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identical_texts_have_no_diff() {
+        assert_eq!(unified("a", "same\n", "b", "same\n"), "");
+    }
+
+    #[test]
+    fn changed_lines_are_marked() {
+        let diff = unified("stable", "one\ntwo\n", "nightly", "one\nthree\n");
+
+        assert!(diff.starts_with("--- stable\n+++ nightly\n"));
+        assert!(diff.contains("-two\n"));
+        assert!(diff.contains("+three\n"));
+    }
+
+    #[test]
+    fn cargo_progress_is_not_a_diagnostic() {
+        let stderr = concat!(
+            "   Compiling playground v0.0.1 (/playground)\n",
+            "error[E0425]: cannot find value `x` in this scope\n",
+            "    Finished dev [unoptimized + debuginfo] target(s) in 0.52s\n",
+            "     Running `target/debug/playground`\n",
+        );
+
+        assert_eq!(
+            diagnostics(stderr),
+            "error[E0425]: cannot find value `x` in this scope\n"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::{
+    coordinator::WorkerPool,
+    image_updates::{self, ImageUpdates, Outcome},
+    in_flight::InFlight,
+    limiter::{ConcurrencyLimiter, Ticket},
+    load_shedding::{LoadShedding, Pressure},
+    queue::Queue,
+    response_cache::ResponseCache,
+    sandbox::{
+        self,
+        circuit_breaker::CircuitBreaker,
+        job::{JobRequest, JobResponse},
+        Sandbox,
+    },
+};
+
+/// Shared state for running requests in sandboxes.
+pub(crate) struct Dispatcher {
+    /// Replaced as a whole when an image is updated, so that each
+    /// sandbox sees a consistent set of images.
+    sandbox_config: RwLock<Arc<sandbox::Config>>,
+    /// The images as they were configured, which updates are pulled
+    /// for.
+    configured_images: sandbox::Images,
+    pub(crate) image_updates: ImageUpdates,
+    pub(crate) responses: ResponseCache,
+    pub(crate) in_flight: InFlight,
+    expensive: ConcurrencyLimiter,
+    cheap: ConcurrencyLimiter,
+    pub(crate) circuit_breaker: CircuitBreaker,
+    channel_health: Arc<sandbox::ChannelHealth>,
+    remote: Option<Remote>,
+    load_shedding: Option<LoadShedding>,
+    draining: AtomicBool,
+    warming_up: AtomicBool,
+}
+
+/// Where a request is run.
+pub(crate) enum Runner {
+    Local(Sandbox),
+    Remote(RemoteSandbox),
+}
+
+/// What runs the requests when they aren't run here.
+#[derive(Debug, Clone)]
+pub(crate) enum Remote {
+    /// Workers take the requests from a queue when they have room.
+    Queue(Queue),
+    /// Each request is sent to the least busy server.
+    Workers(Arc<WorkerPool>),
+}
+
+/// Runs requests somewhere else, the same as a [`Sandbox`] would have
+/// run them here.
+pub(crate) struct RemoteSandbox {
+    remote: Remote,
+    request_id: String,
+}
+
+/// The kinds of operations that are scheduled and rate limited
+/// separately.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum OperationClass {
+    /// Operations that build or run the code, such as compiling,
+    /// executing, or interpreting it.
+    Expensive,
+    /// Operations that only need to look at the code, such as
+    /// formatting or linting.
+    Cheap,
+}
+
+impl Dispatcher {
+    pub(crate) fn new(
+        sandbox_config: sandbox::Config,
+        responses: ResponseCache,
+        expensive: ConcurrencyLimiter,
+        cheap: ConcurrencyLimiter,
+        circuit_breaker: CircuitBreaker,
+        channel_health: sandbox::ChannelHealth,
+    ) -> Self {
+        Self {
+            configured_images: sandbox_config.images.clone(),
+            sandbox_config: RwLock::new(Arc::new(sandbox_config)),
+            image_updates: Default::default(),
+            responses,
+            in_flight: Default::default(),
+            expensive,
+            cheap,
+            circuit_breaker,
+            channel_health: Arc::new(channel_health),
+            remote: None,
+            load_shedding: None,
+            draining: AtomicBool::new(false),
+            warming_up: AtomicBool::new(false),
+        }
+    }
+
+    /// Hands requests to workers instead of running them here.
+    pub(crate) fn with_remote(mut self, remote: Remote) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Turns away expensive operations while the host is short of
+    /// resources.
+    pub(crate) fn with_load_shedding(mut self, load_shedding: LoadShedding) -> Self {
+        self.load_shedding = Some(load_shedding);
+        self
+    }
+
+    /// Checks that sandboxes could be run, then starts keeping them
+    /// ready to run.
+    pub(crate) async fn start(self: &Arc<Self>, image_update_interval: Option<Duration>) {
+        for problem in self.self_check().await {
+            tracing::warn!(%problem, "Sandboxes will not work");
+        }
+        tokio::spawn(self.clone().warm_up());
+        tokio::spawn(self.sweep_scratch_periodically());
+        if let Some(period) = image_update_interval {
+            tokio::spawn(self.clone().update_images_periodically(period));
+        }
+        if let Some(load_shedding) = &self.load_shedding {
+            tokio::spawn(load_shedding.clone().monitor());
+        }
+    }
+
+    /// Cheap operations have sandboxes set aside for them so that
+    /// they are not stuck behind expensive ones.
+    pub(crate) fn limiter(&self, class: OperationClass) -> &ConcurrencyLimiter {
+        match class {
+            OperationClass::Expensive => &self.expensive,
+            OperationClass::Cheap => &self.cheap,
+        }
+    }
+
+    /// Stops starting new sandboxes and waits for the running ones
+    /// to finish.
+    pub(crate) async fn drain(&self) {
+        self.set_draining(true);
+        futures::join!(self.expensive.drain(), self.cheap.drain());
+    }
+
+    /// While draining, new requests should be turned away but those
+    /// that have already been accepted are allowed to finish.
+    pub(crate) fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// What the host is short of, if it is close enough to falling
+    /// over that requests should be shed.
+    pub(crate) fn pressure(&self) -> Option<Pressure> {
+        self.load_shedding.as_ref()?.pressure()
+    }
+
+    /// Runs a program for each channel, so that the first requests
+    /// aren't the ones to wait for images to be loaded and caches to
+    /// be filled. Newer images are warmed up by their smoke test.
+    pub(crate) async fn warm_up(self: Arc<Self>) {
+        self.warming_up.store(true, Ordering::SeqCst);
+
+        for channel in image_updates::CHANNELS {
+            let start = std::time::Instant::now();
+            match sandbox::warm_up(self.sandbox_config(), channel).await {
+                Ok(()) => tracing::info!(?channel, elapsed = ?start.elapsed(), "Warmed up"),
+                Err(error) => tracing::warn!(?channel, %error, "Unable to warm up"),
+            }
+        }
+
+        self.warming_up.store(false, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_warming_up(&self) -> bool {
+        self.warming_up.load(Ordering::SeqCst)
+    }
+
+    /// Cleans up after sandboxes that were not able to do so
+    /// themselves, forever.
+    pub(crate) fn sweep_scratch_periodically(&self) -> impl std::future::Future<Output = ()> {
+        sandbox::sweep_scratch_periodically(self.sandbox_config())
+    }
+
+    /// Pulls newer images for each channel every `period`, forever,
+    /// switching to them once they have passed a smoke test. Running
+    /// sandboxes keep the image that they were started from.
+    pub(crate) async fn update_images_periodically(self: Arc<Self>, period: Duration) {
+        let mut interval = time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            for channel in image_updates::CHANNELS {
+                let outcome = self.update_image(channel).await.unwrap_or_else(|error| {
+                    tracing::warn!(?channel, %error, "Unable to update the image");
+                    Outcome::Failed(error.to_string())
+                });
+                if let Outcome::Updated(image) = &outcome {
+                    tracing::info!(?channel, %image, "Switched to a newer image");
+                }
+                self.image_updates.record(channel, outcome);
+            }
+        }
+    }
+
+    async fn update_image(&self, channel: sandbox::Channel) -> sandbox::Result<Outcome> {
+        let name = self.configured_images.channel(channel);
+
+        // Pulling moves the name to the newer image, which mustn't be
+        // used before it has been checked
+        if self.image_updates.status(channel).active.is_none() {
+            match sandbox::image_id(name).await {
+                Ok(id) => {
+                    // The same image that the name was already used for
+                    self.set_image(channel, &id);
+                    self.image_updates.record(channel, Outcome::Pinned(id));
+                }
+                // Nothing could have been started from it yet
+                Err(sandbox::Error::ImageMissing { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        sandbox::pull_image(name).await?;
+        let id = sandbox::image_id(name).await?;
+
+        let config = self.sandbox_config();
+        if config.images.channel(channel) == id {
+            return Ok(Outcome::Unchanged);
+        }
+
+        sandbox::smoke_test(&config, channel, &id).await?;
+        self.switch_image(channel, &id);
+
+        Ok(Outcome::Updated(id))
+    }
+
+    fn switch_image(&self, channel: sandbox::Channel, image: &str) {
+        self.set_image(channel, image);
+
+        // They came from the image that was just replaced, which the
+        // requests won't be run in again
+        self.responses.clear();
+    }
+
+    fn set_image(&self, channel: sandbox::Channel, image: &str) {
+        let mut current = self
+            .sandbox_config
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+
+        let mut config = sandbox::Config::clone(&current);
+        config.images.set_channel(channel, image.into());
+        *current = Arc::new(config);
+    }
+
+    /// Requests with more code than this are refused.
+    pub(crate) fn max_code_size(&self) -> Option<usize> {
+        self.sandbox_config().max_code_size
+    }
+
+    /// Checks that sandboxes could be run with this configuration.
+    pub(crate) async fn self_check(&self) -> Vec<sandbox::Error> {
+        sandbox::self_check(&self.sandbox_config()).await
+    }
+
+    /// When there are workers, the limits on running sandboxes are up
+    /// to them.
+    pub(crate) fn remote_sandbox(&self, request_id: &str) -> Option<RemoteSandbox> {
+        Some(RemoteSandbox {
+            remote: self.remote.clone()?,
+            request_id: request_id.into(),
+        })
+    }
+
+    pub(crate) async fn sandbox(&self, request_id: &str) -> sandbox::Result<Sandbox> {
+        let sandbox = Sandbox::with_config(self.sandbox_config()).await?;
+        Ok(sandbox
+            .with_request_id(request_id)
+            .with_channel_health(self.channel_health.clone()))
+    }
+
+    fn sandbox_config(&self) -> Arc<sandbox::Config> {
+        self.sandbox_config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl RemoteSandbox {
+    pub(crate) async fn run<Req, Resp>(&self, request: &Req) -> sandbox::Result<Resp>
+    where
+        Req: Clone + Into<JobRequest>,
+        Resp: TryFrom<JobResponse>,
+    {
+        let job = request.clone().into();
+        let outcome = match &self.remote {
+            Remote::Queue(queue) => queue
+                .run(&self.request_id, job)
+                .await
+                .map_err(|e| e.to_string()),
+            Remote::Workers(workers) => workers
+                .run(&self.request_id, job)
+                .await
+                .map_err(|e| e.to_string()),
+        };
+
+        outcome
+            .map_err(|message| sandbox::UnableToReachWorkersSnafu { message }.build())?
+            .into_response()
+    }
+}
+
+/// How far a request has made it through the dispatcher, so that
+/// whoever is waiting for it can be kept informed.
+#[derive(Debug, Default)]
+pub(crate) struct Progress(Mutex<Stage>);
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// The request may be waiting for an identical request to finish.
+    #[default]
+    Pending,
+    /// The request is waiting for a sandbox.
+    Queued(OperationClass, Ticket),
+    /// The request has a sandbox, which says how far it has got once
+    /// it has started a container.
+    Running(Option<sandbox::Phase>),
+}
+
+impl Progress {
+    pub(crate) fn stage(&self) -> Stage {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn advance(&self, stage: Stage) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = stage;
+    }
+}
@@ -1,4 +1,7 @@
 pub use std::env::*;
 
 pub const PLAYGROUND_GITHUB_TOKEN: &str = "PLAYGROUND_GITHUB_TOKEN";
+pub const PLAYGROUND_SHARE_LINK_KEY: &str = "PLAYGROUND_SHARE_LINK_KEY";
+pub const PLAYGROUND_QUEUE_REDIS_URL: &str = "PLAYGROUND_QUEUE_REDIS_URL";
+pub const PLAYGROUND_SNIPPET_DATABASE: &str = "PLAYGROUND_SNIPPET_DATABASE";
 pub const PLAYGROUND_UI_ROOT: &str = "PLAYGROUND_UI_ROOT";
@@ -0,0 +1,543 @@
+//! The same operations as the JSON API, served over gRPC for other
+//! services. Requests go through the same dispatcher, so they share
+//! the sandbox limits and the response cache with the JSON API, and
+//! they are rate limited in the same way, by API key or by address.
+
+// Every method of the service returns a `Status`, however large it is
+#![allow(clippy::result_large_err)]
+
+use crate::{
+    config::HttpLimits,
+    dispatcher::{Dispatcher, OperationClass},
+    idempotency::{Claim, IdempotencyKeys},
+    rate_limit::{Client, RateLimiter},
+    sandbox,
+    server_axum::{
+        admit, is_valid_idempotency_key, with_sandbox, IdempotencyScope, Tracking, IDEMPOTENCY_KEY,
+        IDEMPOTENT_REPLAYED, MAX_IDEMPOTENCY_KEY_LENGTH,
+    },
+    ClippyRequest, ClippyResponse, CompilationSnafu, CompileRequest, CompileResponse, Error,
+    ExecuteRequest, ExecuteResponse, ExecutionSnafu, FormatRequest, FormatResponse,
+    FormattingSnafu, LintingSnafu, Result as HttpResult, WithMetadata,
+};
+use axum::{http::StatusCode, response::IntoResponse};
+use futures::FutureExt;
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{future::Future, net::SocketAddr, sync::Arc};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    Request, Response, Status,
+};
+
+mod proto {
+    tonic::include_proto!("playground");
+}
+
+use proto::{
+    execute_event::Event,
+    playground_server::{Playground, PlaygroundServer},
+};
+
+// Enough for the container to keep writing while a slow client
+// catches up
+const LIVE_OUTPUT_BUFFER: usize = 64;
+
+pub(crate) async fn serve(
+    dispatcher: Arc<Dispatcher>,
+    rate_limiter: Arc<RateLimiter>,
+    limits: HttpLimits,
+    addr: SocketAddr,
+) {
+    let service = PlaygroundServer::new(Service {
+        dispatcher,
+        rate_limiter,
+        idempotency_keys: Default::default(),
+    })
+    .max_decoding_message_size(limits.max_body_size);
+
+    let served = tonic::transport::Server::builder()
+        .timeout(limits.request_timeout)
+        .add_service(service)
+        .serve(addr)
+        .await;
+
+    if let Err(error) = served {
+        tracing::error!(%error, "Unable to serve the gRPC API");
+    }
+}
+
+struct Service {
+    dispatcher: Arc<Dispatcher>,
+    rate_limiter: Arc<RateLimiter>,
+    // Kept as the encoded response message
+    idempotency_keys: IdempotencyKeys<IdempotencyScope, Vec<u8>>,
+}
+
+impl Service {
+    /// Rate limits the request, with the API key given as
+    /// `authorization: Bearer <key>` metadata, then runs it. Retries
+    /// with the same `idempotency-key` metadata get the first response.
+    async fn unary<M, R, F, Fut>(
+        &self,
+        method: &str,
+        request: Request<M>,
+        class: OperationClass,
+        run: F,
+    ) -> Result<Response<R>, Status>
+    where
+        M: Message,
+        R: Message + Default,
+        F: FnOnce(M) -> Fut,
+        Fut: Future<Output = Result<R, Status>>,
+    {
+        let client = self.admit(&request, class)?;
+        let metadata = request.metadata();
+        let key = match metadata.get(IDEMPOTENCY_KEY) {
+            Some(key) => key,
+            None => return run(request.into_inner()).await.map(Response::new),
+        };
+        let key = match key.to_str() {
+            Ok(key) if is_valid_idempotency_key(key) => key.to_owned(),
+            _ => {
+                return Err(status(Error::InvalidIdempotencyKey {
+                    max: MAX_IDEMPOTENCY_KEY_LENGTH,
+                }))
+            }
+        };
+
+        let message = request.into_inner();
+        let fingerprint = Sha256::new()
+            .chain_update(method)
+            .chain_update(message.encode_to_vec())
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        let pending = match self.idempotency_keys.claim((client, key), fingerprint) {
+            Claim::First(pending) => pending,
+            Claim::Running => return Err(status(Error::IdempotencyKeyInUse)),
+            Claim::Mismatched => return Err(status(Error::IdempotencyKeyReused)),
+            Claim::Finished(response) => {
+                let response =
+                    R::decode(&*response).map_err(|e| Status::internal(e.to_string()))?;
+                let mut response = Response::new(response);
+                response
+                    .metadata_mut()
+                    .insert(IDEMPOTENT_REPLAYED, MetadataValue::from_static("true"));
+                return Ok(response);
+            }
+        };
+
+        // Failures aren't kept, so that the request may be retried
+        let response = run(message).await?;
+        pending.finish(response.encode_to_vec());
+        Ok(Response::new(response))
+    }
+
+    fn admit<M>(
+        &self,
+        request: &Request<M>,
+        class: OperationClass,
+    ) -> Result<Option<Client>, Status> {
+        let client = request.remote_addr().map(|addr| Client::Address(addr.ip()));
+        admit(
+            &self.rate_limiter,
+            api_key(request.metadata()),
+            client,
+            class,
+        )
+        .map_err(status)
+    }
+}
+
+fn api_key(metadata: &MetadataMap) -> Option<&str> {
+    metadata
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+#[tonic::async_trait]
+impl Playground for Service {
+    async fn compile(
+        &self,
+        request: Request<proto::CompileRequest>,
+    ) -> Result<Response<proto::CompileResponse>, Status> {
+        self.unary(
+            "compile",
+            request,
+            OperationClass::Expensive,
+            |message| async {
+                let req: CompileRequest = from_proto(message)?;
+
+                let response: WithMetadata<CompileResponse> = with_sandbox(
+                    &self.dispatcher,
+                    req,
+                    &Tracking::new(),
+                    OperationClass::Expensive,
+                    |sb, req| async move { sb.compile(req).await }.boxed(),
+                    CompilationSnafu,
+                )
+                .await
+                .map_err(status)?;
+
+                to_proto(response)
+            },
+        )
+        .await
+    }
+
+    async fn execute(
+        &self,
+        request: Request<proto::ExecuteRequest>,
+    ) -> Result<Response<proto::ExecuteResponse>, Status> {
+        self.unary(
+            "execute",
+            request,
+            OperationClass::Expensive,
+            |message| async {
+                let req: ExecuteRequest = from_proto(message)?;
+
+                let response: WithMetadata<ExecuteResponse> = with_sandbox(
+                    &self.dispatcher,
+                    req,
+                    &Tracking::new(),
+                    OperationClass::Expensive,
+                    |sb, req| async move { sb.execute(req).await }.boxed(),
+                    ExecutionSnafu,
+                )
+                .await
+                .map_err(status)?;
+
+                to_proto(response)
+            },
+        )
+        .await
+    }
+
+    type ExecuteStreamStream = ReceiverStream<Result<proto::ExecuteEvent, Status>>;
+
+    /// When the response is already cached, an identical request is
+    /// already running, or a worker runs it, only the final response
+    /// is sent. A stream can't be sent again, so idempotency keys
+    /// aren't used.
+    async fn execute_stream(
+        &self,
+        request: Request<proto::ExecuteRequest>,
+    ) -> Result<Response<Self::ExecuteStreamStream>, Status> {
+        self.admit(&request, OperationClass::Expensive)?;
+        let req: ExecuteRequest = from_proto(request.into_inner())?;
+        let dispatcher = self.dispatcher.clone();
+        let (events_tx, events_rx) = mpsc::channel(LIVE_OUTPUT_BUFFER);
+
+        tokio::spawn(async move {
+            let (live_tx, mut live_rx) = mpsc::channel(LIVE_OUTPUT_BUFFER);
+            let tracking = Tracking::new();
+
+            let execution = with_sandbox(
+                &dispatcher,
+                req,
+                &tracking,
+                OperationClass::Expensive,
+                move |sb, req| {
+                    async move { sb.with_live_output(live_tx).execute(req).await }.boxed()
+                },
+                ExecutionSnafu,
+            );
+            tokio::pin!(execution);
+
+            // Stopping early when the client has gone away gives up
+            // on the execution, as it would for an HTTP client.
+            let response: HttpResult<WithMetadata<ExecuteResponse>> = loop {
+                tokio::select! {
+                    response = &mut execution => break response,
+                    Some(output) = live_rx.recv() => {
+                        if events_tx.send(Ok(output.into())).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            };
+
+            while let Ok(output) = live_rx.try_recv() {
+                if events_tx.send(Ok(output.into())).await.is_err() {
+                    return;
+                }
+            }
+
+            let event =
+                response
+                    .map_err(status)
+                    .and_then(to_proto)
+                    .map(|response| proto::ExecuteEvent {
+                        event: Some(Event::Response(response)),
+                    });
+            let _ = events_tx.send(event).await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(events_rx)))
+    }
+
+    async fn format(
+        &self,
+        request: Request<proto::FormatRequest>,
+    ) -> Result<Response<proto::FormatResponse>, Status> {
+        self.unary("format", request, OperationClass::Cheap, |message| async {
+            let req: FormatRequest = from_proto(message)?;
+
+            let response: WithMetadata<FormatResponse> = with_sandbox(
+                &self.dispatcher,
+                req,
+                &Tracking::new(),
+                OperationClass::Cheap,
+                |sb, req| async move { sb.format(req).await }.boxed(),
+                FormattingSnafu,
+            )
+            .await
+            .map_err(status)?;
+
+            to_proto(response)
+        })
+        .await
+    }
+
+    async fn clippy(
+        &self,
+        request: Request<proto::ClippyRequest>,
+    ) -> Result<Response<proto::ClippyResponse>, Status> {
+        self.unary("clippy", request, OperationClass::Cheap, |message| async {
+            let req: ClippyRequest = from_proto(message)?;
+
+            let response: WithMetadata<ClippyResponse> = with_sandbox(
+                &self.dispatcher,
+                req,
+                &Tracking::new(),
+                OperationClass::Cheap,
+                |sb, req| async move { sb.clippy(req).await }.boxed(),
+                LintingSnafu,
+            )
+            .await
+            .map_err(status)?;
+
+            to_proto(response)
+        })
+        .await
+    }
+}
+
+impl From<sandbox::LiveOutput> for proto::ExecuteEvent {
+    fn from(output: sandbox::LiveOutput) -> Self {
+        let event = match output {
+            sandbox::LiveOutput::Stdout(stdout) => Event::Stdout(stdout),
+            sandbox::LiveOutput::Stderr(stderr) => Event::Stderr(stderr),
+        };
+
+        Self { event: Some(event) }
+    }
+}
+
+/// The messages have the same fields as the JSON API, so going
+/// through JSON gives missing fields the same defaults as there.
+fn from_proto<M, T>(message: M) -> Result<T, Status>
+where
+    M: Serialize,
+    T: DeserializeOwned,
+{
+    serde_json::to_value(message)
+        .and_then(serde_json::from_value)
+        .map_err(|e| Status::invalid_argument(e.to_string()))
+}
+
+fn to_proto<T, M>(response: T) -> Result<M, Status>
+where
+    T: Serialize,
+    M: DeserializeOwned,
+{
+    serde_json::to_value(response)
+        .and_then(serde_json::from_value)
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+fn status(error: Error) -> Status {
+    let message = error.to_string();
+
+    let code = match error.into_response().status() {
+        StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+        StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+        StatusCode::NOT_FOUND => tonic::Code::NotFound,
+        StatusCode::CONFLICT => tonic::Code::Aborted,
+        StatusCode::UNPROCESSABLE_ENTITY => tonic::Code::FailedPrecondition,
+        StatusCode::TOO_MANY_REQUESTS => tonic::Code::ResourceExhausted,
+        StatusCode::SERVICE_UNAVAILABLE => tonic::Code::Unavailable,
+        _ => tonic::Code::Internal,
+    };
+
+    Status::new(code, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SccacheStats;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn requests_have_the_json_defaults() {
+        let req: ClippyRequest = from_proto(proto::ClippyRequest {
+            code: "fn main() {}".into(),
+            edition: "2021".into(),
+            crate_type: None,
+        })
+        .unwrap();
+
+        assert_eq!(req.crate_type, crate::default_crate_type());
+
+        let req: CompileRequest = from_proto(proto::CompileRequest {
+            target: "asm".into(),
+            assembly_flavor: Some("intel".into()),
+            crate_type: "lib".into(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(req.target, "asm");
+        assert_eq!(req.assembly_flavor.as_deref(), Some("intel"));
+        assert_eq!(req.demangle_assembly, None);
+        assert_eq!(req.crate_type, "lib");
+
+        let req: ExecuteRequest = from_proto(proto::ExecuteRequest {
+            code: "fn main() {}".into(),
+            args: vec!["--verbose".into()],
+            stdin: "input".into(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(req.args, ["--verbose"]);
+        assert_eq!(req.stdin, "input");
+        assert!(!req.keep_binary);
+
+        let req: FormatRequest = from_proto(proto::FormatRequest {
+            code: "fn main() {}".into(),
+            lines: Some(proto::LineRange { start: 2, end: 3 }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(req.lines.map(|l| (l.start, l.end)), Some((2, 3)));
+    }
+
+    #[test]
+    fn executions_keep_their_tests_and_records() {
+        let response: proto::ExecuteResponse = to_proto(serde_json::json!({
+            "success": false,
+            "testResults": [{
+                "name": "tests::it_works",
+                "outcome": "failed",
+                "durationMs": 4,
+                "stdout": "oh no",
+            }],
+            "records": [{ "stream": "stderr", "timestampMs": 1, "text": "oh no\n" }],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            response.test_results,
+            [proto::TestResult {
+                name: "tests::it_works".into(),
+                outcome: "failed".into(),
+                duration_ms: Some(4),
+                stdout: Some("oh no".into()),
+            }]
+        );
+        assert_eq!(
+            response.records,
+            [proto::OutputRecord {
+                stream: "stderr".into(),
+                timestamp_ms: 1,
+                text: "oh no\n".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn api_keys_are_bearer_tokens() {
+        let mut metadata = MetadataMap::new();
+        assert_eq!(api_key(&metadata), None);
+
+        metadata.insert("authorization", MetadataValue::from_static("Bearer secret"));
+        assert_eq!(api_key(&metadata), Some("secret"));
+    }
+
+    #[test]
+    fn rejected_requests_have_matching_codes() {
+        let limited = status(Error::RateLimited {
+            retry_after: std::time::Duration::from_secs(1),
+        });
+        assert_eq!(limited.code(), tonic::Code::ResourceExhausted);
+
+        assert_eq!(
+            status(Error::InvalidApiKey).code(),
+            tonic::Code::Unauthenticated
+        );
+        assert_eq!(
+            status(Error::IdempotencyKeyInUse).code(),
+            tonic::Code::Aborted
+        );
+    }
+
+    #[test]
+    fn responses_keep_their_metadata() {
+        let response: proto::CompileResponse = to_proto(WithMetadata::cached(CompileResponse {
+            success: true,
+            code: "main:".into(),
+            outputs: BTreeMap::new(),
+            artifacts: BTreeMap::new(),
+            stdout: String::new(),
+            stderr: "warning".into(),
+            sccache: Some(SccacheStats {
+                cache_hits: 1,
+                cache_misses: 2,
+            }),
+            hint: Some("try-nightly".into()),
+            warning_count: 1,
+            error_count: 0,
+            suggestions: vec![],
+            image_id: None,
+            stdout_artifact: None,
+            stderr_artifact: None,
+            disk_quota_exceeded: true,
+        }))
+        .unwrap();
+
+        assert!(response.success);
+        assert!(response.cached);
+        assert_eq!(response.code, "main:");
+        assert!(response.outputs.is_empty());
+        assert_eq!(response.stderr, "warning");
+        assert_eq!(response.hint.as_deref(), Some("try-nightly"));
+        assert_eq!(response.warning_count, 1);
+        assert!(response.disk_quota_exceeded);
+        assert_eq!(
+            response.sccache,
+            Some(proto::SccacheStats {
+                cache_hits: 1,
+                cache_misses: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn live_output_becomes_an_event() {
+        let event = proto::ExecuteEvent::from(sandbox::LiveOutput::Stderr("oops".into()));
+
+        assert_eq!(event.event, Some(Event::Stderr("oops".into())));
+    }
+}
@@ -0,0 +1,178 @@
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::fmt::{self, Write};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Color, ThemeSet},
+    html::{self, IncludeBackground},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+lazy_static! {
+    static ref SYNTAXES: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEMES: ThemeSet = ThemeSet::load_defaults();
+}
+
+/// Of the themes built into syntect, those that can be chosen.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Theme {
+    #[default]
+    Light,
+    Dark,
+    SolarizedLight,
+    SolarizedDark,
+}
+
+impl Theme {
+    fn get(self) -> &'static syntect::highlighting::Theme {
+        let name = match self {
+            Theme::Light => "InspiredGitHub",
+            Theme::Dark => "base16-ocean.dark",
+            Theme::SolarizedLight => "Solarized (light)",
+            Theme::SolarizedDark => "Solarized (dark)",
+        };
+        &THEMES.themes[name]
+    }
+}
+
+/// Renders Rust code as a standalone `<pre>` element with inline
+/// styles, so that it can be embedded without any stylesheet.
+///
+/// Each line is wrapped in an element with the ID `L<number>` so that
+/// it can be linked to.
+pub(crate) fn rust_to_html(code: &str, theme: Theme) -> Result<String, syntect::Error> {
+    let syntax = SYNTAXES
+        .find_syntax_by_extension("rs")
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+    let theme = theme.get();
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    let (mut output, _) = html::start_highlighted_html_snippet(theme);
+
+    for (number, line) in (1..).zip(LinesWithEndings::from(code)) {
+        let regions = highlighter.highlight_line(line, &SYNTAXES)?;
+        let line = html::styled_line_to_highlighted_html(&regions, IncludeBackground::No)?;
+
+        // Writing to a `String` cannot fail
+        let _ = write!(
+            output,
+            r##"<span id="L{0}" class="line"><a href="#L{0}" class="line-number">{0}</a>{1}</span>"##,
+            number, line,
+        );
+    }
+
+    output.push_str("</pre>\n");
+    Ok(output)
+}
+
+/// Renders Rust code, and what running it output, as a whole page
+/// that can be shown in an `<iframe>`. Nothing is loaded from
+/// elsewhere, so it's shown the same wherever it's embedded.
+pub(crate) fn embed_to_html(
+    code: &str,
+    output: Option<&str>,
+    theme: Theme,
+) -> Result<String, syntect::Error> {
+    let code = rust_to_html(code, theme)?;
+    let settings = &theme.get().settings;
+    let background = settings.background.unwrap_or(Color::WHITE);
+    let foreground = settings.foreground.unwrap_or(Color::BLACK);
+    let muted = settings.gutter_foreground.unwrap_or(foreground);
+
+    let mut page = String::new();
+    // Writing to a `String` cannot fail
+    let _ = write!(
+        page,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<style>
+body {{ margin: 0; background-color: {background}; color: {foreground}; }}
+pre {{ margin: 0; padding: 0.5em; overflow: auto; font-family: monospace; }}
+.line-number {{ display: inline-block; width: 3ch; margin-right: 1ch; text-align: right; color: {muted}; text-decoration: none; user-select: none; }}
+.output {{ border-top: 1px solid {muted}; }}
+</style>
+</head>
+<body>
+{code}"#,
+        background = Css(background),
+        foreground = Css(foreground),
+        muted = Css(muted),
+        code = code,
+    );
+    if let Some(output) = output {
+        let _ = writeln!(page, r#"<pre class="output">{}</pre>"#, Escape(output));
+    }
+    page.push_str("</body>\n</html>\n");
+
+    Ok(page)
+}
+
+struct Css(Color);
+
+impl fmt::Display for Css {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Color { r, g, b, a } = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+struct Escape<'a>(&'a str);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lines_can_be_linked_to() {
+        let html = rust_to_html("fn main() {\n    1 < 2;\n}\n", Theme::Light).unwrap();
+
+        assert!(html.starts_with("<pre"));
+        for number in 1..=3 {
+            assert!(html.contains(&format!(r#"id="L{}""#, number)));
+        }
+        assert!(!html.contains(r#"id="L4""#));
+    }
+
+    #[test]
+    fn code_is_escaped() {
+        let html = rust_to_html(r#"fn main() { "<script>"; }"#, Theme::Light).unwrap();
+
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn embeds_are_whole_pages_with_the_output() {
+        let html = embed_to_html("fn main() {}", Some("<b>hi</b>\n"), Theme::Dark).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(r#"<pre class="output">&lt;b&gt;hi&lt;/b&gt;"#));
+        assert_ne!(
+            html,
+            embed_to_html("fn main() {}", Some("<b>hi</b>\n"), Theme::Light).unwrap()
+        );
+        assert!(!embed_to_html("fn main() {}", None, Theme::Dark)
+            .unwrap()
+            .contains(r#"class="output""#));
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Long enough for any client that is still retrying
+const KEY_TIME_TO_LIVE: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_KEYS: usize = 10_000;
+
+/// Remembers the response to each request that came with an
+/// idempotency key, so that a client retrying the request gets the
+/// same response instead of causing the request to happen again.
+#[derive(Debug)]
+pub(crate) struct IdempotencyKeys<K, R> {
+    entries: Mutex<HashMap<K, Entry<R>>>,
+}
+
+#[derive(Debug)]
+struct Entry<R> {
+    /// Identifies the request, which must be the same each time the
+    /// key is used.
+    fingerprint: String,
+    /// Only set once the request has finished.
+    response: Option<R>,
+    claimed_at: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) enum Claim<'a, K: Hash + Eq, R> {
+    /// The key is new, so the request should be made.
+    First(Pending<'a, K, R>),
+    /// The request with this key is still being made.
+    Running,
+    Finished(R),
+    /// The key was used for a different request.
+    Mismatched,
+}
+
+/// A request that is being made for the first time. Unless it is
+/// finished, such as when it fails or the client goes away, the key
+/// is released so it may be retried.
+#[derive(Debug)]
+pub(crate) struct Pending<'a, K: Hash + Eq, R> {
+    keys: &'a IdempotencyKeys<K, R>,
+    key: Option<K>,
+}
+
+impl<K, R> Default for IdempotencyKeys<K, R> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<K, R> IdempotencyKeys<K, R>
+where
+    K: Hash + Eq + Clone,
+    R: Clone,
+{
+    pub(crate) fn claim(&self, key: K, fingerprint: String) -> Claim<'_, K, R> {
+        self.claim_at(key, fingerprint, Instant::now())
+    }
+
+    fn claim_at(&self, key: K, fingerprint: String, now: Instant) -> Claim<'_, K, R> {
+        let mut entries = self.lock();
+
+        entries
+            .retain(|_, entry| now.saturating_duration_since(entry.claimed_at) < KEY_TIME_TO_LIVE);
+
+        if let Some(entry) = entries.get(&key) {
+            return if entry.fingerprint != fingerprint {
+                Claim::Mismatched
+            } else {
+                match &entry.response {
+                    Some(response) => Claim::Finished(response.clone()),
+                    None => Claim::Running,
+                }
+            };
+        }
+
+        if entries.len() >= MAX_KEYS {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.claimed_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key.clone(),
+            Entry {
+                fingerprint,
+                response: None,
+                claimed_at: now,
+            },
+        );
+
+        Claim::First(Pending {
+            keys: self,
+            key: Some(key),
+        })
+    }
+}
+
+impl<K, R> Pending<'_, K, R>
+where
+    K: Hash + Eq,
+{
+    /// Keeps the response for when the request is retried.
+    pub(crate) fn finish(mut self, response: R) {
+        if let Some(key) = self.key.take() {
+            if let Some(entry) = self.keys.lock().get_mut(&key) {
+                entry.response = Some(response);
+            }
+        }
+    }
+}
+
+impl<K, R> Drop for Pending<'_, K, R>
+where
+    K: Hash + Eq,
+{
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.keys.lock().remove(&key);
+        }
+    }
+}
+
+impl<K, R> IdempotencyKeys<K, R> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, Entry<R>>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Keys = IdempotencyKeys<&'static str, &'static str>;
+
+    #[test]
+    fn retries_get_the_first_response() {
+        let keys = Keys::default();
+
+        let pending = match keys.claim("key", "request".into()) {
+            Claim::First(pending) => pending,
+            other => panic!("Expected the first claim, got {:?}", other),
+        };
+        assert!(matches!(
+            keys.claim("key", "request".into()),
+            Claim::Running
+        ));
+
+        pending.finish("response");
+        assert!(matches!(
+            keys.claim("key", "request".into()),
+            Claim::Finished("response")
+        ));
+        assert!(matches!(
+            keys.claim("key", "another request".into()),
+            Claim::Mismatched
+        ));
+    }
+
+    #[test]
+    fn unfinished_requests_release_their_key() {
+        let keys = Keys::default();
+
+        let pending = keys.claim("key", "request".into());
+        drop(pending);
+
+        assert!(matches!(
+            keys.claim("key", "another request".into()),
+            Claim::First(_)
+        ));
+    }
+
+    #[test]
+    fn keys_expire() {
+        let keys = Keys::default();
+        let start = Instant::now();
+
+        if let Claim::First(pending) = keys.claim_at("key", "request".into(), start) {
+            pending.finish("response");
+        }
+
+        let later = start + KEY_TIME_TO_LIVE;
+        assert!(matches!(
+            keys.claim_at("key", "another request".into(), later),
+            Claim::First(_)
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashMap, sync::Mutex, time::SystemTime};
+
+use crate::sandbox::Channel;
+
+/// The channels whose images are replaced by newer ones. Stable
+/// releases and dated nightlies never change.
+pub(crate) const CHANNELS: [Channel; 3] = [
+    Channel::Stable { version: None },
+    Channel::Beta,
+    Channel::Nightly { date: None },
+];
+
+/// What has become of the image of each channel since starting.
+#[derive(Debug, Default)]
+pub(crate) struct ImageUpdates(Mutex<HashMap<Channel, ImageStatus>>);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ImageStatus {
+    /// The ID of the image that new sandboxes are started from, once
+    /// it has been pinned.
+    pub(crate) active: Option<String>,
+    /// When the registry was last asked for a newer image.
+    pub(crate) checked_at: Option<SystemTime>,
+    /// When a newer image last passed its smoke test and was switched
+    /// to.
+    pub(crate) updated_at: Option<SystemTime>,
+    /// Why the last check failed. The active image is kept when it
+    /// does.
+    pub(crate) error: Option<String>,
+}
+
+/// How a check for a newer image of a channel went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// The image that was already in use is now used by its ID.
+    Pinned(String),
+    Unchanged,
+    Updated(String),
+    Failed(String),
+}
+
+impl ImageUpdates {
+    pub(crate) fn status(&self, channel: Channel) -> ImageStatus {
+        self.lock().get(&channel).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn record(&self, channel: Channel, outcome: Outcome) {
+        self.record_at(channel, outcome, SystemTime::now())
+    }
+
+    fn record_at(&self, channel: Channel, outcome: Outcome, now: SystemTime) {
+        let mut statuses = self.lock();
+        let status = statuses.entry(channel).or_default();
+
+        match outcome {
+            Outcome::Pinned(id) => {
+                status.active = Some(id);
+                return;
+            }
+            Outcome::Unchanged => status.error = None,
+            Outcome::Updated(id) => {
+                status.active = Some(id);
+                status.updated_at = Some(now);
+                status.error = None;
+            }
+            Outcome::Failed(error) => status.error = Some(error),
+        }
+        status.checked_at = Some(now);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Channel, ImageStatus>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    const CHANNEL: Channel = Channel::Beta;
+
+    #[test]
+    fn failures_keep_the_active_image() {
+        let updates = ImageUpdates::default();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+
+        updates.record_at(CHANNEL, Outcome::Pinned("sha256:old".into()), now);
+        updates.record_at(CHANNEL, Outcome::Failed("pull failed".into()), now);
+
+        let status = updates.status(CHANNEL);
+        assert_eq!(status.active.as_deref(), Some("sha256:old"));
+        assert_eq!(status.checked_at, Some(now));
+        assert_eq!(status.updated_at, None);
+        assert_eq!(status.error.as_deref(), Some("pull failed"));
+    }
+
+    #[test]
+    fn updates_clear_the_last_failure() {
+        let updates = ImageUpdates::default();
+        let earlier = SystemTime::UNIX_EPOCH;
+        let now = earlier + Duration::from_secs(60);
+
+        updates.record_at(CHANNEL, Outcome::Failed("pull failed".into()), earlier);
+        updates.record_at(CHANNEL, Outcome::Updated("sha256:new".into()), now);
+
+        let status = updates.status(CHANNEL);
+        assert_eq!(status.active.as_deref(), Some("sha256:new"));
+        assert_eq!(status.checked_at, Some(now));
+        assert_eq!(status.updated_at, Some(now));
+        assert_eq!(status.error, None);
+        assert_eq!(
+            updates.status(Channel::Nightly { date: None }),
+            ImageStatus::default()
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+use crate::response_cache::key_for;
+
+type AnyValue = Arc<dyn Any + Send + Sync>;
+
+/// Tracks the requests that are currently running so that identical
+/// requests arriving at the same time can share a single execution.
+#[derive(Default)]
+pub(crate) struct InFlight {
+    flights: Mutex<HashMap<u64, Flight>>,
+}
+
+struct Flight {
+    request: AnyValue,
+    response: AnyValue,
+}
+
+impl InFlight {
+    /// Runs `f` to produce the response for `request`, unless an
+    /// identical request is already running, in which case its
+    /// response is shared.
+    ///
+    /// Failures are not shared; if the running request fails, one of
+    /// the waiting requests will try again.
+    pub(crate) async fn run<Req, Resp, E, F, Fut>(&self, request: &Req, f: F) -> Result<Resp, E>
+    where
+        Req: Hash + Eq + Clone + Send + Sync + 'static,
+        Resp: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Resp, E>>,
+    {
+        let key = key_for::<Req, Resp>(request);
+
+        let cell = match self.join::<Req, Resp>(key, request) {
+            Some(cell) => cell,
+            // A different request with the same key is running
+            None => return f().await,
+        };
+
+        let response = cell.get_or_try_init(f).await.cloned();
+
+        self.leave(key, &cell);
+
+        response
+    }
+
+    fn join<Req, Resp>(&self, key: u64, request: &Req) -> Option<Arc<OnceCell<Resp>>>
+    where
+        Req: Eq + Clone + Send + Sync + 'static,
+        Resp: Send + Sync + 'static,
+    {
+        let mut flights = self.lock();
+
+        let flight = flights.entry(key).or_insert_with(|| Flight {
+            request: Arc::new(request.clone()),
+            response: Arc::new(OnceCell::<Resp>::new()),
+        });
+
+        if flight.request.downcast_ref::<Req>() != Some(request) {
+            return None;
+        }
+
+        flight.response.clone().downcast().ok()
+    }
+
+    fn leave<Resp>(&self, key: u64, cell: &Arc<OnceCell<Resp>>) {
+        let mut flights = self.lock();
+
+        // Anyone still waiting holds onto the cell. Later requests
+        // should start afresh (or hit the response cache) instead.
+        let is_ours = flights.get(&key).is_some_and(|flight| {
+            Arc::as_ptr(&flight.response) as *const () == Arc::as_ptr(cell) as *const ()
+        });
+
+        if is_ours {
+            flights.remove(&key);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Flight>> {
+        self.flights.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    async fn slowly_count(count: &AtomicUsize) -> Result<usize, ()> {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(count.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    #[tokio::test]
+    async fn concurrent_identical_requests_run_once() {
+        let in_flight = InFlight::default();
+        let count = AtomicUsize::new(0);
+        let request = String::from("fn main() {}");
+
+        let (a, b) = tokio::join!(
+            in_flight.run(&request, || slowly_count(&count)),
+            in_flight.run(&request, || slowly_count(&count)),
+        );
+
+        assert_eq!(a, Ok(1));
+        assert_eq!(b, Ok(1));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn different_requests_run_separately() {
+        let in_flight = InFlight::default();
+        let count = AtomicUsize::new(0);
+        let a = String::from("fn main() {}");
+        let b = String::from("fn main() { loop {} }");
+
+        let _ = tokio::join!(
+            in_flight.run(&a, || slowly_count(&count)),
+            in_flight.run(&b, || slowly_count(&count)),
+        );
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn sequential_requests_run_again() {
+        let in_flight = InFlight::default();
+        let count = AtomicUsize::new(0);
+        let request = String::from("fn main() {}");
+
+        let a = in_flight.run(&request, || slowly_count(&count)).await;
+        let b = in_flight.run(&request, || slowly_count(&count)).await;
+
+        assert_eq!((a, b), (Ok(1), Ok(2)));
+    }
+
+    #[tokio::test]
+    async fn failures_are_not_shared() {
+        let in_flight = InFlight::default();
+        let count = AtomicUsize::new(0);
+        let request = String::from("fn main() {}");
+
+        let fail = || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            count.fetch_add(1, Ordering::SeqCst);
+            Err::<usize, _>(())
+        };
+
+        let (a, b) = tokio::join!(in_flight.run(&request, fail), in_flight.run(&request, fail));
+
+        assert_eq!((a, b), (Err(()), Err(())));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+}
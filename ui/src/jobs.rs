@@ -0,0 +1,142 @@
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::dispatcher::{Progress, Stage};
+
+// Clients are expected to poll for the outcome shortly after the job
+// finishes
+const FINISHED_JOB_TIME_TO_LIVE: Duration = Duration::from_secs(10 * 60);
+
+/// Requests that are being run on behalf of clients who will check
+/// back for the outcome later.
+#[derive(Debug, Default)]
+pub(crate) struct Jobs {
+    jobs: Mutex<HashMap<JobId, Job>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct JobId(String);
+
+#[derive(Debug)]
+struct Job {
+    progress: Arc<Progress>,
+    finished: Option<(Instant, Outcome)>,
+}
+
+/// What the request would have responded with had it been made
+/// directly.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Outcome {
+    pub(crate) status: u16,
+    pub(crate) body: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Status {
+    Unfinished(Stage),
+    Finished(Outcome),
+}
+
+impl Jobs {
+    pub(crate) fn create(&self) -> (JobId, Arc<Progress>) {
+        let id = JobId::random();
+        let progress = Arc::new(Progress::default());
+
+        let mut jobs = self.lock();
+
+        jobs.retain(|_, job| match &job.finished {
+            Some((finished, _)) => finished.elapsed() < FINISHED_JOB_TIME_TO_LIVE,
+            None => true,
+        });
+
+        jobs.insert(
+            id.clone(),
+            Job {
+                progress: progress.clone(),
+                finished: None,
+            },
+        );
+
+        (id, progress)
+    }
+
+    pub(crate) fn finish(&self, id: &JobId, outcome: Outcome) {
+        if let Some(job) = self.lock().get_mut(id) {
+            job.finished = Some((Instant::now(), outcome));
+        }
+    }
+
+    pub(crate) fn status(&self, id: &JobId) -> Option<Status> {
+        let jobs = self.lock();
+        let job = jobs.get(id)?;
+
+        Some(match &job.finished {
+            Some((_, outcome)) => Status::Finished(outcome.clone()),
+            None => Status::Unfinished(job.progress.stage()),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<JobId, Job>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl JobId {
+    // Knowing the ID is all that is needed to see the outcome, so it
+    // should not be guessable
+    fn random() -> Self {
+        JobId(format!("{:032x}", rand::thread_rng().gen::<u128>()))
+    }
+}
+
+impl From<String> for JobId {
+    fn from(id: String) -> Self {
+        JobId(id)
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jobs_report_their_progress_then_their_outcome() {
+        let jobs = Jobs::default();
+        let (id, progress) = jobs.create();
+
+        assert_eq!(jobs.status(&id), Some(Status::Unfinished(Stage::Pending)));
+
+        progress.advance(Stage::Running(None));
+        assert_eq!(
+            jobs.status(&id),
+            Some(Status::Unfinished(Stage::Running(None)))
+        );
+
+        let outcome = Outcome {
+            status: 200,
+            body: serde_json::json!({ "success": true }),
+        };
+        jobs.finish(&id, outcome.clone());
+        assert_eq!(jobs.status(&id), Some(Status::Finished(outcome)));
+    }
+
+    #[test]
+    fn unknown_jobs_have_no_status() {
+        let jobs = Jobs::default();
+        let (id, _) = jobs.create();
+        let other = JobId::from(format!("{}0", id));
+
+        assert_eq!(jobs.status(&other), None);
+    }
+}
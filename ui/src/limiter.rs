@@ -0,0 +1,287 @@
+use std::{
+    collections::BTreeSet,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+// Used until enough sandboxes have finished to know better
+const INITIAL_RUN_DURATION_ESTIMATE: Duration = Duration::from_secs(10);
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bounds how many sandboxes may run at once and how many requests
+/// may wait for one to become available.
+///
+/// Waiting requests are let through in the order they arrived.
+pub(crate) struct ConcurrencyLimiter {
+    permits: Semaphore,
+    max_running: usize,
+    max_waiting: usize,
+    queue: Mutex<Queue>,
+    run_duration: Mutex<Duration>,
+}
+
+#[derive(Default)]
+struct Queue {
+    next_ticket: u64,
+    waiting: BTreeSet<Ticket>,
+}
+
+/// A place in the line of requests waiting for a sandbox.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Ticket(u64);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct QueuePosition {
+    /// How many requests will be let through first.
+    pub(crate) ahead: usize,
+    pub(crate) estimated_wait: Duration,
+}
+
+/// A snapshot of how busy the limiter is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Usage {
+    pub(crate) running: usize,
+    pub(crate) max_running: usize,
+    pub(crate) waiting: usize,
+    pub(crate) max_waiting: usize,
+    pub(crate) run_duration: Duration,
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(max_running: usize, max_waiting: usize) -> Self {
+        Self {
+            permits: Semaphore::new(max_running),
+            max_running,
+            max_waiting,
+            queue: Default::default(),
+            run_duration: Mutex::new(INITIAL_RUN_DURATION_ESTIMATE),
+        }
+    }
+
+    /// Waits for permission to run a sandbox. Returns `None` without
+    /// waiting when too many other requests are already waiting, or
+    /// when the limiter has been drained.
+    ///
+    /// If the request has to wait, `on_queued` is told its place in
+    /// line.
+    pub(crate) async fn acquire(&self, on_queued: impl FnOnce(Ticket)) -> Option<Permit<'_>> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Some(self.permit(permit));
+        }
+
+        let waiting = {
+            let mut queue = self.lock_queue();
+
+            if queue.waiting.len() >= self.max_waiting {
+                return None;
+            }
+
+            let ticket = Ticket(queue.next_ticket);
+            queue.next_ticket += 1;
+            queue.waiting.insert(ticket);
+
+            WaitingGuard {
+                queue: &self.queue,
+                ticket,
+            }
+        };
+
+        on_queued(waiting.ticket);
+
+        // The semaphore is never closed
+        let permit = self.permits.acquire().await.ok()?;
+        drop(waiting);
+
+        Some(self.permit(permit))
+    }
+
+    /// Turns away all waiting and future requests, then waits for the
+    /// running sandboxes to finish.
+    pub(crate) async fn drain(&self) {
+        self.permits.close();
+
+        // Permits are still returned after the semaphore is closed
+        while self.permits.available_permits() < self.max_running {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Where the ticket is in line, or `None` if it has stopped
+    /// waiting.
+    pub(crate) fn position(&self, ticket: Ticket) -> Option<QueuePosition> {
+        let ahead = {
+            let queue = self.lock_queue();
+            if !queue.waiting.contains(&ticket) {
+                return None;
+            }
+            queue.waiting.range(..ticket).count()
+        };
+
+        // Every running sandbox works through its share of the line
+        let rounds = ahead / self.max_running.max(1) + 1;
+        let estimated_wait = *self.lock_run_duration() * rounds as u32;
+
+        Some(QueuePosition {
+            ahead,
+            estimated_wait,
+        })
+    }
+
+    pub(crate) fn usage(&self) -> Usage {
+        let available = self.permits.available_permits();
+
+        Usage {
+            running: self.max_running.saturating_sub(available),
+            max_running: self.max_running,
+            waiting: self.lock_queue().waiting.len(),
+            max_waiting: self.max_waiting,
+            run_duration: *self.lock_run_duration(),
+        }
+    }
+
+    fn permit<'a>(&'a self, permit: SemaphorePermit<'a>) -> Permit<'a> {
+        Permit {
+            _permit: permit,
+            limiter: self,
+            started: Instant::now(),
+        }
+    }
+
+    fn record_run(&self, duration: Duration) {
+        let mut run_duration = self.lock_run_duration();
+        *run_duration = (*run_duration * 3 + duration) / 4;
+    }
+
+    fn lock_queue(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_run_duration(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.run_duration.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Permission to run a sandbox. How long it is held for is used to
+/// estimate how long waiting requests will wait.
+pub(crate) struct Permit<'a> {
+    _permit: SemaphorePermit<'a>,
+    limiter: &'a ConcurrencyLimiter,
+    started: Instant,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limiter.record_run(self.started.elapsed());
+    }
+}
+
+/// Ensures that a request stops being counted as waiting even if it
+/// is cancelled.
+struct WaitingGuard<'a> {
+    queue: &'a Mutex<Queue>,
+    ticket: Ticket,
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.waiting.remove(&self.ticket);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::{poll, task::Poll};
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn requests_wait_then_are_rejected() {
+        let limiter = ConcurrencyLimiter::new(1, 1);
+
+        let running = limiter.acquire(|_| {}).await;
+        assert!(running.is_some());
+
+        let mut waiting = Box::pin(limiter.acquire(|_| {}));
+        assert!(matches!(poll!(&mut waiting), Poll::Pending));
+
+        assert!(limiter.acquire(|_| {}).await.is_none());
+
+        drop(running);
+        assert!(waiting.await.is_some());
+    }
+
+    #[tokio::test]
+    async fn cancelled_requests_stop_waiting() {
+        let limiter = ConcurrencyLimiter::new(1, 1);
+        let _running = limiter.acquire(|_| {}).await;
+
+        let mut waiting = Box::pin(limiter.acquire(|_| {}));
+        assert!(matches!(poll!(&mut waiting), Poll::Pending));
+        drop(waiting);
+
+        let mut waiting = Box::pin(limiter.acquire(|_| {}));
+        assert!(matches!(poll!(&mut waiting), Poll::Pending));
+    }
+
+    #[tokio::test]
+    async fn draining_waits_for_running_requests() {
+        let limiter = ConcurrencyLimiter::new(1, 1);
+        let running = limiter.acquire(|_| {}).await;
+
+        let mut waiting = Box::pin(limiter.acquire(|_| {}));
+        assert!(matches!(poll!(&mut waiting), Poll::Pending));
+
+        let mut drain = Box::pin(limiter.drain());
+        assert!(matches!(poll!(&mut drain), Poll::Pending));
+        assert!(waiting.await.is_none());
+
+        drop(running);
+        drain.await;
+
+        assert!(limiter.acquire(|_| {}).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn usage_counts_running_and_waiting_requests() {
+        let limiter = ConcurrencyLimiter::new(1, 10);
+        let _running = limiter.acquire(|_| {}).await;
+
+        let mut waiting = Box::pin(limiter.acquire(|_| {}));
+        assert!(matches!(poll!(&mut waiting), Poll::Pending));
+
+        let usage = limiter.usage();
+        assert_eq!(usage.running, 1);
+        assert_eq!(usage.waiting, 1);
+        assert_eq!(usage.max_waiting, 10);
+    }
+
+    #[tokio::test]
+    async fn waiting_requests_know_their_position() {
+        let limiter = ConcurrencyLimiter::new(1, 10);
+        let running = limiter.acquire(|_| {}).await;
+
+        let first_ticket = Cell::new(None);
+        let mut first = Box::pin(limiter.acquire(|t| first_ticket.set(Some(t))));
+        assert!(matches!(poll!(&mut first), Poll::Pending));
+
+        let second_ticket = Cell::new(None);
+        let mut second = Box::pin(limiter.acquire(|t| second_ticket.set(Some(t))));
+        assert!(matches!(poll!(&mut second), Poll::Pending));
+
+        let first_ticket = first_ticket.get().unwrap();
+        let second_ticket = second_ticket.get().unwrap();
+
+        let position = limiter.position(second_ticket).unwrap();
+        assert_eq!(position.ahead, 1);
+        assert_eq!(position.estimated_wait, INITIAL_RUN_DURATION_ESTIMATE * 2);
+
+        drop(running);
+        let _first = first.await;
+
+        assert_eq!(limiter.position(first_ticket), None);
+        assert_eq!(limiter.position(second_ticket).unwrap().ahead, 0);
+    }
+}
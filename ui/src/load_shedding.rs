@@ -0,0 +1,272 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    fs,
+    process::Command,
+    time::{self, MissedTickBehavior},
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far the host may be pushed before expensive operations are
+/// turned away. Unset thresholds are not checked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Thresholds {
+    /// Averaged over the last minute
+    pub(crate) max_load_average: Option<f64>,
+    /// Bytes
+    pub(crate) min_free_memory: Option<u64>,
+    /// Bytes, on the filesystem that sandboxes work in
+    pub(crate) min_free_disk: Option<u64>,
+}
+
+/// Keeps track of whether the host is close to falling over, so that
+/// requests can be turned away before it does.
+#[derive(Debug, Clone)]
+pub(crate) struct LoadShedding(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    thresholds: Thresholds,
+    disk: PathBuf,
+    pressure: Mutex<Option<Pressure>>,
+}
+
+/// What the host is running short of.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pressure {
+    Cpu,
+    Memory,
+    Disk,
+}
+
+impl fmt::Display for Pressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Pressure::Cpu => "is too busy",
+            Pressure::Memory => "is low on memory",
+            Pressure::Disk => "is low on disk space",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+struct Sample {
+    load_average: Option<f64>,
+    free_memory: Option<u64>,
+    free_disk: Option<u64>,
+}
+
+impl Thresholds {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Memory is checked first, as running out of it is what takes a
+    /// host down the fastest.
+    fn exceeded_by(&self, sample: &Sample) -> Option<Pressure> {
+        fn below(value: Option<u64>, min: Option<u64>) -> bool {
+            matches!((value, min), (Some(v), Some(min)) if v < min)
+        }
+
+        if below(sample.free_memory, self.min_free_memory) {
+            Some(Pressure::Memory)
+        } else if below(sample.free_disk, self.min_free_disk) {
+            Some(Pressure::Disk)
+        } else if matches!(
+            (sample.load_average, self.max_load_average),
+            (Some(v), Some(max)) if v > max
+        ) {
+            Some(Pressure::Cpu)
+        } else {
+            None
+        }
+    }
+}
+
+impl LoadShedding {
+    /// `disk` is where the free space is measured.
+    pub(crate) fn new(thresholds: Thresholds, disk: PathBuf) -> Self {
+        Self(Arc::new(Inner {
+            thresholds,
+            disk,
+            pressure: Mutex::new(None),
+        }))
+    }
+
+    /// What the host was short of when it was last checked.
+    pub(crate) fn pressure(&self) -> Option<Pressure> {
+        *self.0.pressure.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Checks the host every few seconds, forever. Anything that
+    /// can't be measured, such as when not on Linux, is ignored.
+    pub(crate) async fn monitor(self) {
+        let Inner {
+            thresholds, disk, ..
+        } = &*self.0;
+
+        let mut interval = time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let first = sample(disk).await;
+        let unmeasured = |threshold: bool, measured: bool, what: &str| {
+            if threshold && !measured {
+                tracing::warn!(
+                    "Unable to measure the {what}, so it won't be used to shed requests"
+                );
+            }
+        };
+        unmeasured(
+            thresholds.max_load_average.is_some(),
+            first.load_average.is_some(),
+            "load average",
+        );
+        unmeasured(
+            thresholds.min_free_memory.is_some(),
+            first.free_memory.is_some(),
+            "free memory",
+        );
+        unmeasured(
+            thresholds.min_free_disk.is_some(),
+            first.free_disk.is_some(),
+            "free disk space",
+        );
+
+        loop {
+            interval.tick().await;
+
+            let sample = sample(disk).await;
+            let pressure = thresholds.exceeded_by(&sample);
+
+            let previous = std::mem::replace(
+                &mut *self.0.pressure.lock().unwrap_or_else(|e| e.into_inner()),
+                pressure,
+            );
+            match (previous, pressure) {
+                (None, Some(pressure)) => {
+                    tracing::warn!(
+                        ?sample,
+                        "The host {}; rejecting expensive requests",
+                        pressure
+                    )
+                }
+                (Some(_), None) => tracing::info!(?sample, "The host has recovered"),
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn sample(disk: &Path) -> Sample {
+    let (load_average, free_memory, free_disk) =
+        futures::join!(load_average(), free_memory(), free_disk(disk));
+
+    Sample {
+        load_average,
+        free_memory,
+        free_disk,
+    }
+}
+
+async fn load_average() -> Option<f64> {
+    let loadavg = fs::read_to_string("/proc/loadavg").await.ok()?;
+    parse_load_average(&loadavg)
+}
+
+async fn free_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").await.ok()?;
+    parse_free_memory(&meminfo)
+}
+
+/// The work directory is only created when the first sandbox needs
+/// it, so the closest directory that exists is measured instead.
+async fn free_disk(path: &Path) -> Option<u64> {
+    let path = path.ancestors().find(|p| p.exists())?;
+    let output = Command::new("df")
+        .args(["-P", "-k", "--"])
+        .arg(path)
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    parse_free_disk(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_load_average(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+fn parse_free_memory(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let kibibytes: u64 = line.trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kibibytes * 1024)
+}
+
+/// Reads the output of `df -P -k`, which has a header line and then
+/// one line for the filesystem.
+fn parse_free_disk(df: &str) -> Option<u64> {
+    let line = df.lines().nth(1)?;
+    let kibibytes: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kibibytes * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_host_is_measured_from_proc_and_df() {
+        assert_eq!(
+            parse_load_average("3.52 2.91 2.40 4/1203 98765\n"),
+            Some(3.52)
+        );
+
+        let meminfo = "MemTotal:       16318412 kB\nMemFree:          412884 kB\nMemAvailable:    8123456 kB\n";
+        assert_eq!(parse_free_memory(meminfo), Some(8123456 * 1024));
+
+        let df = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                  /dev/nvme0n1p2   102626232  61530820  35836148      64% /\n";
+        assert_eq!(parse_free_disk(df), Some(35836148 * 1024));
+
+        assert_eq!(parse_free_memory("MemTotal: 1 kB\n"), None);
+        assert_eq!(parse_free_disk(""), None);
+    }
+
+    #[test]
+    fn only_the_set_thresholds_are_checked() {
+        let sample = Sample {
+            load_average: Some(20.0),
+            free_memory: Some(100),
+            free_disk: Some(100),
+        };
+
+        assert_eq!(Thresholds::default().exceeded_by(&sample), None);
+
+        let thresholds = Thresholds {
+            max_load_average: Some(8.0),
+            min_free_disk: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(thresholds.exceeded_by(&sample), Some(Pressure::Disk));
+
+        let thresholds = Thresholds {
+            max_load_average: Some(8.0),
+            min_free_memory: Some(1000),
+            min_free_disk: Some(1000),
+        };
+        assert_eq!(thresholds.exceeded_by(&sample), Some(Pressure::Memory));
+
+        let unmeasured = Sample::default();
+        assert_eq!(thresholds.exceeded_by(&unmeasured), None);
+    }
+}
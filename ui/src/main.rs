@@ -1,129 +1,76 @@
 #![deny(rust_2018_idioms)]
 
-use crate::env::{PLAYGROUND_GITHUB_TOKEN, PLAYGROUND_UI_ROOT};
+use crate::env::{PLAYGROUND_GITHUB_TOKEN, PLAYGROUND_SHARE_LINK_KEY, PLAYGROUND_SNIPPET_DATABASE};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
-use std::{
-    convert::TryFrom,
-    net::SocketAddr,
-    path::{Path, PathBuf},
-    sync::Arc,
-};
+use std::{collections::BTreeMap, convert::TryFrom, sync::Arc, time::Duration};
+use utoipa::ToSchema;
 
-const DEFAULT_ADDRESS: &str = "127.0.0.1";
-const DEFAULT_PORT: u16 = 5000;
+use playground_sandbox as sandbox;
 
-mod asm_cleanup;
+mod artifacts;
+mod config;
+mod coordinator;
+mod diff;
+mod dispatcher;
 mod env;
 mod gist;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod highlight;
+mod idempotency;
+mod image_updates;
+mod in_flight;
+mod jobs;
+mod limiter;
+mod load_shedding;
 mod metrics;
-mod sandbox;
+mod queue;
+mod rate_limit;
+mod repl;
+mod response_cache;
 mod server_axum;
+mod share_link;
+mod storage;
+mod templates;
+mod usage;
+mod worker;
 
 fn main() {
     // Dotenv may be unable to load environment variables, but that's ok in production
     let _ = dotenv::dotenv();
     openssl_probe::init_ssl_cert_env_vars();
 
-    // Enable info-level logging by default. env_logger's default is error only.
-    let env_logger_config = env_logger::Env::default().default_filter_or("info");
-    env_logger::Builder::from_env(env_logger_config).init();
-
-    let config = Config::from_env();
-    server_axum::serve(config);
-}
-
-struct Config {
-    address: String,
-    cors_enabled: bool,
-    gh_token: Option<String>,
-    metrics_token: Option<String>,
-    port: u16,
-    root: PathBuf,
-}
-
-impl Config {
-    fn from_env() -> Self {
-        let root = if let Some(root) = env::var_os(PLAYGROUND_UI_ROOT) {
-            // Ensure it appears as an absolute path in logs to help user orient
-            // themselves about what directory the PLAYGROUND_UI_ROOT
-            // configuration is interpreted relative to.
-            let mut root = PathBuf::from(root);
-            if !root.is_absolute() {
-                if let Ok(current_dir) = env::current_dir() {
-                    root = current_dir.join(root);
-                }
-            }
-            root
-        } else {
-            // Note this is `env!` (compile time) while the above is
-            // `env::var_os` (run time). We know where the ui is expected to be
-            // relative to the source code that the server was compiled from.
-            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-                .join("frontend")
-                .join("build")
-        };
-
-        let index_html = root.join("index.html");
-        if index_html.exists() {
-            log::info!("Serving playground frontend from {}", root.display());
-        } else {
-            log::error!(
-                "Playground ui does not exist at {}\n\
-                Playground will not work until `yarn run build` has been run or {PLAYGROUND_UI_ROOT} has been fixed",
-                index_html.display(),
-            );
-        }
-
-        let address =
-            env::var("PLAYGROUND_UI_ADDRESS").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
-        let port = env::var("PLAYGROUND_UI_PORT")
-            .ok()
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(DEFAULT_PORT);
-
-        let gh_token = env::var(PLAYGROUND_GITHUB_TOKEN).ok();
-        if gh_token.is_none() {
-            log::warn!("Environment variable {} is not set, so reading and writing GitHub gists will not work", PLAYGROUND_GITHUB_TOKEN);
-        }
-
-        let metrics_token = env::var("PLAYGROUND_METRICS_TOKEN").ok();
+    init_logging();
 
-        let cors_enabled = env::var_os("PLAYGROUND_CORS_ENABLED").is_some();
+    let config = config::Config::load().unwrap_or_else(|error| {
+        tracing::error!(%error, "Unable to load the configuration");
+        std::process::exit(1);
+    });
 
-        Self {
-            address,
-            cors_enabled,
-            gh_token,
-            metrics_token,
-            port,
-            root,
+    // Workers take the jobs that servers have queued
+    match std::env::args().nth(1).as_deref() {
+        None => server_axum::serve(config),
+        Some("worker") => worker::work(config),
+        Some(other) => {
+            tracing::error!("Unknown command {:?}; the only command is `worker`", other);
+            std::process::exit(1);
         }
     }
+}
 
-    fn root_path(&self) -> &Path {
-        &self.root
-    }
-
-    fn asset_path(&self) -> PathBuf {
-        self.root.join("assets")
-    }
-
-    fn use_cors(&self) -> bool {
-        self.cors_enabled
-    }
-
-    fn metrics_token(&self) -> Option<MetricsToken> {
-        self.metrics_token.as_deref().map(MetricsToken::new)
-    }
+fn init_logging() {
+    use tracing_subscriber::EnvFilter;
 
-    fn github_token(&self) -> GhToken {
-        GhToken::new(&self.gh_token)
-    }
+    // Enable info-level logging by default. Set RUST_LOG to change this.
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let logging = tracing_subscriber::fmt().with_env_filter(filter);
 
-    fn server_socket_addr(&self) -> SocketAddr {
-        let address = self.address.parse().expect("Invalid address");
-        SocketAddr::new(address, self.port)
+    if env::var_os("PLAYGROUND_LOG_JSON").is_some() {
+        logging.json().with_current_span(true).init();
+    } else {
+        logging.init();
     }
 }
 
@@ -143,6 +90,26 @@ impl GhToken {
     }
 }
 
+/// Where snippets are stored, if anywhere.
+#[derive(Debug, Clone)]
+struct SnippetStorage(Option<storage::Storage>);
+
+impl SnippetStorage {
+    fn must_get(&self) -> Result<&storage::Storage> {
+        self.0.as_ref().context(NoSnippetStorageSnafu)
+    }
+}
+
+/// What share links are signed with, if anything.
+#[derive(Debug, Clone)]
+struct ShareLinks(Option<share_link::ShareLinks>);
+
+impl ShareLinks {
+    fn must_get(&self) -> Result<&share_link::ShareLinks> {
+        self.0.as_ref().context(NoShareLinkKeySnafu)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct MetricsToken(Arc<String>);
 
@@ -152,6 +119,24 @@ impl MetricsToken {
     }
 }
 
+#[derive(Debug, Clone)]
+struct AdminToken(Arc<String>);
+
+impl AdminToken {
+    fn new(token: impl Into<String>) -> Self {
+        AdminToken(Arc::new(token.into()))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct WorkerToken(Arc<String>);
+
+impl WorkerToken {
+    fn new(token: impl Into<String>) -> Self {
+        WorkerToken(Arc::new(token.into()))
+    }
+}
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Sandbox creation failed: {}", source))]
@@ -162,14 +147,38 @@ pub enum Error {
     Execution { source: sandbox::Error },
     #[snafu(display("Evaluation operation failed: {}", source))]
     Evaluation { source: sandbox::Error },
+    #[snafu(display("Assessment operation failed: {}", source))]
+    Assessment { source: sandbox::Error },
     #[snafu(display("Linting operation failed: {}", source))]
     Linting { source: sandbox::Error },
     #[snafu(display("Expansion operation failed: {}", source))]
     Expansion { source: sandbox::Error },
     #[snafu(display("Formatting operation failed: {}", source))]
     Formatting { source: sandbox::Error },
+    #[snafu(display("Edition migration operation failed: {}", source))]
+    EditionMigration { source: sandbox::Error },
+    #[snafu(display("Profiling operation failed: {}", source))]
+    Profiling { source: sandbox::Error },
+    #[snafu(display("Optimization remarks operation failed: {}", source))]
+    OptimizationRemarks { source: sandbox::Error },
+    #[snafu(display("Completions operation failed: {}", source))]
+    Completions { source: sandbox::Error },
+    #[snafu(display("Hover operation failed: {}", source))]
+    Hovering { source: sandbox::Error },
+    #[snafu(display("Navigation operation failed: {}", source))]
+    Navigating { source: sandbox::Error },
+    #[snafu(display("Inlay hints operation failed: {}", source))]
+    InlayHints { source: sandbox::Error },
     #[snafu(display("Interpreting operation failed: {}", source))]
     Interpreting { source: sandbox::Error },
+    #[snafu(display("Verification operation failed: {}", source))]
+    Verifying { source: sandbox::Error },
+    #[snafu(display("{}", source))]
+    WorkerJob { source: sandbox::Error },
+    #[snafu(display("Bisection operation failed: {}", source))]
+    Bisecting { source: sandbox::Error },
+    #[snafu(display("Reduction operation failed: {}", source))]
+    Reducing { source: sandbox::Error },
     #[snafu(display("Caching operation failed: {}", source))]
     Caching { source: sandbox::Error },
     #[snafu(display("Gist creation failed: {}", source))]
@@ -178,6 +187,36 @@ pub enum Error {
     GistLoading { source: octocrab::Error },
     #[snafu(display("{PLAYGROUND_GITHUB_TOKEN} not set up for reading/writing gists"))]
     NoGithubToken,
+    #[snafu(display("Snippet creation failed: {}", source))]
+    SnippetCreation { source: storage::Error },
+    #[snafu(display("Snippet loading failed: {}", source))]
+    SnippetLoading { source: storage::Error },
+    #[snafu(display("The snippet {} does not exist", id))]
+    SnippetNotFound { id: String },
+    #[snafu(display("Snippet highlighting failed: {}", source))]
+    SnippetHighlighting { source: syntect::Error },
+    #[snafu(display("The snippet highlighting task failed: {}", source))]
+    SnippetHighlightingTask { source: tokio::task::JoinError },
+    #[snafu(display("{PLAYGROUND_SNIPPET_DATABASE} not set up for storing snippets"))]
+    NoSnippetStorage,
+    #[snafu(display("The template {} does not exist", name))]
+    TemplateNotFound { name: String },
+    #[snafu(display("{}", source))]
+    Templates { source: templates::Error },
+    #[snafu(display("Share link creation failed: {}", source))]
+    ShareLinkCreation { source: share_link::Error },
+    #[snafu(display("Share link loading failed: {}", source))]
+    ShareLinkLoading { source: share_link::Error },
+    #[snafu(display("{PLAYGROUND_SHARE_LINK_KEY} not set up for signing share links"))]
+    NoShareLinkKey,
+    #[snafu(display("Unable to keep the output: {}", source))]
+    ArtifactStorage { source: artifacts::Error },
+    #[snafu(display("The artifact {} does not exist or has expired", id))]
+    ArtifactNotFound { id: String },
+    #[snafu(display("{}", source))]
+    Repl { source: repl::Error },
+    #[snafu(display("The REPL session {} does not exist or has ended", id))]
+    ReplSessionNotFound { id: String },
     #[snafu(display("Unable to serialize response: {}", source))]
     Serialization { source: serde_json::Error },
     #[snafu(display("The value {:?} is not a valid target", value))]
@@ -190,28 +229,221 @@ pub enum Error {
     InvalidProcessAssembly { value: String },
     #[snafu(display("The value {:?} is not a valid channel", value,))]
     InvalidChannel { value: String },
+    #[snafu(display("Unoptimized MIR is only available on nightly"))]
+    UnoptimizedMirRequiresNightly,
+    #[snafu(display("Tracing macros and macro backtraces are only available on nightly"))]
+    MacroDebuggingRequiresNightly,
+    #[snafu(display("Careful executions are only available on nightly"))]
+    CarefulRequiresNightly,
     #[snafu(display("The value {:?} is not a valid mode", value))]
     InvalidMode { value: String },
     #[snafu(display("The value {:?} is not a valid edition", value))]
     InvalidEdition { value: String },
     #[snafu(display("The value {:?} is not a valid crate type", value))]
     InvalidCrateType { value: String },
+    #[snafu(display("The value {:?} is not a valid color output", value))]
+    InvalidColorOutput { value: String },
+    #[snafu(display("The value {:?} is not a valid error format", value))]
+    InvalidErrorFormat { value: String },
+    #[snafu(display("The value {:?} is not a valid date", value))]
+    InvalidDate { value: String },
+    #[snafu(display("The value {:?} is not a valid regression", value))]
+    InvalidRegression { value: String },
+    #[snafu(display("The value {:?} is not a valid strategy", value))]
+    InvalidStrategy { value: String },
+    #[snafu(display("The value {:?} is not a valid test runner", value))]
+    InvalidTestRunner { value: String },
+    #[snafu(display("The value {:?} is not a valid navigation target", value))]
+    InvalidNavigationTarget { value: String },
+    #[snafu(display("Bisections may only be run as jobs"))]
+    BisectRequiresJob,
+    #[snafu(display("Reductions may only be run as jobs"))]
+    ReduceRequiresJob,
+    #[snafu(display("The value {:?} is not a valid job endpoint", value))]
+    InvalidJobEndpoint { value: String },
+    #[snafu(display("A batch may have at most {} operations", max))]
+    BatchTooLarge { max: usize },
+    #[snafu(display("The code is {} bytes, but may be at most {} bytes", size, max_size))]
+    CodeTooLarge { size: usize, max_size: usize },
+    #[snafu(display(
+        "The code of the batch is {} bytes in total, but may be at most {} bytes",
+        size,
+        max_size
+    ))]
+    BatchCodeTooLarge { size: usize, max_size: usize },
+    #[snafu(display("At most {} operations may run at once on a WebSocket", max))]
+    WebSocketBusy { max: usize },
+    #[snafu(display("Unable to parse the WebSocket message: {}", source))]
+    InvalidWebSocketMessage { source: serde_json::Error },
     #[snafu(display("No request was provided"))]
     RequestMissing,
+    #[snafu(display(
+        "The Idempotency-Key header must be 1 to {} visible ASCII characters",
+        max
+    ))]
+    InvalidIdempotencyKey { max: usize },
+    #[snafu(display(
+        "A request with this Idempotency-Key is still being made; please try again shortly"
+    ))]
+    IdempotencyKeyInUse,
+    #[snafu(display("This Idempotency-Key was already used for a different request"))]
+    IdempotencyKeyReused,
     #[snafu(display("The cache has been poisoned"))]
     CachePoisoned,
+    #[snafu(display("The server is too busy to handle this request; please try again shortly"))]
+    ServerBusy,
+    #[snafu(display("Too many requests have been made; please try again in {} seconds", retry_after.as_secs()))]
+    RateLimited { retry_after: Duration },
+    #[snafu(display("The daily quota for this API key has been used; please try again in {} seconds", retry_after.as_secs()))]
+    QuotaExceeded { retry_after: Duration },
+    #[snafu(display("The API key is not valid"))]
+    InvalidApiKey,
+    #[snafu(display("Sandboxes are temporarily unavailable; please try again in {} seconds", retry_after.as_secs()))]
+    SandboxUnavailable { retry_after: Duration },
+    #[snafu(display("The job {} does not exist or has expired", id))]
+    JobNotFound { id: String },
+    #[snafu(display("The playground is undergoing maintenance; please try again later"))]
+    Maintenance,
+    #[snafu(display(
+        "The server {}, so code can't be compiled or run right now; please try again shortly",
+        pressure
+    ))]
+    Overloaded { pressure: load_shedding::Pressure },
+    #[snafu(display("The request is {} bytes, but may be at most {} bytes", size, max_size))]
+    RequestTooLarge { size: usize, max_size: usize },
+    #[snafu(display("The request took longer than {} seconds", timeout.as_secs()))]
+    RequestTimedOut { timeout: Duration },
 }
 
 type Result<T, E = Error> = ::std::result::Result<T, E>;
 
-#[derive(Debug, Clone, Serialize)]
+impl Error {
+    fn is_docker_failure(&self) -> bool {
+        self.sandbox_error()
+            .is_some_and(sandbox::Error::is_docker_failure)
+    }
+
+    /// When the requested channel is unavailable, how long until it
+    /// will be tried again.
+    fn channel_retry_after(&self) -> Option<Duration> {
+        match self.sandbox_error()? {
+            sandbox::Error::ChannelUnavailable { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// When the request was too large, how large it was and how
+    /// large it may be.
+    fn size_limit(&self) -> Option<(usize, usize)> {
+        match self {
+            Error::CodeTooLarge { size, max_size }
+            | Error::BatchCodeTooLarge { size, max_size }
+            | Error::RequestTooLarge { size, max_size }
+            | Error::SnippetCreation {
+                source: storage::Error::TooLarge { size, max_size },
+            } => Some((*size, *max_size)),
+            _ => match self.sandbox_error()? {
+                sandbox::Error::CodeTooLarge { size, max_size } => Some((*size, *max_size)),
+                _ => None,
+            },
+        }
+    }
+
+    fn sandbox_error(&self) -> Option<&sandbox::Error> {
+        match self {
+            Error::Compilation { source }
+            | Error::Execution { source }
+            | Error::Evaluation { source }
+            | Error::Assessment { source }
+            | Error::Linting { source }
+            | Error::Expansion { source }
+            | Error::Formatting { source }
+            | Error::EditionMigration { source }
+            | Error::Profiling { source }
+            | Error::OptimizationRemarks { source }
+            | Error::Completions { source }
+            | Error::Hovering { source }
+            | Error::Navigating { source }
+            | Error::InlayHints { source }
+            | Error::Interpreting { source }
+            | Error::Verifying { source }
+            | Error::Bisecting { source }
+            | Error::Reducing { source } => Some(source),
+            Error::Repl {
+                source: repl::Error::Sandbox { source },
+            } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct ErrorJson {
     error: String,
+    #[serde(rename = "requestId", skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    /// For requests that were too large, how large they were, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<usize>,
+    /// For requests that were too large, how large they may be, in
+    /// bytes
+    #[serde(rename = "maxSize", skip_serializing_if = "Option::is_none")]
+    max_size: Option<usize>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl ErrorJson {
+    fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            request_id: None,
+            size: None,
+            max_size: None,
+        }
+    }
+
+    fn with_size_limit(self, size: usize, max_size: usize) -> Self {
+        Self {
+            size: Some(size),
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+}
+
+/// Information about how a response was produced, sent alongside
+/// the response itself.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct WithMetadata<T> {
+    #[serde(flatten)]
+    response: T,
+    cached: bool,
+}
+
+impl<T> WithMetadata<T> {
+    fn fresh(response: T) -> Self {
+        Self {
+            response,
+            cached: false,
+        }
+    }
+
+    fn cached(response: T) -> Self {
+        Self {
+            response,
+            cached: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 struct CompileRequest {
     target: String,
+    /// Also compiled to, in the same build when possible
+    #[serde(default)]
+    targets: Vec<String>,
+    /// Turns off the MIR optimizations, which only nightly allows
+    #[serde(default, rename = "unoptimizedMir")]
+    unoptimized_mir: bool,
     #[serde(rename = "assemblyFlavor")]
     assembly_flavor: Option<String>,
     #[serde(rename = "demangleAssembly")]
@@ -227,18 +459,86 @@ struct CompileRequest {
     tests: bool,
     #[serde(default)]
     backtrace: bool,
+    #[serde(default)]
+    color: String,
     code: String,
+    /// Keeps the output of each target as an artifact to download,
+    /// instead of including it in the response
+    #[serde(default)]
+    artifacts: bool,
+    /// Prints each macro expansion, which only nightly allows
+    #[serde(default, rename = "traceMacros")]
+    trace_macros: bool,
+    /// Shows every macro that an error came out of, which only
+    /// nightly allows
+    #[serde(default, rename = "macroBacktrace")]
+    macro_backtrace: bool,
+    /// `human` or `short`, which shows each diagnostic on one line.
+    /// Defaults to `human`
+    #[serde(default, rename = "errorFormat")]
+    error_format: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct CompileResponse {
     success: bool,
     code: String,
+    /// The output for each of the other targets that were requested
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    outputs: BTreeMap<String, String>,
+    /// Keyed by target, when artifacts were requested or an output
+    /// was too large to be part of the response. The outputs are then
+    /// empty, or only the start of them.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    artifacts: BTreeMap<String, ArtifactResponse>,
     stdout: String,
     stderr: String,
+    /// All of `stdout`, when it was too large for the response to
+    /// have more than the start of it
+    #[serde(rename = "stdoutArtifact", skip_serializing_if = "Option::is_none")]
+    stdout_artifact: Option<ArtifactResponse>,
+    /// Likewise, for `stderr`
+    #[serde(rename = "stderrArtifact", skip_serializing_if = "Option::is_none")]
+    stderr_artifact: Option<ArtifactResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sccache: Option<SccacheStats>,
+    /// `try-nightly` when the code uses features only nightly has
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+    /// How many warnings the compiler reported
+    #[serde(rename = "warningCount")]
+    warning_count: usize,
+    /// How many errors the compiler reported
+    #[serde(rename = "errorCount")]
+    error_count: usize,
+    /// Fixes that the compiler is sure of, to apply as they are
+    suggestions: Vec<Suggestion>,
+    /// Of the image it was run from, when image IDs are reported
+    #[serde(rename = "imageId", skip_serializing_if = "Option::is_none")]
+    image_id: Option<String>,
+    /// It failed for writing more than it may to the disk, rather than
+    /// because of the code
+    #[serde(
+        rename = "diskQuotaExceeded",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    disk_quota_exceeded: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Where a file produced by an operation can be downloaded from,
+/// until it expires.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactResponse {
+    id: String,
+    url: String,
+    /// Bytes
+    size: usize,
+    /// Seconds since the Unix epoch
+    expires_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
 struct ExecuteRequest {
     channel: String,
     mode: String,
@@ -249,24 +549,499 @@ struct ExecuteRequest {
     tests: bool,
     #[serde(default)]
     backtrace: bool,
+    #[serde(default)]
+    color: String,
     code: String,
+    /// Passed to the program, or to the test harness
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    stdin: String,
+    /// Keeps the binary that was built, so that `/rerun` can run it
+    /// again without building it. Tests and libraries don't have one.
+    #[serde(default, rename = "keepBinary")]
+    keep_binary: bool,
+    /// Puts code that has no `main` function inside one, which prints
+    /// the value of the last expression.
+    #[serde(default, rename = "wrapMain")]
+    wrap_main: bool,
+    /// `auto-detect` runs the tests of code that has tests but no
+    /// `main` function; `exact` doesn't. Defaults to `auto-detect`.
+    #[serde(default)]
+    strategy: String,
+    /// Prints each macro expansion, which only nightly allows
+    #[serde(default, rename = "traceMacros")]
+    trace_macros: bool,
+    /// Shows every macro that an error came out of, which only
+    /// nightly allows
+    #[serde(default, rename = "macroBacktrace")]
+    macro_backtrace: bool,
+    /// Also returns stdout and stderr as records, in the order that
+    /// they were written
+    #[serde(default, rename = "outputRecords")]
+    output_records: bool,
+    /// `libtest` or `nextest`, which also reports how each test went.
+    /// Defaults to `libtest`
+    #[serde(default, rename = "testRunner")]
+    test_runner: String,
+    /// Builds and runs it with `cargo careful`, which checks more of
+    /// what is given to the standard library. Only available on
+    /// nightly
+    #[serde(default)]
+    careful: bool,
+    /// Runs the program with a clock that starts at the same moment
+    /// every time, and the tests one at a time, so that it writes the
+    /// same each time it is run
+    #[serde(default)]
+    deterministic: bool,
+    /// `human` or `short`, which shows each diagnostic on one line.
+    /// Defaults to `human`
+    #[serde(default, rename = "errorFormat")]
+    error_format: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct ExecuteResponse {
     success: bool,
     stdout: String,
     stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sccache: Option<SccacheStats>,
+    /// `try-nightly` when the code uses features only nightly has
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+    /// The binary that was built, when it was asked to be kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary: Option<ArtifactResponse>,
+    /// All of `stdout`, when it was too large for the response to
+    /// have more than the start of it
+    #[serde(rename = "stdoutArtifact", skip_serializing_if = "Option::is_none")]
+    stdout_artifact: Option<ArtifactResponse>,
+    /// Likewise, for `stderr`
+    #[serde(rename = "stderrArtifact", skip_serializing_if = "Option::is_none")]
+    stderr_artifact: Option<ArtifactResponse>,
+    /// When they were asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    records: Option<Vec<OutputRecord>>,
+    /// How many warnings the compiler reported
+    #[serde(rename = "warningCount")]
+    warning_count: usize,
+    /// How many errors the compiler reported
+    #[serde(rename = "errorCount")]
+    error_count: usize,
+    /// When the tests were run by nextest
+    #[serde(rename = "testResults", skip_serializing_if = "Option::is_none")]
+    test_results: Option<Vec<TestResult>>,
+    /// The checks that only careful executions make which failed, as
+    /// their messages
+    #[serde(rename = "carefulChecks", skip_serializing_if = "Vec::is_empty")]
+    careful_checks: Vec<String>,
+    /// Of the image it was run from, when image IDs are reported
+    #[serde(rename = "imageId", skip_serializing_if = "Option::is_none")]
+    image_id: Option<String>,
+    /// It was stopped after keeping the CPU busy without writing
+    /// anything
+    #[serde(rename = "abuseSuspected", skip_serializing_if = "std::ops::Not::not")]
+    abuse_suspected: bool,
+    /// It was stopped for writing too quickly, so the output is cut
+    /// short
+    #[serde(
+        rename = "outputRateLimited",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    output_rate_limited: bool,
+    /// It was killed for using more memory than it may, rather than
+    /// for running too long
+    #[serde(rename = "outOfMemory", skip_serializing_if = "std::ops::Not::not")]
+    out_of_memory: bool,
+    /// It failed for writing more than it may to the disk
+    #[serde(
+        rename = "diskQuotaExceeded",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    disk_quota_exceeded: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TestResult {
+    /// Such as `tests::it_works`
+    name: String,
+    /// `passed`, `failed` or `ignored`
+    outcome: String,
+    duration_ms: Option<u64>,
+    /// What it wrote, when it failed
+    stdout: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct Suggestion {
+    message: String,
+    /// Of what to replace, in bytes from the start of the code
+    start: usize,
+    end: usize,
+    replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct OutputRecord {
+    /// Either `stdout` or `stderr`
+    stream: String,
+    /// Since the first line that was written to either stream
+    timestamp_ms: u64,
+    /// With the newline, when the line was finished
+    text: String,
+}
+
+/// An execution, along with the binary that was kept from it.
+#[derive(Debug, Clone)]
+struct Executed {
+    response: sandbox::ExecuteResponse,
+    binary: Option<ArtifactResponse>,
+    spilled: SpilledOutputs,
+}
+
+/// A compilation, along with the outputs of each target that were
+/// too large to be part of the response, keyed by target.
+#[derive(Debug, Clone)]
+struct Compiled {
+    response: sandbox::CompileResponse,
+    outputs: BTreeMap<String, ArtifactResponse>,
+    spilled: SpilledOutputs,
+}
+
+/// The artifacts holding all of `stdout` and `stderr`, when they were
+/// too large to be part of the response.
+#[derive(Debug, Clone, Default)]
+struct SpilledOutputs {
+    stdout: Option<ArtifactResponse>,
+    stderr: Option<ArtifactResponse>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct RerunRequest {
+    /// The ID of the binary that `/execute` kept
+    binary: String,
+    /// The channel that the binary was built with
+    channel: String,
+    #[serde(default)]
+    backtrace: bool,
+    #[serde(default)]
+    color: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    stdin: String,
+}
+
+/// A rerun of a particular binary, so that the response is only
+/// reused for the same one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Rerun {
+    binary: String,
+    request: sandbox::RerunRequest,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReplSessionResponse {
+    id: String,
+    /// Seconds that the session may go unused before it is closed
+    idle_timeout: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct ReplEvaluateRequest {
+    /// Statements, items, or an expression, which may use what
+    /// earlier evaluations in the session defined
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct ReplEvaluateResponse {
+    success: bool,
+    /// The value of the last expression, when the code ended with one
+    value: Option<String>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct ReplVariablesResponse {
+    variables: Vec<ReplVariable>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct ReplVariable {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct SccacheStats {
+    #[serde(rename = "cacheHits")]
+    cache_hits: u64,
+    #[serde(rename = "cacheMisses")]
+    cache_misses: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct CompareRequest {
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(rename = "crateType")]
+    crate_type: String,
+    tests: bool,
+    #[serde(default)]
+    backtrace: bool,
+    code: String,
+}
+
+impl CompareRequest {
+    fn on_channel(&self, channel: &str) -> ExecuteRequest {
+        ExecuteRequest {
+            channel: channel.into(),
+            mode: self.mode.clone(),
+            edition: self.edition.clone(),
+            crate_type: self.crate_type.clone(),
+            tests: self.tests,
+            backtrace: self.backtrace,
+            color: String::new(),
+            code: self.code.clone(),
+            args: vec![],
+            stdin: String::new(),
+            keep_binary: false,
+            wrap_main: false,
+            strategy: String::new(),
+            trace_macros: false,
+            macro_backtrace: false,
+            output_records: false,
+            test_runner: String::new(),
+            careful: false,
+            deterministic: false,
+            error_format: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CompareResponse {
+    stable: ExecuteResponse,
+    beta: ExecuteResponse,
+    nightly: ExecuteResponse,
+    /// A unified diff of the diagnostics on stable and beta
+    beta_diff: String,
+    /// A unified diff of the diagnostics on stable and nightly
+    nightly_diff: String,
+}
+
+impl CompareResponse {
+    fn new(stable: ExecuteResponse, beta: ExecuteResponse, nightly: ExecuteResponse) -> Self {
+        let baseline = diff::diagnostics(&stable.stderr);
+        let diff_from_stable = |name, other: &ExecuteResponse| {
+            diff::unified("stable", &baseline, name, &diff::diagnostics(&other.stderr))
+        };
+
+        Self {
+            beta_diff: diff_from_stable("beta", &beta),
+            nightly_diff: diff_from_stable("nightly", &nightly),
+            stable,
+            beta,
+            nightly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct OutputDiffRequest {
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type")]
+    crate_type: String,
+    #[serde(default)]
+    tests: bool,
+    #[serde(default)]
+    backtrace: bool,
+    before: Submission,
+    after: Submission,
+}
+
+/// The code itself, or the ID of a snippet of it
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+enum Submission {
+    Code { code: String },
+    Snippet { snippet: String },
+}
+
+impl OutputDiffRequest {
+    fn with_code(&self, code: String) -> ExecuteRequest {
+        ExecuteRequest {
+            channel: self.channel.clone(),
+            mode: self.mode.clone(),
+            edition: self.edition.clone(),
+            crate_type: self.crate_type.clone(),
+            tests: self.tests,
+            backtrace: self.backtrace,
+            code,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct OutputDiffResponse {
+    before: ExecuteResponse,
+    after: ExecuteResponse,
+    /// A unified diff of what they wrote to stdout
+    stdout_diff: String,
+    /// A unified diff of the diagnostics
+    diagnostics_diff: String,
+    /// Whether there's no difference in either
+    identical: bool,
+}
+
+impl OutputDiffResponse {
+    fn new(before: ExecuteResponse, after: ExecuteResponse) -> Self {
+        let stdout_diff = diff::unified("before", &before.stdout, "after", &after.stdout);
+        let diagnostics_diff = diff::unified(
+            "before",
+            &diff::diagnostics(&before.stderr),
+            "after",
+            &diff::diagnostics(&after.stderr),
+        );
+
+        Self {
+            identical: stdout_diff.is_empty() && diagnostics_diff.is_empty(),
+            before,
+            after,
+            stdout_diff,
+            diagnostics_diff,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct AssemblyDiffRequest {
+    channel: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type")]
+    crate_type: String,
+    assembly_flavor: Option<String>,
+    /// The mode to compare from
+    #[serde(default = "default_diff_before")]
+    before: String,
+    /// The mode to compare to
+    #[serde(default = "default_diff_after")]
+    after: String,
+    code: String,
+}
+
+impl AssemblyDiffRequest {
+    fn in_mode(&self, mode: &str) -> CompileRequest {
+        CompileRequest {
+            target: "asm".into(),
+            targets: vec![],
+            unoptimized_mir: false,
+            assembly_flavor: self.assembly_flavor.clone(),
+            demangle_assembly: Some("demangle".into()),
+            process_assembly: Some("filter".into()),
+            channel: self.channel.clone(),
+            mode: mode.into(),
+            edition: self.edition.clone(),
+            crate_type: self.crate_type.clone(),
+            tests: false,
+            backtrace: false,
+            color: String::new(),
+            code: self.code.clone(),
+            artifacts: false,
+            trace_macros: false,
+            macro_backtrace: false,
+            error_format: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct AssemblyDiffResponse {
+    before: CompileResponse,
+    after: CompileResponse,
+    /// A unified diff of the assembly
+    diff: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct MirDiffRequest {
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type")]
+    crate_type: String,
+    code: String,
+}
+
+impl MirDiffRequest {
+    fn with_optimizations(&self, optimized: bool) -> CompileRequest {
+        CompileRequest {
+            target: "mir".into(),
+            targets: vec![],
+            unoptimized_mir: !optimized,
+            assembly_flavor: None,
+            demangle_assembly: None,
+            process_assembly: None,
+            channel: "nightly".into(),
+            mode: self.mode.clone(),
+            edition: self.edition.clone(),
+            crate_type: self.crate_type.clone(),
+            tests: false,
+            backtrace: false,
+            color: String::new(),
+            code: self.code.clone(),
+            artifacts: false,
+            trace_macros: false,
+            macro_backtrace: false,
+            error_format: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct MirDiffResponse {
+    unoptimized: CompileResponse,
+    optimized: CompileResponse,
+    /// A unified diff of the MIR
+    diff: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 struct FormatRequest {
     code: String,
     #[serde(default)]
     edition: String,
+    /// Only these lines are formatted, when given
+    #[serde(default)]
+    lines: Option<LineRange>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Counted from one, including both the first and the last line
+#[derive(Debug, Copy, Clone, Deserialize, ToSchema)]
+struct LineRange {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct FormatResponse {
     success: bool,
     code: String,
@@ -274,7 +1049,29 @@ struct FormatResponse {
     stderr: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct EditionMigrationRequest {
+    code: String,
+    /// The edition that the code was written for
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct EditionMigrationResponse {
+    success: bool,
+    /// The code as `cargo fix --edition` left it
+    code: String,
+    /// The edition that the code was migrated to
+    edition: String,
+    /// A unified diff from the code that was given to the migrated code
+    diff: String,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 struct ClippyRequest {
     code: String,
     #[serde(default)]
@@ -283,73 +1080,681 @@ struct ClippyRequest {
     crate_type: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct ClippyResponse {
     success: bool,
     stdout: String,
     stderr: String,
+    /// How many warnings the compiler reported
+    #[serde(rename = "warningCount")]
+    warning_count: usize,
+    /// How many errors the compiler reported
+    #[serde(rename = "errorCount")]
+    error_count: usize,
+    /// Fixes that the compiler is sure of, to apply as they are
+    suggestions: Vec<Suggestion>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 struct MiriRequest {
     code: String,
     #[serde(default)]
     edition: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct MiriResponse {
     success: bool,
     stdout: String,
     stderr: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct MacroExpansionRequest {
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct KaniRequest {
+    /// With `#[kani::proof]` harnesses
     code: String,
     #[serde(default)]
     edition: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct MacroExpansionResponse {
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct KaniResponse {
+    /// Whether every harness was verified
     success: bool,
+    harnesses: Vec<KaniHarness>,
     stdout: String,
     stderr: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
-struct CrateInformation {
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct KaniHarness {
     name: String,
-    version: String,
-    id: String,
+    verified: bool,
+    failed_checks: Vec<KaniCheck>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
-struct MetaCratesResponse {
-    crates: Arc<[CrateInformation]>,
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct KaniCheck {
+    /// Such as `attempt to add with overflow`
+    description: String,
+    /// Counted from one, when the check is in the code itself
+    line: Option<usize>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
-struct MetaVersionResponse {
-    version: Arc<str>,
-    hash: Arc<str>,
-    date: Arc<str>,
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct BisectRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+    /// The date of a nightly without the regression
+    start: String,
+    /// The date of a nightly with the regression, or the latest
+    /// nightly when unset
+    end: Option<String>,
+    /// One of `error`, `success`, `ice`, `non-ice`, or `non-error`
+    #[serde(default)]
+    regression: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReduceRequest {
+    channel: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type")]
+    crate_type: String,
+    code: String,
+    /// `ice`, or the text of the error to keep, such as its code,
+    /// `E0308`. When unset, the first error that the code has.
+    #[serde(default)]
+    preserve: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReduceResponse {
+    /// Whether the code had the problem to begin with
+    reproduced: bool,
+    /// As little of the code as still has the problem
+    code: String,
+    /// How many times it was compiled
+    attempts: usize,
+    /// From compiling the reduced code
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct BisectResponse {
+    success: bool,
+    /// Named like the channel, such as `nightly-2022-06-01`
+    #[serde(rename = "regressedNightly")]
+    regressed_nightly: Option<String>,
+    #[serde(rename = "commitRange")]
+    commit_range: Option<String>,
+    #[serde(rename = "regressedCommit")]
+    regressed_commit: Option<String>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct MacroExpansionRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    /// Only expands the item at this path, such as
+    /// `my_module::MyStruct`, instead of the whole crate
+    #[serde(default, rename = "expandItem")]
+    expand_item: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct MacroExpansionResponse {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct TimePassesRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+    mode: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct TimePassesResponse {
+    success: bool,
+    /// In the order the compiler reported them. Some passes are made
+    /// up of others, so their times overlap
+    passes: Vec<CompilerPass>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct CompilerPass {
+    name: String,
+    milliseconds: u64,
+    /// Not every platform reports the memory that was used
+    rss_before_mb: Option<u64>,
+    rss_after_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct OptimizationRemarksRequest {
+    code: String,
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct OptimizationRemarksResponse {
+    success: bool,
+    /// Only those about the code itself, in the order the compiler
+    /// reported them
+    remarks: Vec<OptimizationRemark>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct OptimizationRemark {
+    /// The LLVM pass that made the remark, such as `inline`
+    pass: String,
+    /// One of `passed`, `missed`, `analysis` or `failure`
+    kind: String,
+    message: String,
+    line: u32,
+    /// Not always known
+    column: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct CompletionsRequest {
+    code: String,
+    /// Where the cursor is, in bytes from the start of the code
+    offset: usize,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct CompletionsResponse {
+    success: bool,
+    /// The most likely first
+    completions: Vec<Completion>,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct Completion {
+    label: String,
+    /// Such as `function`, `struct` or `keyword`
+    kind: Option<String>,
+    /// Usually the type or the signature
+    detail: Option<String>,
+    /// What goes in place of the word at the cursor
+    insert_text: String,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct HoverRequest {
+    code: String,
+    /// Where the cursor is, in bytes from the start of the code
+    offset: usize,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct HoverResponse {
+    success: bool,
+    /// Missing when there is nothing at the cursor to tell about
+    hover: Option<Hover>,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct Hover {
+    /// The type of the symbol, or how it is declared, as Markdown
+    signature: String,
+    /// As Markdown
+    documentation: Option<String>,
+    /// Of the symbol, in bytes from the start of the code
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct NavigationRequest {
+    code: String,
+    /// Where the cursor is, in bytes from the start of the code
+    offset: usize,
+    /// Either `definition` or `references`
+    target: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct NavigationResponse {
+    success: bool,
+    locations: Vec<Location>,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct Location {
+    /// Such as `library/core/src/option.rs` for the standard library.
+    /// Missing for the code itself
+    file: Option<String>,
+    /// Counted from one
+    line: usize,
+    /// In bytes from the start of the code, only for the code itself
+    start: Option<usize>,
+    end: Option<usize>,
+    /// What is there, for any other file
+    excerpt: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct InlayHintsRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct InlayHintsResponse {
+    success: bool,
+    hints: Vec<InlayHint>,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct InlayHint {
+    /// Where the hint goes, in bytes from the start of the code
+    offset: usize,
+    /// Such as `: Vec<i32>` or `capacity:`
+    label: String,
+    /// Either `type` or `parameter`
+    kind: String,
+    /// Whether a space is wanted before the hint
+    padding_left: bool,
+    /// Whether a space is wanted after the hint
+    padding_right: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct CrateInformation {
+    name: String,
+    version: String,
+    id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct MetaCratesResponse {
+    #[schema(value_type = Vec<CrateInformation>)]
+    crates: Arc<[CrateInformation]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct MetaVersionResponse {
+    #[schema(value_type = String)]
+    version: Arc<str>,
+    #[schema(value_type = String)]
+    hash: Arc<str>,
+    #[schema(value_type = String)]
+    date: Arc<str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct ClippyLint {
+    name: String,
+    group: Option<String>,
+    level: String,
+    description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct MetaClippyLintsResponse {
+    #[schema(value_type = Vec<ClippyLint>)]
+    lints: Arc<[ClippyLint]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct MetaVersionsResponse {
+    stable: ChannelVersion,
+    beta: ChannelVersion,
+    nightly: ChannelVersion,
+    #[schema(value_type = String)]
+    rustfmt: Arc<str>,
+    #[schema(value_type = String)]
+    clippy: Arc<str>,
+}
+
+/// A channel that can't be run reports why instead of its version,
+/// without affecting the others.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(untagged)]
+enum ChannelVersion {
+    Available(FullVersion),
+    Unavailable {
+        #[schema(value_type = String)]
+        error: Arc<str>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct FullVersion {
+    #[schema(value_type = String)]
+    rustc: Arc<str>,
+    #[schema(value_type = String)]
+    cargo: Arc<str>,
+    /// Of the image the channel is run from, such as `sha256:0123…`
+    #[schema(value_type = String)]
+    image_id: Arc<str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+struct MetaTargetsResponse {
+    #[schema(value_type = Vec<String>)]
+    stable: Arc<[String]>,
+    #[schema(value_type = Vec<String>)]
+    beta: Arc<[String]>,
+    #[schema(value_type = Vec<String>)]
+    nightly: Arc<[String]>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct MetaImagesResponse {
+    stable: ImageStatus,
+    beta: ImageStatus,
+    nightly: ImageStatus,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ImageStatus {
+    /// The ID of the image that sandboxes are started from, once the
+    /// image has been checked for updates.
+    active: Option<String>,
+    /// Seconds since the Unix epoch
+    checked_at: Option<u64>,
+    /// Seconds since the Unix epoch
+    updated_at: Option<u64>,
+    /// Why the last check for a newer image failed.
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct MetaUsageResponse {
+    /// Seconds since the Unix epoch that the counts start from
+    since: u64,
+    operations: Vec<OperationUsage>,
+}
+
+/// The sandbox operations run by channel and mode
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct OperationUsage {
+    /// Such as `Execute`
+    operation: String,
+    channel: Option<String>,
+    mode: Option<String>,
+    count: u64,
+    /// Of the most recent runs
+    p50_ms: u64,
+    p90_ms: u64,
+    p99_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 struct MetaGistCreateRequest {
     code: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct MetaGistResponse {
     id: String,
     url: String,
     code: String,
 }
 
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct SnippetCreateRequest {
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SnippetResponse {
+    id: String,
+    /// A short link that opens the snippet in the playground.
+    url: String,
+    code: String,
+    /// Seconds since the Unix epoch
+    created_at: u64,
+    retrievals: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct HighlightQuery {
+    #[serde(default)]
+    theme: highlight::Theme,
+}
+
+/// A page of the artifact, instead of all of it
+#[derive(Debug, Clone, Deserialize)]
+struct ArtifactQuery {
+    /// Bytes
+    offset: Option<usize>,
+    /// Bytes
+    length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EmbedQuery {
+    #[serde(default)]
+    theme: highlight::Theme,
+    /// Whether to show what running the code outputs
+    #[serde(default)]
+    run: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TemplateRequest {
+    title: String,
+    #[serde(default)]
+    description: String,
+    code: String,
+    channel: String,
+    #[serde(default)]
+    edition: String,
+    crate_type: String,
+    tests: bool,
+    /// Of the available crates, those that the code uses
+    #[serde(default)]
+    crates: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct TemplateResponse {
+    /// Identifies the template in `/templates/{name}`.
+    name: String,
+    title: String,
+    description: String,
+    code: String,
+    channel: String,
+    edition: String,
+    crate_type: String,
+    tests: bool,
+    /// Of the available crates, those that the code uses.
+    crates: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct ShareLinkCreateRequest {
+    code: String,
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(rename = "crateType")]
+    crate_type: String,
+    tests: bool,
+    #[serde(default)]
+    backtrace: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ShareLinkResponse {
+    /// Holds the code and options itself, so that it can be opened
+    /// without the snippet database.
+    link: String,
+    /// Opens the code in the playground.
+    url: String,
+    code: String,
+    channel: String,
+    mode: String,
+    edition: String,
+    crate_type: String,
+    tests: bool,
+    backtrace: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct JobResponse {
+    id: String,
+    #[serde(flatten)]
+    state: JobState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "state", rename_all = "camelCase")]
+enum JobState {
+    Queued {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<usize>,
+        /// In seconds
+        #[serde(rename = "estimatedWait", skip_serializing_if = "Option::is_none")]
+        estimated_wait: Option<u64>,
+    },
+    Running {
+        /// `container-started`, `compiling` or `running`, once the
+        /// sandbox has said how far it has got
+        #[serde(skip_serializing_if = "Option::is_none")]
+        phase: Option<String>,
+    },
+    Finished {
+        status: u16,
+        response: serde_json::Value,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct BatchRequest {
+    operations: Vec<BatchOperation>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct BatchOperation {
+    /// Any of the endpoints that may be run as a job, such as `compile`
+    endpoint: String,
+    /// The request that would be sent to the endpoint
+    request: serde_json::Value,
+}
+
+/// An operation sent over a WebSocket. Each message sent back is a
+/// [`JobResponse`] with the same ID.
 #[derive(Debug, Clone, Deserialize)]
+struct WebSocketRequest {
+    /// Chosen by the client to tell the operations apart
+    id: String,
+    endpoint: String,
+    request: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct BatchResponse {
+    /// In the same order as the operations
+    results: Vec<BatchResult>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct BatchResult {
+    status: u16,
+    response: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AdminStateResponse {
+    draining: bool,
+    expensive: SandboxPoolState,
+    cheap: SandboxPoolState,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SandboxPoolState {
+    running: usize,
+    max_running: usize,
+    waiting: usize,
+    max_waiting: usize,
+    /// Seconds
+    average_run_time: f64,
+}
+
+impl From<limiter::Usage> for SandboxPoolState {
+    fn from(me: limiter::Usage) -> Self {
+        Self {
+            running: me.running,
+            max_running: me.max_running,
+            waiting: me.waiting,
+            max_waiting: me.max_waiting,
+            average_run_time: me.run_duration.as_secs_f64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadinessResponse {
+    ready: bool,
+    warming_up: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 struct EvaluateRequest {
     version: String,
     optimize: String,
@@ -360,12 +1765,50 @@ struct EvaluateRequest {
     tests: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 struct EvaluateResponse {
     result: String,
     error: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct AssessRequest {
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    crate_type: String,
+    /// The student's code
+    code: String,
+    /// The instructor's tests, such as `use super::*; #[test] fn
+    /// it_adds() { ... }`, which are compiled as a module of their own
+    hidden_tests: String,
+}
+
+/// Neither the hidden tests nor what was written is included, as
+/// either could give the tests away.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct AssessResponse {
+    /// Whether it built and every test passed, including every one of
+    /// the hidden tests
+    success: bool,
+    warning_count: usize,
+    error_count: usize,
+    tests: Vec<AssessedTest>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct AssessedTest {
+    /// Such as `hidden_tests::it_adds`
+    name: String,
+    /// `passed`, `failed` or `ignored`
+    outcome: String,
+    duration_ms: Option<u64>,
+}
+
 impl TryFrom<CompileRequest> for sandbox::CompileRequest {
     type Error = Error;
 
@@ -386,88 +1829,731 @@ impl TryFrom<CompileRequest> for sandbox::CompileRequest {
             None => None,
         };
 
-        let target = match (target, assembly_flavor, demangle, process_assembly) {
-            (
-                sandbox::CompileTarget::Assembly(_, _, _),
-                Some(flavor),
-                Some(demangle),
-                Some(process),
-            ) => sandbox::CompileTarget::Assembly(flavor, demangle, process),
-            _ => target,
-        };
+        let with_assembly_options =
+            |target| match (target, assembly_flavor, demangle, process_assembly) {
+                (
+                    sandbox::CompileTarget::Assembly(_, _, _),
+                    Some(flavor),
+                    Some(demangle),
+                    Some(process),
+                ) => sandbox::CompileTarget::Assembly(flavor, demangle, process),
+                _ => target,
+            };
+
+        let extra_targets = me
+            .targets
+            .iter()
+            .map(|t| parse_target(t).map(with_assembly_options))
+            .collect::<Result<_>>()?;
+
+        let channel = parse_channel(&me.channel)?;
+        ensure!(
+            !me.unoptimized_mir || matches!(channel, sandbox::Channel::Nightly { .. }),
+            UnoptimizedMirRequiresNightlySnafu
+        );
+        ensure_macro_debugging_allowed(channel, me.trace_macros, me.macro_backtrace)?;
 
         Ok(sandbox::CompileRequest {
-            target,
-            channel: parse_channel(&me.channel)?,
+            target: with_assembly_options(target),
+            extra_targets,
+            unoptimized_mir: me.unoptimized_mir,
+            channel,
             mode: parse_mode(&me.mode)?,
             edition: parse_edition(&me.edition)?,
             crate_type: parse_crate_type(&me.crate_type)?,
             tests: me.tests,
             backtrace: me.backtrace,
+            color: parse_color_output(&me.color)?,
+            code: me.code,
+            trace_macros: me.trace_macros,
+            macro_backtrace: me.macro_backtrace,
+            error_format: parse_error_format(&me.error_format)?,
+        })
+    }
+}
+
+impl From<sandbox::CompileResponse> for CompileResponse {
+    fn from(me: sandbox::CompileResponse) -> Self {
+        CompileResponse {
+            success: me.success,
+            code: me.code,
+            outputs: me
+                .extra_outputs
+                .into_iter()
+                .map(|(target, output)| (target_name(target).into(), output))
+                .collect(),
+            artifacts: BTreeMap::new(),
+            stdout: me.stdout,
+            stderr: me.stderr,
+            stdout_artifact: None,
+            stderr_artifact: None,
+            sccache: me.sccache.map(Into::into),
+            hint: me.hint.map(|hint| hint_name(hint).into()),
+            warning_count: me.diagnostics.warnings,
+            error_count: me.diagnostics.errors,
+            suggestions: me.suggestions.into_iter().map(Into::into).collect(),
+            image_id: me.image_id,
+            disk_quota_exceeded: me.disk_quota_exceeded,
+        }
+    }
+}
+
+impl From<sandbox::Suggestion> for Suggestion {
+    fn from(me: sandbox::Suggestion) -> Self {
+        let sandbox::Suggestion {
+            message,
+            range,
+            replacement,
+        } = me;
+        Suggestion {
+            message,
+            start: range.start,
+            end: range.end,
+            replacement,
+        }
+    }
+}
+
+impl CompileResponse {
+    /// Moves the output of `target` and of each of the other targets
+    /// into artifacts. Targets without any output, such as when
+    /// compilation failed, don't get one, and those that were too
+    /// large to be part of the response already have one.
+    async fn keep_artifacts(
+        &mut self,
+        artifacts: &artifacts::Artifacts,
+        target: &str,
+    ) -> Result<()> {
+        let code = std::mem::take(&mut self.code);
+        let outputs = std::mem::take(&mut self.outputs);
+
+        for (target, output) in std::iter::once((target.to_owned(), code)).chain(outputs) {
+            if output.is_empty() || self.artifacts.contains_key(&target) {
+                continue;
+            }
+
+            let artifact = artifacts
+                .store(artifact_file_name(&target), TEXT_CONTENT_TYPE, output)
+                .await
+                .context(ArtifactStorageSnafu)?;
+            self.artifacts.insert(target, artifact.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<ExecuteRequest> for sandbox::ExecuteRequest {
+    type Error = Error;
+
+    fn try_from(me: ExecuteRequest) -> Result<Self> {
+        let channel = parse_channel(&me.channel)?;
+        ensure_macro_debugging_allowed(channel, me.trace_macros, me.macro_backtrace)?;
+        ensure!(
+            !me.careful || matches!(channel, sandbox::Channel::Nightly { .. }),
+            CarefulRequiresNightlySnafu
+        );
+
+        Ok(sandbox::ExecuteRequest {
+            channel,
+            mode: parse_mode(&me.mode)?,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            tests: me.tests,
+            backtrace: me.backtrace,
+            color: parse_color_output(&me.color)?,
+            code: me.code,
+            args: me.args,
+            stdin: me.stdin,
+            keep_binary: me.keep_binary,
+            wrap_main: me.wrap_main,
+            strategy: parse_strategy(&me.strategy)?,
+            trace_macros: me.trace_macros,
+            macro_backtrace: me.macro_backtrace,
+            output_records: me.output_records,
+            test_runner: parse_test_runner(&me.test_runner)?,
+            careful: me.careful,
+            deterministic: me.deterministic,
+            error_format: parse_error_format(&me.error_format)?,
+            hidden_tests: None,
+        })
+    }
+}
+
+impl From<sandbox::ExecuteResponse> for ExecuteResponse {
+    fn from(me: sandbox::ExecuteResponse) -> Self {
+        ExecuteResponse {
+            success: me.success,
+            stdout: me.stdout,
+            stderr: me.stderr,
+            sccache: me.sccache.map(Into::into),
+            hint: me.hint.map(|hint| hint_name(hint).into()),
+            binary: None,
+            stdout_artifact: None,
+            stderr_artifact: None,
+            records: me
+                .records
+                .map(|records| records.into_iter().map(Into::into).collect()),
+            warning_count: me.diagnostics.warnings,
+            error_count: me.diagnostics.errors,
+            test_results: me
+                .test_results
+                .map(|results| results.into_iter().map(Into::into).collect()),
+            careful_checks: me.careful_checks,
+            image_id: me.image_id,
+            abuse_suspected: me.abuse_suspected,
+            output_rate_limited: me.output_rate_limited,
+            out_of_memory: me.out_of_memory,
+            disk_quota_exceeded: me.disk_quota_exceeded,
+        }
+    }
+}
+
+impl From<sandbox::TestResult> for TestResult {
+    fn from(me: sandbox::TestResult) -> Self {
+        TestResult {
+            name: me.name,
+            outcome: test_outcome_name(me.outcome).into(),
+            duration_ms: me.duration_ms,
+            stdout: me.stdout,
+        }
+    }
+}
+
+impl From<sandbox::OutputRecord> for OutputRecord {
+    fn from(me: sandbox::OutputRecord) -> Self {
+        OutputRecord {
+            stream: output_stream_name(me.stream).into(),
+            timestamp_ms: me.timestamp_ms,
+            text: me.text,
+        }
+    }
+}
+
+// Only artifacts of this type are binaries that may be run again
+const KEPT_BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+const KEPT_BINARY_FILE_NAME: &str = "playground";
+
+const TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+impl Executed {
+    /// Moves the binary, if one was kept, into an artifact, along
+    /// with outputs too large to be part of the response. A binary
+    /// too large to keep only means that it can't be run again.
+    async fn new(mut response: sandbox::ExecuteResponse, artifacts: &artifacts::Artifacts) -> Self {
+        let binary = match response.binary.take() {
+            Some(binary) => artifacts
+                .store(KEPT_BINARY_FILE_NAME, KEPT_BINARY_CONTENT_TYPE, binary)
+                .await
+                .map_err(|error| tracing::debug!(%error, "Unable to keep the binary"))
+                .ok(),
+            None => None,
+        };
+        let spilled = std::mem::take(&mut response.spilled);
+        let spilled = SpilledOutputs::new(
+            artifacts,
+            &mut response.stdout,
+            &mut response.stderr,
+            spilled,
+        )
+        .await;
+
+        Self {
+            response,
+            binary: binary.map(Into::into),
+            spilled,
+        }
+    }
+}
+
+impl From<Executed> for ExecuteResponse {
+    fn from(me: Executed) -> Self {
+        ExecuteResponse {
+            binary: me.binary,
+            stdout_artifact: me.spilled.stdout,
+            stderr_artifact: me.spilled.stderr,
+            ..me.response.into()
+        }
+    }
+}
+
+impl Compiled {
+    /// Cargo's output is read from its messages, so it's only spilled
+    /// once all of it has been read.
+    async fn new(
+        mut response: sandbox::CompileResponse,
+        target: sandbox::CompileTarget,
+        artifacts: &artifacts::Artifacts,
+    ) -> Self {
+        let targets = std::iter::once((target, &mut response.code)).chain(
+            response
+                .extra_outputs
+                .iter_mut()
+                .map(|(target, output)| (*target, output)),
+        );
+        let mut outputs = BTreeMap::new();
+        for (target, output) in targets {
+            let target = target_name(target);
+            if let Some(artifact) = spill(artifacts, artifact_file_name(target), output).await {
+                outputs.insert(target.to_owned(), artifact);
+            }
+        }
+        let spilled = SpilledOutputs::new(
+            artifacts,
+            &mut response.stdout,
+            &mut response.stderr,
+            Default::default(),
+        )
+        .await;
+
+        Self {
+            response,
+            outputs,
+            spilled,
+        }
+    }
+}
+
+impl From<Compiled> for CompileResponse {
+    fn from(me: Compiled) -> Self {
+        CompileResponse {
+            artifacts: me.outputs,
+            stdout_artifact: me.spilled.stdout,
+            stderr_artifact: me.spilled.stderr,
+            ..me.response.into()
+        }
+    }
+}
+
+impl SpilledOutputs {
+    /// Outputs that the sandbox already spilled to files are kept as
+    /// they are, as the response only has the start of them.
+    async fn new(
+        artifacts: &artifacts::Artifacts,
+        stdout: &mut String,
+        stderr: &mut String,
+        spilled: sandbox::spill::Spilled,
+    ) -> Self {
+        Self {
+            stdout: keep_spilled(artifacts, "stdout.txt", stdout, spilled.stdout).await,
+            stderr: keep_spilled(artifacts, "stderr.txt", stderr, spilled.stderr).await,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.stdout.is_none() && self.stderr.is_none()
+    }
+}
+
+/// When the file can't be kept, the response only has the start of
+/// the output.
+async fn keep_spilled(
+    artifacts: &artifacts::Artifacts,
+    file_name: &'static str,
+    output: &mut String,
+    file: Option<Arc<sandbox::spill::SpilledFile>>,
+) -> Option<ArtifactResponse> {
+    match file {
+        Some(file) => artifacts
+            .adopt(file_name, TEXT_CONTENT_TYPE, file)
+            .map(Into::into)
+            .map_err(|error| tracing::warn!(%error, "Unable to keep the spilled output"))
+            .ok(),
+        None => spill(artifacts, file_name, output).await,
+    }
+}
+
+/// Cuts `output` short when outputs are being spilled and it's too
+/// large to be part of a response, keeping all of it as an artifact.
+/// When it can't be kept, the response has all of it after all.
+async fn spill(
+    artifacts: &artifacts::Artifacts,
+    file_name: &'static str,
+    output: &mut String,
+) -> Option<ArtifactResponse> {
+    let max_size = artifacts.output_spill()?.keep;
+    if output.len() <= max_size {
+        return None;
+    }
+    let end = (0..=max_size)
+        .rev()
+        .find(|&i| output.is_char_boundary(i))
+        .unwrap_or(0);
+
+    let whole = hyper::body::Bytes::from(std::mem::take(output));
+    let artifact = artifacts
+        .store(file_name, TEXT_CONTENT_TYPE, whole.clone())
+        .await
+        .map_err(|error| tracing::warn!(%error, "Unable to spill the output"))
+        .ok();
+
+    let kept = if artifact.is_some() {
+        &whole[..end]
+    } else {
+        &whole[..]
+    };
+    output.push_str(std::str::from_utf8(kept).expect("It was cut at a character boundary"));
+    artifact.map(Into::into)
+}
+
+impl From<sandbox::repl::Evaluation> for ReplEvaluateResponse {
+    fn from(me: sandbox::repl::Evaluation) -> Self {
+        let sandbox::repl::Evaluation {
+            success,
+            value,
+            stdout,
+            stderr,
+        } = me;
+        ReplEvaluateResponse {
+            success,
+            value,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+impl From<Vec<sandbox::repl::Variable>> for ReplVariablesResponse {
+    fn from(me: Vec<sandbox::repl::Variable>) -> Self {
+        ReplVariablesResponse {
+            variables: me
+                .into_iter()
+                .map(|variable| ReplVariable {
+                    name: variable.name,
+                    ty: variable.ty,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<RerunRequest> for Rerun {
+    type Error = Error;
+
+    fn try_from(me: RerunRequest) -> Result<Self> {
+        Ok(Rerun {
+            binary: me.binary,
+            request: sandbox::RerunRequest {
+                channel: parse_channel(&me.channel)?,
+                backtrace: me.backtrace,
+                color: parse_color_output(&me.color)?,
+                args: me.args,
+                stdin: me.stdin,
+            },
+        })
+    }
+}
+
+// Like code, the input is held on to for caching, so it is limited
+// in the same way
+impl sandbox::CodeRequest for Rerun {
+    fn code(&self) -> &str {
+        &self.request.stdin
+    }
+}
+
+impl From<sandbox::SccacheStats> for SccacheStats {
+    fn from(me: sandbox::SccacheStats) -> Self {
+        SccacheStats {
+            cache_hits: me.cache_hits,
+            cache_misses: me.cache_misses,
+        }
+    }
+}
+
+impl TryFrom<FormatRequest> for sandbox::FormatRequest {
+    type Error = Error;
+
+    fn try_from(me: FormatRequest) -> Result<Self> {
+        Ok(sandbox::FormatRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+            lines: me.lines.map(|lines| sandbox::LineRange {
+                start: lines.start,
+                end: lines.end,
+            }),
+        })
+    }
+}
+
+impl From<sandbox::FormatResponse> for FormatResponse {
+    fn from(me: sandbox::FormatResponse) -> Self {
+        FormatResponse {
+            success: me.success,
+            code: me.code,
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl TryFrom<EditionMigrationRequest> for sandbox::EditionMigrationRequest {
+    type Error = Error;
+
+    fn try_from(me: EditionMigrationRequest) -> Result<Self> {
+        let edition =
+            parse_edition(&me.edition)?.context(InvalidEditionSnafu { value: me.edition })?;
+
+        Ok(sandbox::EditionMigrationRequest {
+            code: me.code,
+            edition,
+            crate_type: parse_crate_type(&me.crate_type)?,
+        })
+    }
+}
+
+impl From<sandbox::EditionMigrationResponse> for EditionMigrationResponse {
+    fn from(me: sandbox::EditionMigrationResponse) -> Self {
+        EditionMigrationResponse {
+            success: me.success,
+            code: me.code,
+            edition: edition_name(me.edition).into(),
+            diff: String::new(),
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl TryFrom<TimePassesRequest> for sandbox::TimePassesRequest {
+    type Error = Error;
+
+    fn try_from(me: TimePassesRequest) -> Result<Self> {
+        Ok(sandbox::TimePassesRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            mode: parse_mode(&me.mode)?,
+        })
+    }
+}
+
+impl From<sandbox::TimePassesResponse> for TimePassesResponse {
+    fn from(me: sandbox::TimePassesResponse) -> Self {
+        TimePassesResponse {
+            success: me.success,
+            passes: me.passes.into_iter().map(Into::into).collect(),
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl From<sandbox::CompilerPass> for CompilerPass {
+    fn from(me: sandbox::CompilerPass) -> Self {
+        let sandbox::CompilerPass {
+            name,
+            milliseconds,
+            rss_before_mb,
+            rss_after_mb,
+        } = me;
+        CompilerPass {
+            name,
+            milliseconds,
+            rss_before_mb,
+            rss_after_mb,
+        }
+    }
+}
+
+impl TryFrom<OptimizationRemarksRequest> for sandbox::OptimizationRemarksRequest {
+    type Error = Error;
+
+    fn try_from(me: OptimizationRemarksRequest) -> Result<Self> {
+        Ok(sandbox::OptimizationRemarksRequest {
             code: me.code,
+            channel: parse_channel(&me.channel)?,
+            mode: parse_mode(&me.mode)?,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+        })
+    }
+}
+
+impl From<sandbox::OptimizationRemarksResponse> for OptimizationRemarksResponse {
+    fn from(me: sandbox::OptimizationRemarksResponse) -> Self {
+        OptimizationRemarksResponse {
+            success: me.success,
+            remarks: me.remarks.into_iter().map(Into::into).collect(),
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl From<sandbox::OptimizationRemark> for OptimizationRemark {
+    fn from(me: sandbox::OptimizationRemark) -> Self {
+        let sandbox::OptimizationRemark {
+            pass,
+            kind,
+            message,
+            span,
+        } = me;
+        OptimizationRemark {
+            pass,
+            kind: remark_kind_name(kind).into(),
+            message,
+            line: span.line,
+            column: span.column,
+        }
+    }
+}
+
+impl TryFrom<CompletionsRequest> for sandbox::CompletionsRequest {
+    type Error = Error;
+
+    fn try_from(me: CompletionsRequest) -> Result<Self> {
+        Ok(sandbox::CompletionsRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            offset: me.offset,
+        })
+    }
+}
+
+impl From<sandbox::CompletionsResponse> for CompletionsResponse {
+    fn from(me: sandbox::CompletionsResponse) -> Self {
+        CompletionsResponse {
+            success: me.success,
+            completions: me.completions.into_iter().map(Into::into).collect(),
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl From<sandbox::Completion> for Completion {
+    fn from(me: sandbox::Completion) -> Self {
+        let sandbox::Completion {
+            label,
+            kind,
+            detail,
+            insert_text,
+        } = me;
+        Completion {
+            label,
+            kind,
+            detail,
+            insert_text,
+        }
+    }
+}
+
+impl TryFrom<HoverRequest> for sandbox::HoverRequest {
+    type Error = Error;
+
+    fn try_from(me: HoverRequest) -> Result<Self> {
+        Ok(sandbox::HoverRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            offset: me.offset,
         })
     }
 }
 
-impl From<sandbox::CompileResponse> for CompileResponse {
-    fn from(me: sandbox::CompileResponse) -> Self {
-        CompileResponse {
+impl From<sandbox::HoverResponse> for HoverResponse {
+    fn from(me: sandbox::HoverResponse) -> Self {
+        HoverResponse {
             success: me.success,
-            code: me.code,
-            stdout: me.stdout,
+            hover: me.hover.map(Into::into),
             stderr: me.stderr,
         }
     }
 }
 
-impl TryFrom<ExecuteRequest> for sandbox::ExecuteRequest {
+impl From<sandbox::Hover> for Hover {
+    fn from(me: sandbox::Hover) -> Self {
+        let sandbox::Hover {
+            signature,
+            documentation,
+            range,
+        } = me;
+        Hover {
+            signature,
+            documentation,
+            start: range.map(|r| r.start),
+            end: range.map(|r| r.end),
+        }
+    }
+}
+
+impl TryFrom<NavigationRequest> for sandbox::NavigationRequest {
     type Error = Error;
 
-    fn try_from(me: ExecuteRequest) -> Result<Self> {
-        Ok(sandbox::ExecuteRequest {
-            channel: parse_channel(&me.channel)?,
-            mode: parse_mode(&me.mode)?,
+    fn try_from(me: NavigationRequest) -> Result<Self> {
+        Ok(sandbox::NavigationRequest {
+            code: me.code,
             edition: parse_edition(&me.edition)?,
             crate_type: parse_crate_type(&me.crate_type)?,
-            tests: me.tests,
-            backtrace: me.backtrace,
-            code: me.code,
+            offset: me.offset,
+            target: parse_navigation_target(&me.target)?,
         })
     }
 }
 
-impl From<sandbox::ExecuteResponse> for ExecuteResponse {
-    fn from(me: sandbox::ExecuteResponse) -> Self {
-        ExecuteResponse {
+impl From<sandbox::NavigationResponse> for NavigationResponse {
+    fn from(me: sandbox::NavigationResponse) -> Self {
+        NavigationResponse {
             success: me.success,
-            stdout: me.stdout,
+            locations: me.locations.into_iter().map(Into::into).collect(),
             stderr: me.stderr,
         }
     }
 }
 
-impl TryFrom<FormatRequest> for sandbox::FormatRequest {
+impl From<sandbox::Location> for Location {
+    fn from(me: sandbox::Location) -> Self {
+        let sandbox::Location {
+            file,
+            line,
+            range,
+            excerpt,
+        } = me;
+        Location {
+            file,
+            line,
+            start: range.map(|r| r.start),
+            end: range.map(|r| r.end),
+            excerpt,
+        }
+    }
+}
+
+impl TryFrom<InlayHintsRequest> for sandbox::InlayHintsRequest {
     type Error = Error;
 
-    fn try_from(me: FormatRequest) -> Result<Self> {
-        Ok(sandbox::FormatRequest {
+    fn try_from(me: InlayHintsRequest) -> Result<Self> {
+        Ok(sandbox::InlayHintsRequest {
             code: me.code,
             edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
         })
     }
 }
 
-impl From<sandbox::FormatResponse> for FormatResponse {
-    fn from(me: sandbox::FormatResponse) -> Self {
-        FormatResponse {
+impl From<sandbox::InlayHintsResponse> for InlayHintsResponse {
+    fn from(me: sandbox::InlayHintsResponse) -> Self {
+        InlayHintsResponse {
             success: me.success,
-            code: me.code,
-            stdout: me.stdout,
+            hints: me.hints.into_iter().map(Into::into).collect(),
             stderr: me.stderr,
         }
     }
 }
 
+impl From<sandbox::InlayHint> for InlayHint {
+    fn from(me: sandbox::InlayHint) -> Self {
+        InlayHint {
+            offset: me.offset,
+            label: me.label,
+            kind: inlay_hint_kind_name(me.kind).into(),
+            padding_left: me.padding_left,
+            padding_right: me.padding_right,
+        }
+    }
+}
+
 impl TryFrom<ClippyRequest> for sandbox::ClippyRequest {
     type Error = Error;
 
@@ -486,6 +2572,9 @@ impl From<sandbox::ClippyResponse> for ClippyResponse {
             success: me.success,
             stdout: me.stdout,
             stderr: me.stderr,
+            warning_count: me.diagnostics.warnings,
+            error_count: me.diagnostics.errors,
+            suggestions: me.suggestions.into_iter().map(Into::into).collect(),
         }
     }
 }
@@ -511,6 +2600,103 @@ impl From<sandbox::MiriResponse> for MiriResponse {
     }
 }
 
+impl TryFrom<KaniRequest> for sandbox::KaniRequest {
+    type Error = Error;
+
+    fn try_from(me: KaniRequest) -> Result<Self> {
+        Ok(sandbox::KaniRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+        })
+    }
+}
+
+impl From<sandbox::KaniResponse> for KaniResponse {
+    fn from(me: sandbox::KaniResponse) -> Self {
+        KaniResponse {
+            success: me.success,
+            harnesses: me.harnesses.into_iter().map(Into::into).collect(),
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl From<sandbox::KaniHarness> for KaniHarness {
+    fn from(me: sandbox::KaniHarness) -> Self {
+        KaniHarness {
+            name: me.name,
+            verified: me.verified,
+            failed_checks: me
+                .failed_checks
+                .into_iter()
+                .map(|check| KaniCheck {
+                    description: check.description,
+                    line: check.line,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<BisectRequest> for sandbox::BisectRequest {
+    type Error = Error;
+
+    fn try_from(me: BisectRequest) -> Result<Self> {
+        Ok(sandbox::BisectRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            start: parse_date(&me.start)?,
+            end: me.end.as_deref().map(parse_date).transpose()?,
+            regression: parse_regression(&me.regression)?,
+        })
+    }
+}
+
+impl From<sandbox::BisectResponse> for BisectResponse {
+    fn from(me: sandbox::BisectResponse) -> Self {
+        BisectResponse {
+            success: me.success,
+            regressed_nightly: me.regressed_nightly.map(|date| format!("nightly-{}", date)),
+            commit_range: me.commit_range,
+            regressed_commit: me.regressed_commit,
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
+impl TryFrom<ReduceRequest> for sandbox::ReduceRequest {
+    type Error = Error;
+
+    fn try_from(me: ReduceRequest) -> Result<Self> {
+        let preserved = match &*me.preserve {
+            "ice" => sandbox::Preserved::Ice,
+            _ => sandbox::Preserved::Error(me.preserve),
+        };
+
+        Ok(sandbox::ReduceRequest {
+            channel: parse_channel(&me.channel)?,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            code: me.code,
+            preserved,
+        })
+    }
+}
+
+impl From<sandbox::ReduceResponse> for ReduceResponse {
+    fn from(me: sandbox::ReduceResponse) -> Self {
+        ReduceResponse {
+            reproduced: me.reproduced,
+            code: me.code,
+            attempts: me.attempts,
+            stderr: me.stderr,
+        }
+    }
+}
+
 impl TryFrom<MacroExpansionRequest> for sandbox::MacroExpansionRequest {
     type Error = Error;
 
@@ -518,6 +2704,7 @@ impl TryFrom<MacroExpansionRequest> for sandbox::MacroExpansionRequest {
         Ok(sandbox::MacroExpansionRequest {
             code: me.code,
             edition: parse_edition(&me.edition)?,
+            expand_item: me.expand_item,
         })
     }
 }
@@ -547,6 +2734,43 @@ impl From<Vec<sandbox::CrateInformation>> for MetaCratesResponse {
     }
 }
 
+impl From<Vec<sandbox::ClippyLint>> for MetaClippyLintsResponse {
+    fn from(me: Vec<sandbox::ClippyLint>) -> Self {
+        let lints = me
+            .into_iter()
+            .map(|lint| ClippyLint {
+                name: lint.name,
+                group: lint.group,
+                level: lint.level,
+                description: lint.description,
+            })
+            .collect();
+
+        MetaClippyLintsResponse { lints }
+    }
+}
+
+impl From<sandbox::FullVersion> for FullVersion {
+    fn from(me: sandbox::FullVersion) -> Self {
+        FullVersion {
+            rustc: me.rustc.into(),
+            cargo: me.cargo.into(),
+            image_id: me.image_id.into(),
+        }
+    }
+}
+
+impl From<sandbox::Result<sandbox::FullVersion>> for ChannelVersion {
+    fn from(me: sandbox::Result<sandbox::FullVersion>) -> Self {
+        match me {
+            Ok(version) => ChannelVersion::Available(version.into()),
+            Err(error) => ChannelVersion::Unavailable {
+                error: error.to_string().into(),
+            },
+        }
+    }
+}
+
 impl From<sandbox::Version> for MetaVersionResponse {
     fn from(me: sandbox::Version) -> Self {
         MetaVersionResponse {
@@ -567,6 +2791,175 @@ impl From<gist::Gist> for MetaGistResponse {
     }
 }
 
+impl From<storage::Snippet> for SnippetResponse {
+    fn from(me: storage::Snippet) -> Self {
+        SnippetResponse {
+            url: format!("/s/{}", me.id),
+            id: me.id,
+            code: me.code,
+            created_at: unix_seconds(me.created_at),
+            retrievals: me.retrievals,
+        }
+    }
+}
+
+impl TemplateRequest {
+    // Templates are only kept when their code could be run
+    fn into_template(self, name: String) -> Result<templates::Template> {
+        parse_channel(&self.channel)?;
+        parse_edition(&self.edition)?;
+        parse_crate_type(&self.crate_type)?;
+
+        Ok(templates::Template {
+            name,
+            title: self.title,
+            description: self.description,
+            code: self.code,
+            channel: self.channel,
+            edition: self.edition,
+            crate_type: self.crate_type,
+            tests: self.tests,
+            crates: self.crates,
+        })
+    }
+}
+
+impl From<templates::Template> for TemplateResponse {
+    fn from(me: templates::Template) -> Self {
+        TemplateResponse {
+            name: me.name,
+            title: me.title,
+            description: me.description,
+            code: me.code,
+            channel: me.channel,
+            edition: me.edition,
+            crate_type: me.crate_type,
+            tests: me.tests,
+            crates: me.crates,
+        }
+    }
+}
+
+impl TryFrom<ShareLinkCreateRequest> for share_link::SharedCode {
+    type Error = Error;
+
+    // Links are only made for code that could be run
+    fn try_from(me: ShareLinkCreateRequest) -> Result<Self> {
+        parse_channel(&me.channel)?;
+        parse_mode(&me.mode)?;
+        parse_edition(&me.edition)?;
+        parse_crate_type(&me.crate_type)?;
+
+        Ok(share_link::SharedCode {
+            code: me.code,
+            channel: me.channel,
+            mode: me.mode,
+            edition: me.edition,
+            crate_type: me.crate_type,
+            tests: me.tests,
+            backtrace: me.backtrace,
+        })
+    }
+}
+
+impl ShareLinkResponse {
+    fn new(link: String, shared: share_link::SharedCode) -> Self {
+        ShareLinkResponse {
+            url: format!("/l/{}", link),
+            link,
+            code: shared.code,
+            channel: shared.channel,
+            mode: shared.mode,
+            edition: shared.edition,
+            crate_type: shared.crate_type,
+            tests: shared.tests,
+            backtrace: shared.backtrace,
+        }
+    }
+}
+
+impl From<share_link::SharedCode> for ExecuteRequest {
+    fn from(me: share_link::SharedCode) -> Self {
+        ExecuteRequest {
+            channel: me.channel,
+            mode: me.mode,
+            edition: me.edition,
+            crate_type: me.crate_type,
+            tests: me.tests,
+            backtrace: me.backtrace,
+            code: me.code,
+            ..Default::default()
+        }
+    }
+}
+
+// Snippets are only the code, so they're run as the playground
+// starts out
+impl From<storage::Snippet> for ExecuteRequest {
+    fn from(me: storage::Snippet) -> Self {
+        ExecuteRequest {
+            channel: "stable".into(),
+            mode: "debug".into(),
+            edition: "2021".into(),
+            crate_type: "bin".into(),
+            code: me.code,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<artifacts::Artifact> for ArtifactResponse {
+    fn from(me: artifacts::Artifact) -> Self {
+        ArtifactResponse {
+            url: format!("/artifacts/{}", me.id),
+            id: me.id,
+            size: me.size,
+            expires_at: unix_seconds(me.expires_at),
+        }
+    }
+}
+
+impl From<image_updates::ImageStatus> for ImageStatus {
+    fn from(me: image_updates::ImageStatus) -> Self {
+        ImageStatus {
+            active: me.active,
+            checked_at: me.checked_at.map(unix_seconds),
+            updated_at: me.updated_at.map(unix_seconds),
+            error: me.error,
+        }
+    }
+}
+
+impl From<&usage::Usage> for MetaUsageResponse {
+    fn from(me: &usage::Usage) -> Self {
+        MetaUsageResponse {
+            since: unix_seconds(me.started_at()),
+            operations: me.operations().into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<usage::OperationUsage> for OperationUsage {
+    fn from(me: usage::OperationUsage) -> Self {
+        let ms = |d: Duration| d.as_millis() as u64;
+        OperationUsage {
+            operation: me.operation.into(),
+            channel: me.channel.map(Into::into),
+            mode: me.mode.map(Into::into),
+            count: me.count,
+            p50_ms: ms(me.p50),
+            p90_ms: ms(me.p90),
+            p99_ms: ms(me.p99),
+        }
+    }
+}
+
+fn unix_seconds(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 impl TryFrom<EvaluateRequest> for sandbox::ExecuteRequest {
     type Error = Error;
 
@@ -582,11 +2975,76 @@ impl TryFrom<EvaluateRequest> for sandbox::ExecuteRequest {
             crate_type: sandbox::CrateType::Binary,
             tests: me.tests,
             backtrace: false,
+            color: sandbox::ColorOutput::Plain,
+            code: me.code,
+            args: vec![],
+            stdin: String::new(),
+            keep_binary: false,
+            wrap_main: false,
+            strategy: sandbox::Strategy::default(),
+            trace_macros: false,
+            macro_backtrace: false,
+            output_records: false,
+            test_runner: sandbox::TestRunner::default(),
+            careful: false,
+            deterministic: false,
+            error_format: sandbox::ErrorFormat::default(),
+            hidden_tests: None,
+        })
+    }
+}
+
+impl TryFrom<AssessRequest> for sandbox::ExecuteRequest {
+    type Error = Error;
+
+    fn try_from(me: AssessRequest) -> Result<Self> {
+        Ok(sandbox::ExecuteRequest {
+            channel: parse_channel(&me.channel)?,
+            mode: parse_mode(&me.mode)?,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            tests: true,
+            backtrace: false,
+            color: sandbox::ColorOutput::Plain,
             code: me.code,
+            args: vec![],
+            stdin: String::new(),
+            keep_binary: false,
+            wrap_main: false,
+            strategy: sandbox::Strategy::Exact,
+            trace_macros: false,
+            macro_backtrace: false,
+            output_records: false,
+            // The only way to know how each test went
+            test_runner: sandbox::TestRunner::Nextest,
+            careful: false,
+            deterministic: false,
+            error_format: sandbox::ErrorFormat::default(),
+            hidden_tests: Some(me.hidden_tests),
         })
     }
 }
 
+impl From<sandbox::ExecuteResponse> for AssessResponse {
+    fn from(me: sandbox::ExecuteResponse) -> Self {
+        AssessResponse {
+            success: me.success,
+            warning_count: me.diagnostics.warnings,
+            error_count: me.diagnostics.errors,
+            tests: me
+                .test_results
+                .unwrap_or_default()
+                .into_iter()
+                .map(|t| AssessedTest {
+                    name: t.name,
+                    outcome: test_outcome_name(t.outcome).into(),
+                    duration_ms: t.duration_ms,
+                })
+                .collect(),
+        }
+    }
+}
+
 impl From<sandbox::ExecuteResponse> for EvaluateResponse {
     fn from(me: sandbox::ExecuteResponse) -> Self {
         // The old playground didn't use Cargo, so it never had the
@@ -628,55 +3086,142 @@ fn parse_target(s: &str) -> Result<sandbox::CompileTarget> {
     })
 }
 
+fn target_name(target: sandbox::CompileTarget) -> &'static str {
+    match target {
+        sandbox::CompileTarget::Assembly(_, _, _) => "asm",
+        sandbox::CompileTarget::LlvmIr => "llvm-ir",
+        sandbox::CompileTarget::Mir => "mir",
+        sandbox::CompileTarget::Hir => "hir",
+        sandbox::CompileTarget::Wasm => "wasm",
+    }
+}
+
+fn edition_name(edition: sandbox::Edition) -> &'static str {
+    match edition {
+        sandbox::Edition::Rust2015 => "2015",
+        sandbox::Edition::Rust2018 => "2018",
+        sandbox::Edition::Rust2021 => "2021",
+    }
+}
+
+fn hint_name(hint: sandbox::Hint) -> &'static str {
+    match hint {
+        sandbox::Hint::TryNightly => "try-nightly",
+    }
+}
+
+fn remark_kind_name(kind: sandbox::RemarkKind) -> &'static str {
+    match kind {
+        sandbox::RemarkKind::Passed => "passed",
+        sandbox::RemarkKind::Missed => "missed",
+        sandbox::RemarkKind::Analysis => "analysis",
+        sandbox::RemarkKind::Failure => "failure",
+    }
+}
+
+fn inlay_hint_kind_name(kind: sandbox::InlayHintKind) -> &'static str {
+    match kind {
+        sandbox::InlayHintKind::Type => "type",
+        sandbox::InlayHintKind::Parameter => "parameter",
+    }
+}
+
+fn output_stream_name(stream: sandbox::OutputStream) -> &'static str {
+    match stream {
+        sandbox::OutputStream::Stdout => "stdout",
+        sandbox::OutputStream::Stderr => "stderr",
+    }
+}
+
+fn test_outcome_name(outcome: sandbox::TestOutcome) -> &'static str {
+    match outcome {
+        sandbox::TestOutcome::Passed => "passed",
+        sandbox::TestOutcome::Failed => "failed",
+        sandbox::TestOutcome::Ignored => "ignored",
+    }
+}
+
+fn phase_name(phase: sandbox::Phase) -> &'static str {
+    match phase {
+        sandbox::Phase::ContainerStarted => "container-started",
+        sandbox::Phase::Compiling => "compiling",
+        sandbox::Phase::Running => "running",
+    }
+}
+
+fn artifact_file_name(target: &str) -> &'static str {
+    match target {
+        "asm" => "main.s",
+        "llvm-ir" => "main.ll",
+        "mir" => "main.mir",
+        "hir" => "main.hir",
+        "wasm" => "main.wat",
+        _ => "output.txt",
+    }
+}
+
+/// The sandbox types name their unit variants the same way the API
+/// does, so their serde names can be parsed directly.
+fn parse_wire_name<T: serde::de::DeserializeOwned>(s: &str) -> Option<T> {
+    use serde::de::{value::StrDeserializer, IntoDeserializer};
+
+    let deserializer: StrDeserializer<'_, serde::de::value::Error> = s.into_deserializer();
+    T::deserialize(deserializer).ok()
+}
+
 fn parse_assembly_flavor(s: &str) -> Result<sandbox::AssemblyFlavor> {
-    Ok(match s {
-        "att" => sandbox::AssemblyFlavor::Att,
-        "intel" => sandbox::AssemblyFlavor::Intel,
-        value => InvalidAssemblyFlavorSnafu { value }.fail()?,
-    })
+    parse_wire_name(s).context(InvalidAssemblyFlavorSnafu { value: s })
 }
 
 fn parse_demangle_assembly(s: &str) -> Result<sandbox::DemangleAssembly> {
-    Ok(match s {
-        "demangle" => sandbox::DemangleAssembly::Demangle,
-        "mangle" => sandbox::DemangleAssembly::Mangle,
-        value => InvalidDemangleAssemblySnafu { value }.fail()?,
-    })
+    parse_wire_name(s).context(InvalidDemangleAssemblySnafu { value: s })
 }
 
 fn parse_process_assembly(s: &str) -> Result<sandbox::ProcessAssembly> {
-    Ok(match s {
-        "filter" => sandbox::ProcessAssembly::Filter,
-        "raw" => sandbox::ProcessAssembly::Raw,
-        value => InvalidProcessAssemblySnafu { value }.fail()?,
-    })
+    parse_wire_name(s).context(InvalidProcessAssemblySnafu { value: s })
 }
 
 fn parse_channel(s: &str) -> Result<sandbox::Channel> {
     Ok(match s {
-        "stable" => sandbox::Channel::Stable,
+        "stable" => sandbox::Channel::Stable { version: None },
         "beta" => sandbox::Channel::Beta,
-        "nightly" => sandbox::Channel::Nightly,
-        value => InvalidChannelSnafu { value }.fail()?,
+        "nightly" => sandbox::Channel::Nightly { date: None },
+        // Named like the toolchains of rustup
+        value => match value.strip_prefix("nightly-") {
+            Some(date) => sandbox::Channel::Nightly {
+                date: Some(date.parse().ok().context(InvalidChannelSnafu { value })?),
+            },
+            None => sandbox::Channel::Stable {
+                version: Some(value.parse().ok().context(InvalidChannelSnafu { value })?),
+            },
+        },
     })
 }
 
+fn ensure_macro_debugging_allowed(
+    channel: sandbox::Channel,
+    trace_macros: bool,
+    macro_backtrace: bool,
+) -> Result<()> {
+    let nightly = matches!(channel, sandbox::Channel::Nightly { .. });
+    ensure!(
+        nightly || !(trace_macros || macro_backtrace),
+        MacroDebuggingRequiresNightlySnafu
+    );
+    Ok(())
+}
+
 fn parse_mode(s: &str) -> Result<sandbox::Mode> {
-    Ok(match s {
-        "debug" => sandbox::Mode::Debug,
-        "release" => sandbox::Mode::Release,
-        value => InvalidModeSnafu { value }.fail()?,
-    })
+    parse_wire_name(s).context(InvalidModeSnafu { value: s })
 }
 
 fn parse_edition(s: &str) -> Result<Option<sandbox::Edition>> {
-    Ok(match s {
-        "" => None,
-        "2015" => Some(sandbox::Edition::Rust2015),
-        "2018" => Some(sandbox::Edition::Rust2018),
-        "2021" => Some(sandbox::Edition::Rust2021),
-        value => InvalidEditionSnafu { value }.fail()?,
-    })
+    if s.is_empty() {
+        return Ok(None);
+    }
+    parse_wire_name(s)
+        .map(Some)
+        .context(InvalidEditionSnafu { value: s })
 }
 
 fn parse_crate_type(s: &str) -> Result<sandbox::CrateType> {
@@ -693,6 +3238,57 @@ fn parse_crate_type(s: &str) -> Result<sandbox::CrateType> {
     })
 }
 
+fn parse_color_output(s: &str) -> Result<sandbox::ColorOutput> {
+    if s.is_empty() {
+        return Ok(sandbox::ColorOutput::Plain);
+    }
+    parse_wire_name(s).context(InvalidColorOutputSnafu { value: s })
+}
+
+fn parse_error_format(s: &str) -> Result<sandbox::ErrorFormat> {
+    if s.is_empty() {
+        return Ok(sandbox::ErrorFormat::default());
+    }
+    parse_wire_name(s).context(InvalidErrorFormatSnafu { value: s })
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate> {
+    s.parse().ok().context(InvalidDateSnafu { value: s })
+}
+
+fn parse_regression(s: &str) -> Result<sandbox::Regression> {
+    if s.is_empty() {
+        return Ok(sandbox::Regression::Error);
+    }
+    parse_wire_name(s).context(InvalidRegressionSnafu { value: s })
+}
+
+fn parse_strategy(s: &str) -> Result<sandbox::Strategy> {
+    if s.is_empty() {
+        return Ok(sandbox::Strategy::default());
+    }
+    parse_wire_name(s).context(InvalidStrategySnafu { value: s })
+}
+
+fn parse_test_runner(s: &str) -> Result<sandbox::TestRunner> {
+    if s.is_empty() {
+        return Ok(sandbox::TestRunner::default());
+    }
+    parse_wire_name(s).context(InvalidTestRunnerSnafu { value: s })
+}
+
+fn parse_navigation_target(s: &str) -> Result<sandbox::NavigationTarget> {
+    parse_wire_name(s).context(InvalidNavigationTargetSnafu { value: s })
+}
+
 fn default_crate_type() -> String {
     "bin".into()
 }
+
+fn default_diff_before() -> String {
+    "debug".into()
+}
+
+fn default_diff_after() -> String {
+    "release".into()
+}
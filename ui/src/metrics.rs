@@ -2,9 +2,16 @@ use futures::future::BoxFuture;
 use lazy_static::lazy_static;
 use prometheus::{self, register_histogram_vec, HistogramVec};
 use regex::Regex;
-use std::{future::Future, time::Instant};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
 
-use crate::sandbox::{self, Channel, CompileTarget, CrateType, Edition, Mode};
+use crate::{
+    sandbox::{self, Channel, CompileTarget, CrateType, Edition, Mode},
+    usage::Usage,
+};
 
 lazy_static! {
     pub(crate) static ref REQUESTS: HistogramVec = register_histogram_vec!(
@@ -14,16 +21,28 @@ lazy_static! {
         vec![0.1, 1.0, 2.5, 5.0, 10.0, 15.0]
     )
     .unwrap();
+    pub(crate) static ref USAGE: Usage = Usage::new();
 }
 
 #[derive(Debug, Copy, Clone, strum::IntoStaticStr)]
 pub(crate) enum Endpoint {
     Compile,
     Execute,
+    Rerun,
     Format,
+    EditionMigration,
     Miri,
+    Kani,
     Clippy,
     MacroExpansion,
+    TimePasses,
+    OptimizationRemarks,
+    Completions,
+    Hover,
+    Navigation,
+    InlayHints,
+    Bisect,
+    Reduce,
     MetaCrates,
     MetaVersionStable,
     MetaVersionBeta,
@@ -31,7 +50,11 @@ pub(crate) enum Endpoint {
     MetaVersionRustfmt,
     MetaVersionClippy,
     MetaVersionMiri,
+    MetaClippyLints,
+    MetaVersions,
+    MetaTargets,
     Evaluate,
+    Assess,
 }
 
 #[derive(Debug, Copy, Clone, strum::IntoStaticStr)]
@@ -40,7 +63,9 @@ pub(crate) enum Outcome {
     ErrorServer,
     ErrorTimeoutSoft,
     ErrorTimeoutHard,
+    ErrorDiskFull,
     ErrorUserCode,
+    AbuseSuspected,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -115,6 +140,44 @@ impl Labels {
     }
 }
 
+impl Labels {
+    fn span(&self) -> tracing::Span {
+        use tracing::field::Empty;
+
+        let endpoint: &'static str = self.endpoint.into();
+        let span = tracing::info_span!(
+            "sandbox",
+            endpoint,
+            target = Empty,
+            channel = Empty,
+            mode = Empty,
+            edition = Empty,
+            crate_type = Empty,
+            tests = Empty,
+            backtrace = Empty,
+        );
+
+        // Only record the labels that apply to this kind of request
+        let labels = Self::LABELS.iter().zip(self.as_values()).skip(2);
+        for (&name, value) in labels {
+            if !value.is_empty() {
+                span.record(name, &value);
+            }
+        }
+
+        span
+    }
+
+    fn log_finished(&self, elapsed: Duration) {
+        let outcome: &'static str = self.outcome.into();
+        tracing::info!(
+            outcome,
+            duration_ms = elapsed.as_millis() as u64,
+            "Finished sandbox operation"
+        );
+    }
+}
+
 pub(crate) trait GenerateLabels {
     fn generate_labels(&self, outcome: Outcome) -> Labels;
 }
@@ -132,13 +195,19 @@ impl GenerateLabels for sandbox::CompileRequest {
     fn generate_labels(&self, outcome: Outcome) -> Labels {
         let Self {
             target,
+            extra_targets: _,
+            unoptimized_mir: _,
             channel,
             crate_type,
             mode,
             edition,
             tests,
             backtrace,
+            color: _,
             code: _,
+            trace_macros: _,
+            macro_backtrace: _,
+            error_format: _,
         } = *self;
 
         Labels {
@@ -163,9 +232,23 @@ impl GenerateLabels for sandbox::ExecuteRequest {
             mode,
             edition,
             crate_type,
-            tests,
+            tests: _,
             backtrace,
+            color: _,
             code: _,
+            args: _,
+            stdin: _,
+            keep_binary: _,
+            wrap_main: _,
+            strategy: _,
+            trace_macros: _,
+            macro_backtrace: _,
+            output_records: _,
+            test_runner: _,
+            careful: _,
+            deterministic: _,
+            error_format: _,
+            hidden_tests: _,
         } = *self;
 
         Labels {
@@ -177,7 +260,32 @@ impl GenerateLabels for sandbox::ExecuteRequest {
             mode: Some(mode),
             edition: Some(edition),
             crate_type: Some(crate_type),
-            tests: Some(tests),
+            tests: Some(self.runs_tests()),
+            backtrace: Some(backtrace),
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::RerunRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            channel,
+            backtrace,
+            color: _,
+            args: _,
+            stdin: _,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::Rerun,
+            outcome,
+
+            target: None,
+            channel: Some(channel),
+            mode: None,
+            edition: None,
+            crate_type: None,
+            tests: None,
             backtrace: Some(backtrace),
         }
     }
@@ -185,7 +293,11 @@ impl GenerateLabels for sandbox::ExecuteRequest {
 
 impl GenerateLabels for sandbox::FormatRequest {
     fn generate_labels(&self, outcome: Outcome) -> Labels {
-        let Self { edition, code: _ } = *self;
+        let Self {
+            edition,
+            code: _,
+            lines: _,
+        } = *self;
 
         Labels {
             endpoint: Endpoint::Format,
@@ -202,6 +314,174 @@ impl GenerateLabels for sandbox::FormatRequest {
     }
 }
 
+impl GenerateLabels for sandbox::EditionMigrationRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::EditionMigration,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(Some(edition)),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::TimePassesRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+            mode,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::TimePasses,
+            outcome,
+
+            target: None,
+            channel: Some(Channel::Nightly { date: None }),
+            mode: Some(mode),
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::OptimizationRemarksRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            channel,
+            mode,
+            edition,
+            crate_type,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::OptimizationRemarks,
+            outcome,
+
+            target: None,
+            channel: Some(channel),
+            mode: Some(mode),
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::CompletionsRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+            offset: _,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::Completions,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::HoverRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+            offset: _,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::Hover,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::NavigationRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+            offset: _,
+            target: _,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::Navigation,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::InlayHintsRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::InlayHints,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
 impl GenerateLabels for sandbox::ClippyRequest {
     fn generate_labels(&self, outcome: Outcome) -> Labels {
         let Self {
@@ -225,6 +505,25 @@ impl GenerateLabels for sandbox::ClippyRequest {
     }
 }
 
+impl GenerateLabels for sandbox::KaniRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self { code: _, edition } = *self;
+
+        Labels {
+            endpoint: Endpoint::Kani,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(edition),
+            crate_type: None,
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
 impl GenerateLabels for sandbox::MiriRequest {
     fn generate_labels(&self, outcome: Outcome) -> Labels {
         let Self { code: _, edition } = *self;
@@ -244,9 +543,64 @@ impl GenerateLabels for sandbox::MiriRequest {
     }
 }
 
+impl GenerateLabels for sandbox::ReduceRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            channel,
+            edition,
+            crate_type,
+            code: _,
+            preserved: _,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::Reduce,
+            outcome,
+
+            target: None,
+            channel: Some(channel),
+            mode: None,
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
+impl GenerateLabels for sandbox::BisectRequest {
+    fn generate_labels(&self, outcome: Outcome) -> Labels {
+        let Self {
+            code: _,
+            edition,
+            crate_type,
+            start: _,
+            end: _,
+            regression: _,
+        } = *self;
+
+        Labels {
+            endpoint: Endpoint::Bisect,
+            outcome,
+
+            target: None,
+            channel: None,
+            mode: None,
+            edition: Some(edition),
+            crate_type: Some(crate_type),
+            tests: None,
+            backtrace: None,
+        }
+    }
+}
+
 impl GenerateLabels for sandbox::MacroExpansionRequest {
     fn generate_labels(&self, outcome: Outcome) -> Labels {
-        let Self { code: _, edition } = *self;
+        let Self {
+            code: _,
+            edition,
+            expand_item: _,
+        } = *self;
 
         Labels {
             endpoint: Endpoint::MacroExpansion,
@@ -289,9 +643,11 @@ fn common_success_details(success: bool, stderr: &str) -> Outcome {
             if stderr
                 .lines()
                 .next_back()
-                .map_or(false, |l| SOFT_TIMEOUT_REGEX.is_match(l))
+                .is_some_and(|l| SOFT_TIMEOUT_REGEX.is_match(l))
             {
                 Outcome::ErrorTimeoutSoft
+            } else if sandbox::disk::is_full(stderr) {
+                Outcome::ErrorDiskFull
             } else {
                 Outcome::ErrorUserCode
             }
@@ -307,6 +663,9 @@ impl SuccessDetails for sandbox::CompileResponse {
 
 impl SuccessDetails for sandbox::ExecuteResponse {
     fn success_details(&self) -> Outcome {
+        if self.abuse_suspected {
+            return Outcome::AbuseSuspected;
+        }
         common_success_details(self.success, &self.stderr)
     }
 }
@@ -317,6 +676,12 @@ impl SuccessDetails for sandbox::FormatResponse {
     }
 }
 
+impl SuccessDetails for sandbox::EditionMigrationResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
 impl SuccessDetails for sandbox::ClippyResponse {
     fn success_details(&self) -> Outcome {
         common_success_details(self.success, &self.stderr)
@@ -329,12 +694,66 @@ impl SuccessDetails for sandbox::MiriResponse {
     }
 }
 
+impl SuccessDetails for sandbox::KaniResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
 impl SuccessDetails for sandbox::MacroExpansionResponse {
     fn success_details(&self) -> Outcome {
         common_success_details(self.success, &self.stderr)
     }
 }
 
+impl SuccessDetails for sandbox::TimePassesResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::OptimizationRemarksResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::CompletionsResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::HoverResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::NavigationResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::InlayHintsResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::BisectResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.success, &self.stderr)
+    }
+}
+
+impl SuccessDetails for sandbox::ReduceResponse {
+    fn success_details(&self) -> Outcome {
+        common_success_details(self.reproduced, &self.stderr)
+    }
+}
+
 impl SuccessDetails for Vec<sandbox::CrateInformation> {
     fn success_details(&self) -> Outcome {
         Outcome::Success
@@ -380,18 +799,23 @@ where
     Resp: SuccessDetails,
     F: FnOnce(&mut Labels),
 {
+    // The outcome is filled in once it is known
+    let mut labels = request.generate_labels(Outcome::Success);
+    f(&mut labels);
+    let span = labels.span();
+
     let start = Instant::now();
-    let response = body(&request).await;
+    let response = body(&request).instrument(span.clone()).await;
     let elapsed = start.elapsed();
 
-    let outcome = SuccessDetails::for_sandbox_result(&response);
-    let mut labels = request.generate_labels(outcome);
-    f(&mut labels);
+    labels.outcome = SuccessDetails::for_sandbox_result(&response);
+    span.in_scope(|| labels.log_finished(elapsed));
     let values = &labels.as_values();
 
     let histogram = REQUESTS.with_label_values(values);
 
     histogram.observe(elapsed.as_secs_f64());
+    USAGE.record(values[0], values[3], values[4], elapsed);
 
     response
 }
@@ -424,6 +848,7 @@ where
         tests: None,
         backtrace: None,
     };
+    labels.span().in_scope(|| labels.log_finished(elapsed));
     let values = &labels.as_values();
     let histogram = REQUESTS.with_label_values(values);
 
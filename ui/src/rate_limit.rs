@@ -0,0 +1,350 @@
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::dispatcher::OperationClass;
+
+// Forget about clients after this many have been seen, as long as
+// they have not made any requests recently.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+const QUOTA_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Client {
+    Address(IpAddr),
+    /// Identified by the name of their API key
+    Key(Arc<str>),
+}
+
+/// Allows a client, such as a bot, to be given its own limits.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ApiKey {
+    pub(crate) name: Arc<str>,
+    /// The SHA-256 digest of the key in hexadecimal, so that the key
+    /// itself does not need to be stored.
+    pub(crate) sha256: String,
+    /// Used instead of the default rates for these operations.
+    pub(crate) rates: HashMap<OperationClass, Rate>,
+    /// The number of requests that may be made each day. This is
+    /// only counted in memory, so it starts over on restart.
+    pub(crate) daily_quota: Option<u32>,
+}
+
+/// Allows each client to make `per_minute` requests each minute. Up
+/// to a minute's worth of unused requests may be saved up and made
+/// all at once.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Rate {
+    pub(crate) per_minute: u32,
+}
+
+impl Rate {
+    pub(crate) fn per_minute(per_minute: u32) -> Self {
+        Self { per_minute }
+    }
+
+    fn burst(&self) -> f64 {
+        f64::from(self.per_minute)
+    }
+
+    fn tokens_per_second(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    rates: HashMap<OperationClass, Rate>,
+    buckets: Mutex<HashMap<(Client, OperationClass), Bucket>>,
+    keys: HashMap<Arc<str>, ApiKey>,
+    names_by_digest: HashMap<String, Arc<str>>,
+    quotas: Mutex<HashMap<Arc<str>, Quota>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Quota {
+    used: u32,
+    started: Instant,
+}
+
+impl RateLimiter {
+    /// Operations without a rate are not limited.
+    pub(crate) fn new(rates: HashMap<OperationClass, Rate>) -> Self {
+        Self {
+            rates,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_api_keys(mut self, keys: impl IntoIterator<Item = ApiKey>) -> Self {
+        for key in keys {
+            self.names_by_digest
+                .insert(key.sha256.to_ascii_lowercase(), key.name.clone());
+            self.keys.insert(key.name.clone(), key);
+        }
+        self
+    }
+
+    pub(crate) fn has_api_keys(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// The client that the API key belongs to, if it is one we know.
+    pub(crate) fn client_for_key(&self, key: &str) -> Option<Client> {
+        let digest: String = Sha256::digest(key.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        self.names_by_digest.get(&digest).cloned().map(Client::Key)
+    }
+
+    /// Records a request from the client, returning how long they
+    /// need to wait, rounded up to the next second, if they have made
+    /// too many.
+    pub(crate) fn check(&self, client: Client, class: OperationClass) -> Result<(), Duration> {
+        self.check_at(client, class, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        client: Client,
+        class: OperationClass,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let rate = match self.rate(&client, class) {
+            Some(rate) => rate,
+            None => return Ok(()),
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|(client, class), bucket| {
+                self.rate(client, *class)
+                    .is_some_and(|rate| bucket.refilled(&rate, now).tokens < rate.burst())
+            });
+        }
+
+        let bucket = buckets.entry((client, class)).or_insert(Bucket {
+            tokens: rate.burst(),
+            updated: now,
+        });
+        *bucket = bucket.refilled(&rate, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if rate.per_minute == 0 {
+            Err(Duration::from_secs(60))
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            let wait = missing / rate.tokens_per_second();
+            Err(Duration::from_secs(wait.ceil() as u64))
+        }
+    }
+
+    /// Gives back what [`check`](Self::check) took for a request that
+    /// was turned away for another reason.
+    pub(crate) fn refund(&self, client: Client, class: OperationClass) {
+        let rate = match self.rate(&client, class) {
+            Some(rate) => rate,
+            None => return,
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bucket) = buckets.get_mut(&(client, class)) {
+            bucket.tokens = (bucket.tokens + 1.0).min(rate.burst());
+        }
+    }
+
+    fn rate(&self, client: &Client, class: OperationClass) -> Option<Rate> {
+        let key_rate = match client {
+            Client::Key(name) => self.keys.get(name).and_then(|k| k.rates.get(&class)),
+            Client::Address(_) => None,
+        };
+
+        key_rate.or_else(|| self.rates.get(&class)).copied()
+    }
+
+    /// Counts a request against the client's daily quota, returning
+    /// how long they need to wait, rounded up to the next second, if
+    /// it has been used up.
+    pub(crate) fn charge_quota(&self, client: &Client) -> Result<(), Duration> {
+        self.charge_quota_at(client, Instant::now())
+    }
+
+    fn charge_quota_at(&self, client: &Client, now: Instant) -> Result<(), Duration> {
+        let name = match client {
+            Client::Key(name) => name,
+            Client::Address(_) => return Ok(()),
+        };
+        let limit = match self.keys.get(name).and_then(|k| k.daily_quota) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        let mut quotas = self.quotas.lock().unwrap_or_else(|e| e.into_inner());
+        let quota = quotas.entry(name.clone()).or_insert(Quota {
+            used: 0,
+            started: now,
+        });
+
+        let elapsed = now.saturating_duration_since(quota.started);
+        if elapsed >= QUOTA_PERIOD {
+            *quota = Quota {
+                used: 0,
+                started: now,
+            };
+        }
+
+        if quota.used < limit {
+            quota.used += 1;
+            Ok(())
+        } else {
+            let wait = QUOTA_PERIOD - elapsed;
+            Err(Duration::from_secs(wait.as_secs_f64().ceil() as u64))
+        }
+    }
+}
+
+impl Bucket {
+    fn refilled(&self, rate: &Rate, now: Instant) -> Self {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let tokens = self.tokens + elapsed * rate.tokens_per_second();
+
+        Self {
+            tokens: tokens.min(rate.burst()),
+            updated: now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    use OperationClass::{Cheap, Expensive};
+
+    fn client(last: u8) -> Client {
+        Client::Address(Ipv4Addr::new(10, 0, 0, last).into())
+    }
+
+    fn limiter(class: OperationClass, per_minute: u32) -> RateLimiter {
+        RateLimiter::new(std::iter::once((class, Rate::per_minute(per_minute))).collect())
+    }
+
+    // The digest of `secret`
+    const SECRET_SHA256: &str = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+
+    fn api_key(daily_quota: Option<u32>) -> ApiKey {
+        ApiKey {
+            name: "bot".into(),
+            sha256: SECRET_SHA256.into(),
+            rates: std::iter::once((Expensive, Rate::per_minute(10))).collect(),
+            daily_quota,
+        }
+    }
+
+    #[test]
+    fn requests_beyond_the_burst_are_rejected() {
+        let limiter = limiter(Expensive, 2);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+
+        let wait = limiter.check_at(client(1), Expensive, now).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn refunded_requests_are_not_counted() {
+        let limiter = limiter(Expensive, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+        limiter.refund(client(1), Expensive);
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+        assert!(limiter.check_at(client(1), Expensive, now).is_err());
+    }
+
+    #[test]
+    fn tokens_are_refilled_over_time() {
+        let limiter = limiter(Expensive, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+        assert!(limiter.check_at(client(1), Expensive, now).is_err());
+
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.check_at(client(1), Expensive, later).is_ok());
+    }
+
+    #[test]
+    fn clients_and_operations_are_limited_separately() {
+        let limiter = limiter(Expensive, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+        assert!(limiter.check_at(client(2), Expensive, now).is_ok());
+        assert!(limiter.check_at(client(1), Cheap, now).is_ok());
+        assert!(limiter.check_at(client(1), Cheap, now).is_ok());
+    }
+
+    #[test]
+    fn keys_are_identified_by_their_digest() {
+        let limiter = limiter(Expensive, 1).with_api_keys(vec![api_key(None)]);
+
+        assert_eq!(
+            limiter.client_for_key("secret"),
+            Some(Client::Key("bot".into()))
+        );
+        assert_eq!(limiter.client_for_key("guess"), None);
+    }
+
+    #[test]
+    fn keys_have_their_own_rates() {
+        let limiter = limiter(Expensive, 1).with_api_keys(vec![api_key(None)]);
+        let key = limiter.client_for_key("secret").unwrap();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            assert!(limiter.check_at(key.clone(), Expensive, now).is_ok());
+        }
+        assert!(limiter.check_at(key, Expensive, now).is_err());
+
+        assert!(limiter.check_at(client(1), Expensive, now).is_ok());
+        assert!(limiter.check_at(client(1), Expensive, now).is_err());
+    }
+
+    #[test]
+    fn quotas_are_used_up_for_the_day() {
+        let limiter = limiter(Expensive, 1).with_api_keys(vec![api_key(Some(2))]);
+        let key = limiter.client_for_key("secret").unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.charge_quota_at(&key, now).is_ok());
+        assert!(limiter.charge_quota_at(&key, now).is_ok());
+
+        let later = now + Duration::from_secs(60 * 60);
+        let wait = limiter.charge_quota_at(&key, later).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(23 * 60 * 60));
+
+        let tomorrow = now + QUOTA_PERIOD;
+        assert!(limiter.charge_quota_at(&key, tomorrow).is_ok());
+        assert!(limiter.charge_quota_at(&client(1), tomorrow).is_ok());
+    }
+}
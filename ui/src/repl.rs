@@ -0,0 +1,191 @@
+use snafu::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    artifacts::random_id,
+    dispatcher::Dispatcher,
+    sandbox::{
+        self,
+        repl::{Evaluation, Session, Variable},
+    },
+};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// REPL sessions, each with a container of its own, kept until the
+/// client closes them or stops using them.
+pub(crate) struct Repls {
+    config: Config,
+    sessions: Mutex<HashMap<String, Arc<Entry>>>,
+    permits: Arc<Semaphore>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Config {
+    /// How many sessions may be open at once. When zero, sessions
+    /// cannot be started.
+    pub(crate) max_sessions: usize,
+    /// Sessions that have not been used for this long are closed.
+    pub(crate) idle_timeout: Duration,
+}
+
+struct Entry {
+    session: tokio::sync::Mutex<Session>,
+    last_used: Mutex<Instant>,
+    // Held until the session is gone
+    _permit: OwnedSemaphorePermit,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("REPL sessions are not available"))]
+    Unavailable,
+    #[snafu(display("There are already {} REPL sessions; please try again later", max))]
+    TooMany { max: usize },
+    #[snafu(display("REPL session failed: {}", source))]
+    Sandbox { source: sandbox::Error },
+}
+
+pub(crate) type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+impl Repls {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            config,
+            sessions: Default::default(),
+            permits: Arc::new(Semaphore::new(config.max_sessions)),
+        }
+    }
+
+    pub(crate) fn idle_timeout(&self) -> Duration {
+        self.config.idle_timeout
+    }
+
+    /// Sessions are only run on this server, as they outlive any
+    /// single request that could be handed to a worker.
+    pub(crate) async fn start(&self, dispatcher: &Dispatcher, request_id: &str) -> Result<String> {
+        let available =
+            self.config.max_sessions != 0 && dispatcher.remote_sandbox(request_id).is_none();
+        ensure!(available, UnavailableSnafu);
+
+        let permit = self
+            .permits
+            .clone()
+            .try_acquire_owned()
+            .ok()
+            .context(TooManySnafu {
+                max: self.config.max_sessions,
+            })?;
+
+        let sandbox = dispatcher.sandbox(request_id).await.context(SandboxSnafu)?;
+        let session = sandbox.start_repl().await.context(SandboxSnafu)?;
+
+        let id = random_id();
+        let entry = Entry {
+            session: tokio::sync::Mutex::new(session),
+            last_used: Mutex::new(Instant::now()),
+            _permit: permit,
+        };
+        self.lock().insert(id.clone(), Arc::new(entry));
+
+        Ok(id)
+    }
+
+    /// `None` when there is no such session.
+    pub(crate) async fn evaluate(&self, id: &str, code: &str) -> Option<Result<Evaluation>> {
+        let entry = self.entry(id)?;
+        let mut session = entry.session.lock().await;
+
+        let evaluation = session.evaluate(code).await.context(SandboxSnafu);
+        self.used(id, &entry, &session);
+        Some(evaluation)
+    }
+
+    /// `None` when there is no such session.
+    pub(crate) async fn variables(&self, id: &str) -> Option<Result<Vec<Variable>>> {
+        let entry = self.entry(id)?;
+        let mut session = entry.session.lock().await;
+
+        let variables = session.variables().await.context(SandboxSnafu);
+        self.used(id, &entry, &session);
+        Some(variables)
+    }
+
+    /// `None` when there is no such session.
+    pub(crate) async fn close(&self, id: &str) -> Option<Result<()>> {
+        let entry = self.lock().remove(id)?;
+        let closed = entry.session.lock().await.close().await;
+        Some(closed.context(SandboxSnafu))
+    }
+
+    /// Closes the idle sessions every so often, forever.
+    pub(crate) async fn sweep_periodically(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let idle = self.take_idle(Instant::now());
+            if !idle.is_empty() {
+                tracing::debug!(count = idle.len(), "Closing idle REPL sessions");
+            }
+
+            for entry in idle {
+                if let Err(e) = entry.session.lock().await.close().await {
+                    tracing::warn!("Unable to close an idle REPL session: {}", e);
+                }
+            }
+        }
+    }
+
+    fn entry(&self, id: &str) -> Option<Arc<Entry>> {
+        let entry = self.lock().get(id)?.clone();
+        *entry.lock_last_used() = Instant::now();
+        Some(entry)
+    }
+
+    // A session that can no longer be used is forgotten right away
+    // instead of waiting to become idle
+    fn used(&self, id: &str, entry: &Arc<Entry>, session: &Session) {
+        *entry.lock_last_used() = Instant::now();
+
+        if session.has_ended() {
+            let mut sessions = self.lock();
+            if sessions.get(id).is_some_and(|e| Arc::ptr_eq(e, entry)) {
+                sessions.remove(id);
+            }
+        }
+    }
+
+    // Sessions in the middle of an evaluation aren't idle, however
+    // long it has been since they were last asked for
+    fn take_idle(&self, now: Instant) -> Vec<Arc<Entry>> {
+        let mut sessions = self.lock();
+
+        let idle: Vec<_> = sessions
+            .iter()
+            .filter(|(_, entry)| {
+                let idle_for = now.saturating_duration_since(*entry.lock_last_used());
+                idle_for >= self.config.idle_timeout && entry.session.try_lock().is_ok()
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        idle.iter().filter_map(|id| sessions.remove(id)).collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Entry>>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Entry {
+    fn lock_last_used(&self) -> std::sync::MutexGuard<'_, Instant> {
+        self.last_used.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type AnyValue = Arc<dyn Any + Send + Sync>;
+
+/// How many times the cache has been cleared, so that responses to
+/// requests that were started before then aren't kept.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Generation(u64);
+
+/// A least-recently-used cache of sandbox responses, keyed by the
+/// request that produced them.
+///
+/// Different kinds of requests share the same cache; the types of the
+/// request and of the response are part of the key, as endpoints that
+/// send the same request may make different responses from it.
+pub(crate) struct ResponseCache {
+    capacity: usize,
+    time_to_live: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<u64, Entry>,
+    /// The key of each entry, by when it was last used
+    by_last_use: BTreeMap<u64, u64>,
+    clock: u64,
+    generation: u64,
+}
+
+struct Entry {
+    request: AnyValue,
+    response: AnyValue,
+    inserted: Instant,
+    last_used: u64,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(capacity: usize, time_to_live: Duration) -> Self {
+        Self {
+            capacity,
+            time_to_live,
+            inner: Default::default(),
+        }
+    }
+
+    pub(crate) fn get<Req, Resp>(&self, request: &Req) -> Option<Resp>
+    where
+        Req: Hash + Eq + 'static,
+        Resp: Clone + 'static,
+    {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let key = key_for::<Req, Resp>(request);
+        let mut inner = self.lock();
+        inner.clock += 1;
+        let now = inner.clock;
+        let Inner {
+            entries,
+            by_last_use,
+            ..
+        } = &mut *inner;
+
+        let entry = entries.get_mut(&key)?;
+
+        if entry.inserted.elapsed() > self.time_to_live {
+            inner.remove(key);
+            return None;
+        }
+
+        // Guard against hash collisions
+        if entry.request.downcast_ref::<Req>() != Some(request) {
+            return None;
+        }
+
+        by_last_use.remove(&entry.last_used);
+        by_last_use.insert(now, key);
+        entry.last_used = now;
+        entry.response.downcast_ref::<Resp>().cloned()
+    }
+
+    pub(crate) fn generation(&self) -> Generation {
+        Generation(self.lock().generation)
+    }
+
+    /// Keeps the response unless the cache was cleared after `since`,
+    /// when the request was started, as it would be out of date.
+    pub(crate) fn insert<Req, Resp>(&self, since: Generation, request: Req, response: Resp)
+    where
+        Req: Hash + Eq + Send + Sync + 'static,
+        Resp: Send + Sync + 'static,
+    {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = key_for::<Req, Resp>(&request);
+        let mut inner = self.lock();
+        if inner.generation != since.0 {
+            return;
+        }
+        inner.clock += 1;
+        let now = inner.clock;
+
+        inner.remove(key);
+        let entry = Entry {
+            request: Arc::new(request),
+            response: Arc::new(response),
+            inserted: Instant::now(),
+            last_used: now,
+        };
+        inner.entries.insert(key, entry);
+        inner.by_last_use.insert(now, key);
+
+        while inner.entries.len() > self.capacity {
+            match inner.by_last_use.first_key_value() {
+                Some((_, &oldest)) => inner.remove(oldest),
+                None => break,
+            }
+        }
+    }
+
+    /// Forgets every response, such as once running the requests
+    /// again could give different ones.
+    pub(crate) fn clear(&self) {
+        let mut inner = self.lock();
+        inner.entries.clear();
+        inner.by_last_use.clear();
+        inner.generation += 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // No operation can leave the cache in an inconsistent state
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.by_last_use.remove(&entry.last_used);
+        }
+    }
+}
+
+pub(crate) fn key_for<Req: Hash + 'static, Resp: 'static>(request: &Req) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    TypeId::of::<Req>().hash(&mut hasher);
+    TypeId::of::<Resp>().hash(&mut hasher);
+    request.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const A_WHILE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn returns_stored_responses() {
+        let cache = ResponseCache::new(10, A_WHILE);
+        cache.insert(cache.generation(), "fn main() {}".to_string(), 42u8);
+
+        assert_eq!(cache.get::<_, u8>(&"fn main() {}".to_string()), Some(42));
+        assert_eq!(cache.get::<_, u8>(&"fn main() {  }".to_string()), None);
+    }
+
+    #[test]
+    fn request_types_do_not_collide() {
+        let cache = ResponseCache::new(10, A_WHILE);
+        cache.insert(cache.generation(), 1u32, "from u32");
+
+        assert_eq!(cache.get::<_, &str>(&1u64), None);
+    }
+
+    #[test]
+    fn response_types_do_not_collide() {
+        let cache = ResponseCache::new(10, A_WHILE);
+        cache.insert(cache.generation(), 1, "as text");
+        cache.insert(cache.generation(), 1, 1u8);
+
+        assert_eq!(cache.get::<_, &str>(&1), Some("as text"));
+        assert_eq!(cache.get::<_, u8>(&1), Some(1));
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        let cache = ResponseCache::new(2, A_WHILE);
+        cache.insert(cache.generation(), 1, 1);
+        cache.insert(cache.generation(), 2, 2);
+        assert_eq!(cache.get::<_, i32>(&1), Some(1));
+
+        cache.insert(cache.generation(), 3, 3);
+
+        assert_eq!(cache.get::<_, i32>(&1), Some(1));
+        assert_eq!(cache.get::<_, i32>(&2), None);
+        assert_eq!(cache.get::<_, i32>(&3), Some(3));
+        assert_eq!(cache.lock().by_last_use.len(), 2);
+    }
+
+    #[test]
+    fn expired_entries_are_not_returned() {
+        let cache = ResponseCache::new(10, Duration::from_secs(0));
+        cache.insert(cache.generation(), 1, 1);
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(cache.get::<_, i32>(&1), None);
+    }
+
+    #[test]
+    fn clearing_forgets_every_response() {
+        let cache = ResponseCache::new(10, A_WHILE);
+        cache.insert(cache.generation(), 1, 1);
+        cache.clear();
+
+        assert_eq!(cache.get::<_, i32>(&1), None);
+        assert!(cache.lock().by_last_use.is_empty());
+    }
+
+    #[test]
+    fn responses_from_before_clearing_are_not_kept() {
+        let cache = ResponseCache::new(10, A_WHILE);
+        let started = cache.generation();
+        cache.clear();
+        cache.insert(started, 1, 1);
+
+        assert_eq!(cache.get::<_, i32>(&1), None);
+
+        cache.insert(cache.generation(), 1, 1);
+        assert_eq!(cache.get::<_, i32>(&1), Some(1));
+    }
+
+    #[test]
+    fn zero_capacity_disables_the_cache() {
+        let cache = ResponseCache::new(0, A_WHILE);
+        cache.insert(cache.generation(), 1, 1);
+
+        assert_eq!(cache.get::<_, i32>(&1), None);
+    }
+}
@@ -1,46 +1,95 @@
 use crate::{
-    gist,
+    artifacts::Artifacts,
+    config::{Config, HttpLimits},
+    coordinator::{self, WorkerLoad},
+    diff,
+    dispatcher::{Dispatcher, OperationClass, Progress, Remote, Runner, Stage},
+    gist, highlight,
+    idempotency::{Claim, IdempotencyKeys},
+    jobs::{JobId, Jobs, Outcome, Status},
     metrics::{
         track_metric_async, track_metric_force_endpoint_async, track_metric_no_request_async,
-        Endpoint, GenerateLabels, SuccessDetails,
+        Endpoint, GenerateLabels, SuccessDetails, USAGE,
     },
-    sandbox::{self, Channel, Sandbox},
-    CachingSnafu, ClippyRequest, ClippyResponse, CompilationSnafu, CompileRequest, CompileResponse,
-    Config, Error, ErrorJson, EvaluateRequest, EvaluateResponse, EvaluationSnafu, ExecuteRequest,
-    ExecuteResponse, ExecutionSnafu, ExpansionSnafu, FormatRequest, FormatResponse,
-    FormattingSnafu, GhToken, GistCreationSnafu, GistLoadingSnafu, InterpretingSnafu, LintingSnafu,
-    MacroExpansionRequest, MacroExpansionResponse, MetaCratesResponse, MetaGistCreateRequest,
-    MetaGistResponse, MetaVersionResponse, MetricsToken, MiriRequest, MiriResponse, Result,
-    SandboxCreationSnafu,
+    phase_name,
+    rate_limit::{Client, RateLimiter},
+    repl::{self, Repls},
+    sandbox::{self, job, Channel, CodeRequest, Sandbox},
+    templates::{self, Templates},
+    AdminStateResponse, AdminToken, ArtifactNotFoundSnafu, ArtifactQuery, ArtifactStorageSnafu,
+    AssemblyDiffRequest, AssemblyDiffResponse, AssessRequest, AssessResponse, AssessmentSnafu,
+    BatchCodeTooLargeSnafu, BatchRequest, BatchResponse, BatchResult, BatchTooLargeSnafu,
+    BisectRequest, BisectRequiresJobSnafu, BisectResponse, BisectingSnafu, CachingSnafu,
+    ClippyRequest, ClippyResponse, CodeTooLargeSnafu, CompareRequest, CompareResponse,
+    CompilationSnafu, CompileRequest, CompileResponse, Compiled, CompletionsRequest,
+    CompletionsResponse, CompletionsSnafu, EditionMigrationRequest, EditionMigrationResponse,
+    EditionMigrationSnafu, EmbedQuery, Error, ErrorJson, EvaluateRequest, EvaluateResponse,
+    EvaluationSnafu, ExecuteRequest, ExecuteResponse, Executed, ExecutionSnafu, ExpansionSnafu,
+    FormatRequest, FormatResponse, FormattingSnafu, GhToken, GistCreationSnafu, GistLoadingSnafu,
+    HighlightQuery, HoverRequest, HoverResponse, HoveringSnafu, InlayHintsRequest,
+    InlayHintsResponse, InlayHintsSnafu, InterpretingSnafu, InvalidJobEndpointSnafu,
+    InvalidWebSocketMessageSnafu, JobNotFoundSnafu, JobResponse, JobState, KaniRequest,
+    KaniResponse, LintingSnafu, MacroExpansionRequest, MacroExpansionResponse, MaintenanceSnafu,
+    MetaClippyLintsResponse, MetaCratesResponse, MetaGistCreateRequest, MetaGistResponse,
+    MetaImagesResponse, MetaTargetsResponse, MetaUsageResponse, MetaVersionResponse,
+    MetaVersionsResponse, MetricsToken, MirDiffRequest, MirDiffResponse, MiriRequest, MiriResponse,
+    NavigatingSnafu, NavigationRequest, NavigationResponse, OptimizationRemarksRequest,
+    OptimizationRemarksResponse, OptimizationRemarksSnafu, OutputDiffRequest, OutputDiffResponse,
+    OverloadedSnafu, ProfilingSnafu, ReadinessResponse, ReduceRequest, ReduceRequiresJobSnafu,
+    ReduceResponse, ReducingSnafu, ReplEvaluateRequest, ReplEvaluateResponse,
+    ReplSessionNotFoundSnafu, ReplSessionResponse, ReplSnafu, ReplVariablesResponse, Rerun,
+    RerunRequest, Result, SandboxCreationSnafu, SandboxUnavailableSnafu, ServerBusySnafu,
+    ShareLinkCreateRequest, ShareLinkCreationSnafu, ShareLinkLoadingSnafu, ShareLinkResponse,
+    ShareLinks, SnippetCreateRequest, SnippetCreationSnafu, SnippetHighlightingSnafu,
+    SnippetHighlightingTaskSnafu, SnippetLoadingSnafu, SnippetNotFoundSnafu, SnippetResponse,
+    SnippetStorage, Submission, TemplateNotFoundSnafu, TemplateRequest, TemplateResponse,
+    TemplatesSnafu, TimePassesRequest, TimePassesResponse, VerifyingSnafu, WebSocketBusySnafu,
+    WebSocketRequest, WithMetadata, WorkerJobSnafu, WorkerToken, KEPT_BINARY_CONTENT_TYPE,
 };
 use async_trait::async_trait;
 use axum::{
-    extract::{self, Extension, Path, TypedHeader},
+    body::{Body, StreamBody},
+    extract::{
+        self,
+        ws::{self, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Extension, Path, Query, TypedHeader,
+    },
     handler::Handler,
-    headers::{authorization::Bearer, Authorization, CacheControl, ETag, IfNoneMatch},
+    headers::{
+        authorization::Bearer, Authorization, CacheControl, ETag, IfModifiedSince, IfNoneMatch,
+        LastModified,
+    },
     http::{header, uri::PathAndQuery, HeaderValue, Method, Request, StatusCode, Uri},
     middleware,
-    response::IntoResponse,
-    routing::{get, get_service, post, MethodRouter},
+    response::{Html, IntoResponse, Redirect},
+    routing::{delete, get, get_service, post, put, MethodRouter},
     Router,
 };
-use futures::{future::BoxFuture, FutureExt};
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+use sha2::{Digest, Sha256};
 use snafu::{prelude::*, IntoError};
 use std::{
     convert::{TryFrom, TryInto},
     future::Future,
-    mem, path,
+    marker::PhantomData,
+    mem,
+    net::SocketAddr,
+    path,
     str::FromStr,
     sync::Arc,
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::Mutex;
+use tower::ServiceExt;
 use tower_http::{
     cors::{self, CorsLayer},
     services::ServeDir,
-    set_header::SetResponseHeader,
-    trace::TraceLayer,
+    set_header::{SetResponseHeader, SetResponseHeaderLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+    LatencyUnit,
 };
+use tracing::Level;
+use utoipa::OpenApi;
 
 const ONE_HOUR: Duration = Duration::from_secs(60 * 60);
 const CORS_CACHE_TIME_TO_LIVE: Duration = ONE_HOUR;
@@ -51,23 +100,217 @@ const SANDBOX_CACHE_TIME_TO_LIVE: Duration = TEN_MINUTES;
 const MAX_AGE_ONE_DAY: HeaderValue = HeaderValue::from_static("public, max-age=86400");
 const MAX_AGE_ONE_YEAR: HeaderValue = HeaderValue::from_static("public, max-age=31536000");
 
+// Roughly how long a single sandbox is expected to run
+const SERVER_BUSY_RETRY_AFTER: HeaderValue = HeaderValue::from_static("10");
+
+const API_VERSION_HEADER: &str = "playground-api-version";
+const API_VERSION: HeaderValue = HeaderValue::from_static("1");
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_REQUEST_ID: &str = "x-request-id";
+pub(crate) const IDEMPOTENCY_KEY: &str = "idempotency-key";
+pub(crate) const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+pub(crate) const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+// Requests to these endpoints may instead be made to
+// `/jobs/<endpoint>`, in which case the response is available from
+// `/jobs/<id>` once it is ready. They may also be part of a batch.
+const JOB_ENDPOINTS: &[&str] = &[
+    "compile",
+    "execute",
+    "rerun",
+    "format",
+    "edition-migration",
+    "clippy",
+    "miri",
+    "kani",
+    "macro-expansion",
+    "time-passes",
+    "optimization-remarks",
+    "completions",
+    "hover",
+    "navigation",
+    "inlay-hints",
+    "compare",
+    "assembly-diff",
+    "mir-diff",
+    "bisect",
+    "reduce",
+];
+
+const MAX_BATCH_SIZE: usize = 16;
+// Each operation still needs a sandbox, so running more at once
+// would only have them wait in the queue
+const BATCH_CONCURRENCY: usize = 4;
+
+const MAX_RUNNING_WEBSOCKET_OPERATIONS: usize = 16;
+const WEBSOCKET_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
 #[tokio::main]
 pub(crate) async fn serve(config: Config) {
+    let remote = config.remote().unwrap_or_else(|error| {
+        tracing::error!(%error, "Unable to hand requests to workers");
+        std::process::exit(1);
+    });
+    if let Some(Remote::Workers(workers)) = &remote {
+        tokio::spawn(workers.clone().check_periodically());
+    }
+    let dispatcher = match remote {
+        Some(remote) => config.dispatcher().with_remote(remote),
+        None => config.dispatcher(),
+    };
+    let dispatcher = Arc::new(dispatcher);
+
+    // The metadata, such as versions and crates, is still found by
+    // running sandboxes here
+    dispatcher.start(config.image_update_interval()).await;
+
     let root_files = static_file_service(config.root_path(), MAX_AGE_ONE_DAY);
     let asset_files = static_file_service(config.asset_path(), MAX_AGE_ONE_YEAR);
     let rewrite_help_as_index = middleware::from_fn(rewrite_help_as_index);
 
-    let mut app = Router::new()
-        .fallback(root_files)
-        .nest("/assets", asset_files)
-        .layer(rewrite_help_as_index)
-        .route("/evaluate.json", post(evaluate))
-        .route("/compile", post(compile))
-        .route("/execute", post(execute))
-        .route("/format", post(format))
-        .route("/clippy", post(clippy))
-        .route("/miri", post(miri))
-        .route("/macro-expansion", post(macro_expansion))
+    let artifacts = Arc::new(config.artifacts());
+    tokio::spawn(artifacts.clone().sweep_periodically());
+
+    let repls = Arc::new(config.repls());
+    tokio::spawn(repls.clone().sweep_periodically());
+
+    let snippets = config.snippet_storage().unwrap_or_else(|error| {
+        tracing::error!(%error, "Unable to open snippet storage");
+        std::process::exit(1);
+    });
+    if let Some(storage) = &snippets.0 {
+        tokio::spawn(storage.clone().sweep_periodically());
+    }
+
+    let templates = Templates::open(config.templates_file())
+        .await
+        .unwrap_or_else(|error| {
+            tracing::error!(%error, "Unable to load the templates");
+            std::process::exit(1);
+        });
+    let templates = Arc::new(templates);
+
+    let rate_limiter = Arc::new(config.rate_limiter());
+    let bisect_rate_limiter = Arc::new(config.bisect_rate_limiter());
+    let trust_forwarded_for = config.trust_forwarded_for();
+    if let Some(addr) = config.grpc_socket_addr() {
+        #[cfg(feature = "grpc")]
+        tokio::spawn(crate::grpc::serve(
+            dispatcher.clone(),
+            rate_limiter.clone(),
+            config.http_limits(),
+            addr,
+        ));
+        #[cfg(not(feature = "grpc"))]
+        tracing::warn!(%addr, "Built without the gRPC API, so it will not be served");
+    }
+    let rate_limited = |rate_limiter: &Arc<RateLimiter>, class| {
+        let rate_limiter = rate_limiter.clone();
+        move |route: MethodRouter| {
+            let rate_limiter = rate_limiter.clone();
+            route.route_layer(middleware::from_fn(move |req, next| {
+                enforce_rate_limit(rate_limiter.clone(), trust_forwarded_for, class, req, next)
+            }))
+        }
+    };
+    // Retries with the same key get the first response instead of
+    // creating something again
+    let idempotency_keys = Arc::new(IdempotencyKeys::default());
+    let idempotent = |route: MethodRouter| {
+        let keys = idempotency_keys.clone();
+        let rate_limiter = rate_limiter.clone();
+        route.route_layer(middleware::from_fn(move |req, next| {
+            enforce_idempotency(
+                keys.clone(),
+                rate_limiter.clone(),
+                trust_forwarded_for,
+                req,
+                next,
+            )
+        }))
+    };
+    let cheap = rate_limited(&rate_limiter, OperationClass::Cheap);
+    let expensive = rate_limited(&rate_limiter, OperationClass::Expensive);
+    let bisect_limited = rate_limited(&bisect_rate_limiter, OperationClass::Expensive);
+
+    let operations = Router::new()
+        .route("/compile", expensive(post(compile)))
+        .route("/execute", expensive(post(execute)))
+        .route("/rerun", expensive(post(rerun)))
+        .route("/format", cheap(post(format)))
+        .route("/edition-migration", expensive(post(edition_migration)))
+        .route("/clippy", cheap(post(clippy)))
+        .route("/miri", expensive(post(miri)))
+        .route("/kani", expensive(post(kani)))
+        .route("/macro-expansion", cheap(post(macro_expansion)))
+        .route("/time-passes", expensive(post(time_passes)))
+        .route("/assess", expensive(post(assess)))
+        .route(
+            "/optimization-remarks",
+            expensive(post(optimization_remarks)),
+        )
+        .route("/completions", expensive(post(completions)))
+        .route("/hover", expensive(post(hover)))
+        .route("/navigation", expensive(post(navigation)))
+        .route("/inlay-hints", expensive(post(inlay_hints)))
+        .route("/compare", expensive(post(compare)))
+        .route("/assembly-diff", expensive(post(assembly_diff)))
+        .route("/mir-diff", expensive(post(mir_diff)))
+        .route("/bisect", bisect_limited(post(bisect)))
+        .route("/reduce", expensive(post(reduce)));
+
+    let submit_job = {
+        let operations = operations.clone();
+        move |jobs, endpoint, req| submit_job(operations.clone(), jobs, endpoint, req)
+    };
+
+    // The operations are new requests, so they don't have the
+    // extensions of the batch
+    let operations_with_extensions = operations
+        .clone()
+        .layer(Extension(dispatcher.clone()))
+        .layer(Extension(artifacts.clone()));
+
+    let batch = {
+        let operations = operations_with_extensions.clone();
+        let max_code_size = config.max_total_code_size();
+        move |headers, connect_info, request_id, req| {
+            batch(
+                operations.clone(),
+                max_code_size,
+                headers,
+                connect_info,
+                request_id,
+                req,
+            )
+        }
+    };
+
+    // A message holds one operation, so it may be as large as a batch
+    let websocket = {
+        let operations = operations_with_extensions;
+        let max_message_size = config.max_total_code_size();
+        move |ws, dispatcher, headers, connect_info, request_id| {
+            websocket(
+                operations.clone(),
+                max_message_size,
+                ws,
+                dispatcher,
+                headers,
+                connect_info,
+                request_id,
+            )
+        }
+    };
+
+    // Versioned so that requests and responses can change without
+    // breaking existing clients. The unversioned paths are the first
+    // version.
+    let api = Router::new()
+        .route("/evaluate.json", expensive(post(evaluate)))
+        .merge(operations)
+        .route("/output-diff", expensive(post(output_diff)))
         .route("/meta/crates", get_or_post(meta_crates))
         .route("/meta/version/stable", get_or_post(meta_version_stable))
         .route("/meta/version/beta", get_or_post(meta_version_beta))
@@ -75,34 +318,193 @@ pub(crate) async fn serve(config: Config) {
         .route("/meta/version/rustfmt", get_or_post(meta_version_rustfmt))
         .route("/meta/version/clippy", get_or_post(meta_version_clippy))
         .route("/meta/version/miri", get_or_post(meta_version_miri))
+        .route("/meta/clippy-lints", get(meta_clippy_lints))
+        .route("/meta/versions", get(meta_versions))
+        .route("/meta/targets", get(meta_targets))
+        .route("/meta/images", get(meta_images))
+        .route("/meta/usage", get(meta_usage))
         .route("/meta/gist", post(meta_gist_create))
         .route("/meta/gist/:id", get(meta_gist_get))
+        .route("/snippets", idempotent(post(snippet_create)))
+        .route("/snippets/:id", get(snippet_get))
+        .route("/snippets/:id/raw", get(snippet_raw))
+        .route("/snippets/:id/highlighted", get(snippet_highlighted))
+        .route("/snippets/:id/embed", expensive(get(snippet_embed)))
+        .route("/templates", get(templates_list))
+        .route("/templates/:name", get(template_get))
+        .route("/share-links", post(share_link_create))
+        .route("/share-links/:link", get(share_link_get))
+        .route(
+            "/share-links/:link/execute",
+            expensive(post(share_link_execute)),
+        )
+        .route("/artifacts/:id", get(artifact_get))
+        .route("/repl/sessions", expensive(post(repl_start)))
+        .route("/repl/sessions/:id", delete(repl_close))
+        .route(
+            "/repl/sessions/:id/evaluate",
+            expensive(post(repl_evaluate)),
+        )
+        .route("/repl/sessions/:id/variables", get(repl_variables))
+        .route(
+            "/jobs/:id",
+            get(job_status).merge(idempotent(post(submit_job))),
+        )
+        .route("/batch", post(batch))
+        .route("/websocket", get(websocket))
+        .route("/openapi.json", get(openapi))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::HeaderName::from_static(API_VERSION_HEADER),
+            API_VERSION,
+        ));
+
+    let mut app = Router::new()
+        .fallback(root_files)
+        .nest("/assets", asset_files)
+        .layer(rewrite_help_as_index)
+        .merge(api.clone())
+        .nest("/api/v1", api)
+        .route("/s/:id", get(snippet_redirect))
+        .route("/l/:link", get(share_link_redirect))
         .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .layer(Extension(Arc::new(Jobs::default())))
         .layer(Extension(Arc::new(SandboxCache::default())))
-        .layer(Extension(config.github_token()));
+        .layer(Extension(dispatcher.clone()))
+        .layer(Extension(artifacts))
+        .layer(Extension(repls))
+        .layer(Extension(config.github_token()))
+        .layer(Extension(snippets.clone()))
+        .layer(Extension(templates.clone()))
+        .layer(Extension(config.share_links()));
 
     if let Some(token) = config.metrics_token() {
         app = app.layer(Extension(token))
     }
 
+    // Without a token, there's no way to use these safely
+    if let Some(token) = config.admin_token() {
+        app = app
+            .route("/admin/state", get(admin_state))
+            .route("/admin/drain", post(admin_drain).delete(admin_resume))
+            .route(
+                "/admin/snippets/:id/keep",
+                put(admin_keep_snippet).delete(admin_unkeep_snippet),
+            )
+            .route(
+                "/admin/templates/:name",
+                put(admin_put_template).delete(admin_remove_template),
+            )
+            .layer(Extension(token))
+            .layer(Extension(dispatcher.clone()))
+            .layer(Extension(snippets))
+            .layer(Extension(templates));
+    }
+
+    // Lets a coordinator send requests here
+    if let Some(token) = config.worker_token() {
+        app = app
+            .route(coordinator::RUN_PATH, post(worker_run))
+            .route(coordinator::LOAD_PATH, get(worker_load))
+            .layer(Extension(token))
+            .layer(Extension(dispatcher.clone()));
+    }
+
     if config.use_cors() {
         app = app.layer({
             CorsLayer::new()
                 .allow_origin(cors::Any)
-                .allow_headers([header::CONTENT_TYPE])
-                .allow_methods([Method::GET, Method::POST])
+                .allow_headers([
+                    header::AUTHORIZATION,
+                    header::CONTENT_TYPE,
+                    header::HeaderName::from_static(IDEMPOTENCY_KEY),
+                ])
+                // REPL sessions are ended with a `DELETE`
+                .allow_methods([Method::GET, Method::POST, Method::DELETE])
                 .allow_credentials(false)
                 .max_age(CORS_CACHE_TIME_TO_LIVE)
         });
     }
 
     // Basic access logging
-    app = app.layer(TraceLayer::new_for_http());
+    app = app.layer(
+        TraceLayer::new_for_http()
+            .make_span_with(request_span)
+            .on_response(
+                DefaultOnResponse::new()
+                    .level(Level::INFO)
+                    .latency_unit(LatencyUnit::Millis),
+            ),
+    );
+
+    // Outside of the access logging so the ID is part of it
+    app = app.layer(middleware::from_fn(assign_request_id));
+
+    // Outside of the request ID so that errors are converted as well
+    app = app.layer(middleware::from_fn(negotiate_format));
+
+    // Outside of everything else, so that nothing waits on a client
+    // for longer than this
+    let http_limits = config.http_limits();
+    app = app.layer(middleware::from_fn(move |req, next| {
+        enforce_http_limits(http_limits, req, next)
+    }));
+
+    let shutdown = shutdown_signal().shared();
+
+    let server = axum::Server::bind(&config.server_socket_addr())
+        .http1_header_read_timeout(http_limits.header_timeout)
+        .http1_max_buf_size(http_limits.max_header_size)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.clone());
+
+    let drain = async {
+        shutdown.clone().await;
+        tracing::info!("Shutting down; waiting for running sandboxes to finish");
+        dispatcher.drain().await;
+        Ok(())
+    };
 
-    axum::Server::bind(&config.server_socket_addr())
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let shutdown_timeout = config.shutdown_timeout();
+    let give_up = async {
+        shutdown.clone().await;
+        tokio::time::sleep(shutdown_timeout).await;
+    };
+
+    tokio::select! {
+        result = futures::future::try_join(server, drain) => result.map(drop).unwrap(),
+        () = give_up => tracing::warn!("Sandboxes did not finish in time"),
+    }
+
+    remove_leftover_containers().await;
+}
+
+/// Anything still running has been abandoned by its request.
+pub(crate) async fn remove_leftover_containers() {
+    match sandbox::remove_containers().await {
+        Ok(0) => {}
+        Ok(count) => tracing::warn!(count, "Removed leftover containers"),
+        Err(error) => tracing::error!(%error, "Unable to remove leftover containers"),
+    }
+}
+
+pub(crate) async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Unable to handle SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
 }
 
 fn get_or_post<T: 'static>(handler: impl Handler<T> + Copy) -> MethodRouter {
@@ -136,12 +538,705 @@ async fn rewrite_help_as_index<B>(
     next.run(req).await
 }
 
+async fn enforce_http_limits(
+    limits: HttpLimits,
+    req: Request<Body>,
+    next: middleware::Next<Body>,
+) -> axum::response::Response {
+    let max_size = limits.max_body_size;
+    let size = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse().ok());
+    if let Some(size) = size.filter(|&size| size > max_size) {
+        return Error::RequestTooLarge { size, max_size }.into_response();
+    }
+
+    // Bodies without a length stop being read once they are too large
+    let req = req.map(|body| {
+        let mut read = 0;
+        Body::wrap_stream(body.map(move |chunk| {
+            let chunk = chunk?;
+            read += chunk.len();
+            if read > max_size {
+                let error = Error::RequestTooLarge {
+                    size: read,
+                    max_size,
+                };
+                return Err(tower::BoxError::from(error));
+            }
+            Ok(chunk)
+        }))
+    });
+
+    let timeout = limits.request_timeout;
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => Error::RequestTimedOut { timeout }.into_response(),
+    }
+}
+
+async fn enforce_rate_limit(
+    rate_limiter: Arc<RateLimiter>,
+    trust_forwarded_for: bool,
+    class: OperationClass,
+    req: Request<Body>,
+    next: middleware::Next<Body>,
+) -> axum::response::Response {
+    let client = client_for(&req, trust_forwarded_for);
+    if let Err(error) = admit(&rate_limiter, api_key(&req), client, class) {
+        return error.into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Counts a request towards the rate and the quota of whoever made
+/// it, which is the owner of the API key when one was given. Shared
+/// with the gRPC API, where `client` is the peer's address.
+pub(crate) fn admit(
+    rate_limiter: &RateLimiter,
+    api_key: Option<&str>,
+    client: Option<Client>,
+    class: OperationClass,
+) -> Result<Option<Client>> {
+    let client = match api_key.filter(|_| rate_limiter.has_api_keys()) {
+        Some(key) => Some(
+            rate_limiter
+                .client_for_key(key)
+                .ok_or(Error::InvalidApiKey)?,
+        ),
+        None => client,
+    };
+
+    if let Some(client) = &client {
+        if let Err(retry_after) = rate_limiter.check(client.clone(), class) {
+            return Err(Error::RateLimited { retry_after });
+        }
+        if let Err(retry_after) = rate_limiter.charge_quota(client) {
+            // The request isn't run, so it shouldn't count towards the
+            // rate either
+            rate_limiter.refund(client.clone(), class);
+            return Err(Error::QuotaExceeded { retry_after });
+        }
+    }
+
+    Ok(client)
+}
+
+pub(crate) type IdempotencyScope = (Option<Client>, String);
+type KeptResponse = (StatusCode, header::HeaderMap, hyper::body::Bytes);
+
+async fn enforce_idempotency(
+    keys: Arc<IdempotencyKeys<IdempotencyScope, KeptResponse>>,
+    rate_limiter: Arc<RateLimiter>,
+    trust_forwarded_for: bool,
+    req: Request<Body>,
+    next: middleware::Next<Body>,
+) -> axum::response::Response {
+    let key = match req.headers().get(IDEMPOTENCY_KEY) {
+        Some(key) => key,
+        None => return next.run(req).await,
+    };
+    let key = match key.to_str() {
+        Ok(key) if is_valid_idempotency_key(key) => key.to_owned(),
+        _ => {
+            return Error::InvalidIdempotencyKey {
+                max: MAX_IDEMPOTENCY_KEY_LENGTH,
+            }
+            .into_response()
+        }
+    };
+
+    // Each client has their own keys, so that nobody can see the
+    // response to someone else's request by guessing their key
+    let client = api_key(&req)
+        .and_then(|key| rate_limiter.client_for_key(key))
+        .or_else(|| client_for(&req, trust_forwarded_for));
+
+    let (parts, body) = req.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            let error = format!("Unable to read request: {e}");
+            return (StatusCode::BAD_REQUEST, ErrorJson::new(error)).into_response();
+        }
+    };
+
+    let fingerprint = Sha256::new()
+        .chain_update(parts.method.as_str())
+        .chain_update(parts.uri.to_string())
+        .chain_update(&body)
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let pending = match keys.claim((client, key), fingerprint) {
+        Claim::First(pending) => pending,
+        Claim::Running => return Error::IdempotencyKeyInUse.into_response(),
+        Claim::Mismatched => return Error::IdempotencyKeyReused.into_response(),
+        Claim::Finished((status, headers, body)) => {
+            let replayed = [(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"))];
+            return (status, headers, replayed, body).into_response();
+        }
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    // Failures aren't kept, so that the request may be retried
+    let failed =
+        !response.status().is_success() || response.extensions().get::<ErrorJson>().is_some();
+    if failed {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            let error = format!("Unable to read response: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, ErrorJson::new(error)).into_response();
+        }
+    };
+    pending.finish((parts.status, parts.headers.clone(), body.clone()));
+
+    axum::response::Response::from_parts(parts, axum::body::boxed(axum::body::Full::from(body)))
+}
+
+pub(crate) fn is_valid_idempotency_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH
+        && key.chars().all(|c| c.is_ascii_graphic())
+}
+
+fn api_key<B>(req: &Request<B>) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+fn client_for<B>(req: &Request<B>, trust_forwarded_for: bool) -> Option<Client> {
+    if trust_forwarded_for {
+        // The proxy in front of us appends the address it saw last
+        let forwarded_for = req
+            .headers()
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .last()
+            .and_then(|v| v.trim().parse().ok());
+
+        if let Some(address) = forwarded_for {
+            return Some(Client::Address(address));
+        }
+    }
+
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| Client::Address(address.ip()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs/{id}",
+    params(
+        ("id" = String, Path, description = "The operation to run, such as `compile`"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retrying with the same key returns the first job instead of running it again"),
+    ),
+    request_body(content = Object, description = "The request for the operation"),
+    responses(
+        (status = 202, description = "The operation has been queued", body = JobResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn submit_job(
+    operations: Router,
+    Extension(jobs): Extension<Arc<Jobs>>,
+    Path(endpoint): Path<String>,
+    mut req: Request<Body>,
+) -> Result<impl IntoResponse> {
+    if !JOB_ENDPOINTS.contains(&&*endpoint) {
+        return InvalidJobEndpointSnafu { value: endpoint }.fail();
+    }
+
+    let (id, progress) = jobs.create();
+    req.extensions_mut().insert(progress);
+    *req.uri_mut() =
+        Uri::from_maybe_shared(format!("/{endpoint}")).expect("Job endpoints are valid URIs");
+
+    tokio::spawn({
+        let id = id.clone();
+        async move {
+            let response = match operations.oneshot(req).await {
+                Ok(response) => response,
+                Err(never) => match never {},
+            };
+            jobs.finish(&id, job_outcome(response).await);
+        }
+    });
+
+    let response = JobResponse {
+        id: id.to_string(),
+        state: JobState::Queued {
+            position: None,
+            estimated_wait: None,
+        },
+    };
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/jobs/{id}"))],
+        Json(response),
+    ))
+}
+
+async fn job_outcome(response: axum::response::Response) -> Outcome {
+    let status = response.status().as_u16();
+
+    let body = match hyper::body::to_bytes(response.into_body()).await {
+        Ok(body) => serde_json::from_slice(&body)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned().into()),
+        Err(e) => serde_json::to_value(ErrorJson::new(format!("Unable to read response: {e}")))
+            .unwrap_or_default(),
+    };
+
+    Outcome { status, body }
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    params(("id" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = JobResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn job_status(
+    Extension(jobs): Extension<Arc<Jobs>>,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobResponse>> {
+    let status = jobs
+        .status(&JobId::from(id.clone()))
+        .context(JobNotFoundSnafu { id: id.clone() })?;
+
+    let state = match status {
+        Status::Unfinished(stage) => unfinished_job_state(&dispatcher, stage),
+        Status::Finished(Outcome { status, body }) => JobState::Finished {
+            status,
+            response: body,
+        },
+    };
+
+    Ok(Json(JobResponse { id, state }))
+}
+
+fn unfinished_job_state(dispatcher: &Dispatcher, stage: Stage) -> JobState {
+    match stage {
+        Stage::Pending => JobState::Queued {
+            position: None,
+            estimated_wait: None,
+        },
+        Stage::Queued(class, ticket) => {
+            let position = dispatcher.limiter(class).position(ticket);
+            JobState::Queued {
+                position: position.map(|p| p.ahead),
+                estimated_wait: position.map(|p| p.estimated_wait.as_secs()),
+            }
+        }
+        Stage::Running(phase) => JobState::Running {
+            phase: phase.map(|phase| phase_name(phase).into()),
+        },
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/batch",
+    request_body = BatchRequest,
+    responses(
+        (status = 200, description = "Success", body = BatchResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Runs several operations, a few at a time. Each is rate limited as
+/// if it were its own request.
+async fn batch(
+    operations: Router,
+    max_code_size: usize,
+    headers: header::HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_id: Option<Extension<RequestId>>,
+    Json(req): Json<BatchRequest>,
+) -> Result<Json<BatchResponse>> {
+    ensure!(
+        req.operations.len() <= MAX_BATCH_SIZE,
+        BatchTooLargeSnafu {
+            max: MAX_BATCH_SIZE
+        }
+    );
+    if let Some(op) = req
+        .operations
+        .iter()
+        .find(|op| !JOB_ENDPOINTS.contains(&&*op.endpoint))
+    {
+        return InvalidJobEndpointSnafu {
+            value: op.endpoint.clone(),
+        }
+        .fail();
+    }
+
+    let size: usize = req
+        .operations
+        .iter()
+        .filter_map(|op| op.request.get("code")?.as_str())
+        .map(str::len)
+        .sum();
+    ensure!(
+        size <= max_code_size,
+        BatchCodeTooLargeSnafu {
+            size,
+            max_size: max_code_size,
+        }
+    );
+
+    let requests = req.operations.into_iter().map(|op| {
+        operation_request(
+            &op.endpoint,
+            &op.request,
+            &headers,
+            connect_info,
+            request_id.as_ref(),
+        )
+    });
+
+    let results = futures::stream::iter(requests)
+        .map(move |req| {
+            let operations = operations.clone();
+            async move {
+                let response = match operations.oneshot(req).await {
+                    Ok(response) => response,
+                    Err(never) => match never {},
+                };
+                let Outcome { status, body } = job_outcome(response).await;
+                BatchResult {
+                    status,
+                    response: body,
+                }
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    Ok(Json(BatchResponse { results }))
+}
+
+/// A request for one of the job endpoints, made on behalf of the
+/// request with `headers` so that it is rate limited the same way.
+fn operation_request(
+    endpoint: &str,
+    request: &serde_json::Value,
+    headers: &header::HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_id: Option<&Extension<RequestId>>,
+) -> Request<Body> {
+    let mut req = Request::new(Body::from(request.to_string()));
+    *req.method_mut() = Method::POST;
+    *req.uri_mut() =
+        Uri::from_maybe_shared(format!("/{endpoint}")).expect("Job endpoints are valid URIs");
+
+    *req.headers_mut() = headers.clone();
+    req.headers_mut().remove(header::CONTENT_LENGTH);
+    req.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(APPLICATION_JSON),
+    );
+
+    if let Some(connect_info) = connect_info {
+        req.extensions_mut().insert(connect_info);
+    }
+    if let Some(Extension(request_id)) = request_id {
+        req.extensions_mut().insert(request_id.clone());
+    }
+
+    req
+}
+
+/// Runs the operations sent over the socket, several at a time, and
+/// sends back the state of each whenever it changes so that clients
+/// don't have to poll for it.
+async fn websocket(
+    operations: Router,
+    max_message_size: usize,
+    ws: WebSocketUpgrade,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    headers: header::HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_id: Option<Extension<RequestId>>,
+) -> impl IntoResponse {
+    ws.max_message_size(max_message_size)
+        .on_upgrade(move |socket| async move {
+            let operation = |req: WebSocketRequest| {
+                operation_request(
+                    &req.endpoint,
+                    &req.request,
+                    &headers,
+                    connect_info,
+                    request_id.as_ref(),
+                )
+            };
+            serve_websocket(socket, operations, dispatcher, operation).await
+        })
+}
+
+async fn serve_websocket(
+    mut socket: WebSocket,
+    operations: Router,
+    dispatcher: Arc<Dispatcher>,
+    operation: impl Fn(WebSocketRequest) -> Request<Body>,
+) {
+    let (updates_tx, mut updates) = tokio::sync::mpsc::unbounded_channel();
+    // Dropped along with the socket, which stops the operations
+    let mut running = futures::stream::FuturesUnordered::new();
+
+    loop {
+        let message = tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(ws::Message::Text(text))) => text,
+                    Some(Ok(ws::Message::Close(_)) | Err(_)) | None => break,
+                    // Pings are answered for us
+                    Some(Ok(_)) => continue,
+                };
+
+                match serde_json::from_str::<WebSocketRequest>(&text) {
+                    Err(source) => {
+                        let error = InvalidWebSocketMessageSnafu.into_error(source);
+                        serde_json::to_string(&ErrorJson::new(error.to_string()))
+                    }
+                    Ok(req) => match check_websocket_request(&req, running.len()) {
+                        Err(error) => {
+                            let Outcome { status, body } = job_outcome(error.into_response()).await;
+                            let state = JobState::Finished { status, response: body };
+                            serde_json::to_string(&JobResponse { id: req.id, state })
+                        }
+                        Ok(()) => {
+                            running.push(run_websocket_operation(
+                                operations.clone(),
+                                dispatcher.clone(),
+                                req.id.clone(),
+                                operation(req),
+                                updates_tx.clone(),
+                            ));
+                            continue;
+                        }
+                    },
+                }
+            }
+            Some(update) = updates.recv() => serde_json::to_string(&update),
+            Some(()) = running.next(), if !running.is_empty() => continue,
+        };
+
+        let message = match message {
+            Ok(message) => message,
+            Err(error) => {
+                tracing::error!(%error, "Unable to serialize a WebSocket message");
+                continue;
+            }
+        };
+        if socket.send(ws::Message::Text(message)).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn check_websocket_request(req: &WebSocketRequest, running: usize) -> Result<()> {
+    ensure!(
+        JOB_ENDPOINTS.contains(&&*req.endpoint),
+        InvalidJobEndpointSnafu {
+            value: &req.endpoint
+        }
+    );
+    ensure!(
+        running < MAX_RUNNING_WEBSOCKET_OPERATIONS,
+        WebSocketBusySnafu {
+            max: MAX_RUNNING_WEBSOCKET_OPERATIONS
+        }
+    );
+    Ok(())
+}
+
+async fn run_websocket_operation(
+    operations: Router,
+    dispatcher: Arc<Dispatcher>,
+    id: String,
+    mut req: Request<Body>,
+    updates: tokio::sync::mpsc::UnboundedSender<JobResponse>,
+) {
+    let progress = Arc::new(Progress::default());
+    req.extensions_mut().insert(progress.clone());
+
+    let response = operations.oneshot(req);
+    tokio::pin!(response);
+
+    let mut interval = tokio::time::interval(WEBSOCKET_PROGRESS_INTERVAL);
+    let mut reported = None;
+
+    let response = loop {
+        tokio::select! {
+            response = &mut response => match response {
+                Ok(response) => break response,
+                Err(never) => match never {},
+            },
+            _ = interval.tick() => {
+                let state = unfinished_job_state(&dispatcher, progress.stage());
+                if reported.as_ref() != Some(&state) {
+                    reported = Some(state.clone());
+                    let _ = updates.send(JobResponse { id: id.clone(), state });
+                }
+            }
+        }
+    };
+
+    let Outcome { status, body } = job_outcome(response).await;
+    let state = JobState::Finished {
+        status,
+        response: body,
+    };
+    let _ = updates.send(JobResponse { id, state });
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Rust Playground"),
+    paths(
+        evaluate,
+        assess,
+        compile,
+        execute,
+        rerun,
+        format,
+        edition_migration,
+        clippy,
+        miri,
+        kani,
+        macro_expansion,
+        time_passes,
+        optimization_remarks,
+        completions,
+        hover,
+        navigation,
+        inlay_hints,
+        compare,
+        output_diff,
+        assembly_diff,
+        mir_diff,
+        bisect,
+        reduce,
+        meta_crates,
+        meta_version_stable,
+        meta_version_beta,
+        meta_version_nightly,
+        meta_version_rustfmt,
+        meta_version_clippy,
+        meta_version_miri,
+        meta_clippy_lints,
+        meta_versions,
+        meta_targets,
+        meta_images,
+        meta_usage,
+        meta_gist_create,
+        meta_gist_get,
+        snippet_create,
+        snippet_get,
+        snippet_raw,
+        snippet_highlighted,
+        snippet_embed,
+        templates_list,
+        template_get,
+        share_link_create,
+        share_link_get,
+        share_link_execute,
+        artifact_get,
+        repl_start,
+        repl_evaluate,
+        repl_variables,
+        repl_close,
+        submit_job,
+        job_status,
+        batch,
+    )
+)]
+struct ApiDoc;
+
+/// Describes the routes of the API, generated from the types that
+/// they accept and return.
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// The process is up and able to respond.
+async fn healthz() -> &'static str {
+    "OK"
+}
+
+/// The process is able to run sandboxes, and has run one for each
+/// channel since starting.
+async fn readyz(Extension(dispatcher): Extension<Arc<Dispatcher>>) -> impl IntoResponse {
+    let problems: Vec<_> = dispatcher
+        .self_check()
+        .await
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let warming_up = dispatcher.is_warming_up();
+    let ready = problems.is_empty() && !warming_up;
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            warming_up,
+            problems,
+        }),
+    )
+}
+
 // This is a backwards compatibilty shim. The Rust documentation uses
 // this to run code in place.
-async fn evaluate(Json(req): Json<EvaluateRequest>) -> Result<Json<EvaluateResponse>> {
+#[utoipa::path(
+    post,
+    path = "/evaluate.json",
+    request_body = EvaluateRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<EvaluateResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn evaluate(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<EvaluateRequest>,
+) -> Result<Json<WithMetadata<EvaluateResponse>>> {
     with_sandbox_force_endpoint(
+        &dispatcher,
         req,
-        Endpoint::Evaluate,
+        &tracking,
+        OperationClass::Expensive,
+        Some(Endpoint::Evaluate),
         |sb, req| async move { sb.execute(req).await }.boxed(),
         EvaluationSnafu,
     )
@@ -149,29 +1244,174 @@ async fn evaluate(Json(req): Json<EvaluateRequest>) -> Result<Json<EvaluateRespo
     .map(Json)
 }
 
-async fn compile(Json(req): Json<CompileRequest>) -> Result<Json<CompileResponse>> {
-    with_sandbox(
-        req,
-        |sb, req| async move { sb.compile(req).await }.boxed(),
-        CompilationSnafu,
+#[utoipa::path(
+    post,
+    path = "/compile",
+    request_body = CompileRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<CompileResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn compile(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    Extension(artifacts): Extension<Arc<Artifacts>>,
+    tracking: Tracking,
+    Json(req): Json<CompileRequest>,
+) -> Result<Json<WithMetadata<CompileResponse>>> {
+    let keep_artifacts = req.artifacts.then(|| req.target.clone());
+    let request: sandbox::CompileRequest = req.try_into()?;
+    let spill_to = artifacts.clone();
+
+    // Outputs too large to be part of the response are kept as
+    // artifacts, and the response is then not cached, as they expire
+    let mut response: WithMetadata<CompileResponse> = dispatch(
+        &dispatcher,
+        request,
+        &tracking,
+        OperationClass::Expensive,
+        |runner, request| {
+            async move {
+                let f = |sb: Sandbox, req| async move { sb.compile(req).await }.boxed();
+                let response = track_metric_async(request, |request| run(runner, f, request))
+                    .await
+                    .context(CompilationSnafu)?;
+                Ok(Compiled::new(response, request.target, &spill_to).await)
+            }
+            .boxed()
+        },
+    )
+    .await?;
+
+    if let Some(target) = keep_artifacts {
+        response
+            .response
+            .keep_artifacts(&artifacts, &target)
+            .await?;
+    }
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/execute",
+    request_body = ExecuteRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<ExecuteResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn execute(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    Extension(artifacts): Extension<Arc<Artifacts>>,
+    tracking: Tracking,
+    Json(req): Json<ExecuteRequest>,
+) -> Result<Json<WithMetadata<ExecuteResponse>>> {
+    let request: sandbox::ExecuteRequest = req.try_into()?;
+    let output_spill = artifacts.output_spill();
+
+    // The binary and outputs too large to be part of the response are
+    // kept as artifacts, and the response is then not cached, as they
+    // expire
+    dispatch(
+        &dispatcher,
+        request,
+        &tracking,
+        OperationClass::Expensive,
+        |runner, request| {
+            async move {
+                let f = |sb: Sandbox, req| {
+                    let sb = with_output_spill(sb, output_spill);
+                    async move { sb.execute(req).await }.boxed()
+                };
+                let response = track_metric_async(request, |request| run(runner, f, request))
+                    .await
+                    .context(ExecutionSnafu)?;
+                Ok(Executed::new(response, &artifacts).await)
+            }
+            .boxed()
+        },
     )
     .await
     .map(Json)
 }
 
-async fn execute(Json(req): Json<ExecuteRequest>) -> Result<Json<ExecuteResponse>> {
-    with_sandbox(
-        req,
-        |sb, req| async move { sb.execute(req).await }.boxed(),
-        ExecutionSnafu,
+#[utoipa::path(
+    post,
+    path = "/rerun",
+    request_body = RerunRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<ExecuteResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Runs a binary that `/execute` kept again, such as with other
+/// input, without building it again.
+async fn rerun(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    Extension(artifacts): Extension<Arc<Artifacts>>,
+    tracking: Tracking,
+    Json(req): Json<RerunRequest>,
+) -> Result<Json<WithMetadata<ExecuteResponse>>> {
+    let request: Rerun = req.try_into()?;
+    let binary = artifacts
+        .load(&request.binary)
+        .filter(|artifact| artifact.content_type == KEPT_BINARY_CONTENT_TYPE)
+        .context(ArtifactNotFoundSnafu {
+            id: &request.binary,
+        })?;
+    let binary = binary.read().await.context(ArtifactStorageSnafu)?;
+    let output_spill = artifacts.output_spill();
+
+    dispatch(
+        &dispatcher,
+        request,
+        &tracking,
+        OperationClass::Expensive,
+        |runner, rerun| {
+            async move {
+                let sb = match runner {
+                    Runner::Local(sb) => sb,
+                    // Binaries are only kept when they were built here
+                    Runner::Remote(_) => {
+                        return ArtifactNotFoundSnafu { id: &rerun.binary }.fail();
+                    }
+                };
+                let sb = with_output_spill(sb, output_spill);
+                let response = track_metric_async(&rerun.request, |request| {
+                    async move { sb.rerun(request, &binary).await }.boxed()
+                })
+                .await
+                .context(ExecutionSnafu)?;
+                Ok(Executed::new(response, &artifacts).await)
+            }
+            .boxed()
+        },
     )
     .await
     .map(Json)
 }
 
-async fn format(Json(req): Json<FormatRequest>) -> Result<Json<FormatResponse>> {
+#[utoipa::path(
+    post,
+    path = "/format",
+    request_body = FormatRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<FormatResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn format(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<FormatRequest>,
+) -> Result<Json<WithMetadata<FormatResponse>>> {
     with_sandbox(
+        &dispatcher,
         req,
+        &tracking,
+        OperationClass::Cheap,
         |sb, req| async move { sb.format(req).await }.boxed(),
         FormattingSnafu,
     )
@@ -179,9 +1419,25 @@ async fn format(Json(req): Json<FormatRequest>) -> Result<Json<FormatResponse>>
     .map(Json)
 }
 
-async fn clippy(Json(req): Json<ClippyRequest>) -> Result<Json<ClippyResponse>> {
+#[utoipa::path(
+    post,
+    path = "/clippy",
+    request_body = ClippyRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<ClippyResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn clippy(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<ClippyRequest>,
+) -> Result<Json<WithMetadata<ClippyResponse>>> {
     with_sandbox(
+        &dispatcher,
         req,
+        &tracking,
+        OperationClass::Cheap,
         |sb, req| async move { sb.clippy(req).await }.boxed(),
         LintingSnafu,
     )
@@ -189,9 +1445,60 @@ async fn clippy(Json(req): Json<ClippyRequest>) -> Result<Json<ClippyResponse>>
     .map(Json)
 }
 
-async fn miri(Json(req): Json<MiriRequest>) -> Result<Json<MiriResponse>> {
+#[utoipa::path(
+    post,
+    path = "/edition-migration",
+    request_body = EditionMigrationRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<EditionMigrationResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Migrates the code to the edition after the one it was written for,
+/// along with a diff of what that changed.
+async fn edition_migration(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<EditionMigrationRequest>,
+) -> Result<Json<WithMetadata<EditionMigrationResponse>>> {
+    let code = req.code.clone();
+    let before = req.edition.clone();
+
+    let mut migrated: WithMetadata<EditionMigrationResponse> = with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.migrate_edition(req).await }.boxed(),
+        EditionMigrationSnafu,
+    )
+    .await?;
+
+    let after = &mut migrated.response;
+    after.diff = diff::unified(&before, &code, &after.edition, &after.code);
+
+    Ok(Json(migrated))
+}
+
+#[utoipa::path(
+    post,
+    path = "/miri",
+    request_body = MiriRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<MiriResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn miri(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<MiriRequest>,
+) -> Result<Json<WithMetadata<MiriResponse>>> {
     with_sandbox(
+        &dispatcher,
         req,
+        &tracking,
+        OperationClass::Expensive,
         |sb, req| async move { sb.miri(req).await }.boxed(),
         InterpretingSnafu,
     )
@@ -199,11 +1506,80 @@ async fn miri(Json(req): Json<MiriRequest>) -> Result<Json<MiriResponse>> {
     .map(Json)
 }
 
+#[utoipa::path(
+    post,
+    path = "/assess",
+    request_body = AssessRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<AssessResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Grades the code by running hidden tests against it, reporting only
+/// how each test went.
+async fn assess(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<AssessRequest>,
+) -> Result<Json<WithMetadata<AssessResponse>>> {
+    with_sandbox_force_endpoint(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        Some(Endpoint::Assess),
+        |sb, req| async move { sb.execute(req).await }.boxed(),
+        AssessmentSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/kani",
+    request_body = KaniRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<KaniResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn kani(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<KaniRequest>,
+) -> Result<Json<WithMetadata<KaniResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.kani(req).await }.boxed(),
+        VerifyingSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/macro-expansion",
+    request_body = MacroExpansionRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<MacroExpansionResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn macro_expansion(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
     Json(req): Json<MacroExpansionRequest>,
-) -> Result<Json<MacroExpansionResponse>> {
+) -> Result<Json<WithMetadata<MacroExpansionResponse>>> {
     with_sandbox(
+        &dispatcher,
         req,
+        &tracking,
+        OperationClass::Cheap,
         |sb, req| async move { sb.macro_expansion(req).await }.boxed(),
         ExpansionSnafu,
     )
@@ -211,166 +1587,1449 @@ async fn macro_expansion(
     .map(Json)
 }
 
-async fn with_sandbox<F, Req, Resp, SbReq, SbResp, Ctx>(req: Req, f: F, ctx: Ctx) -> Result<Resp>
-where
-    for<'req> F: FnOnce(Sandbox, &'req SbReq) -> BoxFuture<'req, sandbox::Result<SbResp>>,
-    Resp: From<SbResp>,
-    SbReq: TryFrom<Req, Error = Error> + GenerateLabels,
-    SbResp: SuccessDetails,
-    Ctx: IntoError<Error, Source = sandbox::Error>,
-{
-    let sandbox = Sandbox::new().await.context(SandboxCreationSnafu)?;
-    let request = req.try_into()?;
-    track_metric_async(request, |request| f(sandbox, request))
-        .await
-        .map(Into::into)
-        .context(ctx)
+#[utoipa::path(
+    post,
+    path = "/time-passes",
+    request_body = TimePassesRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<TimePassesResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Builds the code on nightly with `-Ztime-passes`, to see where the
+/// compiler spends its time.
+async fn time_passes(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<TimePassesRequest>,
+) -> Result<Json<WithMetadata<TimePassesResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.time_passes(req).await }.boxed(),
+        ProfilingSnafu,
+    )
+    .await
+    .map(Json)
 }
 
-async fn with_sandbox_force_endpoint<F, Req, Resp, SbReq, SbResp, Ctx>(
-    req: Req,
-    endpoint: Endpoint,
+#[utoipa::path(
+    post,
+    path = "/optimization-remarks",
+    request_body = OptimizationRemarksRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<OptimizationRemarksResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Builds the code with `-Cremark=all`, to see which optimizations
+/// LLVM made to it and which it missed.
+async fn optimization_remarks(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<OptimizationRemarksRequest>,
+) -> Result<Json<WithMetadata<OptimizationRemarksResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.optimization_remarks(req).await }.boxed(),
+        OptimizationRemarksSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/completions",
+    request_body = CompletionsRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<CompletionsResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Asks rust-analyzer what could be written at the cursor, which is
+/// counted in bytes from the start of the code.
+async fn completions(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<CompletionsRequest>,
+) -> Result<Json<WithMetadata<CompletionsResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.completions(req).await }.boxed(),
+        CompletionsSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/hover",
+    request_body = HoverRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<HoverResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Asks rust-analyzer for the type and documentation of the symbol at
+/// the cursor, which is counted in bytes from the start of the code.
+async fn hover(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<HoverRequest>,
+) -> Result<Json<WithMetadata<HoverResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.hover(req).await }.boxed(),
+        HoveringSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/navigation",
+    request_body = NavigationRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<NavigationResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Asks rust-analyzer where the symbol at the cursor is defined, or
+/// everywhere that it is used, including in the standard library.
+async fn navigation(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<NavigationRequest>,
+) -> Result<Json<WithMetadata<NavigationResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.navigate(req).await }.boxed(),
+        NavigatingSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/inlay-hints",
+    request_body = InlayHintsRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<InlayHintsResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Asks rust-analyzer for the inferred types and the parameter names
+/// to show alongside the code.
+async fn inlay_hints(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<InlayHintsRequest>,
+) -> Result<Json<WithMetadata<InlayHintsResponse>>> {
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.inlay_hints(req).await }.boxed(),
+        InlayHintsSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/compare",
+    request_body = CompareRequest,
+    responses(
+        (status = 200, description = "Success", body = CompareResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Runs the code on every channel, such as to see if something that
+/// fails on stable has been fixed on nightly.
+async fn compare(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<CompareRequest>,
+) -> Result<Json<CompareResponse>> {
+    let on_channel = |channel| {
+        let req = req.on_channel(channel);
+        let dispatcher = &dispatcher;
+        let tracking = &tracking;
+
+        async move {
+            with_sandbox(
+                dispatcher,
+                req,
+                tracking,
+                OperationClass::Expensive,
+                |sb, req| async move { sb.execute(req).await }.boxed(),
+                ExecutionSnafu,
+            )
+            .await
+            .map(|r| r.response)
+        }
+    };
+
+    let (stable, beta, nightly) = futures::try_join!(
+        on_channel("stable"),
+        on_channel("beta"),
+        on_channel("nightly"),
+    )?;
+
+    Ok(Json(CompareResponse::new(stable, beta, nightly)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/output-diff",
+    request_body = OutputDiffRequest,
+    responses(
+        (status = 200, description = "Success", body = OutputDiffResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Runs two versions of the code the same way, such as to show that a
+/// change didn't change what it outputs.
+async fn output_diff(
+    Extension(snippets): Extension<SnippetStorage>,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<OutputDiffRequest>,
+) -> Result<Json<OutputDiffResponse>> {
+    let before = submitted_code(&snippets, req.before.clone()).await?;
+    let after = submitted_code(&snippets, req.after.clone()).await?;
+
+    let run = |code| {
+        let req = req.with_code(code);
+        let dispatcher = &dispatcher;
+        let tracking = &tracking;
+
+        async move {
+            with_sandbox(
+                dispatcher,
+                req,
+                tracking,
+                OperationClass::Expensive,
+                |sb, req| async move { sb.execute(req).await }.boxed(),
+                ExecutionSnafu,
+            )
+            .await
+            .map(|r| r.response)
+        }
+    };
+
+    let (before, after) = futures::try_join!(run(before), run(after))?;
+
+    Ok(Json(OutputDiffResponse::new(before, after)))
+}
+
+async fn submitted_code(snippets: &SnippetStorage, submission: Submission) -> Result<String> {
+    match submission {
+        Submission::Code { code } => Ok(code),
+        Submission::Snippet { snippet } => snippets
+            .must_get()?
+            .load(snippet.clone())
+            .await
+            .context(SnippetLoadingSnafu)?
+            .map(|s| s.code)
+            .context(SnippetNotFoundSnafu { id: snippet }),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/assembly-diff",
+    request_body = AssemblyDiffRequest,
+    responses(
+        (status = 200, description = "Success", body = AssemblyDiffResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Compiles the code to assembly in two modes, such as to see what
+/// optimizations have done.
+async fn assembly_diff(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<AssemblyDiffRequest>,
+) -> Result<Json<AssemblyDiffResponse>> {
+    let in_mode = |mode| {
+        let req = req.in_mode(mode);
+        let dispatcher = &dispatcher;
+        let tracking = &tracking;
+
+        async move {
+            with_sandbox(
+                dispatcher,
+                req,
+                tracking,
+                OperationClass::Expensive,
+                |sb, req| async move { sb.compile(req).await }.boxed(),
+                CompilationSnafu,
+            )
+            .await
+            .map(|r: WithMetadata<CompileResponse>| r.response)
+        }
+    };
+
+    let (before, after) = futures::try_join!(in_mode(&req.before), in_mode(&req.after))?;
+    let diff = diff::unified(&req.before, &before.code, &req.after, &after.code);
+
+    Ok(Json(AssemblyDiffResponse {
+        before,
+        after,
+        diff,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mir-diff",
+    request_body = MirDiffRequest,
+    responses(
+        (status = 200, description = "Success", body = MirDiffResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Compiles the code to MIR on nightly with and without the MIR
+/// optimizations, to see what they have done.
+async fn mir_diff(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    Json(req): Json<MirDiffRequest>,
+) -> Result<Json<MirDiffResponse>> {
+    let with_optimizations = |optimized| {
+        let req = req.with_optimizations(optimized);
+        let dispatcher = &dispatcher;
+        let tracking = &tracking;
+
+        async move {
+            with_sandbox(
+                dispatcher,
+                req,
+                tracking,
+                OperationClass::Expensive,
+                |sb, req| async move { sb.compile(req).await }.boxed(),
+                CompilationSnafu,
+            )
+            .await
+            .map(|r: WithMetadata<CompileResponse>| r.response)
+        }
+    };
+
+    let (unoptimized, optimized) =
+        futures::try_join!(with_optimizations(false), with_optimizations(true))?;
+    let diff = diff::unified(
+        "unoptimized",
+        &unoptimized.code,
+        "optimized",
+        &optimized.code,
+    );
+
+    Ok(Json(MirDiffResponse {
+        unoptimized,
+        optimized,
+        diff,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/bisect",
+    request_body = BisectRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<BisectResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Finds the nightly that introduced a regression. This may take a
+/// long time, so it is only available from `/jobs/bisect`.
+async fn bisect(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    job: Option<Extension<Arc<Progress>>>,
+    tracking: Tracking,
+    Json(req): Json<BisectRequest>,
+) -> Result<Json<WithMetadata<BisectResponse>>> {
+    ensure!(job.is_some(), BisectRequiresJobSnafu);
+
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.bisect(req).await }.boxed(),
+        BisectingSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/reduce",
+    request_body = ReduceRequest,
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<ReduceResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Makes code that has a compiler error or panic smaller, such as for
+/// reporting a compiler bug. It's compiled many times, so this is only
+/// available from `/jobs/reduce`.
+async fn reduce(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    job: Option<Extension<Arc<Progress>>>,
+    tracking: Tracking,
+    Json(req): Json<ReduceRequest>,
+) -> Result<Json<WithMetadata<ReduceResponse>>> {
+    ensure!(job.is_some(), ReduceRequiresJobSnafu);
+
+    with_sandbox(
+        &dispatcher,
+        req,
+        &tracking,
+        OperationClass::Expensive,
+        |sb, req| async move { sb.reduce(req).await }.boxed(),
+        ReducingSnafu,
+    )
+    .await
+    .map(Json)
+}
+
+pub(crate) async fn with_sandbox<F, Req, Resp, SbReq, SbResp, Ctx>(
+    dispatcher: &Dispatcher,
+    req: Req,
+    tracking: &Tracking,
+    class: OperationClass,
+    f: F,
+    ctx: Ctx,
+) -> Result<WithMetadata<Resp>>
+where
+    for<'req> F: FnOnce(Sandbox, &'req SbReq) -> BoxFuture<'req, sandbox::Result<SbResp>>,
+    F: Send + 'static,
+    Resp: From<SbResp>,
+    SbReq: TryFrom<Req, Error = Error>
+        + Into<job::JobRequest>
+        + CodeRequest
+        + GenerateLabels
+        + CacheableRequest,
+    SbResp: TryFrom<job::JobResponse> + SuccessDetails + CacheableResponse,
+    Ctx: IntoError<Error, Source = sandbox::Error> + Send + 'static,
+{
+    with_sandbox_force_endpoint(dispatcher, req, tracking, class, None, f, ctx).await
+}
+
+/// Like [`with_sandbox`], but with the metrics recorded under
+/// `endpoint` rather than the one the request would have been counted
+/// as, when there is one.
+async fn with_sandbox_force_endpoint<F, Req, Resp, SbReq, SbResp, Ctx>(
+    dispatcher: &Dispatcher,
+    req: Req,
+    tracking: &Tracking,
+    class: OperationClass,
+    endpoint: Option<Endpoint>,
     f: F,
     ctx: Ctx,
-) -> Result<Resp>
+) -> Result<WithMetadata<Resp>>
 where
     for<'req> F: FnOnce(Sandbox, &'req SbReq) -> BoxFuture<'req, sandbox::Result<SbResp>>,
+    F: Send + 'static,
     Resp: From<SbResp>,
-    SbReq: TryFrom<Req, Error = Error> + GenerateLabels,
-    SbResp: SuccessDetails,
-    Ctx: IntoError<Error, Source = sandbox::Error>,
+    SbReq: TryFrom<Req, Error = Error>
+        + Into<job::JobRequest>
+        + CodeRequest
+        + GenerateLabels
+        + CacheableRequest,
+    SbResp: TryFrom<job::JobResponse> + SuccessDetails + CacheableResponse,
+    Ctx: IntoError<Error, Source = sandbox::Error> + Send + 'static,
 {
-    let sandbox = Sandbox::new().await.context(SandboxCreationSnafu)?;
     let request = req.try_into()?;
-    track_metric_force_endpoint_async(request, endpoint, |request| f(sandbox, request))
-        .await
-        .map(Into::into)
-        .context(ctx)
+    dispatch(dispatcher, request, tracking, class, |runner, request| {
+        async move {
+            let response = match endpoint {
+                Some(endpoint) => {
+                    track_metric_force_endpoint_async(request, endpoint, |request| {
+                        run(runner, f, request)
+                    })
+                    .await
+                }
+                None => track_metric_async(request, |request| run(runner, f, request)).await,
+            };
+            response.context(ctx)
+        }
+        .boxed()
+    })
+    .await
 }
 
+/// Workers run the same operation that `f` would have run here.
+fn run<'req, F, SbReq, SbResp>(
+    runner: Runner,
+    f: F,
+    request: &'req SbReq,
+) -> BoxFuture<'req, sandbox::Result<SbResp>>
+where
+    F: FnOnce(Sandbox, &'req SbReq) -> BoxFuture<'req, sandbox::Result<SbResp>>,
+    SbReq: Into<job::JobRequest> + CacheableRequest,
+    SbResp: TryFrom<job::JobResponse> + CacheableResponse,
+{
+    match runner {
+        Runner::Local(sandbox) => f(sandbox, request),
+        Runner::Remote(sandbox) => async move { sandbox.run(request).await }.boxed(),
+    }
+}
+
+fn with_output_spill(sb: Sandbox, spill: Option<sandbox::spill::OutputSpill>) -> Sandbox {
+    match spill {
+        Some(spill) => sb.with_output_spill(spill),
+        None => sb,
+    }
+}
+
+/// Avoids running the request in a new sandbox when the response is
+/// already known or an identical request is currently running.
+async fn dispatch<F, Resp, SbReq, SbResp>(
+    dispatcher: &Dispatcher,
+    request: SbReq,
+    tracking: &Tracking,
+    class: OperationClass,
+    f: F,
+) -> Result<WithMetadata<Resp>>
+where
+    for<'req> F: FnOnce(Runner, &'req SbReq) -> BoxFuture<'req, Result<SbResp>>,
+    Resp: From<SbResp>,
+    SbReq: CodeRequest + CacheableRequest,
+    SbResp: CacheableResponse,
+{
+    // Checked again by the sandbox, but this avoids hashing, queueing,
+    // or sending it anywhere first
+    if let Some(max_size) = dispatcher.max_code_size() {
+        let size = request.code().len();
+        ensure!(size <= max_size, CodeTooLargeSnafu { size, max_size });
+    }
+
+    if let Some(response) = dispatcher.responses.get::<SbReq, SbResp>(&request) {
+        return Ok(WithMetadata::cached(response.into()));
+    }
+    // Switching images clears the cache, and a request that was
+    // started before then may have run on the old one
+    let generation = dispatcher.responses.generation();
+
+    let response = dispatcher
+        .in_flight
+        .run(&request, || async {
+            ensure!(!dispatcher.is_draining(), MaintenanceSnafu);
+
+            if let Some(sandbox) = dispatcher.remote_sandbox(tracking.request_id.as_str()) {
+                tracking.advance(Stage::Running(None));
+                return f(Runner::Remote(sandbox), &request).await;
+            }
+
+            // Cheap operations are what keep the editor useful, and
+            // they barely add to the load, so only expensive ones are
+            // shed
+            if class == OperationClass::Expensive {
+                if let Some(pressure) = dispatcher.pressure() {
+                    return OverloadedSnafu { pressure }.fail();
+                }
+            }
+
+            if let Err(retry_after) = dispatcher.circuit_breaker.allow() {
+                return SandboxUnavailableSnafu { retry_after }.fail();
+            }
+
+            let _permit = dispatcher
+                .limiter(class)
+                .acquire(|ticket| tracking.advance(Stage::Queued(class, ticket)))
+                .await
+                .context(ServerBusySnafu)?;
+            tracking.advance(Stage::Running(None));
+            let mut sandbox = dispatcher
+                .sandbox(tracking.request_id.as_str())
+                .await
+                .context(SandboxCreationSnafu)?;
+            if let Some(progress) = tracking.progress.clone() {
+                sandbox = sandbox
+                    .with_progress(move |phase| progress.advance(Stage::Running(Some(phase))));
+            }
+            let response = f(Runner::Local(sandbox), &request).await;
+
+            match &response {
+                Err(e) if e.is_docker_failure() => dispatcher.circuit_breaker.record_failure(),
+                _ => dispatcher.circuit_breaker.record_success(),
+            }
+
+            response
+        })
+        .await?;
+
+    if response.is_cacheable() {
+        dispatcher
+            .responses
+            .insert(generation, request, response.clone());
+    }
+    Ok(WithMetadata::fresh(response.into()))
+}
+
+pub(crate) trait CacheableRequest:
+    std::hash::Hash + Eq + Clone + Send + Sync + 'static
+{
+}
+
+impl<T> CacheableRequest for T where T: std::hash::Hash + Eq + Clone + Send + Sync + 'static {}
+
+pub(crate) trait CacheableResponse: Clone + Send + Sync + 'static {
+    /// Responses that refer to artifacts, or hold on to what they are
+    /// made from, aren't cached, as the artifacts expire before the
+    /// response would. Nor are executions that a limit stopped, as
+    /// they might finish when run again.
+    fn is_cacheable(&self) -> bool {
+        true
+    }
+}
+
+impl CacheableResponse for sandbox::ExecuteResponse {
+    fn is_cacheable(&self) -> bool {
+        self.binary.is_none() && self.spilled == Default::default() && !self.was_stopped()
+    }
+}
+
+impl CacheableResponse for Executed {
+    fn is_cacheable(&self) -> bool {
+        self.binary.is_none() && self.spilled.is_empty() && self.response.is_cacheable()
+    }
+}
+
+impl CacheableResponse for Compiled {
+    fn is_cacheable(&self) -> bool {
+        self.outputs.is_empty() && self.spilled.is_empty()
+    }
+}
+
+impl CacheableResponse for job::JobResponse {
+    fn is_cacheable(&self) -> bool {
+        match self {
+            job::JobResponse::Execute(response) => response.is_cacheable(),
+            _ => true,
+        }
+    }
+}
+
+impl CacheableResponse for sandbox::BisectResponse {}
+impl CacheableResponse for sandbox::ClippyResponse {}
+impl CacheableResponse for sandbox::CompileResponse {}
+impl CacheableResponse for sandbox::CompletionsResponse {}
+impl CacheableResponse for sandbox::EditionMigrationResponse {}
+impl CacheableResponse for sandbox::FormatResponse {}
+impl CacheableResponse for sandbox::HoverResponse {}
+impl CacheableResponse for sandbox::InlayHintsResponse {}
+impl CacheableResponse for sandbox::KaniResponse {}
+impl CacheableResponse for sandbox::MacroExpansionResponse {}
+impl CacheableResponse for sandbox::MiriResponse {}
+impl CacheableResponse for sandbox::NavigationResponse {}
+impl CacheableResponse for sandbox::OptimizationRemarksResponse {}
+impl CacheableResponse for sandbox::ReduceResponse {}
+impl CacheableResponse for sandbox::TimePassesResponse {}
+
+#[utoipa::path(
+    get,
+    path = "/meta/crates",
+    responses(
+        (status = 200, description = "Success", body = MetaCratesResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn meta_crates(
     Extension(cache): Extension<Arc<SandboxCache>>,
     if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Result<impl IntoResponse> {
     // Json<MetaCratesResponse
     let value = track_metric_no_request_async(Endpoint::MetaCrates, || cache.crates()).await?;
 
-    apply_timestamped_caching(value, if_none_match)
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
 }
 
+#[utoipa::path(
+    get,
+    path = "/meta/version/stable",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn meta_version_stable(
     Extension(cache): Extension<Arc<SandboxCache>>,
     if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Result<impl IntoResponse> {
     let value =
         track_metric_no_request_async(Endpoint::MetaVersionStable, || cache.version_stable())
             .await?;
-    apply_timestamped_caching(value, if_none_match)
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
 }
 
+#[utoipa::path(
+    get,
+    path = "/meta/version/beta",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn meta_version_beta(
     Extension(cache): Extension<Arc<SandboxCache>>,
     if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Result<impl IntoResponse> {
     let value =
         track_metric_no_request_async(Endpoint::MetaVersionBeta, || cache.version_beta()).await?;
-    apply_timestamped_caching(value, if_none_match)
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
 }
 
+#[utoipa::path(
+    get,
+    path = "/meta/version/nightly",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn meta_version_nightly(
     Extension(cache): Extension<Arc<SandboxCache>>,
     if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Result<impl IntoResponse> {
     let value =
         track_metric_no_request_async(Endpoint::MetaVersionNightly, || cache.version_nightly())
             .await?;
-    apply_timestamped_caching(value, if_none_match)
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
 }
 
+#[utoipa::path(
+    get,
+    path = "/meta/version/rustfmt",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn meta_version_rustfmt(
     Extension(cache): Extension<Arc<SandboxCache>>,
     if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Result<impl IntoResponse> {
     let value =
         track_metric_no_request_async(Endpoint::MetaVersionRustfmt, || cache.version_rustfmt())
             .await?;
-    apply_timestamped_caching(value, if_none_match)
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
 }
 
+#[utoipa::path(
+    get,
+    path = "/meta/version/clippy",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
 async fn meta_version_clippy(
     Extension(cache): Extension<Arc<SandboxCache>>,
     if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Result<impl IntoResponse> {
     let value =
         track_metric_no_request_async(Endpoint::MetaVersionClippy, || cache.version_clippy())
             .await?;
-    apply_timestamped_caching(value, if_none_match)
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
+}
+
+#[utoipa::path(
+    get,
+    path = "/meta/version/miri",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn meta_version_miri(
+    Extension(cache): Extension<Arc<SandboxCache>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<impl IntoResponse> {
+    let value =
+        track_metric_no_request_async(Endpoint::MetaVersionMiri, || cache.version_miri()).await?;
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
+}
+
+#[utoipa::path(
+    get,
+    path = "/meta/clippy-lints",
+    responses(
+        (status = 200, description = "Success", body = MetaClippyLintsResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn meta_clippy_lints(
+    Extension(cache): Extension<Arc<SandboxCache>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<impl IntoResponse> {
+    let value =
+        track_metric_no_request_async(Endpoint::MetaClippyLints, || cache.clippy_lints()).await?;
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
+}
+
+#[utoipa::path(
+    get,
+    path = "/meta/versions",
+    responses(
+        (status = 200, description = "Success", body = MetaVersionsResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn meta_versions(
+    Extension(cache): Extension<Arc<SandboxCache>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<impl IntoResponse> {
+    let value = track_metric_no_request_async(Endpoint::MetaVersions, || cache.versions()).await?;
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
+}
+
+#[utoipa::path(
+    get,
+    path = "/meta/targets",
+    responses(
+        (status = 200, description = "Success", body = MetaTargetsResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn meta_targets(
+    Extension(cache): Extension<Arc<SandboxCache>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<impl IntoResponse> {
+    let value = track_metric_no_request_async(Endpoint::MetaTargets, || cache.targets()).await?;
+    apply_timestamped_caching(value, if_none_match, if_modified_since)
+}
+
+/// How the images of each channel have been kept up to date.
+#[utoipa::path(
+    get,
+    path = "/meta/images",
+    responses(
+        (status = 200, description = "Success", body = MetaImagesResponse),
+    ),
+)]
+async fn meta_images(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+) -> Json<MetaImagesResponse> {
+    let status = |channel| dispatcher.image_updates.status(channel).into();
+
+    Json(MetaImagesResponse {
+        stable: status(Channel::Stable { version: None }),
+        beta: status(Channel::Beta),
+        nightly: status(Channel::Nightly { date: None }),
+    })
+}
+
+/// How often each sandbox operation has been run on this server since
+/// it started, and how long it took.
+#[utoipa::path(
+    get,
+    path = "/meta/usage",
+    responses(
+        (status = 200, description = "Success", body = MetaUsageResponse),
+    ),
+)]
+async fn meta_usage() -> Json<MetaUsageResponse> {
+    Json((&*USAGE).into())
+}
+
+fn apply_timestamped_caching<T>(
+    value: Stamped<T>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<impl IntoResponse>
+where
+    Json<T>: IntoResponse,
+{
+    let (value, modified) = value;
+
+    let last_modified = LastModified::from(modified);
+
+    let timestamp = modified.duration_since(UNIX_EPOCH).unwrap();
+    let etag = format!(r#""pg-ts-{}""#, timestamp.as_secs());
+    let etag = ETag::from_str(&etag).unwrap();
+
+    let cache_control = CacheControl::new()
+        .with_max_age(SANDBOX_CACHE_TIME_TO_LIVE)
+        .with_public();
+
+    // The entity tag is more precise, so the date is only used when
+    // there isn't one
+    let use_fresh = match (if_none_match, if_modified_since) {
+        (Some(if_none_match), _) => if_none_match.0.precondition_passes(&etag),
+        (None, Some(if_modified_since)) => if_modified_since.0.is_modified(modified),
+        (None, None) => true,
+    };
+
+    let etag = TypedHeader(etag);
+    let cache_control = TypedHeader(cache_control);
+    let last_modified = TypedHeader(last_modified);
+
+    let response = if use_fresh {
+        (StatusCode::OK, Json(value)).into_response()
+    } else {
+        StatusCode::NOT_MODIFIED.into_response()
+    };
+
+    Ok((etag, cache_control, last_modified, response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/meta/gist",
+    request_body = MetaGistCreateRequest,
+    responses(
+        (status = 200, description = "Success", body = MetaGistResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn meta_gist_create(
+    Extension(token): Extension<GhToken>,
+    Json(req): Json<MetaGistCreateRequest>,
+) -> Result<Json<MetaGistResponse>> {
+    let token = token.must_get()?;
+    gist::create_future(token, req.code)
+        .await
+        .map(Into::into)
+        .map(Json)
+        .context(GistCreationSnafu)
+}
+
+#[utoipa::path(
+    get,
+    path = "/meta/gist/{id}",
+    params(("id" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = MetaGistResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn meta_gist_get(
+    Extension(token): Extension<GhToken>,
+    Path(id): Path<String>,
+) -> Result<Json<MetaGistResponse>> {
+    let token = token.must_get()?;
+    gist::load_future(token, &id)
+        .await
+        .map(Into::into)
+        .map(Json)
+        .context(GistLoadingSnafu)
+}
+
+#[utoipa::path(
+    post,
+    path = "/snippets",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Retrying with the same key returns the first response")),
+    request_body = SnippetCreateRequest,
+    responses(
+        (status = 200, description = "Success", body = SnippetResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn snippet_create(
+    Extension(snippets): Extension<SnippetStorage>,
+    Json(req): Json<SnippetCreateRequest>,
+) -> Result<Json<SnippetResponse>> {
+    snippets
+        .must_get()?
+        .create(req.code)
+        .await
+        .map(Into::into)
+        .map(Json)
+        .context(SnippetCreationSnafu)
+}
+
+#[utoipa::path(
+    get,
+    path = "/snippets/{id}",
+    params(("id" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = SnippetResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn snippet_get(
+    Extension(snippets): Extension<SnippetStorage>,
+    Path(id): Path<String>,
+) -> Result<Json<SnippetResponse>> {
+    snippets
+        .must_get()?
+        .load(id.clone())
+        .await
+        .context(SnippetLoadingSnafu)?
+        .map(Into::into)
+        .map(Json)
+        .context(SnippetNotFoundSnafu { id })
+}
+
+#[utoipa::path(
+    get,
+    path = "/snippets/{id}/raw",
+    params(("id" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = String, content_type = "text/plain"),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Just the code, so that it can be downloaded directly.
+async fn snippet_raw(
+    Extension(snippets): Extension<SnippetStorage>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let snippet = snippets
+        .must_get()?
+        .load(id.clone())
+        .await
+        .context(SnippetLoadingSnafu)?
+        .context(SnippetNotFoundSnafu { id })?;
+
+    let headers = [
+        (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+        (header::CONTENT_DISPOSITION, r#"inline; filename="main.rs""#),
+    ];
+
+    Ok((headers, snippet.code))
+}
+
+#[utoipa::path(
+    get,
+    path = "/snippets/{id}/highlighted",
+    params(
+        ("id" = String, Path),
+        ("theme" = Option<String>, Query, description = "`light`, `dark`, `solarized-light`, or `solarized-dark`"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = String, content_type = "text/html"),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// For embeds and previews that can't highlight the code themselves.
+async fn snippet_highlighted(
+    Extension(snippets): Extension<SnippetStorage>,
+    Path(id): Path<String>,
+    Query(query): Query<HighlightQuery>,
+) -> Result<Html<String>> {
+    let snippet = snippets
+        .must_get()?
+        .load(id.clone())
+        .await
+        .context(SnippetLoadingSnafu)?
+        .context(SnippetNotFoundSnafu { id })?;
+
+    // Highlighting large snippets takes long enough to block other requests
+    let html =
+        tokio::task::spawn_blocking(move || highlight::rust_to_html(&snippet.code, query.theme))
+            .await
+            .context(SnippetHighlightingTaskSnafu)?
+            .context(SnippetHighlightingSnafu)?;
+
+    Ok(Html(html))
+}
+
+#[utoipa::path(
+    get,
+    path = "/snippets/{id}/embed",
+    params(
+        ("id" = String, Path),
+        ("theme" = Option<String>, Query, description = "`light`, `dark`, `solarized-light`, or `solarized-dark`"),
+        ("run" = Option<bool>, Query, description = "Whether to show what running the code outputs"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = String, content_type = "text/html"),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// A page of the highlighted code for blogs and documentation to show
+/// in an `<iframe>`. When it's run, the output is cached like any
+/// other execution, so showing it often doesn't run it each time.
+async fn snippet_embed(
+    Extension(snippets): Extension<SnippetStorage>,
+    dispatcher: Extension<Arc<Dispatcher>>,
+    artifacts: Extension<Arc<Artifacts>>,
+    tracking: Tracking,
+    Path(id): Path<String>,
+    Query(query): Query<EmbedQuery>,
+) -> Result<Html<String>> {
+    let snippet = snippets
+        .must_get()?
+        .load(id.clone())
+        .await
+        .context(SnippetLoadingSnafu)?
+        .context(SnippetNotFoundSnafu { id })?;
+    let code = snippet.code.clone();
+
+    let output = if query.run {
+        let Json(executed) = execute(dispatcher, artifacts, tracking, Json(snippet.into())).await?;
+        let ExecuteResponse { stdout, stderr, .. } = executed.response;
+        let output: Vec<_> = vec![stderr, stdout]
+            .into_iter()
+            .filter(|o| !o.is_empty())
+            .collect();
+        Some(output.join("\n"))
+    } else {
+        None
+    };
+
+    let html = tokio::task::spawn_blocking(move || {
+        highlight::embed_to_html(&code, output.as_deref(), query.theme)
+    })
+    .await
+    .context(SnippetHighlightingTaskSnafu)?
+    .context(SnippetHighlightingSnafu)?;
+
+    Ok(Html(html))
+}
+
+#[utoipa::path(
+    get,
+    path = "/templates",
+    responses(
+        (status = 200, description = "Success", body = Vec<TemplateResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// The code to start from, such as an async skeleton, along with what
+/// it needs to be built.
+async fn templates_list(
+    Extension(templates): Extension<Arc<Templates>>,
+) -> Json<Vec<TemplateResponse>> {
+    let templates = templates.list().await;
+    Json(templates.into_iter().map(Into::into).collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/templates/{name}",
+    params(("name" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = TemplateResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn template_get(
+    Extension(templates): Extension<Arc<Templates>>,
+    Path(name): Path<String>,
+) -> Result<Json<TemplateResponse>> {
+    let template = templates
+        .get(&name)
+        .await
+        .context(TemplateNotFoundSnafu { name })?;
+
+    Ok(Json(template.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/share-links",
+    request_body = ShareLinkCreateRequest,
+    responses(
+        (status = 200, description = "Success", body = ShareLinkResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Makes a link that holds the code and its options, which works
+/// without the snippet database or GitHub.
+async fn share_link_create(
+    Extension(share_links): Extension<ShareLinks>,
+    Json(req): Json<ShareLinkCreateRequest>,
+) -> Result<Json<ShareLinkResponse>> {
+    let share_links = share_links.must_get()?;
+    let shared = req.try_into()?;
+    let link = share_links
+        .encode(&shared)
+        .context(ShareLinkCreationSnafu)?;
+
+    Ok(Json(ShareLinkResponse::new(link, shared)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/share-links/{link}",
+    params(("link" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = ShareLinkResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn share_link_get(
+    Extension(share_links): Extension<ShareLinks>,
+    Path(link): Path<String>,
+) -> Result<Json<ShareLinkResponse>> {
+    let shared = share_links
+        .must_get()?
+        .decode(&link)
+        .context(ShareLinkLoadingSnafu)?;
+
+    Ok(Json(ShareLinkResponse::new(link, shared)))
 }
 
-async fn meta_version_miri(
-    Extension(cache): Extension<Arc<SandboxCache>>,
-    if_none_match: Option<TypedHeader<IfNoneMatch>>,
-) -> Result<impl IntoResponse> {
-    let value =
-        track_metric_no_request_async(Endpoint::MetaVersionMiri, || cache.version_miri()).await?;
-    apply_timestamped_caching(value, if_none_match)
+#[utoipa::path(
+    post,
+    path = "/share-links/{link}/execute",
+    params(("link" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = WithMetadata<ExecuteResponse>),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Runs the code in the link with its options, as `/execute` would.
+async fn share_link_execute(
+    Extension(share_links): Extension<ShareLinks>,
+    dispatcher: Extension<Arc<Dispatcher>>,
+    artifacts: Extension<Arc<Artifacts>>,
+    tracking: Tracking,
+    Path(link): Path<String>,
+) -> Result<Json<WithMetadata<ExecuteResponse>>> {
+    let shared = share_links
+        .must_get()?
+        .decode(&link)
+        .context(ShareLinkLoadingSnafu)?;
+
+    execute(dispatcher, artifacts, tracking, Json(shared.into())).await
 }
 
-fn apply_timestamped_caching<T>(
-    value: Stamped<T>,
-    if_none_match: Option<TypedHeader<IfNoneMatch>>,
-) -> Result<impl IntoResponse>
-where
-    Json<T>: IntoResponse,
-{
-    let (value, timestamp) = value;
+#[utoipa::path(
+    get,
+    path = "/artifacts/{id}",
+    params(
+        ("id" = String, Path),
+        ("offset" = Option<usize>, Query, description = "Where the page starts, in bytes"),
+        ("length" = Option<usize>, Query, description = "The most bytes the page may have"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = String, content_type = "text/plain"),
+        (status = 206, description = "A page of it", body = String, content_type = "text/plain"),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// A file produced by an operation, which is only kept for a while.
+/// Large ones may be downloaded a page at a time.
+async fn artifact_get(
+    Extension(artifacts): Extension<Arc<Artifacts>>,
+    Path(id): Path<String>,
+    Query(query): Query<ArtifactQuery>,
+) -> Result<impl IntoResponse> {
+    let artifact = artifacts.load(&id).context(ArtifactNotFoundSnafu { id })?;
+
+    // Artifacts never change, but caches shouldn't outlive them
+    let max_age = artifact
+        .expires_at
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    let headers = [
+        (header::CONTENT_TYPE, artifact.content_type.to_owned()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!(r#"attachment; filename="{}""#, artifact.file_name),
+        ),
+        (
+            header::CACHE_CONTROL,
+            format!("private, max-age={}", max_age.as_secs()),
+        ),
+    ];
+
+    // Never more than there is, however much was asked for
+    let start = query.offset.unwrap_or(0);
+    let length = query.length.unwrap_or(artifact.size).min(artifact.size);
+    let range = artifact.range(start..start.saturating_add(length));
+    let content = artifact
+        .stream_range(range.clone())
+        .await
+        .context(ArtifactStorageSnafu)?;
+    let length = [(header::CONTENT_LENGTH, range.len().to_string())];
+    let content = StreamBody::new(content);
 
-    let timestamp = timestamp.duration_since(UNIX_EPOCH).unwrap();
-    let etag = format!(r#""pg-ts-{}""#, timestamp.as_secs());
-    let etag = ETag::from_str(&etag).unwrap();
+    if query.offset.is_none() && query.length.is_none() {
+        return Ok((headers, length, content).into_response());
+    }
 
-    let cache_control = CacheControl::new()
-        .with_max_age(SANDBOX_CACHE_TIME_TO_LIVE)
-        .with_public();
+    let range = match range.len() {
+        0 => format!("bytes */{}", artifact.size),
+        len => format!(
+            "bytes {}-{}/{}",
+            range.start,
+            range.start + len - 1,
+            artifact.size
+        ),
+    };
+    let mut response = (StatusCode::PARTIAL_CONTENT, headers, length, content).into_response();
+    if let Ok(range) = range.parse() {
+        response.headers_mut().insert(header::CONTENT_RANGE, range);
+    }
+    Ok(response)
+}
 
-    let use_fresh = if_none_match.map_or(true, |if_none_match| {
-        if_none_match.0.precondition_passes(&etag)
-    });
+#[utoipa::path(
+    post,
+    path = "/repl/sessions",
+    responses(
+        (status = 200, description = "Success", body = ReplSessionResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Starts a REPL session, in which each evaluation can use what the
+/// earlier ones defined.
+async fn repl_start(
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    Extension(repls): Extension<Arc<Repls>>,
+    tracking: Tracking,
+) -> Result<Json<ReplSessionResponse>> {
+    ensure!(!dispatcher.is_draining(), MaintenanceSnafu);
+
+    let id = repls
+        .start(&dispatcher, tracking.request_id.as_str())
+        .await
+        .context(ReplSnafu)?;
 
-    let etag = TypedHeader(etag);
-    let cache_control = TypedHeader(cache_control);
+    Ok(Json(ReplSessionResponse {
+        id,
+        idle_timeout: repls.idle_timeout().as_secs(),
+    }))
+}
 
-    let response = if use_fresh {
-        (StatusCode::OK, Json(value)).into_response()
-    } else {
-        StatusCode::NOT_MODIFIED.into_response()
-    };
+#[utoipa::path(
+    post,
+    path = "/repl/sessions/{id}/evaluate",
+    params(("id" = String, Path)),
+    request_body = ReplEvaluateRequest,
+    responses(
+        (status = 200, description = "Success", body = ReplEvaluateResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+/// Evaluates code in the session. Code that runs past the sandbox
+/// timeout ends the session.
+async fn repl_evaluate(
+    Extension(repls): Extension<Arc<Repls>>,
+    Path(id): Path<String>,
+    Json(req): Json<ReplEvaluateRequest>,
+) -> Result<Json<ReplEvaluateResponse>> {
+    let evaluation = repls
+        .evaluate(&id, &req.code)
+        .await
+        .context(ReplSessionNotFoundSnafu { id })?
+        .context(ReplSnafu)?;
 
-    Ok((etag, cache_control, response))
+    Ok(Json(evaluation.into()))
 }
 
-async fn meta_gist_create(
-    Extension(token): Extension<GhToken>,
-    Json(req): Json<MetaGistCreateRequest>,
-) -> Result<Json<MetaGistResponse>> {
-    let token = token.must_get()?;
-    gist::create_future(token, req.code)
+#[utoipa::path(
+    get,
+    path = "/repl/sessions/{id}/variables",
+    params(("id" = String, Path)),
+    responses(
+        (status = 200, description = "Success", body = ReplVariablesResponse),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn repl_variables(
+    Extension(repls): Extension<Arc<Repls>>,
+    Path(id): Path<String>,
+) -> Result<Json<ReplVariablesResponse>> {
+    let variables = repls
+        .variables(&id)
         .await
-        .map(Into::into)
-        .map(Json)
-        .context(GistCreationSnafu)
+        .context(ReplSessionNotFoundSnafu { id })?
+        .context(ReplSnafu)?;
+
+    Ok(Json(variables.into()))
 }
 
-async fn meta_gist_get(
-    Extension(token): Extension<GhToken>,
+#[utoipa::path(
+    delete,
+    path = "/repl/sessions/{id}",
+    params(("id" = String, Path)),
+    responses(
+        (status = 204, description = "The session was closed"),
+        (status = "default", description = "The request failed", body = ErrorJson),
+    ),
+)]
+async fn repl_close(
+    Extension(repls): Extension<Arc<Repls>>,
     Path(id): Path<String>,
-) -> Result<Json<MetaGistResponse>> {
-    let token = token.must_get()?;
-    gist::load_future(token, &id)
+) -> Result<StatusCode> {
+    repls
+        .close(&id)
         .await
-        .map(Into::into)
-        .map(Json)
-        .context(GistLoadingSnafu)
+        .context(ReplSessionNotFoundSnafu { id })?
+        .context(ReplSnafu)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Opens the snippet in the playground.
+async fn snippet_redirect(
+    Extension(snippets): Extension<SnippetStorage>,
+    Path(id): Path<String>,
+) -> Result<Redirect> {
+    let snippet = snippets
+        .must_get()?
+        .load(id.clone())
+        .await
+        .context(SnippetLoadingSnafu)?
+        .context(SnippetNotFoundSnafu { id })?;
+
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("code", &snippet.code)
+        .finish();
+
+    Ok(Redirect::to(&format!("/?{}", query)))
+}
+
+/// Opens the code in the link in the playground, with its options.
+async fn share_link_redirect(
+    Extension(share_links): Extension<ShareLinks>,
+    Path(link): Path<String>,
+) -> Result<Redirect> {
+    let shared = share_links
+        .must_get()?
+        .decode(&link)
+        .context(ShareLinkLoadingSnafu)?;
+
+    // As the frontend names them
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    query
+        .append_pair("code", &shared.code)
+        .append_pair("version", &shared.channel)
+        .append_pair("mode", &shared.mode);
+    if !shared.edition.is_empty() {
+        query.append_pair("edition", &shared.edition);
+    }
+
+    Ok(Redirect::to(&format!("/?{}", query.finish())))
 }
 
 async fn metrics(_: MetricsAuthorization) -> Result<Vec<u8>, StatusCode> {
@@ -405,15 +3064,10 @@ where
     async fn from_request(req: &mut extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
         match Extension::<MetricsToken>::from_request(req).await {
             Ok(Extension(expected)) => {
-                match TypedHeader::<Authorization<Bearer>>::from_request(req).await {
-                    Ok(TypedHeader(Authorization(actual))) => {
-                        if actual.token() == *expected.0 {
-                            Ok(Self)
-                        } else {
-                            Err(Self::FAILURE)
-                        }
-                    }
-                    Err(_) => Err(Self::FAILURE),
+                if has_bearer_token(req, &expected.0).await {
+                    Ok(Self)
+                } else {
+                    Err(Self::FAILURE)
                 }
             }
             // If we haven't set a code at all, allow the request.
@@ -422,6 +3076,205 @@ where
     }
 }
 
+async fn admin_state(
+    _: AdminAuthorization,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+) -> impl IntoResponse {
+    axum::Json(AdminStateResponse {
+        draining: dispatcher.is_draining(),
+        expensive: dispatcher.limiter(OperationClass::Expensive).usage().into(),
+        cheap: dispatcher.limiter(OperationClass::Cheap).usage().into(),
+    })
+}
+
+/// Turns away new requests while letting those already accepted
+/// finish, such as before a deploy.
+async fn admin_drain(
+    _: AdminAuthorization,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+) -> impl IntoResponse {
+    tracing::info!("Draining; new requests will be rejected");
+    dispatcher.set_draining(true);
+    StatusCode::NO_CONTENT
+}
+
+async fn admin_resume(
+    _: AdminAuthorization,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+) -> impl IntoResponse {
+    tracing::info!("No longer draining");
+    dispatcher.set_draining(false);
+    StatusCode::NO_CONTENT
+}
+
+/// Runs a request that a coordinator has sent, as if it had been
+/// made here.
+async fn worker_run(
+    _: WorkerAuthorization,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+    tracking: Tracking,
+    axum::Json(job): axum::Json<job::JobRequest>,
+) -> impl IntoResponse {
+    let class = match job {
+        job::JobRequest::Format(_)
+        | job::JobRequest::Clippy(_)
+        | job::JobRequest::MacroExpansion(_) => OperationClass::Cheap,
+        _ => OperationClass::Expensive,
+    };
+
+    let response = dispatch(&dispatcher, job, &tracking, class, |runner, job| {
+        async move {
+            run(runner, |sandbox, job| job.run(sandbox).boxed(), job)
+                .await
+                .context(WorkerJobSnafu)
+        }
+        .boxed()
+    })
+    .await;
+
+    axum::Json(match response {
+        Ok(WithMetadata { response, .. }) => job::JobOutcome::Finished(response),
+        Err(error) => job::JobOutcome::Failed {
+            message: error.to_string(),
+        },
+    })
+}
+
+async fn worker_load(
+    _: WorkerAuthorization,
+    Extension(dispatcher): Extension<Arc<Dispatcher>>,
+) -> impl IntoResponse {
+    let expensive = dispatcher.limiter(OperationClass::Expensive).usage();
+    let cheap = dispatcher.limiter(OperationClass::Cheap).usage();
+
+    axum::Json(WorkerLoad {
+        running: expensive.running + cheap.running,
+        capacity: expensive.max_running + cheap.max_running,
+        draining: dispatcher.is_draining(),
+    })
+}
+
+/// Curated examples should stay around even when nobody has used them
+/// in a while.
+async fn admin_keep_snippet(
+    auth: AdminAuthorization,
+    snippets: Extension<SnippetStorage>,
+    id: Path<String>,
+) -> Result<StatusCode> {
+    set_snippet_keep(auth, snippets, id, true).await
+}
+
+async fn admin_unkeep_snippet(
+    auth: AdminAuthorization,
+    snippets: Extension<SnippetStorage>,
+    id: Path<String>,
+) -> Result<StatusCode> {
+    set_snippet_keep(auth, snippets, id, false).await
+}
+
+/// Adds the template, or replaces the one with the same name.
+async fn admin_put_template(
+    _: AdminAuthorization,
+    Extension(templates): Extension<Arc<Templates>>,
+    Path(name): Path<String>,
+    Json(req): Json<TemplateRequest>,
+) -> Result<Json<TemplateResponse>> {
+    let template = req.into_template(name)?;
+    templates
+        .put(template.clone())
+        .await
+        .context(TemplatesSnafu)?;
+
+    Ok(Json(template.into()))
+}
+
+async fn admin_remove_template(
+    _: AdminAuthorization,
+    Extension(templates): Extension<Arc<Templates>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode> {
+    let found = templates.remove(&name).await.context(TemplatesSnafu)?;
+    ensure!(found, TemplateNotFoundSnafu { name });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_snippet_keep(
+    _: AdminAuthorization,
+    Extension(snippets): Extension<SnippetStorage>,
+    Path(id): Path<String>,
+    keep: bool,
+) -> Result<StatusCode> {
+    let found = snippets
+        .must_get()?
+        .set_keep(id.clone(), keep)
+        .await
+        .context(SnippetLoadingSnafu)?;
+    ensure!(found, SnippetNotFoundSnafu { id });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A token that requests send as `Authorization: Bearer <token>`.
+trait BearerToken: Clone + Send + Sync + 'static {
+    fn token(&self) -> &str;
+}
+
+impl BearerToken for AdminToken {
+    fn token(&self) -> &str {
+        &self.0
+    }
+}
+
+impl BearerToken for WorkerToken {
+    fn token(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The request has the token of type `T`. Unlike metrics, there must
+/// always be a token.
+#[derive(Debug)]
+struct TokenAuthorization<T>(PhantomData<T>);
+
+type AdminAuthorization = TokenAuthorization<AdminToken>;
+
+type WorkerAuthorization = TokenAuthorization<WorkerToken>;
+
+#[async_trait]
+impl<B, T> extract::FromRequest<B> for TokenAuthorization<T>
+where
+    B: Send,
+    T: BearerToken,
+{
+    type Rejection = MetricsAuthorizationRejection;
+
+    async fn from_request(req: &mut extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(expected) = Extension::<T>::from_request(req)
+            .await
+            .map_err(|_| MetricsAuthorization::FAILURE)?;
+
+        if has_bearer_token(req, expected.token()).await {
+            Ok(Self(PhantomData))
+        } else {
+            Err(MetricsAuthorization::FAILURE)
+        }
+    }
+}
+
+async fn has_bearer_token<B>(req: &mut extract::RequestParts<B>, expected: &str) -> bool
+where
+    B: Send,
+{
+    let header: Result<TypedHeader<Authorization<Bearer>>, _> =
+        extract::FromRequest::from_request(req).await;
+
+    match header {
+        Ok(TypedHeader(Authorization(actual))) => actual.token() == expected,
+        Err(_) => false,
+    }
+}
+
 type Stamped<T> = (T, SystemTime);
 
 #[derive(Debug, Default)]
@@ -433,6 +3286,9 @@ struct SandboxCache {
     version_rustfmt: CacheOne<MetaVersionResponse>,
     version_clippy: CacheOne<MetaVersionResponse>,
     version_miri: CacheOne<MetaVersionResponse>,
+    clippy_lints: CacheOne<MetaClippyLintsResponse>,
+    versions: CacheOne<MetaVersionsResponse>,
+    targets: CacheOne<MetaTargetsResponse>,
 }
 
 impl SandboxCache {
@@ -448,7 +3304,7 @@ impl SandboxCache {
         self.version_stable
             .fetch(|sandbox| async move {
                 let version = sandbox
-                    .version(Channel::Stable)
+                    .version(Channel::Stable { version: None })
                     .await
                     .context(CachingSnafu)?;
                 Ok(version.into())
@@ -469,7 +3325,7 @@ impl SandboxCache {
         self.version_nightly
             .fetch(|sandbox| async move {
                 let version = sandbox
-                    .version(Channel::Nightly)
+                    .version(Channel::Nightly { date: None })
                     .await
                     .context(CachingSnafu)?;
                 Ok(version.into())
@@ -504,6 +3360,57 @@ impl SandboxCache {
             })
             .await
     }
+
+    async fn versions(&self) -> Result<Stamped<MetaVersionsResponse>> {
+        self.versions
+            .fetch(|sandbox| async move {
+                let (stable, beta, nightly, rustfmt, clippy) = futures::join!(
+                    sandbox.full_version(Channel::Stable { version: None }),
+                    sandbox.full_version(Channel::Beta),
+                    sandbox.full_version(Channel::Nightly { date: None }),
+                    sandbox.full_version_rustfmt(),
+                    sandbox.full_version_clippy(),
+                );
+                let rustfmt = rustfmt.context(CachingSnafu)?;
+                let clippy = clippy.context(CachingSnafu)?;
+
+                Ok(MetaVersionsResponse {
+                    stable: stable.into(),
+                    beta: beta.into(),
+                    nightly: nightly.into(),
+                    rustfmt: rustfmt.into(),
+                    clippy: clippy.into(),
+                })
+            })
+            .await
+    }
+
+    async fn targets(&self) -> Result<Stamped<MetaTargetsResponse>> {
+        self.targets
+            .fetch(|sandbox| async move {
+                let (stable, beta, nightly) = futures::try_join!(
+                    sandbox.targets(Channel::Stable { version: None }),
+                    sandbox.targets(Channel::Beta),
+                    sandbox.targets(Channel::Nightly { date: None }),
+                )
+                .context(CachingSnafu)?;
+
+                Ok(MetaTargetsResponse {
+                    stable: stable.into(),
+                    beta: beta.into(),
+                    nightly: nightly.into(),
+                })
+            })
+            .await
+    }
+
+    async fn clippy_lints(&self) -> Result<Stamped<MetaClippyLintsResponse>> {
+        self.clippy_lints
+            .fetch(|sandbox| async move {
+                Ok(sandbox.clippy_lints().await.context(CachingSnafu)?.into())
+            })
+            .await
+    }
 }
 
 #[derive(Debug)]
@@ -600,15 +3507,289 @@ impl<T> CacheInfo<T> {
 
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        Json(ErrorJson {
-            error: self.to_string(),
+        let size_limit = self.size_limit();
+
+        let (status, retry_after) = if let Some(retry_after) = self.channel_retry_after() {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some(retry_after.as_secs().max(1).into()),
+            )
+        } else if size_limit.is_some() {
+            (StatusCode::PAYLOAD_TOO_LARGE, None)
+        } else {
+            match &self {
+                Error::ServerBusy => (StatusCode::TOO_MANY_REQUESTS, Some(SERVER_BUSY_RETRY_AFTER)),
+                Error::RateLimited { retry_after } => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    Some(retry_after.as_secs().into()),
+                ),
+                Error::QuotaExceeded { retry_after } => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    Some(retry_after.as_secs().into()),
+                ),
+                Error::InvalidApiKey => (StatusCode::UNAUTHORIZED, None),
+                Error::InvalidIdempotencyKey { .. } => (StatusCode::BAD_REQUEST, None),
+                Error::IdempotencyKeyInUse => (StatusCode::CONFLICT, None),
+                Error::IdempotencyKeyReused => (StatusCode::UNPROCESSABLE_ENTITY, None),
+                Error::SandboxUnavailable { retry_after } => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Some(retry_after.as_secs().max(1).into()),
+                ),
+                Error::JobNotFound { .. } => (StatusCode::NOT_FOUND, None),
+                Error::Maintenance => (StatusCode::SERVICE_UNAVAILABLE, None),
+                Error::Overloaded { .. } => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Some(SERVER_BUSY_RETRY_AFTER),
+                ),
+                Error::SnippetNotFound { .. } => (StatusCode::NOT_FOUND, None),
+                Error::ShareLinkLoading { .. } => (StatusCode::BAD_REQUEST, None),
+                Error::TemplateNotFound { .. } => (StatusCode::NOT_FOUND, None),
+                Error::Templates {
+                    source: templates::Error::InvalidName { .. },
+                } => (StatusCode::BAD_REQUEST, None),
+                Error::ArtifactNotFound { .. } => (StatusCode::NOT_FOUND, None),
+                Error::ArtifactStorage { .. } => (StatusCode::INSUFFICIENT_STORAGE, None),
+                Error::Repl {
+                    source: repl::Error::Unavailable,
+                } => (StatusCode::SERVICE_UNAVAILABLE, None),
+                Error::Repl {
+                    source: repl::Error::TooMany { .. },
+                } => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Some(SERVER_BUSY_RETRY_AFTER),
+                ),
+                Error::ReplSessionNotFound { .. } => (StatusCode::NOT_FOUND, None),
+                Error::RequestTimedOut { .. } => (StatusCode::REQUEST_TIMEOUT, None),
+                _ => (StatusCode::OK, None),
+            }
+        };
+
+        let mut error = ErrorJson::new(self.to_string());
+        if let Some((size, max_size)) = size_limit {
+            error = error.with_size_limit(size, max_size);
+        }
+
+        let mut response = (status, error).into_response();
+        if let Some(retry_after) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after);
+        }
+        response
+    }
+}
+
+impl IntoResponse for ErrorJson {
+    fn into_response(self) -> axum::response::Response {
+        let mut response = axum::Json(&self).into_response();
+        // Allows the request ID to be added later on
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Identifies a request in logs, containers, and error responses.
+#[derive(Debug, Clone)]
+struct RequestId(Arc<str>);
+
+impl RequestId {
+    const MAX_LENGTH: usize = 64;
+
+    /// Requests may bring their own ID, such as one assigned by a
+    /// proxy, as long as it is reasonable.
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+
+        let reasonable = !value.is_empty()
+            && value.len() <= Self::MAX_LENGTH
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+        reasonable.then(|| RequestId(value.into()))
+    }
+
+    fn random() -> Self {
+        let id = format!("{:032x}", rand::random::<u128>());
+        RequestId(id.into())
+    }
+
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+const TEXT_PLAIN: &str = "text/plain";
+const APPLICATION_JSON: &str = "application/json";
+/// The first is the one we respond with.
+const MSGPACK: [&str; 3] = [
+    "application/msgpack",
+    "application/vnd.msgpack",
+    "application/x-msgpack",
+];
+
+// Ignores parameters such as the charset
+fn media_type(value: &str) -> String {
+    let media_type = value.split(';').next().unwrap_or_default();
+    media_type.trim().to_ascii_lowercase()
+}
+
+fn accepts_msgpack(headers: &header::HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|t| MSGPACK.contains(&&*media_type(t)))
+}
+
+/// Converts JSON responses to MessagePack for clients that ask for it.
+async fn negotiate_format<B>(
+    req: Request<B>,
+    next: middleware::Next<B>,
+) -> axum::response::Response {
+    let msgpack = accepts_msgpack(req.headers());
+
+    let mut response = next.run(req).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| media_type(v) == APPLICATION_JSON);
+    if !is_json {
+        return response;
+    }
+
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    if !msgpack {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let packed = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|body| {
+            serde_json::from_slice::<serde_json::Value>(&body).map_err(|e| e.to_string())
+        })
+        .and_then(|value| rmp_serde::to_vec_named(&value).map_err(|e| e.to_string()));
+
+    match packed {
+        Ok(packed) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts
+                .headers
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK[0]));
+            let body = axum::body::boxed(axum::body::Full::from(packed));
+            axum::response::Response::from_parts(parts, body)
+        }
+        Err(e) => {
+            let error = format!("Unable to convert response: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, ErrorJson::new(error)).into_response()
+        }
+    }
+}
+
+async fn assign_request_id<B>(
+    mut req: Request<B>,
+    next: middleware::Next<B>,
+) -> axum::response::Response {
+    let request_id = req
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(RequestId::from_header)
+        .unwrap_or_else(RequestId::random);
+    req.extensions_mut().insert(request_id.clone());
+
+    let mut response = next.run(req).await;
+
+    if let Some(error) = response.extensions_mut().remove::<ErrorJson>() {
+        let error = ErrorJson {
+            request_id: Some(request_id.as_str().to_owned()),
+            ..error
+        };
+        let (mut parts, _) = response.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        let body = axum::body::boxed(axum::body::Full::from(
+            serde_json::to_vec(&error).unwrap_or_default(),
+        ));
+        response = axum::response::Response::from_parts(parts, body);
+    }
+
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response.headers_mut().insert(X_REQUEST_ID, value);
+    }
+
+    response
+}
+
+fn request_span<B>(req: &Request<B>) -> tracing::Span {
+    let request_id = req.extensions().get::<RequestId>().map(RequestId::as_str);
+
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id,
+    )
+}
+
+/// What the dispatcher needs to know about a request beyond the
+/// request itself.
+pub(crate) struct Tracking {
+    request_id: RequestId,
+    /// Requests that are made directly have no job to keep up to
+    /// date.
+    progress: Option<Arc<Progress>>,
+}
+
+impl Tracking {
+    fn advance(&self, stage: Stage) {
+        if let Some(progress) = &self.progress {
+            progress.advance(stage);
+        }
+    }
+}
+
+impl Tracking {
+    /// For requests that don't come through the HTTP API.
+    pub(crate) fn new() -> Self {
+        Self {
+            request_id: RequestId::random(),
+            progress: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl<B> extract::FromRequest<B> for Tracking
+where
+    B: Send,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request(req: &mut extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let extensions = req.extensions();
+
+        let request_id = extensions.get::<RequestId>().cloned();
+        let progress = extensions.get::<Arc<Progress>>().cloned();
+
+        Ok(Self {
+            request_id: request_id.unwrap_or_else(RequestId::random),
+            progress,
         })
-        .into_response()
     }
 }
 
 /// This type only exists so that we can recover from the `axum::Json`
 /// error and format it using our expected JSON error object.
+///
+/// Besides JSON, requests may be sent as MessagePack, or as plain
+/// text containing only the code with the other fields in the query
+/// string.
 struct Json<T>(T);
 
 #[async_trait]
@@ -622,16 +3803,61 @@ where
     type Rejection = axum::response::Response;
 
     async fn from_request(req: &mut extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
-        match axum::Json::<T>::from_request(req).await {
-            Ok(v) => Ok(Self(v.0)),
-            Err(e) => {
-                let error = format!("Unable to deserialize request: {e}");
-                Err(axum::Json(ErrorJson { error }).into_response())
-            }
-        }
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(media_type);
+
+        let value = match content_type.as_deref() {
+            Some(TEXT_PLAIN) => query_with_code(req).await,
+            Some(t) if MSGPACK.contains(&t) => msgpack_body(req).await,
+            _ => axum::Json::<T>::from_request(req)
+                .await
+                .map(|v| v.0)
+                .map_err(|e| e.to_string()),
+        };
+
+        value.map(Self).map_err(|e| {
+            let error = format!("Unable to deserialize request: {e}");
+            ErrorJson::new(error).into_response()
+        })
     }
 }
 
+// The code is the body, everything else is in the query string
+async fn query_with_code<T, B>(req: &mut extract::RequestParts<B>) -> Result<T, String>
+where
+    T: serde::de::DeserializeOwned,
+    B: axum::body::HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<axum::BoxError>,
+{
+    use extract::FromRequest;
+
+    let query = req.uri().query().unwrap_or_default().to_owned();
+    let code = String::from_request(req).await.map_err(|e| e.to_string())?;
+    let query = form_urlencoded::Serializer::for_suffix(query, 0)
+        .append_pair("code", &code)
+        .finish();
+    serde_urlencoded::from_str(&query).map_err(|e| e.to_string())
+}
+
+async fn msgpack_body<T, B>(req: &mut extract::RequestParts<B>) -> Result<T, String>
+where
+    T: serde::de::DeserializeOwned,
+    B: axum::body::HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<axum::BoxError>,
+{
+    use extract::FromRequest;
+
+    let body = hyper::body::Bytes::from_request(req)
+        .await
+        .map_err(|e| e.to_string())?;
+    rmp_serde::from_slice(&body).map_err(|e| e.to_string())
+}
+
 impl<T> IntoResponse for Json<T>
 where
     T: serde::Serialize,
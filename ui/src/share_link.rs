@@ -0,0 +1,244 @@
+//! Links that hold the code itself, compressed and signed, so that
+//! sharing still works when neither the snippet database nor GitHub
+//! does. A link looks like `<data>.<signature>`, both in unpadded
+//! URL-safe base64, where the data is a version byte followed by the
+//! deflated MessagePack of a [`SharedCode`].
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::prelude::*;
+use std::io::{Read, Write};
+
+const VERSION: u8 = 1;
+
+const SEPARATOR: char = '.';
+
+// Longer links are cut off by some browsers and proxies
+const MAX_LINK_LENGTH: usize = 8 * 1024;
+
+// Deflate can grow the data a thousandfold, so what a link decodes
+// to is limited too. The same as the default most code a request may
+// have.
+const MAX_DECODED_SIZE: u64 = 512 * 1024;
+
+// Of SHA-256
+const BLOCK_SIZE: usize = 64;
+
+// Half of the HMAC is plenty to make forging unfeasible, and keeps
+// the links shorter
+const SIGNATURE_SIZE: usize = 16;
+
+const BASE64: base64::Config = base64::URL_SAFE_NO_PAD;
+
+/// The code and the options to open or run it with, as the request
+/// to execute it would have them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SharedCode {
+    pub(crate) code: String,
+    pub(crate) channel: String,
+    pub(crate) mode: String,
+    pub(crate) edition: String,
+    pub(crate) crate_type: String,
+    pub(crate) tests: bool,
+    pub(crate) backtrace: bool,
+}
+
+/// Signs links with a key, so that only links made here are trusted.
+#[derive(Debug, Clone)]
+pub(crate) struct ShareLinks {
+    key: Vec<u8>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to encode the code: {}", source))]
+    Encoding { source: rmp_serde::encode::Error },
+    #[snafu(display(
+        "The link would be {} characters long, but may be at most {} characters",
+        length,
+        MAX_LINK_LENGTH
+    ))]
+    TooLong { length: usize },
+    #[snafu(display("The link is not a valid share link"))]
+    Malformed,
+    #[snafu(display("The link was not made by this playground or has been changed"))]
+    Forged,
+    #[snafu(display("The link was made by a newer version of the playground"))]
+    UnknownVersion,
+    #[snafu(display("The link holds more than {} bytes", MAX_DECODED_SIZE))]
+    TooLarge,
+}
+
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl ShareLinks {
+    pub(crate) fn new(key: &str) -> Self {
+        let key = key.as_bytes();
+        let key = if key.len() > BLOCK_SIZE {
+            Sha256::digest(key).to_vec()
+        } else {
+            key.to_vec()
+        };
+
+        Self { key }
+    }
+
+    pub(crate) fn encode(&self, shared: &SharedCode) -> Result<String> {
+        let packed = rmp_serde::to_vec(shared).context(EncodingSnafu)?;
+
+        let mut data = vec![VERSION];
+        let mut encoder = DeflateEncoder::new(&mut data, Compression::best());
+        encoder
+            .write_all(&packed)
+            .and_then(|_| encoder.finish())
+            .expect("Writing to a Vec can't fail");
+
+        let signature = self.sign(&data);
+        let link = format!(
+            "{}{}{}",
+            base64::encode_config(&data, BASE64),
+            SEPARATOR,
+            base64::encode_config(signature, BASE64),
+        );
+
+        ensure!(
+            link.len() <= MAX_LINK_LENGTH,
+            TooLongSnafu { length: link.len() }
+        );
+        Ok(link)
+    }
+
+    pub(crate) fn decode(&self, link: &str) -> Result<SharedCode> {
+        ensure!(
+            link.len() <= MAX_LINK_LENGTH,
+            TooLongSnafu { length: link.len() }
+        );
+
+        let (data, signature) = link.split_once(SEPARATOR).context(MalformedSnafu)?;
+        let data = base64::decode_config(data, BASE64)
+            .ok()
+            .context(MalformedSnafu)?;
+        let signature = base64::decode_config(signature, BASE64)
+            .ok()
+            .context(MalformedSnafu)?;
+
+        ensure!(constant_time_eq(&signature, &self.sign(&data)), ForgedSnafu);
+
+        let (&version, compressed) = data.split_first().context(MalformedSnafu)?;
+        ensure!(version == VERSION, UnknownVersionSnafu);
+
+        let mut packed = vec![];
+        DeflateDecoder::new(compressed)
+            .take(MAX_DECODED_SIZE + 1)
+            .read_to_end(&mut packed)
+            .ok()
+            .context(MalformedSnafu)?;
+        ensure!(packed.len() as u64 <= MAX_DECODED_SIZE, TooLargeSnafu);
+
+        rmp_serde::from_slice(&packed).ok().context(MalformedSnafu)
+    }
+
+    // HMAC-SHA256, cut short
+    fn sign(&self, data: &[u8]) -> [u8; SIGNATURE_SIZE] {
+        let mut inner_key = [0x36; BLOCK_SIZE];
+        let mut outer_key = [0x5c; BLOCK_SIZE];
+        for (i, b) in self.key.iter().enumerate() {
+            inner_key[i] ^= b;
+            outer_key[i] ^= b;
+        }
+
+        let inner = Sha256::new()
+            .chain_update(inner_key)
+            .chain_update(data)
+            .finalize();
+        let outer = Sha256::new()
+            .chain_update(outer_key)
+            .chain_update(inner)
+            .finalize();
+
+        let mut signature = [0; SIGNATURE_SIZE];
+        signature.copy_from_slice(&outer[..SIGNATURE_SIZE]);
+        signature
+    }
+}
+
+// So that how long the comparison takes doesn't tell how much of a
+// forged signature was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{distributions::Alphanumeric, rngs::StdRng, Rng, SeedableRng};
+
+    fn shared() -> SharedCode {
+        SharedCode {
+            code: r#"fn main() { println!("Hello, world!"); }"#.into(),
+            channel: "nightly".into(),
+            mode: "release".into(),
+            edition: "2021".into(),
+            crate_type: "bin".into(),
+            tests: false,
+            backtrace: true,
+        }
+    }
+
+    #[test]
+    fn links_decode_to_what_they_were_made_from() {
+        let links = ShareLinks::new("secret");
+
+        let link = links.encode(&shared()).unwrap();
+
+        assert!(link
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b)));
+        assert_eq!(links.decode(&link).unwrap(), shared());
+    }
+
+    #[test]
+    fn changed_links_are_rejected() {
+        let links = ShareLinks::new("secret");
+        let link = links.encode(&shared()).unwrap();
+
+        let (data, signature) = link.split_once(SEPARATOR).unwrap();
+        let mut data = base64::decode_config(data, BASE64).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        let changed = format!(
+            "{}{}{}",
+            base64::encode_config(&data, BASE64),
+            SEPARATOR,
+            signature
+        );
+
+        assert!(matches!(links.decode(&changed), Err(Error::Forged)));
+        assert!(matches!(links.decode("nonsense"), Err(Error::Malformed)));
+    }
+
+    #[test]
+    fn links_made_with_another_key_are_rejected() {
+        let link = ShareLinks::new("secret").encode(&shared()).unwrap();
+
+        assert!(matches!(
+            ShareLinks::new("other secret").decode(&link),
+            Err(Error::Forged)
+        ));
+    }
+
+    #[test]
+    fn code_that_makes_too_long_a_link_is_rejected() {
+        let links = ShareLinks::new("secret");
+
+        // Random enough not to compress much
+        let code = StdRng::seed_from_u64(0)
+            .sample_iter(&Alphanumeric)
+            .take(2 * MAX_LINK_LENGTH)
+            .map(char::from)
+            .collect();
+        let code = SharedCode { code, ..shared() };
+
+        assert!(matches!(links.encode(&code), Err(Error::TooLong { .. })));
+    }
+}
@@ -0,0 +1,455 @@
+use rand::{distributions::Alphanumeric, Rng};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension};
+use sha2::{Digest, Sha256};
+use snafu::prelude::*;
+use std::{
+    convert::TryInto,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Over 10^17 possibilities, so collisions are rare and IDs can't be
+// enumerated
+const ID_LENGTH: usize = 10;
+const ID_ATTEMPTS: usize = 5;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Each entry is applied once, in order. Never change an entry once it
+// has been released; add a new one instead.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE snippets (
+        id TEXT PRIMARY KEY NOT NULL,
+        code TEXT NOT NULL,
+        hash TEXT NOT NULL
+    )",
+    "ALTER TABLE snippets ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE snippets ADD COLUMN retrievals INTEGER NOT NULL DEFAULT 0;",
+    // Existing snippets get a full lifetime from when this is applied
+    "ALTER TABLE snippets ADD COLUMN last_used_at INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE snippets ADD COLUMN keep INTEGER NOT NULL DEFAULT 0;
+    UPDATE snippets SET last_used_at = CAST(strftime('%s', 'now') AS INTEGER);",
+    "CREATE INDEX snippets_hash ON snippets (hash)",
+];
+
+/// Code shared through the playground itself, so that sharing doesn't
+/// depend on GitHub.
+#[derive(Debug, Clone)]
+pub(crate) struct Storage {
+    db: Arc<Mutex<Connection>>,
+    config: Config,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Config {
+    /// Snippets larger than this many bytes are rejected.
+    pub(crate) max_size: usize,
+    /// Snippets that haven't been created or loaded for this long are
+    /// removed, unless they have been marked to be kept. When unset,
+    /// snippets are kept forever.
+    pub(crate) time_to_live: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Snippet {
+    pub(crate) id: String,
+    pub(crate) code: String,
+    pub(crate) created_at: SystemTime,
+    /// How many times the snippet has been loaded.
+    pub(crate) retrievals: u64,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to open the snippet database {}: {}", path.display(), source))]
+    UnableToOpen {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+    #[snafu(display("Unable to update the snippet database: {}", source))]
+    UnableToMigrate { source: rusqlite::Error },
+    #[snafu(display("The snippet database failed: {}", source))]
+    Database { source: rusqlite::Error },
+    #[snafu(display("The snippet database task failed: {}", source))]
+    TaskFailed { source: tokio::task::JoinError },
+    #[snafu(display("The snippet is {} bytes, but may be at most {} bytes", size, max_size))]
+    TooLarge { size: usize, max_size: usize },
+    #[snafu(display("Unable to find an unused snippet ID"))]
+    NoUnusedId,
+}
+
+pub(crate) type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+impl Storage {
+    pub(crate) fn open(path: &Path, config: Config) -> Result<Self> {
+        let db = Connection::open(path).context(UnableToOpenSnafu { path })?;
+        Self::new(db, config)
+    }
+
+    #[cfg(test)]
+    fn in_memory(config: Config) -> Result<Self> {
+        let db = Connection::open_in_memory().context(UnableToOpenSnafu { path: ":memory:" })?;
+        Self::new(db, config)
+    }
+
+    fn new(mut db: Connection, config: Config) -> Result<Self> {
+        migrate(&mut db).context(UnableToMigrateSnafu)?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+            config,
+        })
+    }
+
+    /// Identical code shares one snippet, so the existing snippet is
+    /// returned if there is one.
+    pub(crate) async fn create(&self, code: String) -> Result<Snippet> {
+        self.create_at(code, SystemTime::now()).await
+    }
+
+    /// Counts as a retrieval of the snippet.
+    pub(crate) async fn load(&self, id: String) -> Result<Option<Snippet>> {
+        self.load_at(id, SystemTime::now()).await
+    }
+
+    /// Marks the snippet to be kept even when it hasn't been used
+    /// recently, such as for curated examples. Returns if the snippet
+    /// exists.
+    pub(crate) async fn set_keep(&self, id: String, keep: bool) -> Result<bool> {
+        self.with_db(move |db| {
+            db.execute(
+                "UPDATE snippets SET keep = ?2 WHERE id = ?1",
+                params![id, keep],
+            )
+            .map(|updated| updated != 0)
+        })
+        .await
+    }
+
+    /// Removes expired snippets every so often, forever.
+    pub(crate) async fn sweep_periodically(self) {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            match self.sweep_at(SystemTime::now()).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!(count, "Removed expired snippets"),
+                Err(error) => tracing::error!(%error, "Unable to remove expired snippets"),
+            }
+        }
+    }
+
+    async fn create_at(&self, code: String, now: SystemTime) -> Result<Snippet> {
+        let size = code.len();
+        let max_size = self.config.max_size;
+        ensure!(size <= max_size, TooLargeSnafu { size, max_size });
+
+        let hash = hash(&code);
+        // Only whole seconds are stored
+        let created_at = from_unix_seconds(unix_seconds(now));
+
+        self.with_db(move |db| {
+            // Compare the code too, rather than trusting the hash alone
+            let existing = db
+                .query_row(
+                    "UPDATE snippets SET last_used_at = ?3
+                     WHERE hash = ?1 AND code = ?2
+                     RETURNING id, created_at, retrievals",
+                    params![hash, code, unix_seconds(created_at)],
+                    |row| {
+                        Ok(Snippet {
+                            id: row.get(0)?,
+                            code: code.clone(),
+                            created_at: from_unix_seconds(row.get(1)?),
+                            retrievals: row.get::<_, i64>(2)?.try_into().unwrap_or_default(),
+                        })
+                    },
+                )
+                .optional()?;
+
+            if existing.is_some() {
+                return Ok(existing);
+            }
+
+            for _ in 0..ID_ATTEMPTS {
+                let id = random_id();
+
+                let inserted = db.execute(
+                    "INSERT INTO snippets (id, code, hash, created_at, last_used_at)
+                     VALUES (?1, ?2, ?3, ?4, ?4)",
+                    params![id, code, hash, unix_seconds(created_at)],
+                );
+
+                match inserted {
+                    Ok(_) => {
+                        return Ok(Some(Snippet {
+                            id,
+                            code,
+                            created_at,
+                            retrievals: 0,
+                        }))
+                    }
+                    Err(e) if is_duplicate(&e) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Ok(None)
+        })
+        .await?
+        .context(NoUnusedIdSnafu)
+    }
+
+    async fn load_at(&self, id: String, now: SystemTime) -> Result<Option<Snippet>> {
+        // Expired snippets may not have been swept yet
+        let cutoff = self.expiry_cutoff(now);
+
+        self.with_db(move |db| {
+            db.query_row(
+                "UPDATE snippets SET retrievals = retrievals + 1, last_used_at = ?2
+                 WHERE id = ?1 AND (keep OR ?3 IS NULL OR last_used_at >= ?3)
+                 RETURNING code, created_at, retrievals",
+                params![id, unix_seconds(now), cutoff],
+                |row| {
+                    Ok(Snippet {
+                        id: id.clone(),
+                        code: row.get(0)?,
+                        created_at: from_unix_seconds(row.get(1)?),
+                        retrievals: row.get::<_, i64>(2)?.try_into().unwrap_or_default(),
+                    })
+                },
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn sweep_at(&self, now: SystemTime) -> Result<usize> {
+        let cutoff = match self.expiry_cutoff(now) {
+            Some(cutoff) => cutoff,
+            None => return Ok(0),
+        };
+
+        self.with_db(move |db| {
+            db.execute(
+                "DELETE FROM snippets WHERE NOT keep AND last_used_at < ?1",
+                params![cutoff],
+            )
+        })
+        .await
+    }
+
+    /// Snippets last used before this have expired.
+    fn expiry_cutoff(&self, now: SystemTime) -> Option<i64> {
+        let time_to_live = self.config.time_to_live?;
+        Some(unix_seconds(
+            now.checked_sub(time_to_live).unwrap_or(UNIX_EPOCH),
+        ))
+    }
+
+    // SQLite blocks, so it's kept off of the async threads
+    async fn with_db<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let db = db.lock().unwrap_or_else(|e| e.into_inner());
+            f(&db)
+        })
+        .await
+        .context(TaskFailedSnafu)?
+        .context(DatabaseSnafu)
+    }
+}
+
+fn migrate(db: &mut Connection) -> rusqlite::Result<()> {
+    let applied: u32 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    let tx = db.transaction()?;
+    for (version, migration) in (1..).zip(MIGRATIONS).skip(applied as usize) {
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version)?;
+    }
+    tx.commit()
+}
+
+fn random_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+fn is_duplicate(error: &rusqlite::Error) -> bool {
+    error.sqlite_error_code() == Some(ErrorCode::ConstraintViolation)
+}
+
+// SQLite only has signed integers
+fn unix_seconds(time: SystemTime) -> i64 {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    seconds.try_into().unwrap_or(i64::MAX)
+}
+
+fn from_unix_seconds(seconds: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds.try_into().unwrap_or_default())
+}
+
+fn hash(code: &str) -> String {
+    Sha256::digest(code.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TIME_TO_LIVE: Duration = Duration::from_secs(60 * 60);
+
+    const CONFIG: Config = Config {
+        max_size: 1024,
+        time_to_live: Some(TIME_TO_LIVE),
+    };
+
+    #[tokio::test]
+    async fn snippets_can_be_loaded_after_being_created() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+
+        let snippet = storage.create("fn main() {}".into()).await.unwrap();
+        assert_eq!(snippet.id.len(), ID_LENGTH);
+
+        let loaded = storage.load(snippet.id.clone()).await.unwrap().unwrap();
+        assert_eq!(loaded.code, snippet.code);
+        assert_eq!(loaded.created_at, snippet.created_at);
+    }
+
+    #[tokio::test]
+    async fn retrievals_are_counted() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+        let snippet = storage.create("fn main() {}".into()).await.unwrap();
+
+        storage.load(snippet.id.clone()).await.unwrap();
+        let loaded = storage.load(snippet.id.clone()).await.unwrap().unwrap();
+
+        assert_eq!(loaded.retrievals, 2);
+    }
+
+    #[tokio::test]
+    async fn identical_code_shares_a_snippet() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+
+        let first = storage.create("fn main() {}".into()).await.unwrap();
+        storage.load(first.id.clone()).await.unwrap();
+        let second = storage.create("fn main() {}".into()).await.unwrap();
+        let different = storage.create("fn main() { }".into()).await.unwrap();
+
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.created_at, first.created_at);
+        assert_eq!(second.retrievals, 1);
+        assert_ne!(different.id, first.id);
+    }
+
+    #[tokio::test]
+    async fn recreating_a_snippet_keeps_it_from_expiring() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+        let now = SystemTime::now();
+
+        let snippet = storage.create_at("shared".into(), now).await.unwrap();
+        let later = now + TIME_TO_LIVE;
+        storage.create_at("shared".into(), later).await.unwrap();
+
+        let much_later = later + Duration::from_secs(1);
+        assert_eq!(storage.sweep_at(much_later).await.unwrap(), 0);
+        assert!(storage
+            .load_at(snippet.id, much_later)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_snippets_are_not_found() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+
+        assert_eq!(storage.load("nope".into()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn large_snippets_are_rejected() {
+        let storage = Storage::in_memory(Config {
+            max_size: 4,
+            ..CONFIG
+        })
+        .unwrap();
+
+        let error = storage.create("fn main() {}".into()).await.unwrap_err();
+        assert!(matches!(error, Error::TooLarge { size: 12, .. }));
+    }
+
+    #[tokio::test]
+    async fn unused_snippets_expire() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+        let now = SystemTime::now();
+
+        let used = storage.create_at("used".into(), now).await.unwrap();
+        let unused = storage.create_at("unused".into(), now).await.unwrap();
+
+        let later = now + TIME_TO_LIVE;
+        storage.load_at(used.id.clone(), later).await.unwrap();
+
+        let much_later = later + Duration::from_secs(1);
+        assert_eq!(storage.sweep_at(much_later).await.unwrap(), 1);
+        assert!(storage
+            .load_at(used.id, much_later)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(storage
+            .load_at(unused.id, much_later)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn kept_snippets_never_expire() {
+        let storage = Storage::in_memory(CONFIG).unwrap();
+        let now = SystemTime::now();
+
+        let snippet = storage.create_at("kept".into(), now).await.unwrap();
+        assert!(storage.set_keep(snippet.id.clone(), true).await.unwrap());
+
+        let much_later = now + TIME_TO_LIVE * 100;
+        assert!(storage
+            .load_at(snippet.id.clone(), much_later)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            storage
+                .sweep_at(much_later + TIME_TO_LIVE * 2)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn migrations_are_only_applied_once() {
+        let mut db = Connection::open_in_memory().unwrap();
+
+        migrate(&mut db).unwrap();
+        migrate(&mut db).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! Named code to start from, such as an async skeleton, along with
+//! what it needs to be built. Admins may change them; when a file is
+//! configured, the changes are saved there and loaded from it again
+//! on starting.
+
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, sync::Mutex};
+
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Debug)]
+pub(crate) struct Templates {
+    path: Option<PathBuf>,
+    templates: Mutex<BTreeMap<String, Template>>,
+}
+
+/// The options are as the request to execute the code would have
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Template {
+    pub(crate) name: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) code: String,
+    pub(crate) channel: String,
+    pub(crate) edition: String,
+    pub(crate) crate_type: String,
+    pub(crate) tests: bool,
+    /// Of the available crates, those that the code uses.
+    pub(crate) crates: Vec<String>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read the templates from {}: {}", path.display(), source))]
+    UnableToRead { path: PathBuf, source: io::Error },
+    #[snafu(display("Unable to parse the templates in {}: {}", path.display(), source))]
+    UnableToParse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[snafu(display("Unable to save the templates to {}: {}", path.display(), source))]
+    UnableToSave { path: PathBuf, source: io::Error },
+    #[snafu(display(
+        "The template name {:?} must be at most {} lowercase letters, digits, and dashes",
+        name,
+        MAX_NAME_LENGTH
+    ))]
+    InvalidName { name: String },
+}
+
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Templates {
+    /// Without a file, or until it has been saved to, the templates
+    /// are the built-in ones.
+    pub(crate) async fn open(path: Option<PathBuf>) -> Result<Self> {
+        let templates = match &path {
+            Some(path) => load(path).await?,
+            None => None,
+        };
+        let templates = templates
+            .unwrap_or_else(built_in)
+            .into_iter()
+            .map(|t| (t.name.clone(), t))
+            .collect();
+
+        Ok(Self {
+            path,
+            templates: Mutex::new(templates),
+        })
+    }
+
+    /// Ordered by name.
+    pub(crate) async fn list(&self) -> Vec<Template> {
+        self.templates.lock().await.values().cloned().collect()
+    }
+
+    pub(crate) async fn get(&self, name: &str) -> Option<Template> {
+        self.templates.lock().await.get(name).cloned()
+    }
+
+    /// Adds the template, or replaces the one with the same name.
+    pub(crate) async fn put(&self, template: Template) -> Result<()> {
+        ensure!(
+            is_valid_name(&template.name),
+            InvalidNameSnafu {
+                name: template.name
+            }
+        );
+
+        let mut templates = self.templates.lock().await;
+        let mut changed = templates.clone();
+        changed.insert(template.name.clone(), template);
+
+        self.save(&changed).await?;
+        *templates = changed;
+        Ok(())
+    }
+
+    /// Whether there was a template to remove.
+    pub(crate) async fn remove(&self, name: &str) -> Result<bool> {
+        let mut templates = self.templates.lock().await;
+        if !templates.contains_key(name) {
+            return Ok(false);
+        }
+        let mut changed = templates.clone();
+        changed.remove(name);
+
+        self.save(&changed).await?;
+        *templates = changed;
+        Ok(true)
+    }
+
+    // Written beside it first, so that it's never left half written
+    async fn save(&self, templates: &BTreeMap<String, Template>) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let templates: Vec<_> = templates.values().collect();
+        let json = serde_json::to_vec_pretty(&templates).expect("Templates are always valid JSON");
+        let partial = path.with_extension("partial");
+
+        let context = || UnableToSaveSnafu { path };
+        fs::write(&partial, json)
+            .await
+            .with_context(|_| context())?;
+        fs::rename(&partial, path).await.with_context(|_| context())
+    }
+}
+
+async fn load(path: &Path) -> Result<Option<Vec<Template>>> {
+    let json = match fs::read(path).await {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(UnableToReadSnafu { path }),
+    };
+
+    serde_json::from_slice(&json)
+        .map(Some)
+        .context(UnableToParseSnafu { path })
+}
+
+// So that it can be part of a path
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+fn built_in() -> Vec<Template> {
+    let template = |name: &str, title: &str, description: &str, code: &str| Template {
+        name: name.into(),
+        title: title.into(),
+        description: description.into(),
+        code: code.trim_start().into(),
+        channel: "stable".into(),
+        edition: "2021".into(),
+        crate_type: "bin".into(),
+        tests: false,
+        crates: vec![],
+    };
+
+    vec![
+        template(
+            "hello-world",
+            "Hello, world!",
+            "The smallest program that prints something.",
+            r#"
+fn main() {
+    println!("Hello, world!");
+}
+"#,
+        ),
+        Template {
+            crates: vec!["tokio".into()],
+            ..template(
+                "tokio",
+                "Async with Tokio",
+                "An async main function, run by the Tokio runtime.",
+                r#"
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() {
+    let handle = tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        "from a task"
+    });
+
+    println!("Hello, {}!", handle.await.unwrap());
+}
+"#,
+            )
+        },
+        Template {
+            crate_type: "proc-macro".into(),
+            crates: vec!["proc-macro2".into(), "quote".into(), "syn".into()],
+            ..template(
+                "proc-macro",
+                "Procedural macro",
+                "A derive macro. Procedural macros can only be built here, as they must be used from another crate.",
+                r#"
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput};
+
+#[proc_macro_derive(Hello)]
+pub fn derive_hello(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    quote! {
+        impl #name {
+            pub fn hello() -> &'static str {
+                concat!("Hello from ", stringify!(#name))
+            }
+        }
+    }
+    .into()
+}
+"#,
+            )
+        },
+        Template {
+            crate_type: "lib".into(),
+            tests: true,
+            ..template(
+                "tests",
+                "Tests",
+                "A function along with the tests of it.",
+                r#"
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_adds() {
+        assert_eq!(add(2, 2), 4);
+    }
+}
+"#,
+            )
+        },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn template(name: &str) -> Template {
+        Template {
+            name: name.into(),
+            code: "fn main() {}".into(),
+            ..built_in().remove(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn the_built_in_templates_are_there_by_default() {
+        let templates = Templates::open(None).await.unwrap();
+
+        let names: Vec<_> = templates.list().await.into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["hello-world", "proc-macro", "tests", "tokio"]);
+    }
+
+    #[tokio::test]
+    async fn changes_are_loaded_again() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("templates.json");
+
+        let templates = Templates::open(Some(path.clone())).await.unwrap();
+        templates.put(template("mine")).await.unwrap();
+        assert!(templates.remove("tokio").await.unwrap());
+        assert!(!templates.remove("tokio").await.unwrap());
+
+        let reopened = Templates::open(Some(path)).await.unwrap();
+        assert_eq!(reopened.get("mine").await, Some(template("mine")));
+        assert_eq!(reopened.get("tokio").await, None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn names_must_fit_in_a_path() {
+        let templates = Templates::open(None).await.unwrap();
+
+        for name in ["", "Capitals", "a/b", &"a".repeat(MAX_NAME_LENGTH + 1)] {
+            assert!(matches!(
+                templates.put(template(name)).await,
+                Err(Error::InvalidName { .. })
+            ));
+        }
+    }
+}
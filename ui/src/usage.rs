@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// How many of the most recent durations of each kind of operation
+/// the percentiles are taken from.
+const MAX_SAMPLES: usize = 1000;
+
+/// How often each kind of sandbox operation has been run since
+/// starting, and how long it took, kept apart from Prometheus so that
+/// it can be shown to anyone.
+#[derive(Debug)]
+pub(crate) struct Usage {
+    started_at: SystemTime,
+    operations: Mutex<BTreeMap<Key, Samples>>,
+}
+
+/// The operation, channel, and mode, the same as their labels in
+/// Prometheus. The channel and mode are empty for operations that
+/// don't have one.
+type Key = (&'static str, &'static str, &'static str);
+
+#[derive(Debug, Default)]
+struct Samples {
+    count: u64,
+    recent: VecDeque<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OperationUsage {
+    pub(crate) operation: &'static str,
+    pub(crate) channel: Option<&'static str>,
+    pub(crate) mode: Option<&'static str>,
+    pub(crate) count: u64,
+    pub(crate) p50: Duration,
+    pub(crate) p90: Duration,
+    pub(crate) p99: Duration,
+}
+
+impl Usage {
+    pub(crate) fn new() -> Self {
+        Self {
+            started_at: SystemTime::now(),
+            operations: Default::default(),
+        }
+    }
+
+    pub(crate) fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    pub(crate) fn record(
+        &self,
+        operation: &'static str,
+        channel: &'static str,
+        mode: &'static str,
+        elapsed: Duration,
+    ) {
+        let mut operations = self.lock();
+        let samples = operations.entry((operation, channel, mode)).or_default();
+
+        samples.count += 1;
+        if samples.recent.len() == MAX_SAMPLES {
+            samples.recent.pop_front();
+        }
+        samples.recent.push_back(elapsed);
+    }
+
+    /// Ordered by operation, then channel, then mode.
+    pub(crate) fn operations(&self) -> Vec<OperationUsage> {
+        let operations = self.lock();
+
+        operations
+            .iter()
+            .map(|(&(operation, channel, mode), samples)| {
+                let mut recent: Vec<_> = samples.recent.iter().copied().collect();
+                recent.sort_unstable();
+
+                let present = |v: &'static str| Some(v).filter(|v| !v.is_empty());
+                OperationUsage {
+                    operation,
+                    channel: present(channel),
+                    mode: present(mode),
+                    count: samples.count,
+                    p50: percentile(&recent, 50),
+                    p90: percentile(&recent, 90),
+                    p99: percentile(&recent, 99),
+                }
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<Key, Samples>> {
+        self.operations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// The nearest rank of the sorted durations
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn operations_are_counted_apart() {
+        let usage = Usage::new();
+
+        usage.record("Execute", "Stable", "Debug", ms(10));
+        usage.record("Execute", "Nightly", "Debug", ms(20));
+        usage.record("Execute", "Stable", "Debug", ms(30));
+        usage.record("Format", "", "", ms(5));
+
+        let counts: Vec<_> = usage
+            .operations()
+            .into_iter()
+            .map(|o| (o.operation, o.channel, o.mode, o.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("Execute", Some("Nightly"), Some("Debug"), 1),
+                ("Execute", Some("Stable"), Some("Debug"), 2),
+                ("Format", None, None, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn percentiles_are_of_the_recent_durations() {
+        let usage = Usage::new();
+
+        for i in 1..=MAX_SAMPLES as u64 + 100 {
+            usage.record("Compile", "Beta", "Release", ms(i));
+        }
+
+        let operations = usage.operations();
+        let compile = &operations[0];
+        assert_eq!(compile.count, MAX_SAMPLES as u64 + 100);
+        assert_eq!(compile.p50, ms(600));
+        assert_eq!(compile.p90, ms(1000));
+        assert_eq!(compile.p99, ms(1090));
+    }
+}
@@ -0,0 +1,46 @@
+use std::{convert::TryFrom, sync::Arc};
+use tokio::{sync::Semaphore, time};
+
+use crate::{config::Config, env::PLAYGROUND_QUEUE_REDIS_URL, server_axum};
+
+/// Runs the jobs that servers have put in the queue, instead of
+/// serving requests.
+#[tokio::main]
+pub(crate) async fn work(config: Config) {
+    let queue = match config.queue() {
+        Ok(Some(queue)) => queue,
+        Ok(None) => {
+            tracing::error!("{} must be set to run a worker", PLAYGROUND_QUEUE_REDIS_URL);
+            std::process::exit(1);
+        }
+        Err(error) => {
+            tracing::error!(%error, "Unable to use the job queue");
+            std::process::exit(1);
+        }
+    };
+
+    let dispatcher = Arc::new(config.dispatcher());
+    dispatcher.start(config.image_update_interval()).await;
+
+    let concurrency = config.max_running_sandboxes();
+    let permits = Arc::new(Semaphore::new(concurrency));
+    tracing::info!(concurrency, "Taking jobs from the queue");
+
+    // A job whose reply from Redis is still on its way when the
+    // shutdown starts is put back in the queue once its lease expires
+    tokio::select! {
+        () = queue.work(dispatcher, permits.clone()) => {}
+        () = server_axum::shutdown_signal() => {}
+    }
+
+    tracing::info!("Shutting down; waiting for running jobs to finish");
+    let all = u32::try_from(concurrency).unwrap_or(u32::MAX);
+    if time::timeout(config.shutdown_timeout(), permits.acquire_many(all))
+        .await
+        .is_err()
+    {
+        tracing::warn!("Jobs did not finish in time");
+    }
+
+    server_axum::remove_leftover_containers().await;
+}